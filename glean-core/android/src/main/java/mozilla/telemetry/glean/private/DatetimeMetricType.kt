@@ -6,6 +6,7 @@ package mozilla.telemetry.glean.private
 
 import androidx.annotation.VisibleForTesting
 import mozilla.telemetry.glean.internal.DatetimeMetric
+import mozilla.telemetry.glean.testing.ErrorType
 import mozilla.telemetry.glean.utils.calendarToDatetime
 import java.util.Calendar
 import java.util.Date
@@ -68,4 +69,13 @@ class DatetimeMetricType(meta: CommonMetricData, timeUnit: TimeUnit = TimeUnit.M
     fun testGetValueAsString(pingName: String? = null): String? {
         return inner.testGetValueAsString(pingName)
     }
+
+    /**
+     * Returns the number of errors recorded for the given metric.
+     *
+     * @param errorType The type of the error recorded.
+     * @return the number of errors recorded for the metric.
+     */
+    @VisibleForTesting(otherwise = VisibleForTesting.NONE)
+    fun testGetNumRecordedErrors(errorType: ErrorType) = inner.testGetNumRecordedErrors(errorType)
 }
@@ -12,6 +12,7 @@ package mozilla.telemetry.glean.private
 
 import androidx.test.core.app.ApplicationProvider
 import androidx.test.ext.junit.runners.AndroidJUnit4
+import mozilla.telemetry.glean.testing.ErrorType
 import mozilla.telemetry.glean.testing.GleanTestRule
 import org.junit.Assert.assertEquals
 import org.junit.Assert.assertNull
@@ -117,4 +118,25 @@ class DatetimeMetricTypeTest {
 
         assertEquals(now, datetimeMetric.testGetValue()!!.asSeconds())
     }
+
+    @Test
+    fun `implausible datetimes are not clamped by default`() {
+        val datetimeMetric = DatetimeMetricType(
+            CommonMetricData(
+                disabled = false,
+                category = "telemetry",
+                lifetime = Lifetime.APPLICATION,
+                name = "datetimeMetric",
+                sendInPings = listOf("store1")
+            )
+        )
+
+        val value = Calendar.getInstance()
+        value.set(1993, 1, 23, 9, 5, 43)
+        value.timeZone = TimeZone.getTimeZone("GMT+0")
+        datetimeMetric.set(value)
+
+        assertEquals("1993-02-23T09:05+00:00", datetimeMetric.testGetValueAsString())
+        assertEquals(0, datetimeMetric.testGetNumRecordedErrors(ErrorType.CLOCK_SKEW))
+    }
 }
@@ -68,6 +68,23 @@ class DatetimeMetricTypeTests: XCTestCase {
                      year: 1969, month: 8, day: 20, hour: 20, minute: 17, second: 3)
     }
 
+    func testImplausibleDatetimesAreNotClampedByDefault() {
+        let datetimeMetric = DatetimeMetricType(CommonMetricData(
+            category: "telemetry",
+            name: "datetime_metric",
+            sendInPings: ["store1"],
+            lifetime: .application,
+            disabled: false
+        ), .minute)
+
+        testDatetime(metric: datetimeMetric,
+                     testString: "1993-02-23T09:05+00:00",
+                     timeZone: TimeZone(abbreviation: "GMT")!,
+                     year: 1993, month: 2, day: 23, hour: 9, minute: 5, second: 43)
+
+        XCTAssertEqual(0, datetimeMetric.testGetNumRecordedErrors(.clockSkew))
+    }
+
     func testDatetimeMustNotRecordIfDisabled() {
         let datetimeMetric = DatetimeMetricType(CommonMetricData(
             category: "telemetry",
@@ -5,6 +5,7 @@
 import datetime
 
 from glean import metrics
+from glean import testing
 from glean.metrics import Lifetime, CommonMetricData
 
 
@@ -76,3 +77,24 @@ def test_disabled_datetimes_must_not_record_data():
 
     datetime_metric.set()
     assert datetime_metric.test_get_value() is None
+
+
+def test_implausible_datetimes_are_not_clamped_by_default():
+    datetime_metric = metrics.DatetimeMetricType(
+        CommonMetricData(
+            disabled=False,
+            category="telemetry",
+            lifetime=Lifetime.APPLICATION,
+            name="datetime_metric",
+            send_in_pings=["store1"],
+            dynamic_label=None,
+        ),
+        time_unit=metrics.TimeUnit.MINUTE,
+    )
+
+    value = datetime.datetime(1993, 2, 23, 5, 43, tzinfo=datetime.timezone.utc)
+    datetime_metric.set(value)
+    assert "1993-02-23T05:43:00+00:00" == datetime_metric.test_get_value_as_str()
+    assert 0 == datetime_metric.test_get_num_recorded_errors(
+        testing.ErrorType.CLOCK_SKEW
+    )
@@ -4,6 +4,7 @@
 
 use crate::ClientInfoMetrics;
 use crate::Configuration;
+use std::path::PathBuf;
 use std::sync::{Mutex, MutexGuard};
 
 use once_cell::sync::Lazy;
@@ -29,6 +30,24 @@ pub(crate) fn lock_test() -> MutexGuard<'static, ()> {
     lock
 }
 
+/// Creates the configuration tests initialize Glean with, storing data in `data_path`.
+///
+/// Tests needing other options override them, e.g.
+/// `Configuration { uploader: Some(Box::new(uploader.clone())), ..test_configuration(tmpname) }`.
+pub(crate) fn test_configuration(data_path: PathBuf) -> Configuration {
+    Configuration {
+        data_path,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        ..Default::default()
+    }
+}
+
 // Create a new instance of Glean with a temporary directory.
 // We need to keep the `TempDir` alive, so that it's not deleted before we stop using it.
 pub(crate) fn new_glean(
@@ -40,18 +59,17 @@ pub(crate) fn new_glean(
 
     let cfg = match configuration {
         Some(c) => c,
-        None => Configuration {
-            data_path: tmpname,
-            application_id: GLOBAL_APPLICATION_ID.into(),
-            upload_enabled: true,
-            max_events: None,
-            delay_ping_lifetime_io: false,
-            server_endpoint: Some("invalid-test-host".into()),
-            uploader: None,
-            use_core_mps: false,
-        },
+        None => test_configuration(tmpname),
     };
 
     crate::test_reset_glean(cfg, ClientInfoMetrics::unknown(), clear_stores);
     dir
 }
+
+/// Decodes the gzipped JSON body of a ping, as passed to the uploader.
+pub(crate) fn decode_payload(body: &[u8]) -> serde_json::Value {
+    let mut decoder = flate2::read::GzDecoder::new(body);
+    let mut payload = String::new();
+    std::io::Read::read_to_string(&mut decoder, &mut payload).unwrap();
+    serde_json::from_str(&payload).unwrap()
+}
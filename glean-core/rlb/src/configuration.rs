@@ -2,9 +2,19 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::net::PingUploader;
+#[cfg(feature = "ping_encryption")]
+use crate::net::Jwk;
+use crate::net::{
+    PingTransform, PingUploader, SchemaCircuitObserver, SchemaValidator, UploadInterceptor,
+    UrlBuilder,
+};
+use crate::{PingFormat, StorageBackend, TimeUnit, UnregisteredPingBehavior};
 
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// The default server pings are sent to.
 pub(crate) const DEFAULT_GLEAN_ENDPOINT: &str = "https://incoming.telemetry.mozilla.org";
@@ -12,7 +22,7 @@ pub(crate) const DEFAULT_GLEAN_ENDPOINT: &str = "https://incoming.telemetry.mozi
 /// The Glean configuration.
 ///
 /// Optional values will be filled in with default values.
-#[derive(Debug)]
+/// Options not set explicitly can be taken from [`Configuration::default`].
 pub struct Configuration {
     /// Whether upload should be enabled.
     pub upload_enabled: bool,
@@ -21,8 +31,19 @@ pub struct Configuration {
     /// The application ID (will be sanitized during initialization).
     pub application_id: String,
     /// The maximum number of events to store before sending a ping containing events.
+    ///
+    /// Defaults to 500. Once a ping's event buffer holds that many events,
+    /// the ping is submitted with the reason `max_capacity`, which clears the buffer.
     pub max_events: Option<usize>,
     /// Whether Glean should delay persistence of data from metrics with ping lifetime.
+    ///
+    /// If set, ping-lifetime data is kept in memory and only persisted on
+    /// [`persist_ping_lifetime_data`](crate::persist_ping_lifetime_data) and [`shutdown`](crate::shutdown).
+    /// Data recorded since it was persisted last is lost if the application crashes.
+    /// The next run, whatever this setting is then, continues with the data persisted last.
+    /// If changed data was persisted before the crash,
+    /// it counts the possible loss in `glean.database.ping_lifetime_data_lost`.
+    /// Data persisted in either mode is never lost by changing this setting between runs.
     pub delay_ping_lifetime_io: bool,
     /// The server pings are sent to.
     pub server_endpoint: Option<String>,
@@ -30,4 +51,400 @@ pub struct Configuration {
     pub uploader: Option<Box<dyn PingUploader + 'static>>,
     /// Whether Glean should schedule "metrics" pings for you.
     pub use_core_mps: bool,
+    /// An optional header, as a (name, value) pair, to attach to every ping upload request.
+    ///
+    /// This is useful, e.g., to route pings to a staging pipeline.
+    /// Invalid header names are rejected during initialization.
+    pub pipeline_header: Option<(String, String)>,
+    /// An optional callback invoked with the number of dropped tasks
+    /// if the pre-init task queue overflowed.
+    ///
+    /// It is called once, on a separate thread, after initialization flushed the queue.
+    pub on_queue_overflow: Option<Box<dyn Fn(usize) + Send + Sync + 'static>>,
+    /// The number of consecutive recoverable upload failures after which uploading is paused.
+    ///
+    /// `None` disables the circuit breaker.
+    pub upload_failure_threshold: Option<u32>,
+    /// For how long uploading is paused once `upload_failure_threshold` is reached.
+    /// Pings stay pending in the meantime.
+    ///
+    /// Defaults to 10 minutes. Only whole seconds are taken into account.
+    pub upload_cooldown: Option<Duration>,
+    /// The time unit the `glean.baseline.duration` metric is reported in.
+    ///
+    /// Only `TimeUnit::Second` and `TimeUnit::Millisecond` are supported.
+    /// Defaults to seconds.
+    pub baseline_duration_unit: Option<TimeUnit>,
+    /// Whether to only log assembled pings instead of uploading them.
+    ///
+    /// Metrics are recorded and pings are assembled as usual,
+    /// but they are never written to disk nor handed to the uploader.
+    /// Unlike disabling upload, this keeps all recorded data.
+    pub dry_run: bool,
+    /// The locale to report in `client_info`, e.g. "es-ES".
+    ///
+    /// Use this if the application manages its locale independently of the operating system.
+    /// It must be a valid BCP 47 language tag, otherwise an `InvalidValue` error is recorded
+    /// and the default locale, if any, is reported instead.
+    pub locale_override: Option<String>,
+    /// The maximum number of pending pings of the same type to send in a single upload request.
+    ///
+    /// Batches are sent to `/batch/<app_id>/<ping_name>` with a body of type
+    /// [`BATCH_CONTENT_TYPE`](crate::net::BATCH_CONTENT_TYPE), see [`PingBatch`](crate::net::PingBatch).
+    /// `None`, the default, uploads every ping in its own request.
+    pub batch_uploads: Option<usize>,
+    /// An optional callback to transform ping bodies right before they are uploaded,
+    /// e.g. to encrypt them or wrap them in an application-specific envelope.
+    ///
+    /// It is called on the upload thread with the uncompressed ping payload
+    /// and returns the bytes to send instead.
+    /// These are compressed again if the ping was going to be sent compressed,
+    /// and the `Content-Length` header is updated to match.
+    /// Other headers, like `Content-Type`, are sent as they are.
+    pub ping_transform: Option<PingTransform>,
+    /// An optional public key to encrypt every ping for.
+    ///
+    /// If set, ping payloads are encrypted as JWE, using ECDH-ES with A256GCM,
+    /// and sent as `{"payload": "<JWE in compact serialization>"}`,
+    /// after `ping_transform` was applied.
+    /// Only P-256 elliptic curve keys are supported.
+    /// Glean isn't initialized if the key is invalid, so pings are never sent unencrypted.
+    /// `None`, the default, sends pings unencrypted.
+    ///
+    /// Only available with the `ping_encryption` feature.
+    #[cfg(feature = "ping_encryption")]
+    pub ping_public_key: Option<Jwk>,
+    /// An optional uploader to try after `uploader` failed twice in a row
+    /// with a recoverable failure, e.g. one using a backup endpoint or transport.
+    ///
+    /// It is passed the same URL, body and headers as `uploader`.
+    pub fallback_uploader: Option<Box<dyn PingUploader + 'static>>,
+    /// How long assembling a ping may take before it's reported as slow.
+    ///
+    /// Slow assemblies are logged and counted in `glean.assembly.slow`,
+    /// they still complete as usual. Defaults to 1 second.
+    pub slow_assembly_threshold: Option<Duration>,
+    /// The maximum number of pings waiting to be uploaded.
+    ///
+    /// Once exceeded, the oldest pending pings are deleted and counted in
+    /// `glean.upload.pending_pings_evicted`. Deletion-request pings are never evicted.
+    /// This applies in addition to the limits on the size of the pending pings directory.
+    /// `None`, the default, doesn't cap the count.
+    pub max_pending_pings: Option<usize>,
+    /// Whether to measure how long recording metrics takes.
+    ///
+    /// Every recording is timed on the dispatcher thread and accumulated into
+    /// `glean.sdk.record_latency`, which is sent in the "metrics" ping.
+    /// This adds a small overhead itself, so it's meant for profiling.
+    pub profile_recording: bool,
+    /// The format to name the files of pending pings after,
+    /// for tools post-processing the pending pings directory.
+    ///
+    /// `{doc_id}` is replaced by the document id and must occur exactly once.
+    /// `{ping_name}` and `{timestamp}`, the milliseconds since the Unix epoch, may be used too,
+    /// e.g. `{ping_name}-{timestamp}-{doc_id}.json`.
+    /// Invalid formats are rejected during initialization.
+    /// `None`, the default, names files after the document id only.
+    pub pending_ping_filename_format: Option<String>,
+    /// Whether to also record every metric into a local "debug" store.
+    ///
+    /// The debug store is never sent in a ping, so it accumulates everything recorded
+    /// and can be inspected with [`dump_store("debug")`](crate::dump_store)
+    /// while developing instrumentation.
+    /// Events are only mirrored in memory, keeping the most recent `max_events` of them.
+    pub mirror_to_debug_store: bool,
+    /// The maximum number of buckets a distribution keeps, to bound its memory use.
+    ///
+    /// Once a distribution holds more buckets, its highest buckets are merged into one,
+    /// trading precision for memory. The number of merged buckets is counted in
+    /// `glean.distribution.buckets_merged`.
+    /// `None`, the default, keeps all buckets.
+    pub max_distribution_buckets: Option<usize>,
+    /// An optional callback to validate pings right before they are uploaded,
+    /// e.g. against their JSON schema.
+    ///
+    /// It is called on the upload thread with the ping name and the uncompressed payload.
+    /// Pings it rejects are deleted without being uploaded,
+    /// except for deletion-request pings, which are always uploaded.
+    /// Once a ping type was rejected `schema_failure_threshold` times in a row,
+    /// it isn't assembled anymore until Glean is initialized again,
+    /// which is counted in `glean.error.schema_circuit_open`.
+    pub schema_validator: Option<SchemaValidator>,
+    /// The number of consecutive pings of the same type `schema_validator` has to reject
+    /// to stop assembling that ping type. Defaults to 5.
+    pub schema_failure_threshold: Option<u32>,
+    /// An optional callback invoked with the ping name when a ping type
+    /// isn't assembled anymore because it kept failing schema validation.
+    ///
+    /// It is called on the upload thread, once per ping type.
+    pub on_schema_circuit_open: Option<SchemaCircuitObserver>,
+    /// For how long to skip pings identical to the previously submitted ping of the same type.
+    ///
+    /// Pings are compared ignoring their document id, sequence number and start and end times.
+    /// Skipped pings are counted in `glean.upload.deduplicated`.
+    /// Deletion-request pings are never skipped.
+    /// `None`, the default, doesn't deduplicate pings.
+    pub ping_dedup_window: Option<Duration>,
+    /// The date to report as `first_run_date` on the very first run, instead of today.
+    ///
+    /// Use this to preserve the original install date when migrating from another
+    /// telemetry system. It has no effect if a first run date was recorded before.
+    /// Dates in the future are ignored.
+    pub first_run_date: Option<DateTime<FixedOffset>>,
+    /// An optional callback building the full submission URL of every ping,
+    /// for embedders with bespoke ingestion.
+    ///
+    /// It is passed the application id, as it appears in the default URL,
+    /// the ping name and the document id, and replaces both `server_endpoint`
+    /// and the built-in `/submit/...` path layout.
+    /// Batched uploads still use the built-in layout.
+    pub url_builder: Option<UrlBuilder>,
+    /// An optional storage to keep metrics in, instead of the default rkv database on disk.
+    ///
+    /// Use this where rkv isn't suitable, e.g. to keep metrics in memory
+    /// or in storage the platform provides.
+    /// Events and pending pings are still stored in files in `data_path`.
+    pub storage_backend: Option<Box<dyn StorageBackend>>,
+    /// Whether Glean records its own `glean.*` metrics, e.g. upload statistics,
+    /// into the "metrics" ping.
+    ///
+    /// If `false`, the "metrics" ping only carries the application's metrics
+    /// and the errors recorded for them in `glean.error.*`.
+    /// Pings depending on Glean's metrics, like "baseline" and "crash", still have them.
+    /// This should usually be `true`.
+    pub enable_internal_metrics: bool,
+    /// An optional observer of every request right before it's passed to the uploader,
+    /// for network debugging.
+    ///
+    /// Unlike the ping itself, it sees the exact URL, headers and body length sent,
+    /// after the URL builder, ping transform and pipeline header were applied,
+    /// and it is called again for every retry and for the fallback uploader.
+    /// It can't change requests, use `ping_transform` for that.
+    pub upload_interceptor: Option<Box<dyn UploadInterceptor + 'static>>,
+    /// Whether active experiments are kept across runs.
+    ///
+    /// If `true`, experiments set with [`set_experiment_active`](crate::set_experiment_active)
+    /// are stored in the database in `data_path` and annotate pings on the following runs too,
+    /// until they are set inactive.
+    /// By default, the application has to set them active again on every run.
+    pub persist_experiments: bool,
+    /// How long to wait for initialization to finish before giving up on
+    /// the recordings and other tasks queued until then.
+    ///
+    /// If initialization hangs, e.g. on stuck IO, these tasks would otherwise pile up forever.
+    /// Once the timeout passes they are dropped, as is everything recorded until
+    /// initialization finishes, which is counted in `glean.error.init_timeout`
+    /// and `glean.error.preinit_tasks_overflow`.
+    /// `None`, the default, waits forever.
+    pub init_timeout: Option<Duration>,
+    /// The servers to send specific pings to instead of `server_endpoint`, by ping name,
+    /// e.g. for deployments routing some pings to a different ingestion host.
+    ///
+    /// Pings not in the map are sent to `server_endpoint`.
+    /// The `url_builder`, if any, takes precedence for single pings.
+    pub endpoint_overrides: Option<HashMap<String, String>>,
+    /// The local hour of the day, from 0 to 23, to schedule the "metrics" ping for.
+    ///
+    /// Invalid hours are logged and ignored.
+    /// `None`, the default, schedules it for 04:00.
+    pub metrics_ping_schedule_hour: Option<u32>,
+    /// The `Content-Type` header to send pings with, for ingestion endpoints expecting another one.
+    ///
+    /// `None`, the default, sends `application/json; charset=utf-8`.
+    /// Gzipped pings still get a separate `Content-Encoding: gzip` header.
+    pub content_type: Option<String>,
+    /// The maximum number of distinct dynamic labels every labeled metric stores per ping,
+    /// below the built-in limit of 16.
+    ///
+    /// Further labels are recorded as `__other__`, counted in `glean.error.label_overflow`.
+    /// `None`, the default, only applies the built-in limit.
+    pub max_distinct_labels: Option<usize>,
+    /// The only metric categories allowed to record, enforcing an instrumentation contract.
+    ///
+    /// Metrics in other categories are disabled and recording into them
+    /// is counted in `glean.error.disallowed_category`.
+    /// Glean's own metrics are always allowed.
+    /// `None`, the default, allows all categories.
+    pub allowed_metric_categories: Option<Vec<String>>,
+    /// The maximum length of a dynamic label, in bytes, for pipelines with other limits.
+    ///
+    /// Longer labels are recorded as `__other__`, with an `InvalidLabel` error.
+    /// `None`, the default, allows 61 bytes.
+    pub max_label_length: Option<usize>,
+    /// Whether to clamp datetimes recorded while the system clock is set implausibly.
+    ///
+    /// Datetimes before 2010 or more than a day in the future are recorded as the nearest bound,
+    /// with a `glean.error.clock_skew` error.
+    /// Defaults to `false`, recording datetimes as they are.
+    pub clamp_skewed_datetimes: bool,
+    /// What happens to recordings into pings that aren't registered yet,
+    /// e.g. because the ping is registered only after metrics start recording.
+    ///
+    /// `None`, the default, records them right away, as if the ping was registered.
+    /// With [`UnregisteredPingBehavior::Buffer`] they are held until the ping is registered,
+    /// with [`UnregisteredPingBehavior::Strict`] they are dropped and counted
+    /// in `glean.error.unknown_ping`.
+    pub unregistered_ping_behavior: Option<UnregisteredPingBehavior>,
+    /// How ping bodies are serialized for upload.
+    ///
+    /// `None`, the default, sends JSON.
+    /// With [`PingFormat::MessagePack`] payloads are sent as MessagePack,
+    /// with an `application/msgpack` Content-Type unless [`content_type`](Self::content_type) is set.
+    /// Schema validation and batched uploads only work with JSON bodies.
+    pub ping_format: Option<PingFormat>,
+}
+
+/// Uploads are disabled and all options are unset, i.e. they take the values documented above.
+///
+/// `data_path` and `application_id` have no meaningful default and must always be set,
+/// so use this to fill in the remaining fields of a [`Configuration`]:
+///
+/// ```rust
+/// # use glean::Configuration;
+/// let cfg = Configuration {
+///     upload_enabled: true,
+///     data_path: "/tmp/glean".into(),
+///     application_id: "org.mozilla.glean_core.example".into(),
+///     ..Default::default()
+/// };
+/// ```
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            upload_enabled: false,
+            data_path: PathBuf::new(),
+            application_id: String::new(),
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: None,
+            uploader: None,
+            use_core_mps: false,
+            pipeline_header: None,
+            on_queue_overflow: None,
+            upload_failure_threshold: None,
+            upload_cooldown: None,
+            baseline_duration_unit: None,
+            dry_run: false,
+            locale_override: None,
+            batch_uploads: None,
+            ping_transform: None,
+            #[cfg(feature = "ping_encryption")]
+            ping_public_key: None,
+            fallback_uploader: None,
+            slow_assembly_threshold: None,
+            max_pending_pings: None,
+            profile_recording: false,
+            pending_ping_filename_format: None,
+            mirror_to_debug_store: false,
+            max_distribution_buckets: None,
+            schema_validator: None,
+            schema_failure_threshold: None,
+            on_schema_circuit_open: None,
+            ping_dedup_window: None,
+            first_run_date: None,
+            url_builder: None,
+            storage_backend: None,
+            enable_internal_metrics: true,
+            upload_interceptor: None,
+            persist_experiments: false,
+            init_timeout: None,
+            endpoint_overrides: None,
+            metrics_ping_schedule_hour: None,
+            content_type: None,
+            max_distinct_labels: None,
+            allowed_metric_categories: None,
+            max_label_length: None,
+            clamp_skewed_datetimes: false,
+            unregistered_ping_behavior: None,
+            ping_format: None,
+        }
+    }
+}
+
+impl fmt::Debug for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Configuration");
+        debug
+            .field("upload_enabled", &self.upload_enabled)
+            .field("data_path", &self.data_path)
+            .field("application_id", &self.application_id)
+            .field("max_events", &self.max_events)
+            .field("delay_ping_lifetime_io", &self.delay_ping_lifetime_io)
+            .field("server_endpoint", &self.server_endpoint)
+            .field("uploader", &self.uploader)
+            .field("use_core_mps", &self.use_core_mps)
+            .field("pipeline_header", &self.pipeline_header)
+            .field(
+                "on_queue_overflow",
+                &self.on_queue_overflow.as_ref().map(|_| "Fn(usize)"),
+            )
+            .field("upload_failure_threshold", &self.upload_failure_threshold)
+            .field("upload_cooldown", &self.upload_cooldown)
+            .field("baseline_duration_unit", &self.baseline_duration_unit)
+            .field("dry_run", &self.dry_run)
+            .field("locale_override", &self.locale_override)
+            .field("batch_uploads", &self.batch_uploads)
+            .field(
+                "ping_transform",
+                &self
+                    .ping_transform
+                    .as_ref()
+                    .map(|_| "Fn(Vec<u8>) -> Vec<u8>"),
+            );
+        #[cfg(feature = "ping_encryption")]
+        debug.field("ping_public_key", &self.ping_public_key);
+        debug
+            .field("fallback_uploader", &self.fallback_uploader)
+            .field("slow_assembly_threshold", &self.slow_assembly_threshold)
+            .field("max_pending_pings", &self.max_pending_pings)
+            .field("profile_recording", &self.profile_recording)
+            .field(
+                "pending_ping_filename_format",
+                &self.pending_ping_filename_format,
+            )
+            .field("mirror_to_debug_store", &self.mirror_to_debug_store)
+            .field("max_distribution_buckets", &self.max_distribution_buckets)
+            .field(
+                "schema_validator",
+                &self
+                    .schema_validator
+                    .as_ref()
+                    .map(|_| "Fn(&str, &JsonValue) -> Result<(), String>"),
+            )
+            .field("schema_failure_threshold", &self.schema_failure_threshold)
+            .field(
+                "on_schema_circuit_open",
+                &self.on_schema_circuit_open.as_ref().map(|_| "Fn(&str)"),
+            )
+            .field("ping_dedup_window", &self.ping_dedup_window)
+            .field("first_run_date", &self.first_run_date)
+            .field(
+                "url_builder",
+                &self
+                    .url_builder
+                    .as_ref()
+                    .map(|_| "Fn(&str, &str, &str) -> String"),
+            )
+            .field("storage_backend", &self.storage_backend)
+            .field("enable_internal_metrics", &self.enable_internal_metrics)
+            .field("upload_interceptor", &self.upload_interceptor)
+            .field("persist_experiments", &self.persist_experiments)
+            .field("init_timeout", &self.init_timeout)
+            .field("endpoint_overrides", &self.endpoint_overrides)
+            .field(
+                "metrics_ping_schedule_hour",
+                &self.metrics_ping_schedule_hour,
+            )
+            .field("content_type", &self.content_type)
+            .field("max_distinct_labels", &self.max_distinct_labels)
+            .field("allowed_metric_categories", &self.allowed_metric_categories)
+            .field("max_label_length", &self.max_label_length)
+            .field("clamp_skewed_datetimes", &self.clamp_skewed_datetimes)
+            .field(
+                "unregistered_ping_behavior",
+                &self.unregistered_ping_behavior,
+            )
+            .field("ping_format", &self.ping_format)
+            .finish()
+    }
 }
@@ -13,6 +13,12 @@ pub struct ClientInfoMetrics {
     pub app_display_version: String,
     /// The product-provided release channel (e.g. "beta").
     pub channel: Option<String>,
+    /// The identifier of the exact binary, used to correlate pings with it
+    /// (e.g. "20230615093512"). It is reported in `client_info.build_id` and must not be empty.
+    ///
+    /// The pipeline schema doesn't accept `client_info.build_id` yet,
+    /// so pings carrying it are rejected on ingestion until it does.
+    pub build_id: Option<String>,
 }
 
 impl ClientInfoMetrics {
@@ -22,6 +28,7 @@ impl ClientInfoMetrics {
             app_build: "Unknown".to_string(),
             app_display_version: "Unknown".to_string(),
             channel: None,
+            build_id: None,
         }
     }
 }
@@ -32,6 +39,7 @@ impl From<ClientInfoMetrics> for glean_core::ClientInfoMetrics {
             app_build: metrics.app_build,
             app_display_version: metrics.app_display_version,
             channel: metrics.channel,
+            build_id: metrics.build_id,
             os_version: system::get_os_version(),
             architecture: system::ARCH.to_string(),
             ..Default::default()
@@ -28,6 +28,7 @@
 //!     server_endpoint: None,
 //!     uploader: None,
 //!     use_core_mps: false,
+//!     ..Default::default()
 //! };
 //! glean::initialize(cfg, ClientInfoMetrics::unknown());
 //!
@@ -37,14 +38,24 @@
 //! ```
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use once_cell::sync::Lazy;
 
 pub use configuration::Configuration;
 use configuration::DEFAULT_GLEAN_ENDPOINT;
 pub use core_metrics::ClientInfoMetrics;
 pub use glean_core::{
-    metrics::{Datetime, DistributionData, MemoryUnit, Rate, RecordedEvent, TimeUnit, TimerId},
-    traits, CommonMetricData, Error, ErrorType, Glean, HistogramType, Lifetime, RecordedExperiment,
-    Result,
+    metrics::{
+        Clock, Datetime, DistributionData, MemoryUnit, Metric, MetricRef, MetricType, PingPriority,
+        Rate, RecordedEvent, TimeUnit, TimerId,
+    },
+    traits, CommonMetricData, CrashCause, DataSensitivity, Error, ErrorType, Glean,
+    GlobalMetricObserver, HistogramType, Lifetime, MetricDescriptor, MetricObserver, ObserverId,
+    PingFormat, RecordedExperiment, RecordingBatch, Result, StorageBackend, StorageChange,
+    UnregisteredPingBehavior, UploadDisabledReason,
 };
 
 mod configuration;
@@ -52,12 +63,17 @@ mod core_metrics;
 pub mod net;
 pub mod private;
 mod system;
+pub mod test;
 
 #[cfg(test)]
 mod common_test;
 
 const LANGUAGE_BINDING_NAME: &str = "Rust";
 
+/// The upload manager of the current Glean instance, preparing pings for [`drain_pings`].
+static DRAIN_UPLOAD_MANAGER: Lazy<Mutex<Option<net::UploadManager>>> =
+    Lazy::new(|| Mutex::new(None));
+
 /// Creates and initializes a new Glean object.
 ///
 /// See [`glean_core::Glean::new`] for more information.
@@ -74,6 +90,8 @@ pub fn initialize(cfg: Configuration, client_info: ClientInfoMetrics) {
 struct GleanEvents {
     /// An instance of the upload manager
     upload_manager: net::UploadManager,
+    /// The embedder's callback for pre-init queue overflows.
+    on_queue_overflow: Option<Arc<dyn Fn(usize) + Send + Sync + 'static>>,
 }
 
 impl glean_core::OnGleanEvents for GleanEvents {
@@ -96,19 +114,64 @@ impl glean_core::OnGleanEvents for GleanEvents {
         // intentionally left empty
         Ok(())
     }
+
+    fn on_queue_overflow(&self, dropped: usize) {
+        // The embedder's callback might do arbitrary work,
+        // so we keep it off the init thread.
+        if let Some(callback) = &self.on_queue_overflow {
+            let callback = Arc::clone(callback);
+            let res = thread::Builder::new()
+                .name("glean.overflow".into())
+                .spawn(move || callback(dropped));
+            if let Err(e) = res {
+                log::error!("Failed to spawn the queue overflow callback thread: {}", e);
+            }
+        }
+    }
 }
 
 fn initialize_internal(cfg: Configuration, client_info: ClientInfoMetrics) -> Option<()> {
     // Initialize the ping uploader.
+    let schema_failure_threshold = cfg
+        .schema_failure_threshold
+        .unwrap_or(net::DEFAULT_SCHEMA_FAILURE_THRESHOLD);
+    let on_schema_circuit_open = cfg.on_schema_circuit_open;
+    #[cfg(feature = "ping_encryption")]
+    let ping_transform = match cfg.ping_public_key.as_ref().map(net::PingEncryption::new) {
+        Some(Ok(encryption)) => Some(encryption.after(cfg.ping_transform)),
+        Some(Err(e)) => {
+            // Never fall back to uploading pings unencrypted.
+            log::error!("Invalid ping public key, not initializing Glean: {}", e);
+            return None;
+        }
+        None => cfg.ping_transform,
+    };
+    #[cfg(not(feature = "ping_encryption"))]
+    let ping_transform = cfg.ping_transform;
     let upload_manager = net::UploadManager::new(
         cfg.server_endpoint
             .unwrap_or_else(|| DEFAULT_GLEAN_ENDPOINT.to_string()),
+        cfg.endpoint_overrides.unwrap_or_default(),
         cfg.uploader
             .unwrap_or_else(|| Box::new(net::HttpUploader) as Box<dyn net::PingUploader>),
+        cfg.fallback_uploader,
+        cfg.pipeline_header,
+        cfg.batch_uploads,
+        ping_transform,
+        cfg.schema_validator.map(|validator| {
+            net::SchemaCheck::new(validator, schema_failure_threshold, on_schema_circuit_open)
+        }),
+        cfg.url_builder,
+        cfg.upload_interceptor,
     );
 
+    *DRAIN_UPLOAD_MANAGER.lock().unwrap() = Some(upload_manager.clone());
+
     // Now make this the global object available to others.
-    let callbacks = Box::new(GleanEvents { upload_manager });
+    let callbacks = Box::new(GleanEvents {
+        upload_manager,
+        on_queue_overflow: cfg.on_queue_overflow.map(Arc::from),
+    });
 
     let core_cfg = glean_core::InternalConfiguration {
         upload_enabled: cfg.upload_enabled,
@@ -119,9 +182,45 @@ fn initialize_internal(cfg: Configuration, client_info: ClientInfoMetrics) -> Op
         delay_ping_lifetime_io: cfg.delay_ping_lifetime_io,
         app_build: client_info.app_build.clone(),
         use_core_mps: cfg.use_core_mps,
+        upload_failure_threshold: cfg.upload_failure_threshold,
+        upload_cooldown_secs: cfg.upload_cooldown.map(|cooldown| cooldown.as_secs()),
+        baseline_duration_unit: cfg.baseline_duration_unit,
+        dry_run: cfg.dry_run,
+        locale_override: cfg.locale_override,
+        slow_assembly_threshold_ms: cfg
+            .slow_assembly_threshold
+            .map(|threshold| threshold.as_millis() as u64),
+        max_pending_pings: cfg.max_pending_pings.map(|max| max as u64),
+        profile_recording: cfg.profile_recording,
+        pending_ping_filename_format: cfg.pending_ping_filename_format,
+        mirror_to_debug_store: cfg.mirror_to_debug_store,
+        max_distribution_buckets: cfg.max_distribution_buckets.map(|max| max as u64),
+        ping_dedup_window_ms: cfg
+            .ping_dedup_window
+            .map(|window| window.as_millis() as u64),
+        first_run_date: cfg.first_run_date.map(Into::into),
+        enable_internal_metrics: cfg.enable_internal_metrics,
+        persist_experiments: cfg.persist_experiments,
+        init_timeout_ms: cfg.init_timeout.map(|timeout| timeout.as_millis() as u64),
+        metrics_ping_schedule_hour: cfg.metrics_ping_schedule_hour,
+        content_type: cfg.content_type,
+        max_distinct_labels: cfg.max_distinct_labels.map(|max| max as u64),
+        allowed_metric_categories: cfg.allowed_metric_categories,
+        max_label_length: cfg.max_label_length.map(|max| max as u64),
+        clamp_skewed_datetimes: cfg.clamp_skewed_datetimes,
+        unregistered_ping_behavior: cfg.unregistered_ping_behavior,
+        ping_format: cfg.ping_format,
     };
 
-    glean_core::glean_initialize(core_cfg, client_info.into(), callbacks);
+    match cfg.storage_backend {
+        Some(backend) => glean_core::glean_initialize_with_storage_backend(
+            core_cfg,
+            client_info.into(),
+            callbacks,
+            backend,
+        ),
+        None => glean_core::glean_initialize(core_cfg, client_info.into(), callbacks),
+    }
     Some(())
 }
 
@@ -130,6 +229,16 @@ pub fn shutdown() {
     glean_core::shutdown()
 }
 
+/// Runs `callback` once Glean finished initializing,
+/// e.g. to safely read metrics recorded before.
+///
+/// The callback runs on Glean's dispatcher, after the tasks queued before initialization.
+/// If Glean is initialized already, it runs right away.
+/// If the initialization fails, it never runs.
+pub fn on_initialized(callback: Box<dyn FnOnce() + Send>) {
+    glean_core::glean_on_initialized(callback)
+}
+
 /// Sets whether upload is enabled or not.
 ///
 /// See [`glean_core::Glean::set_upload_enabled`].
@@ -137,6 +246,92 @@ pub fn set_upload_enabled(enabled: bool) {
     glean_core::glean_set_upload_enabled(enabled)
 }
 
+/// Sets whether upload is enabled or not,
+/// reporting why it's disabled in the `deletion-request` ping.
+///
+/// See [`glean_core::Glean::set_upload_enabled_with_reason`].
+pub fn set_upload_enabled_with_reason(enabled: bool, reason: Option<UploadDisabledReason>) {
+    glean_core::glean_set_upload_enabled_with_reason(enabled, reason)
+}
+
+/// Gets whether upload is currently enabled, e.g. to show the state in a settings UI.
+///
+/// Calls to [`set_upload_enabled`] are applied in order with all other Glean calls,
+/// so one made right before might not be reflected yet.
+/// Returns `false` until Glean is initialized.
+///
+/// See [`glean_core::Glean::is_upload_enabled`].
+pub fn upload_enabled() -> bool {
+    glean_core::glean_is_upload_enabled()
+}
+
+/// Stores the recordings made by an auxiliary process of a multi-process application.
+///
+/// Only the main process initializes Glean and writes to its database.
+/// Other processes record into a [`RecordingBatch`] and hand its
+/// [`serialize`](RecordingBatch::serialize)d form to the main process,
+/// which passes it here. Invalid blobs are logged and ignored.
+pub fn ingest_recordings(blob: &[u8]) {
+    glean_core::glean_ingest_recordings(blob.to_vec())
+}
+
+/// Writes all pending pings into a single archive file at `path`,
+/// e.g. to move them off a device without network access.
+///
+/// The pings stay pending. Waits for queued tasks to finish first.
+///
+/// # Returns
+///
+/// The number of pings written, or an error if Glean isn't initialized
+/// or the archive couldn't be written.
+pub fn export_pending_pings(path: &Path) -> Result<usize> {
+    glean_core::glean_export_pending_pings(path.to_path_buf())
+}
+
+/// Removes all pending pings and returns them instead of uploading them,
+/// for embedders sending pings through a transport of their own.
+///
+/// The pings are prepared as they would be for the configured [`net::PingUploader`],
+/// which never sees them. Waits for queued tasks to finish first,
+/// so pings submitted before are included.
+///
+/// # Returns
+///
+/// The pings, in the order they would have been uploaded in.
+/// Empty if Glean isn't initialized.
+pub fn drain_pings() -> Vec<net::AssembledPing> {
+    let upload_manager = match DRAIN_UPLOAD_MANAGER.lock().unwrap().clone() {
+        Some(upload_manager) => upload_manager,
+        None => return Vec::new(),
+    };
+    glean_core::glean_drain_pings()
+        .into_iter()
+        .map(|request| upload_manager.assemble(request))
+        .collect()
+}
+
+/// Imports the pings of an archive written by [`export_pending_pings`] and uploads them.
+///
+/// The pings keep their document ids and URLs. Pings already pending are skipped.
+///
+/// # Returns
+///
+/// The number of pings imported, or an error if Glean isn't initialized
+/// or the archive couldn't be read.
+pub fn import_pending_pings(path: &Path) -> Result<usize> {
+    glean_core::glean_import_pending_pings(path.to_path_buf())
+}
+
+/// Pauses or resumes recording metrics, without disabling upload.
+///
+/// Data recorded so far is kept and still uploaded.
+/// While paused, recording a metric doesn't change it.
+///
+/// See [`glean_core::Glean::set_recording_paused`].
+pub fn set_recording_paused(paused: bool) {
+    glean_core::glean_set_recording_paused(paused)
+}
+
 /// Collects and submits a ping for eventual uploading by name.
 ///
 /// Note that this needs to be public in order for RLB consumers to
@@ -149,6 +344,14 @@ pub fn submit_ping_by_name(ping: &str, reason: Option<&str>) {
     glean_core::glean_submit_ping_by_name(ping, reason)
 }
 
+/// Collects and submits the built-in "metrics" ping for eventual uploading right away,
+/// e.g. to test the metrics ping scheduler without waiting for it.
+///
+/// See [`glean_core::glean_submit_metrics_ping`].
+pub fn submit_metrics_ping(reason: Option<&str>) {
+    glean_core::glean_submit_metrics_ping(reason.map(|s| s.to_string()))
+}
+
 /// Indicate that an experiment is running.  Glean will then add an
 /// experiment annotation to the environment which is sent with pings. This
 /// infomration is not persisted between runs.
@@ -169,6 +372,36 @@ pub fn set_experiment_inactive(experiment_id: String) {
     glean_core::glean_set_experiment_inactive(experiment_id)
 }
 
+/// Records a JSON value into a metric, choosing the operation by the metric's type.
+///
+/// This is meant for driving Glean from a generic source of data, e.g. an event bus,
+/// without matching on every metric type:
+///
+/// * booleans, quantities and strings are set,
+/// * counters are incremented,
+/// * string lists are set from an array of strings.
+///
+/// JSON values that don't match the metric's type record an
+/// [`ErrorType::InvalidValue`] error on the metric.
+///
+/// ```rust,ignore
+/// glean::record_from_json(&metrics::bus::connected, serde_json::json!(true));
+/// ```
+pub fn record_from_json(metric: impl Into<MetricRef>, value: serde_json::Value) {
+    glean_core::glean_record_from_json(metric.into(), value)
+}
+
+/// Deletes all data stored for a metric, in all stores it is sent in.
+///
+/// Unlike resetting a metric, this also removes the errors recorded for it,
+/// e.g. to honor a request to delete a specific data point.
+/// Deleting a metric that has no data stored is a no-op.
+///
+/// See [`glean_core::Glean::delete_metric`].
+pub fn delete_metric(category: &str, name: &str) {
+    glean_core::glean_delete_metric(category.to_string(), name.to_string())
+}
+
 /// Performs the collection/cleanup operations required by becoming active.
 ///
 /// This functions generates a baseline ping with reason `active`
@@ -189,6 +422,77 @@ pub fn handle_client_inactive() {
     glean_core::glean_handle_client_inactive()
 }
 
+/// Gets the names of all registered pings, sorted alphabetically.
+///
+/// This includes the built-in pings (e.g. `baseline` or `deletion-request`),
+/// which Glean registers on initialization.
+///
+/// This will block on the dispatcher and panic if called before Glean is initialized.
+pub fn get_registered_ping_names() -> Vec<String> {
+    glean_core::glean_get_registered_ping_names()
+}
+
+/// Registers an observer notified whenever a new value is stored for any metric.
+///
+/// The observer is called with the metric identifier and the new value on the dispatcher thread.
+/// To observe a single metric use [`MetricType::observe`].
+///
+/// # Returns
+///
+/// The id to pass to [`remove_metric_observer`] to remove the observer again.
+pub fn observe_metrics(observer: GlobalMetricObserver) -> ObserverId {
+    glean_core::glean_observe_metrics(observer)
+}
+
+/// Removes an observer registered with [`observe_metrics`] or [`MetricType::observe`].
+pub fn remove_metric_observer(id: ObserverId) {
+    glean_core::glean_remove_metric_observer(id)
+}
+
+/// Enables or disables a single ping, e.g. to turn off the `events` ping,
+/// without disabling all metrics.
+///
+/// A disabled ping is neither assembled nor uploaded when submitted,
+/// and metrics that are only sent in disabled pings are not recorded anymore.
+/// This is persisted across restarts.
+/// The `deletion-request` ping can't be disabled.
+pub fn set_ping_enabled(ping_name: &str, enabled: bool) {
+    glean_core::glean_set_ping_enabled(ping_name.to_string(), enabled)
+}
+
+/// Collects and submits the built-in `crash` ping.
+///
+/// The ping contains the time of the crash, its `cause` and the given `annotations`,
+/// in addition to all metrics sent in the `crash` ping.
+/// It is assembled and persisted synchronously, without waiting on pending recordings,
+/// to maximize the chance it is stored before the process goes down.
+/// It is uploaded on the next opportunity, usually on the next start.
+///
+/// # Arguments
+///
+/// * `cause` - What caused the crash.
+/// * `annotations` - Additional information, keyed by label.
+///   Labels must be valid labels for labeled metrics.
+///
+/// # Returns
+///
+/// Whether the ping was successfully stored. `false` if Glean is not initialized.
+pub fn submit_crash_ping(cause: CrashCause, annotations: HashMap<String, String>) -> bool {
+    glean_core::glean_submit_crash_ping(cause, annotations)
+}
+
+/// Gets the path of the directory Glean keeps its data in,
+/// e.g. to locate the pending pings for inspection.
+///
+/// This is the `data_path` of the [`Configuration`] Glean was initialized with.
+///
+/// # Returns
+///
+/// The data path, or `None` if Glean isn't initialized (yet).
+pub fn data_path() -> Option<PathBuf> {
+    glean_core::glean_get_data_path()
+}
+
 /// TEST ONLY FUNCTION.
 /// Checks if an experiment is currently active.
 pub fn test_is_experiment_active(experiment_id: String) -> bool {
@@ -202,6 +506,29 @@ pub fn test_get_experiment_data(experiment_id: String) -> Option<RecordedExperim
     glean_core::glean_test_get_experiment_data(experiment_id)
 }
 
+/// TEST ONLY FUNCTION.
+/// Returns the metrics currently stored for the ping `ping_name` as JSON,
+/// as they would be collected into the ping payload.
+///
+/// Waits for all pending recordings before taking the snapshot.
+/// If `clear_store` is `true`, data with `ping` lifetime is cleared, as if the ping was sent,
+/// but nothing is uploaded.
+/// Returns `serde_json::Value::Null` if there is no data stored for this ping.
+pub fn test_snapshot_store(ping_name: &str, clear_store: bool) -> serde_json::Value {
+    glean_core::glean_test_snapshot_store(ping_name.to_string(), clear_store)
+        .unwrap_or(serde_json::Value::Null)
+}
+
+/// Gets all data recorded into a store, including its events, as JSON.
+///
+/// Use `dump_store("debug")` to inspect everything recorded while
+/// [`Configuration::mirror_to_debug_store`] is enabled.
+/// Waits for all pending recordings first. Nothing is cleared.
+/// Returns `serde_json::Value::Null` if there is no data stored.
+pub fn dump_store(store_name: &str) -> serde_json::Value {
+    glean_core::glean_dump_store(store_name.to_string()).unwrap_or(serde_json::Value::Null)
+}
+
 /// Destroy the global Glean state.
 pub(crate) fn destroy_glean(clear_stores: bool) {
     glean_core::glean_test_destroy_glean(clear_stores)
@@ -259,6 +586,21 @@ pub fn set_source_tags(tags: Vec<String>) {
     glean_core::glean_set_source_tags(tags);
 }
 
+/// Attaches metadata to every ping submitted from now on, for the rest of this session,
+/// e.g. a deployment environment tag.
+///
+/// The entries show in the `metadata` object of the ping payloads.
+/// Metadata set on a ping itself through [`PingType::set_metadata`](private::PingType::set_metadata) takes precedence.
+///
+/// Keys must be non-empty and at most 40 bytes long, values at most 100 bytes long.
+/// At most 20 keys can be set, invalid entries are ignored.
+///
+/// The pipeline schema doesn't accept the `metadata` object yet,
+/// so pings carrying it are rejected on ingestion until it does.
+pub fn set_ping_metadata(key: &str, value: &str) {
+    glean_core::glean_set_ping_metadata(key.to_string(), value.to_string());
+}
+
 /// Returns a timestamp corresponding to "now" with millisecond precision.
 pub fn get_timestamp_ms() -> u64 {
     glean_core::get_timestamp_ms()
@@ -271,6 +613,3 @@ pub fn get_timestamp_ms() -> u64 {
 pub fn persist_ping_lifetime_data() {
     glean_core::persist_ping_lifetime_data();
 }
-
-#[cfg(test)]
-mod test;
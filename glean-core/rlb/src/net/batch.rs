@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Grouping several pings into a single upload request.
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::net::PingRequest;
+
+/// The content type of a batched upload request.
+pub const BATCH_CONTENT_TYPE: &str = "application/x-glean-batch+json; charset=utf-8";
+
+/// The body of a batched upload request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingBatch {
+    /// The pings in this batch, in the order they were collected.
+    pub pings: Vec<BatchedPing>,
+}
+
+/// A single ping in a [`PingBatch`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchedPing {
+    /// The document id of the ping.
+    pub document_id: String,
+    /// The path the ping would have been uploaded to on its own.
+    pub path: String,
+    /// The uncompressed ping payload.
+    pub payload: JsonValue,
+}
+
+impl BatchedPing {
+    /// Unpacks the payload of a ping request.
+    ///
+    /// Returns `None` if the body is not valid JSON.
+    pub(crate) fn from_request(request: &PingRequest) -> Option<Self> {
+        let gzipped = request.headers.get("Content-Encoding").map(String::as_str) == Some("gzip");
+
+        let payload = if gzipped {
+            let mut body = Vec::with_capacity(request.body.len());
+            GzDecoder::new(&request.body[..])
+                .read_to_end(&mut body)
+                .ok()?;
+            serde_json::from_slice(&body).ok()?
+        } else {
+            serde_json::from_slice(&request.body).ok()?
+        };
+
+        Some(Self {
+            document_id: request.document_id.clone(),
+            path: request.path.clone(),
+            payload,
+        })
+    }
+}
+
+/// Returns the name of the ping the request is for.
+///
+/// The path format is `/submit/<app_id>/<ping_name>/<schema_version>/<doc_id>`.
+pub(crate) fn ping_name(request: &PingRequest) -> Option<&str> {
+    request.path.split('/').nth(3)
+}
+
+/// Returns the path to upload a batch to, given the path of one of its pings.
+///
+/// The batch path format is `/batch/<app_id>/<ping_name>`.
+pub(crate) fn batch_path(ping_path: &str) -> Option<String> {
+    let mut parts = ping_path.split('/').skip(2);
+    let app_id = parts.next()?;
+    let ping_name = parts.next()?;
+    Some(format!("/batch/{}/{}", app_id, ping_name))
+}
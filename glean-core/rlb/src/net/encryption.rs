@@ -0,0 +1,326 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Encrypting pings as JWE before upload.
+//!
+//! Pings are encrypted with ECDH-ES key agreement on the P-256 curve and A256GCM
+//! content encryption, see RFC 7516 and RFC 7518, the same way Glean.js encrypts them.
+//!
+//! Only available with the `ping_encryption` feature.
+
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit, Nonce, OsRng};
+use aes_gcm::Aes256Gcm;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+
+use super::PingTransform;
+
+/// The key management algorithm, as named in the JWE header.
+const ALGORITHM: &str = "ECDH-ES";
+/// The content encryption algorithm, as named in the JWE header.
+const ENCRYPTION: &str = "A256GCM";
+/// The size of the A256GCM content encryption key, in bytes.
+const KEY_SIZE: usize = 32;
+
+/// A public key in JSON Web Key format, see RFC 7517.
+///
+/// Only elliptic curve keys on the P-256 curve are supported.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    /// The key type, `"EC"`.
+    pub kty: String,
+    /// The curve, `"P-256"`.
+    pub crv: String,
+    /// The base64url-encoded x coordinate of the key.
+    pub x: String,
+    /// The base64url-encoded y coordinate of the key.
+    pub y: String,
+    /// The id of the key, if any, sent along so the server knows which key to decrypt with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+/// Encrypts ping payloads for a public key.
+#[derive(Debug)]
+pub(crate) struct PingEncryption {
+    key: PublicKey,
+    kid: Option<String>,
+}
+
+impl PingEncryption {
+    /// Creates a new encryption for the given public key.
+    ///
+    /// Fails if the key isn't a valid P-256 elliptic curve key.
+    pub(crate) fn new(jwk: &Jwk) -> Result<Self, String> {
+        if jwk.kty != "EC" || jwk.crv != "P-256" {
+            return Err(format!(
+                "unsupported key type '{}' on curve '{}', only P-256 EC keys are supported",
+                jwk.kty, jwk.crv
+            ));
+        }
+        let x = decode_coordinate(&jwk.x)?;
+        let y = decode_coordinate(&jwk.y)?;
+        let point = EncodedPoint::from_affine_coordinates(&x.into(), &y.into(), false);
+        let key = Option::from(PublicKey::from_encoded_point(&point))
+            .ok_or_else(|| "the key is not a point on the P-256 curve".to_string())?;
+
+        Ok(Self {
+            key,
+            kid: jwk.kid.clone(),
+        })
+    }
+
+    /// Turns this encryption into a ping transform,
+    /// which encrypts payloads after applying `transform` to them, if any.
+    pub(crate) fn after(self, transform: Option<PingTransform>) -> PingTransform {
+        Box::new(move |mut payload| {
+            if let Some(transform) = &transform {
+                payload = transform(payload);
+            }
+            self.encrypt(&payload)
+        })
+    }
+
+    /// Encrypts a ping payload.
+    ///
+    /// Returns the JSON body to upload instead, `{"payload": "<JWE>"}`,
+    /// with the JWE in its compact serialization.
+    pub(crate) fn encrypt(&self, payload: &[u8]) -> Vec<u8> {
+        let ephemeral_secret = SecretKey::random(&mut OsRng);
+        let iv = Aes256Gcm::generate_nonce(&mut OsRng);
+        self.encrypt_with(&ephemeral_secret, &iv, payload)
+    }
+
+    /// Encrypts a ping payload with the given ephemeral key and initialization vector.
+    fn encrypt_with(
+        &self,
+        ephemeral_secret: &SecretKey,
+        iv: &Nonce<Aes256Gcm>,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let ephemeral_key = ephemeral_secret.public_key().to_encoded_point(false);
+        let mut header = json!({
+            "alg": ALGORITHM,
+            "enc": ENCRYPTION,
+            "typ": "JWE",
+            "epk": {
+                "kty": "EC",
+                "crv": "P-256",
+                "x": encode(ephemeral_key.x().expect("IMPOSSIBLE: Uncompressed point without x")),
+                "y": encode(ephemeral_key.y().expect("IMPOSSIBLE: Uncompressed point without y")),
+            },
+        });
+        if let Some(kid) = &self.kid {
+            header["kid"] = json!(kid);
+        }
+        let protected_header = encode(header.to_string());
+
+        let key = derive_key(&self.shared_secret(ephemeral_secret));
+        let (ciphertext, tag) = seal(&key, iv, protected_header.as_bytes(), payload);
+
+        // ECDH-ES agrees on the content encryption key directly,
+        // so the encrypted key part of the JWE is empty.
+        let jwe = format!(
+            "{}..{}.{}.{}",
+            protected_header,
+            encode(iv),
+            encode(ciphertext),
+            encode(tag)
+        );
+        serde_json::to_vec(&json!({ "payload": jwe }))
+            .expect("IMPOSSIBLE: Serializing a string failed")
+    }
+
+    /// Agrees on the ECDH shared secret between an ephemeral key and the public key.
+    fn shared_secret(&self, ephemeral_secret: &SecretKey) -> Vec<u8> {
+        p256::ecdh::diffie_hellman(ephemeral_secret.to_nonzero_scalar(), self.key.as_affine())
+            .raw_secret_bytes()
+            .to_vec()
+    }
+}
+
+/// Derives the A256GCM content encryption key from the ECDH shared secret.
+fn derive_key(shared_secret: &[u8]) -> [u8; KEY_SIZE] {
+    let mut key = [0; KEY_SIZE];
+    concat_kdf(shared_secret, ENCRYPTION, b"", b"", &mut key);
+    key
+}
+
+/// Fills `key` from the ECDH shared secret,
+/// using the Concat KDF as specified in RFC 7518, section 4.6.2.
+///
+/// `party_u` and `party_v` are the `apu` and `apv` header parameters, if any.
+fn concat_kdf(
+    shared_secret: &[u8],
+    algorithm: &str,
+    party_u: &[u8],
+    party_v: &[u8],
+    key: &mut [u8],
+) {
+    // AlgorithmID || PartyUInfo || PartyVInfo || SuppPubInfo,
+    // each but the last prefixed by its length, with the key size in bits.
+    let mut other_info = Vec::new();
+    for datum in [algorithm.as_bytes(), party_u, party_v] {
+        other_info.extend_from_slice(&(datum.len() as u32).to_be_bytes());
+        other_info.extend_from_slice(datum);
+    }
+    other_info.extend_from_slice(&((key.len() * 8) as u32).to_be_bytes());
+
+    concat_kdf::derive_key_into::<Sha256>(shared_secret, &other_info, key)
+        .expect("IMPOSSIBLE: Deriving the key failed");
+}
+
+/// Encrypts the plaintext with A256GCM, authenticating the additional data.
+///
+/// Returns the ciphertext and the authentication tag.
+fn seal(
+    key: &[u8; KEY_SIZE],
+    iv: &Nonce<Aes256Gcm>,
+    additional_data: &[u8],
+    plaintext: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let mut ciphertext = plaintext.to_vec();
+    let tag = Aes256Gcm::new(key.into())
+        .encrypt_in_place_detached(iv, additional_data, &mut ciphertext)
+        .expect("IMPOSSIBLE: Ping too large to encrypt");
+    (ciphertext, tag.to_vec())
+}
+
+/// Encodes bytes as unpadded base64url, as used throughout JOSE.
+fn encode<T: AsRef<[u8]>>(bytes: T) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes a base64url-encoded key coordinate.
+fn decode_coordinate(coordinate: &str) -> Result<[u8; 32], String> {
+    let bytes = base64::decode_config(coordinate, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| format!("invalid key coordinate: {}", e))?;
+    let mut decoded = [0; 32];
+    if bytes.len() != decoded.len() {
+        return Err(format!(
+            "invalid key coordinate: expected 32 bytes, got {}",
+            bytes.len()
+        ));
+    }
+    decoded.copy_from_slice(&bytes);
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(input: &str) -> Vec<u8> {
+        base64::decode_config(input, base64::URL_SAFE_NO_PAD).unwrap()
+    }
+
+    // The ECDH-ES key agreement example of RFC 7518, Appendix C.
+    const ALICE_PRIVATE_KEY: &str = "0_NxaRPUMQoAJt50Gz8YiTr8gRTwyEaCumd-MToTmIo";
+    const BOB_PUBLIC_KEY_X: &str = "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ";
+    const BOB_PUBLIC_KEY_Y: &str = "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck";
+    const SHARED_SECRET: [u8; 32] = [
+        158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49, 110,
+        163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+    ];
+
+    #[test]
+    fn key_agreement_matches_rfc7518_appendix_c() {
+        let encryption = PingEncryption::new(&Jwk {
+            kty: "EC".into(),
+            crv: "P-256".into(),
+            x: BOB_PUBLIC_KEY_X.into(),
+            y: BOB_PUBLIC_KEY_Y.into(),
+            kid: None,
+        })
+        .unwrap();
+        let ephemeral_secret = SecretKey::from_be_bytes(&decode(ALICE_PRIVATE_KEY)).unwrap();
+
+        assert_eq!(
+            SHARED_SECRET.to_vec(),
+            encryption.shared_secret(&ephemeral_secret)
+        );
+    }
+
+    #[test]
+    fn pings_are_encrypted_with_the_ephemeral_key() {
+        let encryption = PingEncryption::new(&Jwk {
+            kty: "EC".into(),
+            crv: "P-256".into(),
+            x: BOB_PUBLIC_KEY_X.into(),
+            y: BOB_PUBLIC_KEY_Y.into(),
+            kid: Some("bob".into()),
+        })
+        .unwrap();
+        let ephemeral_secret = SecretKey::from_be_bytes(&decode(ALICE_PRIVATE_KEY)).unwrap();
+        let iv = [7; 12];
+
+        let body = encryption.encrypt_with(&ephemeral_secret, &iv.into(), b"{}");
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let parts: Vec<&str> = body["payload"].as_str().unwrap().split('.').collect();
+        assert_eq!(5, parts.len());
+
+        let header: serde_json::Value = serde_json::from_slice(&decode(parts[0])).unwrap();
+        assert_eq!("ECDH-ES", header["alg"]);
+        assert_eq!("A256GCM", header["enc"]);
+        assert_eq!("bob", header["kid"]);
+        assert_eq!(
+            "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+            header["epk"]["x"]
+        );
+        assert_eq!(
+            "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps",
+            header["epk"]["y"]
+        );
+        assert_eq!("", parts[1]);
+        assert_eq!(iv.to_vec(), decode(parts[2]));
+
+        let mut payload = decode(parts[3]);
+        Aes256Gcm::new(&derive_key(&SHARED_SECRET).into())
+            .decrypt_in_place_detached(
+                &iv.into(),
+                parts[0].as_bytes(),
+                &mut payload,
+                decode(parts[4])[..].into(),
+            )
+            .unwrap();
+        assert_eq!(b"{}".to_vec(), payload);
+    }
+
+    #[test]
+    fn concat_kdf_matches_rfc7518_appendix_c() {
+        let mut key = [0; 16];
+        concat_kdf(&SHARED_SECRET, "A128GCM", b"Alice", b"Bob", &mut key);
+
+        assert_eq!(decode("VqqN6vgjbSBcIijNcacQGg"), key);
+    }
+
+    #[test]
+    fn content_encryption_matches_rfc7516_appendix_a1() {
+        let key = [
+            177, 161, 244, 128, 84, 143, 225, 115, 63, 180, 3, 255, 107, 154, 212, 246, 138, 7,
+            110, 91, 112, 46, 34, 105, 47, 130, 203, 46, 122, 234, 64, 252,
+        ];
+        let iv = decode("48V1_ALb6US04U3b");
+        let protected_header = "eyJhbGciOiJSU0EtT0FFUCIsImVuYyI6IkEyNTZHQ00ifQ";
+        let plaintext = "The true sign of intelligence is not knowledge but imagination.";
+
+        let (ciphertext, tag) = seal(
+            &key,
+            iv[..].into(),
+            protected_header.as_bytes(),
+            plaintext.as_bytes(),
+        );
+
+        assert_eq!(
+            decode(
+                "5eym8TW_c8SuK0ltJ3rpYIzOeDQz7TALvtu6UG9oMo4vpzs9tX_EFShS8iB7j6jiSdiwkIr3ajwQzaBtQD_A"
+            ),
+            ciphertext
+        );
+        assert_eq!(decode("XFBoMYUZodetZdvTiFvSkQ"), tag);
+    }
+}
@@ -0,0 +1,41 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+use crate::net::{PingUploader, UploadResult};
+
+/// An uploader that logs every ping instead of sending it.
+///
+/// The URL, headers and (decompressed) body of each ping are logged at `info` level
+/// and the upload is reported as successful.
+#[derive(Debug)]
+pub struct LoggingUploader;
+
+impl PingUploader for LoggingUploader {
+    fn upload(&self, url: String, body: Vec<u8>, headers: Vec<(String, String)>) -> UploadResult {
+        let gzipped = headers
+            .iter()
+            .any(|(name, value)| name == "Content-Encoding" && value == "gzip");
+        let body = if gzipped {
+            let mut decoded = String::new();
+            match GzDecoder::new(&body[..]).read_to_string(&mut decoded) {
+                Ok(_) => decoded,
+                Err(e) => format!("<failed to decompress body: {}>", e),
+            }
+        } else {
+            String::from_utf8_lossy(&body).into_owned()
+        };
+
+        log::info!(
+            "Uploading ping to {}\nHeaders: {:?}\n{}",
+            url,
+            headers,
+            body
+        );
+        UploadResult::http_status(200)
+    }
+}
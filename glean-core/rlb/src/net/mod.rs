@@ -7,19 +7,48 @@
 //! This doesn't perform the actual upload but rather handles
 //! retries, upload limitations and error tracking.
 
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read, Write};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc, Mutex,
 };
 use std::thread;
 use std::time::Duration;
 
+use crossbeam_channel::{Receiver, Sender};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use glean_core::upload::PingUploadTask;
 pub use glean_core::upload::{PingRequest, UploadResult, UploadTaskAction};
+use once_cell::sync::Lazy;
 
+pub use batch::{BatchedPing, PingBatch, BATCH_CONTENT_TYPE};
+#[cfg(feature = "ping_encryption")]
+pub use encryption::Jwk;
+#[cfg(feature = "ping_encryption")]
+pub(crate) use encryption::PingEncryption;
 pub use http_uploader::*;
+pub use logging_uploader::*;
+pub use noop_uploader::*;
+#[cfg(any(test, feature = "test_utils"))]
+pub use recording_uploader::{RecordedUpload, RecordingUploader};
+pub(crate) use schema::{SchemaCheck, DEFAULT_SCHEMA_FAILURE_THRESHOLD};
+pub use schema::{SchemaCircuitObserver, SchemaValidator};
 
+mod batch;
+#[cfg(feature = "ping_encryption")]
+mod encryption;
 mod http_uploader;
+mod logging_uploader;
+mod noop_uploader;
+#[cfg(any(test, feature = "test_utils"))]
+mod recording_uploader;
+mod schema;
+
+/// The size of a request body, in bytes, from which on it is passed to
+/// [`PingUploader::upload_streaming`] instead of [`PingUploader::upload`].
+pub const STREAMING_UPLOAD_THRESHOLD: usize = 256 * 1024;
 
 /// A description of a component used to upload pings.
 pub trait PingUploader: std::fmt::Debug + Send + Sync {
@@ -32,42 +61,492 @@ pub trait PingUploader: std::fmt::Debug + Send + Sync {
     /// * `headers` - a vector of tuples containing the headers to send with
     ///   the request, i.e. (Name, Value).
     fn upload(&self, url: String, body: Vec<u8>, headers: Vec<(String, String)>) -> UploadResult;
+
+    /// Uploads a ping to a server, reading the body while sending it.
+    ///
+    /// Glean uses this instead of [`upload`](PingUploader::upload) for bodies of at least
+    /// [`STREAMING_UPLOAD_THRESHOLD`] bytes, so uploaders streaming to the network
+    /// don't need to hold another copy of large pings.
+    /// The default implementation passes the body on to [`upload`](PingUploader::upload).
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL path to upload the data to.
+    /// * `body` - a reader of the serialized text data to send,
+    ///   owning the buffer so the default implementation can pass it on without a copy.
+    ///   Its length is given in the `Content-Length` header.
+    /// * `headers` - a vector of tuples containing the headers to send with
+    ///   the request, i.e. (Name, Value).
+    fn upload_streaming(
+        &self,
+        url: String,
+        body: Cursor<Vec<u8>>,
+        headers: Vec<(String, String)>,
+    ) -> UploadResult {
+        self.upload(url, body.into_inner(), headers)
+    }
+}
+
+/// An observer of the requests Glean hands to the [`PingUploader`], for network debugging.
+///
+/// See [`Configuration::upload_interceptor`](crate::Configuration::upload_interceptor).
+pub trait UploadInterceptor: std::fmt::Debug + Send + Sync {
+    /// Called right before a request is passed to [`PingUploader::upload`].
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL the request is sent to.
+    /// * `headers` - the headers sent with the request, i.e. (Name, Value).
+    /// * `body_len` - the length of the body in bytes, as sent.
+    fn before_upload(&self, url: &str, headers: &[(String, String)], body_len: usize);
+}
+
+/// Checks whether `name` is a valid HTTP header name.
+///
+/// A header name must be a non-empty token, as defined in
+/// [RFC 7230](https://datatracker.ietf.org/doc/html/rfc7230#section-3.2.6).
+fn is_valid_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// The number of consecutive recoverable failures of the primary uploader
+/// after which the fallback uploader is tried.
+const FALLBACK_AFTER_FAILURES: u32 = 2;
+
+/// Callers of [`submit_ping_and_await`](crate::test::submit_ping_and_await)
+/// waiting for the next upload result of a ping, by ping name.
+static UPLOAD_WAITERS: Lazy<Mutex<HashMap<String, Vec<Sender<UploadResult>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns a receiver for the result of the next upload of a ping named `ping_name`.
+pub(crate) fn await_upload(ping_name: &str) -> Receiver<UploadResult> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    UPLOAD_WAITERS
+        .lock()
+        .unwrap()
+        .entry(ping_name.to_string())
+        .or_default()
+        .push(sender);
+    receiver
+}
+
+/// Stops waiting for any upload results.
+///
+/// Anyone still waiting is told that no result will arrive.
+pub(crate) fn clear_upload_waiters() {
+    UPLOAD_WAITERS.lock().unwrap().clear();
+}
+
+/// Passes the result of an upload of the ping at `path` to everyone waiting for it.
+fn notify_waiters(path: &str, result: &UploadResult) {
+    let ping_name = match path.split('/').nth(3) {
+        Some(ping_name) => ping_name,
+        None => return,
+    };
+    let mut waiters = UPLOAD_WAITERS.lock().unwrap();
+    if let Some(senders) = waiters.remove(ping_name) {
+        for sender in senders {
+            // The waiter might have given up already.
+            let _ = sender.send(result.clone());
+        }
+    }
+}
+
+/// A ping ready to be sent, as returned by [`drain_pings`](crate::drain_pings).
+///
+/// This is what would have been passed to the [`PingUploader`],
+/// after applying the ping transform and adding the pipeline header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembledPing {
+    /// The name of the ping, e.g. `metrics`.
+    pub ping_name: String,
+    /// The document id of the ping.
+    pub document_id: String,
+    /// The URL to upload the ping to.
+    pub url: String,
+    /// The request body. It is gzip-compressed if `headers` contain `Content-Encoding: gzip`.
+    pub body: Vec<u8>,
+    /// The headers to send with the request.
+    pub headers: Vec<(String, String)>,
 }
 
 /// The logic for uploading pings: this leaves the actual upload mechanism as
 /// a detail of the user-provided object implementing [`PingUploader`].
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct UploadManager {
     inner: Arc<Inner>,
 }
 
-#[derive(Debug)]
 struct Inner {
     server_endpoint: String,
+    /// Servers to send specific pings to instead, by ping name.
+    endpoint_overrides: HashMap<String, String>,
     uploader: Box<dyn PingUploader + 'static>,
+    fallback_uploader: Option<Box<dyn PingUploader + 'static>>,
+    /// The number of consecutive recoverable failures of `uploader`.
+    primary_failures: AtomicU32,
+    pipeline_header: Option<(String, String)>,
+    batch_uploads: Option<usize>,
+    ping_transform: Option<PingTransform>,
+    schema_check: Option<SchemaCheck>,
+    url_builder: Option<UrlBuilder>,
+    upload_interceptor: Option<Box<dyn UploadInterceptor + 'static>>,
     thread_running: AtomicBool,
 }
 
+/// A callback transforming request bodies before upload.
+///
+/// See [`Configuration::ping_transform`](crate::Configuration::ping_transform).
+pub type PingTransform = Box<dyn Fn(Vec<u8>) -> Vec<u8> + Send + Sync + 'static>;
+
+/// A callback building the submission URL of a ping
+/// from its application id, ping name and document id.
+///
+/// See [`Configuration::url_builder`](crate::Configuration::url_builder).
+pub type UrlBuilder = Box<dyn Fn(&str, &str, &str) -> String + Send + Sync + 'static>;
+
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Inner")
+            .field("server_endpoint", &self.server_endpoint)
+            .field("endpoint_overrides", &self.endpoint_overrides)
+            .field("uploader", &self.uploader)
+            .field("fallback_uploader", &self.fallback_uploader)
+            .field("primary_failures", &self.primary_failures)
+            .field("pipeline_header", &self.pipeline_header)
+            .field("batch_uploads", &self.batch_uploads)
+            .field(
+                "ping_transform",
+                &self
+                    .ping_transform
+                    .as_ref()
+                    .map(|_| "Fn(Vec<u8>) -> Vec<u8>"),
+            )
+            .field("schema_check", &self.schema_check)
+            .field(
+                "url_builder",
+                &self
+                    .url_builder
+                    .as_ref()
+                    .map(|_| "Fn(&str, &str, &str) -> String"),
+            )
+            .field("upload_interceptor", &self.upload_interceptor)
+            .field("thread_running", &self.thread_running)
+            .finish()
+    }
+}
+
+impl Inner {
+    /// Builds the URL to upload a request for `path` to.
+    ///
+    /// The URL builder, if any, is used for paths of single pings,
+    /// `/submit/<app_id>/<ping_name>/<schema_version>/<doc_id>`.
+    /// Everything else is uploaded to the path on the server endpoint of the ping,
+    /// which is overridden for some pings.
+    fn upload_url(&self, path: &str) -> String {
+        let parts: Vec<&str> = path.split('/').collect();
+        if let Some(url_builder) = &self.url_builder {
+            if let ["", "submit", app_id, ping_name, _, doc_id] = parts[..] {
+                return url_builder(app_id, ping_name, doc_id);
+            }
+        }
+
+        // Both single pings and batches have the ping name in the 4th position.
+        let endpoint = parts
+            .get(3)
+            .and_then(|ping_name| self.endpoint_overrides.get(*ping_name))
+            .unwrap_or(&self.server_endpoint);
+        format!("{}{}", endpoint, path)
+    }
+
+    /// Passes a request to `uploader`, letting the interceptor, if any, see it first.
+    fn upload_with(
+        &self,
+        uploader: &dyn PingUploader,
+        url: String,
+        body: Vec<u8>,
+        headers: Vec<(String, String)>,
+    ) -> UploadResult {
+        if let Some(interceptor) = &self.upload_interceptor {
+            interceptor.before_upload(&url, &headers, body.len());
+        }
+        if body.len() >= STREAMING_UPLOAD_THRESHOLD {
+            uploader.upload_streaming(url, Cursor::new(body), headers)
+        } else {
+            uploader.upload(url, body, headers)
+        }
+    }
+
+    /// Applies the ping transform to a request and adds the pipeline header.
+    fn prepare(
+        &self,
+        mut body: Vec<u8>,
+        mut headers: Vec<(String, String)>,
+    ) -> (Vec<u8>, Vec<(String, String)>) {
+        if let Some(transform) = &self.ping_transform {
+            body = transform_body(transform, body, &headers);
+            for (name, value) in headers.iter_mut() {
+                if name == "Content-Length" {
+                    *value = body.len().to_string();
+                }
+            }
+        }
+        if let Some(header) = &self.pipeline_header {
+            headers.push(header.clone());
+        }
+        (body, headers)
+    }
+
+    /// Sends a request through the uploader, after preparing it.
+    fn send(&self, path: &str, body: Vec<u8>, headers: Vec<(String, String)>) -> UploadResult {
+        let (body, headers) = self.prepare(body, headers);
+        let upload_url = self.upload_url(path);
+
+        let fallback = match &self.fallback_uploader {
+            Some(fallback) => fallback,
+            None => return self.upload_with(&*self.uploader, upload_url, body, headers),
+        };
+
+        let result = self.upload_with(
+            &*self.uploader,
+            upload_url.clone(),
+            body.clone(),
+            headers.clone(),
+        );
+        if !matches!(result, UploadResult::RecoverableFailure { .. }) {
+            self.primary_failures.store(0, Ordering::SeqCst);
+            return result;
+        }
+
+        let failures = self.primary_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures < FALLBACK_AFTER_FAILURES {
+            return result;
+        }
+        log::info!(
+            "Primary uploader failed {} times in a row. Trying the fallback uploader.",
+            failures
+        );
+        self.upload_with(&**fallback, upload_url, body, headers)
+    }
+
+    /// Whether the request passes schema validation, if any.
+    fn is_valid(&self, request: &PingRequest) -> bool {
+        match &self.schema_check {
+            Some(check) => check.check(request),
+            None => true,
+        }
+    }
+
+    /// Uploads a single ping and processes the response.
+    fn upload(&self, request: PingRequest) -> UploadTaskAction {
+        let doc_id = request.document_id.clone();
+        if !self.is_valid(&request) {
+            // Retrying won't make the ping valid, so it is dropped.
+            let result = UploadResult::unrecoverable_failure();
+            notify_waiters(&request.path, &result);
+            return glean_core::glean_process_ping_upload_response(doc_id, result);
+        }
+        let headers: Vec<(String, String)> = request.headers.into_iter().collect();
+        let result = self.send(&request.path, request.body, headers);
+        notify_waiters(&request.path, &result);
+        glean_core::glean_process_ping_upload_response(doc_id, result)
+    }
+
+    /// Uploads a batch of pings of the same type in a single request
+    /// and processes the response for each of them.
+    fn upload_batch(&self, requests: Vec<PingRequest>) -> UploadTaskAction {
+        let mut action = UploadTaskAction::Next;
+        let mut pings = Vec::with_capacity(requests.len());
+        for request in &requests {
+            let ping = if self.is_valid(request) {
+                batch::BatchedPing::from_request(request)
+            } else {
+                None
+            };
+            match ping {
+                Some(ping) => pings.push(ping),
+                None => {
+                    log::error!(
+                        "Failed to unpack or validate ping {} for batching. Dropping it.",
+                        request.document_id
+                    );
+                    notify_waiters(&request.path, &UploadResult::unrecoverable_failure());
+                    let result = glean_core::glean_process_ping_upload_response(
+                        request.document_id.clone(),
+                        UploadResult::unrecoverable_failure(),
+                    );
+                    if let UploadTaskAction::End = result {
+                        action = UploadTaskAction::End;
+                    }
+                }
+            }
+        }
+
+        let path = match pings.first().and_then(|ping| batch::batch_path(&ping.path)) {
+            Some(path) => path,
+            None => return action,
+        };
+        let doc_ids: Vec<String> = pings.iter().map(|ping| ping.document_id.clone()).collect();
+        let body = serde_json::to_vec(&batch::PingBatch { pings })
+            .expect("Failed to serialize a batch of pings");
+
+        // All pings in a batch come from the same Glean instance,
+        // so the headers of the first one apply to the whole batch.
+        let mut headers: Vec<(String, String)> = requests[0]
+            .headers
+            .iter()
+            .filter(|(name, _)| !name.starts_with("Content-"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        headers.push(("Content-Type".to_string(), BATCH_CONTENT_TYPE.to_string()));
+        headers.push(("Content-Length".to_string(), body.len().to_string()));
+
+        let result = self.send(&path, body, headers);
+        notify_waiters(&requests[0].path, &result);
+        for doc_id in doc_ids {
+            let response = glean_core::glean_process_ping_upload_response(doc_id, result.clone());
+            if let UploadTaskAction::End = response {
+                action = UploadTaskAction::End;
+            }
+        }
+        action
+    }
+}
+
+/// Applies `transform` to the uncompressed payload of a request body.
+///
+/// Gzip-compressed bodies are decompressed first and the result is compressed again,
+/// so the `Content-Encoding` header stays accurate.
+fn transform_body(
+    transform: &PingTransform,
+    body: Vec<u8>,
+    headers: &[(String, String)],
+) -> Vec<u8> {
+    let gzipped = headers
+        .iter()
+        .any(|(name, value)| name == "Content-Encoding" && value == "gzip");
+    if !gzipped {
+        return transform(body);
+    }
+
+    let mut payload = Vec::with_capacity(body.len());
+    if let Err(e) = GzDecoder::new(&body[..]).read_to_end(&mut payload) {
+        log::error!("Failed to decompress the ping body to transform it: {}", e);
+        return transform(body);
+    }
+    let transformed = transform(payload);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&transformed)
+        .expect("IMPOSSIBLE: Compressing into memory failed");
+    encoder
+        .finish()
+        .expect("IMPOSSIBLE: Compressing into memory failed")
+}
+
+/// Collects up to `max` pings of the same type as `first` that are ready for upload.
+///
+/// Returns the batch and, if one was fetched, the next ping of another type.
+fn collect_batch(first: PingRequest, max: usize) -> (Vec<PingRequest>, Option<PingRequest>) {
+    let ping_name = batch::ping_name(&first).map(String::from);
+    let mut requests = vec![first];
+    while requests.len() < max {
+        match glean_core::glean_get_upload_task() {
+            PingUploadTask::Upload { request } => {
+                if batch::ping_name(&request) == ping_name.as_deref() {
+                    requests.push(request);
+                } else {
+                    return (requests, Some(request));
+                }
+            }
+            // Nothing else is ready right now, send what we have.
+            _ => break,
+        }
+    }
+    (requests, None)
+}
+
 impl UploadManager {
     /// Create a new instance of the upload manager.
     ///
     /// # Arguments
     ///
     /// * `server_endpoint` -  the server pings are sent to.
+    /// * `endpoint_overrides` - the servers to send specific pings to instead, by ping name.
     /// * `new_uploader` - the instance of the uploader used to send pings.
+    /// * `fallback_uploader` - an optional uploader to try when `new_uploader`
+    ///   repeatedly reports recoverable failures.
+    /// * `pipeline_header` - an optional (name, value) header to attach to every request.
+    ///   It is dropped, with an error logged, if the name is not a valid HTTP header name.
+    /// * `batch_uploads` - the maximum number of pings of the same type to send in one request.
+    ///   `None` sends every ping on its own.
+    /// * `ping_transform` - an optional callback replacing each request body before upload.
+    /// * `schema_check` - an optional check rejecting invalid pings before upload.
+    /// * `url_builder` - an optional callback building the URL of each ping instead.
+    /// * `upload_interceptor` - an optional observer of every request passed to an uploader.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         server_endpoint: String,
+        endpoint_overrides: HashMap<String, String>,
         new_uploader: Box<dyn PingUploader + 'static>,
+        fallback_uploader: Option<Box<dyn PingUploader + 'static>>,
+        pipeline_header: Option<(String, String)>,
+        batch_uploads: Option<usize>,
+        ping_transform: Option<PingTransform>,
+        schema_check: Option<SchemaCheck>,
+        url_builder: Option<UrlBuilder>,
+        upload_interceptor: Option<Box<dyn UploadInterceptor + 'static>>,
     ) -> Self {
+        let pipeline_header = pipeline_header.filter(|(name, _)| {
+            let valid = is_valid_header_name(name);
+            if !valid {
+                log::error!("Invalid pipeline header name '{}'. Ignoring it.", name);
+            }
+            valid
+        });
+
         Self {
             inner: Arc::new(Inner {
                 server_endpoint,
+                endpoint_overrides,
                 uploader: new_uploader,
+                fallback_uploader,
+                primary_failures: AtomicU32::new(0),
+                pipeline_header,
+                batch_uploads: batch_uploads.map(|max| max.max(1)),
+                ping_transform,
+                schema_check,
+                url_builder,
+                upload_interceptor,
                 thread_running: AtomicBool::new(false),
             }),
         }
     }
 
+    /// Prepares a request the way it would be uploaded, without uploading it.
+    pub(crate) fn assemble(&self, request: PingRequest) -> AssembledPing {
+        let ping_name = request
+            .path
+            .split('/')
+            .nth(3)
+            .unwrap_or_default()
+            .to_string();
+        let url = self.inner.upload_url(&request.path);
+        let headers = request.headers.into_iter().collect();
+        let (body, headers) = self.inner.prepare(request.body, headers);
+        AssembledPing {
+            ping_name,
+            document_id: request.document_id,
+            url,
+            body,
+            headers,
+        }
+    }
+
     /// Signals Glean to upload pings at the next best opportunity.
     pub(crate) fn trigger_upload(&self) {
         // If no other upload proces is running, we're the one starting it.
@@ -88,19 +567,26 @@ impl UploadManager {
             .name("glean.upload".into())
             .spawn(move || {
                 log::trace!("Started glean.upload thread");
+                // A ping fetched while collecting a batch of another type.
+                let mut held = None;
                 loop {
-                    let incoming_task = glean_core::glean_get_upload_task();
+                    let incoming_task = match held.take() {
+                        Some(request) => PingUploadTask::Upload { request },
+                        None => glean_core::glean_get_upload_task(),
+                    };
 
                     match incoming_task {
                         PingUploadTask::Upload { request } => {
                             log::trace!("Received upload task with request {:?}", request);
-                            let doc_id = request.document_id.clone();
-                            let upload_url = format!("{}{}", inner.server_endpoint, request.path);
-                            let headers: Vec<(String, String)> =
-                                request.headers.into_iter().collect();
-                            let result = inner.uploader.upload(upload_url, request.body, headers);
-                            // Process the upload response.
-                            match glean_core::glean_process_ping_upload_response(doc_id, result) {
+                            let action = match inner.batch_uploads {
+                                Some(max) => {
+                                    let (requests, next) = collect_batch(request, max);
+                                    held = next;
+                                    inner.upload_batch(requests)
+                                }
+                                None => inner.upload(request),
+                            };
+                            match action {
                                 UploadTaskAction::Next => continue,
                                 UploadTaskAction::End => break,
                             }
@@ -123,3 +609,19 @@ impl UploadManager {
             .expect("Failed to spawn Glean's uploader thread");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header_name_validation() {
+        assert!(is_valid_header_name("X-Pipeline"));
+        assert!(is_valid_header_name("x_staging.route"));
+
+        assert!(!is_valid_header_name(""));
+        assert!(!is_valid_header_name("X Pipeline"));
+        assert!(!is_valid_header_name("X-Pipeline:"));
+        assert!(!is_valid_header_name("X-Pipel\u{e9}ne"));
+    }
+}
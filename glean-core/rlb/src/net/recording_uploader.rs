@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::net::{PingUploader, UploadResult};
+
+/// How long [`RecordingUploader::assert_sent`] waits for a ping to be uploaded.
+const ASSERT_SENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request captured by a [`RecordingUploader`]: the URL, body and headers.
+pub type RecordedUpload = (String, Vec<u8>, Vec<(String, String)>);
+
+/// **TEST-ONLY.** An uploader capturing every request instead of sending it,
+/// for tests asserting on the pings sent.
+///
+/// Clones share the captured requests, so keep one and pass a clone as
+/// [`Configuration::uploader`](crate::Configuration::uploader).
+/// Every upload is reported as successful.
+///
+/// ```rust,ignore
+/// let uploader = RecordingUploader::new();
+/// let cfg = Configuration {
+///     uploader: Some(Box::new(uploader.clone())),
+///     ..Default::default()
+/// };
+/// glean::initialize(cfg, client_info);
+///
+/// custom_ping.submit(None);
+/// uploader.assert_sent("custom");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RecordingUploader {
+    uploads: Arc<(Mutex<Vec<RecordedUpload>>, Condvar)>,
+}
+
+/// Gets the ping name from the URL of a single ping or a batch,
+/// `.../submit/<app_id>/<ping_name>/...` or `.../batch/<app_id>/<ping_name>`.
+fn ping_name_of(url: &str) -> Option<&str> {
+    let mut parts = url.split('/');
+    parts.find(|&part| part == "submit" || part == "batch")?;
+    parts.nth(1)
+}
+
+impl RecordingUploader {
+    /// Creates an uploader that hasn't captured anything yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets all requests captured so far, in the order they were uploaded.
+    pub fn uploads(&self) -> Vec<RecordedUpload> {
+        self.uploads.0.lock().unwrap().clone()
+    }
+
+    /// Gets the requests captured so far for pings named `ping_name`.
+    pub fn uploads_of(&self, ping_name: &str) -> Vec<RecordedUpload> {
+        self.uploads()
+            .into_iter()
+            .filter(|(url, _, _)| ping_name_of(url) == Some(ping_name))
+            .collect()
+    }
+
+    /// Asserts that a ping named `ping_name` was uploaded.
+    ///
+    /// Uploads happen in the background, so this waits for the ping for a few seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such ping was uploaded in time.
+    pub fn assert_sent(&self, ping_name: &str) {
+        self.wait_for_uploads(ping_name, 1);
+    }
+
+    /// Waits for `count` pings named `ping_name` to be uploaded.
+    ///
+    /// Uploads happen in the background, so this waits for the pings for a few seconds.
+    ///
+    /// # Returns
+    ///
+    /// The requests captured for these pings, in the order they were uploaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer pings were uploaded in time.
+    pub fn wait_for_uploads(&self, ping_name: &str, count: usize) -> Vec<RecordedUpload> {
+        let deadline = Instant::now() + ASSERT_SENT_TIMEOUT;
+        let (uploads, uploaded) = &*self.uploads;
+        let mut uploads = uploads.lock().unwrap();
+        loop {
+            let sent: Vec<RecordedUpload> = uploads
+                .iter()
+                .filter(|(url, _, _)| ping_name_of(url) == Some(ping_name))
+                .cloned()
+                .collect();
+            if sent.len() >= count {
+                return sent;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                let urls: Vec<&str> = uploads.iter().map(|(url, _, _)| url.as_str()).collect();
+                panic!(
+                    "Expected {} '{}' pings to be sent, but {} were. Sent: {:?}",
+                    count,
+                    ping_name,
+                    sent.len(),
+                    urls
+                );
+            }
+            uploads = uploaded.wait_timeout(uploads, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Asserts that no ping named `ping_name` was uploaded so far.
+    ///
+    /// This doesn't wait for uploads in progress.
+    /// Make sure the ping would have been uploaded by now,
+    /// e.g. by asserting that a ping submitted afterwards was sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if such a ping was uploaded.
+    pub fn assert_not_sent(&self, ping_name: &str) {
+        let sent = self.uploads_of(ping_name);
+        assert!(
+            sent.is_empty(),
+            "Expected no '{}' ping to be sent, but {} were",
+            ping_name,
+            sent.len()
+        );
+    }
+}
+
+impl PingUploader for RecordingUploader {
+    fn upload(&self, url: String, body: Vec<u8>, headers: Vec<(String, String)>) -> UploadResult {
+        let (uploads, uploaded) = &*self.uploads;
+        uploads.lock().unwrap().push((url, body, headers));
+        uploaded.notify_all();
+        UploadResult::http_status(200)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ping_names_are_taken_from_the_url() {
+        assert_eq!(
+            Some("metrics"),
+            ping_name_of("https://example.com/submit/app-id/metrics/1/a-doc-id")
+        );
+        assert_eq!(
+            Some("baseline"),
+            ping_name_of("invalid-test-host/batch/app-id/baseline")
+        );
+        assert_eq!(None, ping_name_of("https://example.com/other/path"));
+    }
+}
@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Validating ping payloads before upload.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use serde_json::Value as JsonValue;
+
+use crate::net::{batch, PingRequest};
+
+/// A callback validating ping payloads before upload.
+///
+/// It is passed the ping name and the uncompressed payload
+/// and returns a description of the problem if the payload is invalid.
+/// See [`Configuration::schema_validator`](crate::Configuration::schema_validator).
+pub type SchemaValidator = Box<dyn Fn(&str, &JsonValue) -> Result<(), String> + Send + Sync>;
+
+/// The default number of consecutive validation failures of a ping type
+/// after which it's not assembled anymore.
+pub(crate) const DEFAULT_SCHEMA_FAILURE_THRESHOLD: u32 = 5;
+
+/// A callback invoked with the name of a ping type that isn't assembled anymore
+/// because it kept failing validation.
+///
+/// See [`Configuration::on_schema_circuit_open`](crate::Configuration::on_schema_circuit_open).
+pub type SchemaCircuitObserver = Box<dyn Fn(&str) + Send + Sync + 'static>;
+
+/// Validates pings and stops assembling ping types that keep failing validation.
+pub(crate) struct SchemaCheck {
+    validator: SchemaValidator,
+    threshold: u32,
+    on_circuit_open: Option<SchemaCircuitObserver>,
+    /// The number of consecutive validation failures, by ping name.
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl fmt::Debug for SchemaCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchemaCheck")
+            .field("validator", &"Fn(&str, &JsonValue) -> Result<(), String>")
+            .field("threshold", &self.threshold)
+            .field(
+                "on_circuit_open",
+                &self.on_circuit_open.as_ref().map(|_| "Fn(&str)"),
+            )
+            .field("failures", &self.failures)
+            .finish()
+    }
+}
+
+impl SchemaCheck {
+    /// Creates a new schema check.
+    ///
+    /// # Arguments
+    ///
+    /// * `validator` - the callback validating ping payloads.
+    /// * `threshold` - the number of consecutive failures of a ping type after which
+    ///   it's not assembled anymore.
+    /// * `on_circuit_open` - an optional callback alerted when that happens.
+    pub(crate) fn new(
+        validator: SchemaValidator,
+        threshold: u32,
+        on_circuit_open: Option<SchemaCircuitObserver>,
+    ) -> Self {
+        Self {
+            validator,
+            threshold: threshold.max(1),
+            on_circuit_open,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks whether the ping of a request passes validation.
+    ///
+    /// Requests that can't be decoded fail validation.
+    /// Once a ping type failed `threshold` times in a row, Glean stops assembling it.
+    ///
+    /// Deletion-request pings are always uploaded, as dropping them would keep
+    /// the server from deleting the client's data. Failures are still logged.
+    pub(crate) fn check(&self, request: &PingRequest) -> bool {
+        let ping_name = batch::ping_name(request).unwrap_or_default();
+        let result = match batch::BatchedPing::from_request(request) {
+            Some(ping) => (self.validator)(ping_name, &ping.payload),
+            None => Err("The payload is not valid JSON".to_string()),
+        };
+
+        let mut failures = self.failures.lock().unwrap();
+        match result {
+            Ok(()) => {
+                failures.remove(ping_name);
+                true
+            }
+            Err(e) => {
+                log::error!(
+                    "Ping {} of type {} failed schema validation: {}",
+                    request.document_id,
+                    ping_name,
+                    e
+                );
+                if request.is_deletion_request() {
+                    return true;
+                }
+                let count = failures.entry(ping_name.to_string()).or_insert(0);
+                *count += 1;
+                if *count == self.threshold {
+                    glean_core::glean_open_schema_circuit(ping_name.to_string());
+                    if let Some(observer) = &self.on_circuit_open {
+                        observer(ping_name);
+                    }
+                }
+                false
+            }
+        }
+    }
+}
@@ -57,6 +57,13 @@ impl<K: traits::ExtraKeys> EventMetric<K> {
     pub fn record_with_time(&self, timestamp: u64, extra: HashMap<String, String>) {
         self.inner.record_with_time(timestamp, extra);
     }
+
+    /// Clears all recorded events of this metric.
+    ///
+    /// Events recorded before this call are cleared, events recorded after it are kept.
+    pub fn reset(&self) {
+        glean_core::metrics::MetricType::reset(&self.inner);
+    }
 }
 
 #[cfg(test)]
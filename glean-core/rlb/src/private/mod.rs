@@ -17,6 +17,7 @@ pub use glean_core::NumeratorMetric;
 pub use glean_core::QuantityMetric;
 pub use glean_core::RateMetric;
 pub use glean_core::RecordedExperiment;
+pub use glean_core::SignedCounterMetric;
 pub use glean_core::StringListMetric;
 pub use glean_core::StringMetric;
 pub use glean_core::TimespanMetric;
@@ -25,6 +26,7 @@ pub use glean_core::UrlMetric;
 pub use glean_core::UuidMetric;
 pub use glean_core::{AllowLabeled, LabeledMetric};
 pub use glean_core::{Datetime, DatetimeMetric};
+pub use glean_core::{RecordValue, RingBufferMetric};
 pub use ping::PingType;
 
 // Re-export types that are used by the glean_parser-generated code.
@@ -4,6 +4,9 @@
 
 use std::sync::{Arc, Mutex};
 
+use chrono::{DateTime, FixedOffset};
+use glean_core::metrics::PingPriority;
+
 type BoxedCallback = Box<dyn FnOnce(Option<&str>) + Send + 'static>;
 
 /// A ping is a bundle of related metrics, gathered in a payload to be transmitted.
@@ -34,11 +37,39 @@ impl PingType {
         send_if_empty: bool,
         reason_codes: Vec<String>,
     ) -> Self {
-        let inner = glean_core::metrics::PingType::new(
+        Self::new_with_priority(
+            name,
+            include_client_id,
+            send_if_empty,
+            reason_codes,
+            PingPriority::default(),
+        )
+    }
+
+    /// Creates a new ping type with the given upload priority.
+    ///
+    /// Pending pings of a higher priority are uploaded first.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the ping.
+    /// * `include_client_id` - Whether to include the client ID in the assembled ping when.
+    /// * `send_if_empty` - Whether the ping should be sent empty or not.
+    /// * `reason_codes` - The valid reason codes for this ping.
+    /// * `priority` - The upload priority of this ping.
+    pub fn new_with_priority<A: Into<String>>(
+        name: A,
+        include_client_id: bool,
+        send_if_empty: bool,
+        reason_codes: Vec<String>,
+        priority: PingPriority,
+    ) -> Self {
+        let inner = glean_core::metrics::PingType::new_with_priority(
             name.into(),
             include_client_id,
             send_if_empty,
             reason_codes,
+            priority,
         );
 
         Self {
@@ -47,6 +78,29 @@ impl PingType {
         }
     }
 
+    /// The reason codes this ping declared as valid.
+    pub fn reason_codes(&self) -> &[String] {
+        self.inner.reason_codes()
+    }
+
+    /// Attaches static metadata to every instance of this ping submitted from now on.
+    ///
+    /// See [`glean_core::metrics::PingType::set_metadata`] for the constraints on keys and values.
+    ///
+    /// The pipeline schema doesn't accept the `metadata` object yet,
+    /// so pings carrying it are rejected on ingestion until it does.
+    pub fn set_metadata<K: Into<String>, V: Into<String>>(&self, key: K, value: V) {
+        self.inner.set_metadata(key, value);
+    }
+
+    /// Drops instances of this ping submitted from now on
+    /// if they weren't uploaded within `deadline` of their submission.
+    ///
+    /// See [`glean_core::metrics::PingType::set_upload_deadline`].
+    pub fn set_upload_deadline(&self, deadline: std::time::Duration) {
+        self.inner.set_upload_deadline(deadline);
+    }
+
     /// Submits the ping for eventual uploading.
     ///
     /// The ping content is assembled as soon as possible, but upload is not
@@ -69,6 +123,48 @@ impl PingType {
         self.inner.submit(reason.map(|s| s.to_string()))
     }
 
+    /// **Debug-only API**
+    ///
+    /// Submits the ping for eventual uploading, as if it was submitted at `submitted_at`.
+    ///
+    /// The assembled ping reports `submitted_at` as its `ping_info.end_time`.
+    /// Use this to replay historical data, not for regular submissions.
+    /// Submission times more than a minute in the future are rejected.
+    /// It doesn't change the `ping_info.start_time` of the pings submitted after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - the reason the ping was triggered. Included in the
+    ///   `ping_info.reason` part of the payload.
+    /// * `submitted_at` - the time the ping is reported to have been submitted at.
+    pub fn submit_at(&self, reason: Option<&str>, submitted_at: DateTime<FixedOffset>) {
+        let mut cb = self.test_callback.lock().unwrap();
+        let cb = cb.take();
+        if let Some(cb) = cb {
+            cb(reason)
+        }
+
+        self.inner
+            .submit_at(reason.map(|s| s.to_string()), submitted_at)
+    }
+
+    /// Submits the ping on the calling thread, once the dispatcher emptied,
+    /// and triggers its upload.
+    ///
+    /// Returns whether the ping was submitted.
+    pub(crate) fn submit_blocking(&self, reason: Option<&str>) -> bool {
+        let mut cb = self.test_callback.lock().unwrap();
+        let cb = cb.take();
+        if let Some(cb) = cb {
+            cb(reason)
+        }
+
+        glean_core::glean_test_submit_ping_by_name(
+            self.inner.name().to_string(),
+            reason.map(|s| s.to_string()),
+        )
+    }
+
     /// **Test-only API**
     ///
     /// Attach a callback to be called right before a new ping is submitted.
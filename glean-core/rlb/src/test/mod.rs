@@ -0,0 +1,189 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Helpers for testing code instrumented with Glean.
+//!
+//! These are **test-only** and must not be used in production code.
+//!
+//! `RecordingUploader` requires the `test_utils` feature.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::net::{self, UploadResult};
+use crate::private::PingType;
+
+#[cfg(any(test, feature = "test_utils"))]
+pub use crate::net::{RecordedUpload, RecordingUploader};
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Tears Glean down completely.
+///
+/// This shuts Glean down, waits for its threads to finish, clears all stored data
+/// and forgets all state set before initialization,
+/// e.g. the debug view tag or pings registered early,
+/// as well as the metric descriptors and any callers still awaiting an upload.
+/// The next call to [`initialize`](crate::initialize) or
+/// [`test_reset_glean`](crate::test_reset_glean) in the same process starts
+/// from scratch, just like the first one.
+pub fn destroy() {
+    glean_core::glean_test_destroy();
+    crate::net::clear_upload_waiters();
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets every error recorded since the last "metrics" ping, across all metrics.
+///
+/// Waits for all pending recordings first.
+///
+/// # Returns
+///
+/// A `(metric identifier, error type, number of errors)` tuple
+/// for every metric and type of error it recorded, sorted by metric identifier.
+pub fn all_errors() -> Vec<(String, crate::ErrorType, i32)> {
+    glean_core::glean_test_get_all_errors()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets the errors recorded by metrics sent in a ping, since it was last submitted.
+///
+/// Waits for all pending recordings first.
+///
+/// # Returns
+///
+/// The number of errors by type, for every metric that recorded any.
+pub fn error_summary(ping_name: &str) -> HashMap<String, HashMap<crate::ErrorType, i32>> {
+    let mut summary: HashMap<String, HashMap<_, _>> = HashMap::new();
+    for (identifier, error, count) in glean_core::glean_test_get_errors_for_ping(ping_name.into()) {
+        summary.entry(identifier).or_default().insert(error, count);
+    }
+    summary
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Clears the errors recorded by metrics sent in a ping,
+/// e.g. to check a later step of a test doesn't record any.
+///
+/// The errors are kept in the other pings they are destined for, e.g. the "metrics" ping.
+pub fn clear_errors(ping_name: &str) {
+    glean_core::glean_test_clear_errors(ping_name.into())
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets the identifier of the current session.
+///
+/// A new one is generated every time Glean is initialized.
+/// Unlike the client id it's not persisted.
+/// It is not sent in pings yet.
+pub fn session_id() -> String {
+    glean_core::glean_test_get_session_id()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets the number of sessions.
+///
+/// It's incremented every time Glean is initialized with upload enabled
+/// and starts over when upload is disabled.
+/// It is not sent in pings yet.
+pub fn session_count() -> i32 {
+    glean_core::glean_test_get_session_count()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets an overview of where storage is going.
+///
+/// Waits for all pending recordings first.
+///
+/// # Returns
+///
+/// A `(store name, number of metrics, approximate size in bytes)` tuple for every store
+/// with data, sorted by store name.
+/// Internal stores, e.g. `glean_client_info`, are included.
+/// Every label of a labeled metric counts as a metric of its own.
+/// Events are not included.
+pub fn store_stats() -> Vec<(String, usize, usize)> {
+    glean_core::glean_test_get_store_stats()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets the number of tasks waiting to be run by Glean's dispatcher.
+///
+/// The task currently running, if any, isn't counted.
+/// Before Glean is initialized, all tasks are held back and counted.
+pub fn dispatcher_queue_depth() -> usize {
+    glean_core::glean_test_get_dispatcher_queue_depth()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Replaces the client id with a newly generated one, e.g. to reset the telemetry identity.
+///
+/// The new id is persisted and sent with all pings from now on.
+/// The `first_run_date` is kept as is.
+///
+/// This will block on the dispatcher and panic if called before Glean is initialized.
+///
+/// # Returns
+///
+/// The new client id, or `None` if upload is disabled.
+/// In that case nothing is changed and pings keep the known "disabled" client id.
+pub fn rotate_client_id() -> Option<uuid::Uuid> {
+    glean_core::glean_rotate_client_id()
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Gets the time of the last successful ping upload.
+///
+/// This is useful to show e.g. a "last synced" time.
+/// It is kept across restarts, but cleared when upload is disabled.
+///
+/// This will panic if called before Glean is initialized.
+pub fn last_upload_time() -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    glean_core::glean_last_upload_time()
+}
+
+/// How long [`submit_ping_and_await`] waits for an upload result at most.
+pub const SUBMIT_AWAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Submits `ping` and blocks until the uploader returned a result for it.
+///
+/// This waits for all tasks queued before and for the first upload attempt of the next ping
+/// of that type, so it must never be called on threads that shouldn't block.
+/// It returns [`UploadResult::Done`] right away if the ping wasn't
+/// submitted, e.g. because it's empty, and after [`SUBMIT_AWAIT_TIMEOUT`] without a result,
+/// e.g. because uploading is throttled.
+pub fn submit_ping_and_await(ping: &PingType, reason: Option<&str>) -> UploadResult {
+    let receiver = net::await_upload(ping.inner.name());
+    if !ping.submit_blocking(reason) {
+        return UploadResult::done();
+    }
+    receiver
+        .recv_timeout(SUBMIT_AWAIT_TIMEOUT)
+        .unwrap_or_else(|_| UploadResult::done())
+}
+
+/// **TEST-ONLY FUNCTION.**
+///
+/// Describes every metric created so far with a `description` or `data_sensitivity`
+/// in its [`CommonMetricData`](crate::CommonMetricData), sorted by identifier.
+///
+/// This doesn't require Glean to be initialized.
+/// Metrics only show up once they have been created.
+pub fn describe_metrics() -> Vec<crate::MetricDescriptor> {
+    glean_core::glean_describe_metrics()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,4238 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use crate::private::PingType;
+use crate::private::{BooleanMetric, CounterMetric, EventMetric, StringMetric};
+
+use crate::common_test::{
+    decode_payload, lock_test, new_glean, test_configuration, GLOBAL_APPLICATION_ID,
+};
+use crate::*;
+
+#[test]
+fn send_a_ping() {
+    let _lock = lock_test();
+
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Define a new ping and submit it.
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains(PING_NAME));
+}
+
+#[test]
+fn recording_uploader_captures_submitted_pings() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    uploader.assert_sent(PING_NAME);
+    uploader.assert_not_sent("other-ping");
+    let (url, _, _) = uploader.uploads_of(PING_NAME).remove(0);
+    assert!(url.contains(PING_NAME));
+}
+
+#[test]
+fn disabling_upload_disables_metrics_recording() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let metric = BooleanMetric::new(CommonMetricData {
+        name: "bool_metric".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    crate::set_upload_enabled(false);
+
+    assert!(metric.test_get_value(Some("store1".into())).is_none())
+}
+
+#[test]
+fn test_experiments_recording() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    set_experiment_active("experiment_test".to_string(), "branch_a".to_string(), None);
+    let mut extra = HashMap::new();
+    extra.insert("test_key".to_string(), "value".to_string());
+    set_experiment_active(
+        "experiment_api".to_string(),
+        "branch_b".to_string(),
+        Some(extra),
+    );
+    assert!(test_is_experiment_active("experiment_test".to_string()));
+    assert!(test_is_experiment_active("experiment_api".to_string()));
+    set_experiment_inactive("experiment_test".to_string());
+    assert!(!test_is_experiment_active("experiment_test".to_string()));
+    assert!(test_is_experiment_active("experiment_api".to_string()));
+    let stored_data = test_get_experiment_data("experiment_api".to_string()).unwrap();
+    assert_eq!("branch_b", stored_data.branch);
+    assert_eq!("value", stored_data.extra.unwrap()["test_key"]);
+}
+
+#[test]
+fn test_experiments_recording_before_glean_inits() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+
+    set_experiment_active(
+        "experiment_set_preinit".to_string(),
+        "branch_a".to_string(),
+        None,
+    );
+    set_experiment_active(
+        "experiment_preinit_disabled".to_string(),
+        "branch_a".to_string(),
+        None,
+    );
+    set_experiment_inactive("experiment_preinit_disabled".to_string());
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        false,
+    );
+
+    assert!(test_is_experiment_active(
+        "experiment_set_preinit".to_string()
+    ));
+    assert!(!test_is_experiment_active(
+        "experiment_preinit_disabled".to_string()
+    ));
+}
+
+#[test]
+fn sending_of_foreground_background_pings() {
+    let _lock = lock_test();
+
+    let click: EventMetric<traits::NoExtraKeys> = private::EventMetric::new(CommonMetricData {
+        name: "click".into(),
+        category: "ui".into(),
+        send_in_pings: vec!["events".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<String>(3);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Simulate becoming active.
+    handle_client_active();
+
+    // We expect a baseline ping to be generated here (reason: 'active').
+    let url = r.recv().unwrap();
+    assert!(url.contains("baseline"));
+
+    // Recording an event so that an "events" ping will contain data.
+    click.record(None);
+
+    // Simulate becoming inactive
+    handle_client_inactive();
+
+    // Wait for the pings to arrive.
+    let mut expected_pings = vec!["baseline", "events"];
+    for _ in 0..2 {
+        let url = r.recv().unwrap();
+        // If the url contains the expected reason, remove it from the list.
+        expected_pings.retain(|&name| !url.contains(name));
+    }
+    // We received all the expected pings.
+    assert_eq!(0, expected_pings.len());
+
+    // Simulate becoming active again.
+    handle_client_active();
+
+    // We expect a baseline ping to be generated here (reason: 'active').
+    let url = r.recv().unwrap();
+    assert!(url.contains("baseline"));
+}
+
+#[test]
+fn sending_of_startup_baseline_ping() {
+    let _lock = lock_test();
+
+    // Create an instance of Glean and then flip the dirty
+    // bit to true.
+    let data_dir = new_glean(None, true);
+
+    glean_core::glean_set_dirty_flag(true);
+
+    // Restart glean and wait for a baseline ping to be generated.
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let tmpname = data_dir.path().to_path_buf();
+
+    // Now reset Glean: it should still send a baseline ping with reason
+    // dirty_startup when starting, because of the dirty bit being set.
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: Some(Box::new(FakeUploader { sender: s })),
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        false,
+    );
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains("baseline"));
+}
+
+#[test]
+fn no_dirty_baseline_on_clean_shutdowns() {
+    let _lock = lock_test();
+
+    // Create an instance of Glean, wait for init and then flip the dirty
+    // bit to true.
+    let data_dir = new_glean(None, true);
+
+    glean_core::glean_set_dirty_flag(true);
+
+    crate::shutdown();
+
+    // Restart glean and wait for a baseline ping to be generated.
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let tmpname = data_dir.path().to_path_buf();
+
+    // Now reset Glean: it should not send a baseline ping, because
+    // we cleared the dirty bit.
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: Some(Box::new(FakeUploader { sender: s })),
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        false,
+    );
+
+    // We don't expect a startup ping.
+    assert_eq!(r.try_recv(), Err(crossbeam_channel::TryRecvError::Empty));
+}
+
+#[test]
+fn initialize_must_not_crash_if_data_dir_is_messed_up() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpdirname = dir.path();
+    // Create a file in the temporary dir and use that as the
+    // name of the Glean data dir.
+    let file_path = tmpdirname.to_path_buf().join("notadir");
+    std::fs::write(file_path.clone(), "test").expect("The test Glean dir file must be created");
+
+    let cfg = Configuration {
+        data_path: file_path,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    test_reset_glean(cfg, ClientInfoMetrics::unknown(), false);
+
+    // We don't need to sleep here.
+    // The `test_reset_glean` already waited on the initialize task.
+}
+
+#[test]
+fn queued_recorded_metrics_correctly_record_during_init() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+
+    let metric = CounterMetric::new(CommonMetricData {
+        name: "counter_metric".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    // This will queue 3 tasks that will add to the metric value once Glean is initialized
+    for _ in 0..3 {
+        metric.add(1);
+    }
+
+    // TODO: To be fixed in bug 1677150.
+    // Ensure that no value has been stored yet since the tasks have only been queued
+    // and not executed yet
+
+    // Calling `new_glean` here will cause Glean to be initialized and should cause the queued
+    // tasks recording metrics to execute
+    let _t = new_glean(None, false);
+
+    // Verify that the callback was executed by testing for the correct value
+    assert!(metric.test_get_value(None).is_some(), "Value must exist");
+    assert_eq!(3, metric.test_get_value(None).unwrap(), "Value must match");
+}
+
+#[test]
+fn initializing_twice_is_a_noop() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname.clone(),
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        true,
+    );
+
+    // Glean was initialized and it waited for a full initialization to finish.
+    // We now just want to try to initialize again.
+    // This will bail out early.
+
+    crate::initialize(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+    );
+
+    // We don't need to sleep here.
+    // The `test_reset_glean` already waited on the initialize task,
+    // and the 2nd initialize will bail out early.
+    //
+    // All we tested is that this didn't crash.
+}
+
+#[test]
+#[ignore] // TODO: To be done in bug 1673668.
+fn dont_handle_events_when_uninitialized() {
+    todo!()
+}
+
+// TODO: Should probably move into glean-core.
+#[test]
+fn the_app_channel_must_be_correctly_set_if_requested() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    // Internal metric, replicated here for testing.
+    let app_channel = StringMetric::new(CommonMetricData {
+        name: "app_channel".into(),
+        category: "".into(),
+        send_in_pings: vec!["glean_client_info".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+
+    // No app_channel reported.
+    let client_info = ClientInfoMetrics {
+        channel: None,
+        ..ClientInfoMetrics::unknown()
+    };
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname.clone(),
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        client_info,
+        true,
+    );
+    assert!(app_channel.test_get_value(None).is_none());
+
+    // Custom app_channel reported.
+    let client_info = ClientInfoMetrics {
+        channel: Some("testing".into()),
+        ..ClientInfoMetrics::unknown()
+    };
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        client_info,
+        true,
+    );
+    assert_eq!("testing", app_channel.test_get_value(None).unwrap());
+}
+
+#[test]
+#[ignore] // TODO: To be done in bug 1673672.
+fn ping_collection_must_happen_after_concurrently_scheduled_metrics_recordings() {
+    todo!()
+}
+
+#[test]
+fn basic_metrics_should_be_cleared_when_disabling_uploading() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, false);
+
+    let metric = private::StringMetric::new(CommonMetricData {
+        name: "string_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["default".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    assert!(metric.test_get_value(None).is_none());
+
+    metric.set("TEST VALUE".into());
+    assert!(metric.test_get_value(None).is_some());
+
+    set_upload_enabled(false);
+    assert!(metric.test_get_value(None).is_none());
+    metric.set("TEST VALUE".into());
+    assert!(metric.test_get_value(None).is_none());
+
+    set_upload_enabled(true);
+    assert!(metric.test_get_value(None).is_none());
+    metric.set("TEST VALUE".into());
+    assert_eq!("TEST VALUE", metric.test_get_value(None).unwrap());
+}
+
+// TODO: Should probably move into glean-core.
+#[test]
+fn core_metrics_should_be_cleared_and_restored_when_disabling_and_enabling_uploading() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    // No app_channel reported.
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: None,
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        true,
+    );
+
+    // Internal metric, replicated here for testing.
+    let os_version = StringMetric::new(CommonMetricData {
+        name: "os_version".into(),
+        category: "".into(),
+        send_in_pings: vec!["glean_client_info".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+
+    assert!(os_version.test_get_value(None).is_some());
+
+    set_upload_enabled(false);
+    assert!(os_version.test_get_value(None).is_none());
+
+    set_upload_enabled(true);
+    assert!(os_version.test_get_value(None).is_some());
+}
+
+#[test]
+fn sending_deletion_ping_if_disabled_outside_of_run() {
+    let _lock = lock_test();
+
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname.clone(),
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Now reset Glean and disable upload: it should still send a deletion request
+    // ping even though we're just starting.
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: false,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: Some(Box::new(FakeUploader { sender: s })),
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        false,
+    );
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains("deletion-request"));
+}
+
+#[test]
+fn no_sending_of_deletion_ping_if_unchanged_outside_of_run() {
+    let _lock = lock_test();
+
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname.clone(),
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Now reset Glean and keep upload enabled: no deletion-request
+    // should be sent.
+    test_reset_glean(
+        Configuration {
+            data_path: tmpname,
+            application_id: GLOBAL_APPLICATION_ID.into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            server_endpoint: Some("invalid-test-host".into()),
+            uploader: Some(Box::new(FakeUploader { sender: s })),
+            use_core_mps: false,
+            ..Default::default()
+        },
+        ClientInfoMetrics::unknown(),
+        false,
+    );
+
+    assert_eq!(0, r.len());
+}
+
+#[test]
+#[ignore] // TODO: To be done in bug 1672956.
+fn test_sending_of_startup_baseline_ping_with_application_lifetime_metric() {
+    todo!()
+}
+
+#[test]
+#[ignore] // TODO: To be done in bug 1672956.
+fn test_dirty_flag_is_reset_to_false() {
+    todo!()
+}
+
+#[test]
+fn setting_debug_view_tag_before_initialization_should_not_crash() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<Vec<(String, String)>>(1);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<Vec<(String, String)>>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(headers).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Attempt to set a debug view tag before Glean is initialized.
+    set_debug_view_tag("valid-tag");
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Submit a baseline ping.
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    // Wait for the ping to arrive.
+    let headers = r.recv().unwrap();
+    assert_eq!(
+        "valid-tag",
+        headers.iter().find(|&kv| kv.0 == "X-Debug-ID").unwrap().1
+    );
+}
+
+#[test]
+fn setting_source_tags_before_initialization_should_not_crash() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+    //assert!(!was_initialize_called());
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<Vec<(String, String)>>(1);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<Vec<(String, String)>>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(headers).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Attempt to set source tags before Glean is initialized.
+    set_source_tags(vec!["valid-tag1".to_string(), "valid-tag2".to_string()]);
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Submit a baseline ping.
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    // Wait for the ping to arrive.
+    let headers = r.recv().unwrap();
+    assert_eq!(
+        "valid-tag1,valid-tag2",
+        headers
+            .iter()
+            .find(|&kv| kv.0 == "X-Source-Tags")
+            .unwrap()
+            .1
+    );
+}
+
+#[test]
+fn setting_source_tags_after_initialization_should_not_crash() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+    //assert!(!was_initialize_called());
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<Vec<(String, String)>>(1);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<Vec<(String, String)>>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(headers).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Attempt to set source tags after `Glean.initialize` is called,
+    // but before Glean is fully initialized.
+    //assert!(was_initialize_called());
+    set_source_tags(vec!["valid-tag1".to_string(), "valid-tag2".to_string()]);
+
+    // Submit a baseline ping.
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    // Wait for the ping to arrive.
+    let headers = r.recv().unwrap();
+    assert_eq!(
+        "valid-tag1,valid-tag2",
+        headers
+            .iter()
+            .find(|&kv| kv.0 == "X-Source-Tags")
+            .unwrap()
+            .1
+    );
+}
+
+#[test]
+fn flipping_upload_enabled_respects_order_of_events() {
+    // NOTES(janerik):
+    // I'm reasonably sure this test is excercising the right code paths
+    // and from the log output it does the right thing:
+    //
+    // * It fully initializes with the assumption uploadEnabled=true
+    // * It then disables upload
+    // * Then it submits the custom ping, which rightfully is ignored because uploadEnabled=false.
+    //
+    // The test passes.
+    let _lock = lock_test();
+
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    // We create a ping and a metric before we initialize Glean
+    let sample_ping = PingType::new("sample-ping-1", true, false, vec![]);
+    let metric = private::StringMetric::new(CommonMetricData {
+        name: "string_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["sample-ping-1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Glean might still be initializing. Disable upload.
+    set_upload_enabled(false);
+
+    // Set data and try to submit a custom ping.
+    metric.set("some-test-value".into());
+    sample_ping.submit(None);
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains("deletion-request"));
+}
+
+#[test]
+fn registering_pings_before_init_must_work() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+    //assert!(!was_initialize_called());
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom ping and attempt its registration.
+    let sample_ping = PingType::new("pre-register", true, true, vec![]);
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Submit a baseline ping.
+    sample_ping.submit(None);
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains("pre-register"));
+}
+
+#[test]
+fn test_a_ping_before_submission() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that reports back the submission headers
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Create a custom ping and register it.
+    let sample_ping = PingType::new("custom1", true, true, vec![]);
+
+    let metric = CounterMetric::new(CommonMetricData {
+        name: "counter_metric".into(),
+        category: "test".into(),
+        send_in_pings: vec!["custom1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    metric.add(1);
+
+    sample_ping.test_before_next_submit(move |reason| {
+        assert_eq!(None, reason);
+        assert_eq!(1, metric.test_get_value(None).unwrap());
+    });
+
+    // Submit a baseline ping.
+    sample_ping.submit(None);
+
+    // Wait for the ping to arrive.
+    let url = r.recv().unwrap();
+    assert!(url.contains("custom1"));
+}
+
+#[test]
+fn test_boolean_get_num_errors() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, false);
+
+    let metric = BooleanMetric::new(CommonMetricData {
+        name: "counter_metric".into(),
+        category: "test".into(),
+        send_in_pings: vec!["custom1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        dynamic_label: Some(str::to_string("asdf")),
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    // Check specifically for an invalid label
+    let result = metric.test_get_num_recorded_errors(ErrorType::InvalidLabel);
+
+    assert_eq!(result, 0);
+}
+
+#[test]
+fn signaling_done() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        barrier: Arc<Barrier>,
+        counter: Arc<Mutex<HashMap<ThreadId, u32>>>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            let mut map = self.counter.lock().unwrap();
+            *map.entry(thread::current().id()).or_insert(0) += 1;
+
+            // Wait for the sync.
+            self.barrier.wait();
+
+            // Signal that this uploader thread is done.
+            net::UploadResult::done()
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    // We use a barrier to sync this test thread with the uploader thread.
+    let barrier = Arc::new(Barrier::new(2));
+    // We count how many times `upload` was invoked per thread.
+    let call_count = Arc::new(Mutex::default());
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: GLOBAL_APPLICATION_ID.into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(FakeUploader {
+            barrier: Arc::clone(&barrier),
+            counter: Arc::clone(&call_count),
+        })),
+        use_core_mps: false,
+        ..Default::default()
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Define a new ping and submit it.
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+    custom_ping.submit(None);
+
+    // Sync up with the upload thread.
+    barrier.wait();
+
+    // Submit another ping and wait for it to do work.
+    custom_ping.submit(None);
+
+    // Sync up with the upload thread again.
+    // This will not be the same thread as the one before (hopefully).
+    barrier.wait();
+
+    // No one's ever gonna wait for the uploader thread (the RLB doesn't store the handle to it),
+    // so all we can do is hope it finishes within time.
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let map = call_count.lock().unwrap();
+    assert_eq!(2, map.len(), "should have launched 2 uploader threads");
+    for &count in map.values() {
+        assert_eq!(1, count, "each thread should call upload only once");
+    }
+}
+
+#[test]
+fn pipeline_header_is_attached_to_every_ping() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        pipeline_header: Some(("X-Pipeline".into(), "staging".into())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    uploader.assert_sent(PING_NAME);
+    uploader.assert_sent("baseline");
+    for (_, _, headers) in uploader.uploads() {
+        assert_eq!(
+            "staging",
+            headers.iter().find(|&kv| kv.0 == "X-Pipeline").unwrap().1
+        );
+    }
+}
+
+#[test]
+fn source_tags_are_sent_with_every_ping_and_invalid_updates_are_ignored() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    set_source_tags(vec!["tag1".to_string(), "tag2".to_string()]);
+
+    // More than 5 tags are rejected, the previous tags are kept.
+    set_source_tags((1..=6).map(|i| format!("tag{}", i)).collect());
+    // So are invalid tags.
+    set_source_tags(vec!["invalid_tag!".to_string()]);
+
+    submit_ping_by_name("baseline", Some("inactive"));
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    for (_, _, headers) in uploader.wait_for_uploads("baseline", 2) {
+        assert_eq!(
+            "tag1,tag2",
+            headers
+                .iter()
+                .find(|&kv| kv.0 == "X-Source-Tags")
+                .unwrap()
+                .1
+        );
+    }
+}
+
+#[test]
+fn built_in_pings_are_registered_on_init() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let names = get_registered_ping_names();
+    for ping in &["baseline", "deletion-request", "events", "metrics"] {
+        assert!(
+            names.iter().any(|name| name == ping),
+            "'{}' should be registered, got {:?}",
+            ping,
+            names
+        );
+    }
+
+    // Without registering it ourselves, the deletion-request ping is sent on disabling upload.
+    set_upload_enabled(false);
+    uploader.assert_sent("deletion-request");
+}
+
+#[test]
+fn circuit_breaker_stops_calling_the_uploader_after_repeated_failures() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that always fails and reports back
+    // every call using a crossbeam channel.
+    let (s, r) = crossbeam_channel::unbounded::<String>();
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::recoverable_failure()
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        upload_failure_threshold: Some(2),
+        upload_cooldown: Some(std::time::Duration::from_secs(3600)),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    // The ping is attempted until the breaker opens.
+    for _ in 0..2 {
+        let url = r.recv().unwrap();
+        assert!(url.contains(PING_NAME));
+    }
+
+    // With the breaker open, new pings stay pending.
+    custom_ping.submit(None);
+    assert!(r.recv_timeout(std::time::Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn snapshot_store_returns_and_optionally_clears_ping_data() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    counter.add(2);
+    string.set("hello".into());
+
+    let expected = serde_json::json!({
+        "counter": { "test.counter": 2 },
+        "string": { "test.string": "hello" },
+    });
+
+    // Snapshotting without clearing keeps the data around.
+    assert_eq!(expected, test_snapshot_store("store1", false));
+    assert_eq!(expected, test_snapshot_store("store1", true));
+
+    // Only data with `ping` lifetime is cleared.
+    assert_eq!(
+        serde_json::json!({ "string": { "test.string": "hello" } }),
+        test_snapshot_store("store1", false)
+    );
+    assert_eq!(
+        serde_json::Value::Null,
+        test_snapshot_store("unknown", false)
+    );
+}
+
+#[test]
+fn observers_are_notified_about_counter_increments() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "observed".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+
+    let (s, r) = crossbeam_channel::unbounded();
+    let id = counter.observe(Box::new(move |value| {
+        s.send(value.clone()).unwrap();
+    }));
+
+    let (global_s, global_r) = crossbeam_channel::unbounded();
+    let global_id = observe_metrics(Box::new(move |identifier, _| {
+        global_s.send(identifier.to_string()).unwrap();
+    }));
+
+    counter.add(1);
+    // Invalid values are rejected and don't notify observers.
+    counter.add(-1);
+    counter.add(2);
+    assert_eq!(Some(3), counter.test_get_value(None));
+
+    assert_eq!(
+        vec![Metric::Counter(1), Metric::Counter(3)],
+        r.try_iter().collect::<Vec<_>>()
+    );
+    // Global observers also see the recorded error.
+    assert_eq!(
+        vec![
+            "test.observed".to_string(),
+            "glean.error.invalid_value/test.observed".to_string(),
+            "test.observed".to_string()
+        ],
+        global_r.try_iter().collect::<Vec<_>>()
+    );
+
+    // Removed observers are not notified anymore.
+    remove_metric_observer(id);
+    remove_metric_observer(global_id);
+    counter.add(1);
+    assert_eq!(Some(4), counter.test_get_value(None));
+    assert!(r.try_recv().is_err());
+    assert!(global_r.try_recv().is_err());
+}
+
+#[test]
+fn baseline_duration_is_reported_in_the_configured_time_unit() {
+    let _lock = lock_test();
+
+    let mut durations = vec![];
+    for unit in [None, Some(TimeUnit::Millisecond)] {
+        let uploader = test::RecordingUploader::new();
+
+        // Create a custom configuration to use a recording uploader.
+        let dir = tempfile::tempdir().unwrap();
+        let tmpname = dir.path().to_path_buf();
+
+        let cfg = Configuration {
+            uploader: Some(Box::new(uploader.clone())),
+            baseline_duration_unit: unit,
+            ..test_configuration(tmpname)
+        };
+
+        let _t = new_glean(Some(cfg), true);
+
+        handle_client_active();
+        // The 'active' baseline ping has no duration.
+        uploader.wait_for_uploads("baseline", 1);
+
+        thread::sleep(std::time::Duration::from_millis(50));
+        handle_client_inactive();
+
+        let (_, body, _) = uploader.wait_for_uploads("baseline", 2).remove(1);
+        let payload = decode_payload(&body);
+        durations.push(payload["metrics"]["timespan"]["glean.baseline.duration"].clone());
+    }
+
+    // Seconds are the default, a short activity rounds down to 0.
+    assert_eq!("second", durations[0]["time_unit"]);
+    assert_eq!(0, durations[0]["value"]);
+    assert_eq!("millisecond", durations[1]["time_unit"]);
+    assert!(durations[1]["value"].as_u64().unwrap() >= 50);
+}
+
+#[test]
+fn dry_run_records_metrics_but_never_uploads() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        dry_run: true,
+        ..test_configuration(tmpname.clone())
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["test-ping".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        dynamic_label: None,
+        min_record_interval_ms: None,
+        description: None,
+        data_sensitivity: None,
+        only_once_per_session: false,
+    });
+    counter.add(3);
+    assert_eq!(Some(3), counter.test_get_value(None));
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+    custom_ping.submit(None);
+    handle_client_active();
+
+    // The pings were assembled, clearing the ping lifetime data...
+    assert_eq!(None, counter.test_get_value(None));
+
+    // ...but never stored or uploaded.
+    thread::sleep(std::time::Duration::from_millis(500));
+    assert!(uploader.uploads().is_empty());
+    let pending_dir = tmpname.join("pending_pings");
+    assert_eq!(
+        0,
+        std::fs::read_dir(pending_dir)
+            .map(|dir| dir.count())
+            .unwrap_or(0)
+    );
+}
+
+#[test]
+fn locale_override_is_reported_in_client_info() {
+    let _lock = lock_test();
+
+    let mut client_infos = vec![];
+    for locale_override in ["es-ES", "not a locale"] {
+        let uploader = test::RecordingUploader::new();
+
+        // Create a custom configuration to use a recording uploader.
+        let dir = tempfile::tempdir().unwrap();
+        let tmpname = dir.path().to_path_buf();
+
+        let cfg = Configuration {
+            uploader: Some(Box::new(uploader.clone())),
+            locale_override: Some(locale_override.into()),
+            ..test_configuration(tmpname)
+        };
+
+        let _t = new_glean(Some(cfg), true);
+
+        handle_client_active();
+        let (_, body, _) = uploader.wait_for_uploads("baseline", 1).remove(0);
+        let payload = decode_payload(&body);
+        client_infos.push(payload["client_info"].clone());
+    }
+
+    assert_eq!("es-ES", client_infos[0]["locale"]);
+
+    // Invalid overrides are not reported and record an error instead.
+    assert_eq!(serde_json::Value::Null, client_infos[1]["locale"]);
+    let metrics = test_snapshot_store("metrics", false);
+    assert_eq!(
+        1,
+        metrics["labeled_counter"]["glean.error.invalid_value"]["locale"]
+    );
+}
+
+#[test]
+fn pending_pings_of_the_same_type_are_uploaded_in_batches() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that reports back every request using a crossbeam channel.
+    // It only returns once it's released, so that pings pile up in the meantime.
+    type Upload = (String, Vec<u8>, Vec<(String, String)>);
+    let (s, r) = crossbeam_channel::unbounded::<Upload>();
+    let (release_s, release_r) = crossbeam_channel::unbounded::<()>();
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<Upload>,
+        release: crossbeam_channel::Receiver<()>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            body: Vec<u8>,
+            headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send((url, body, headers)).unwrap();
+            let _ = self.release.recv();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader {
+            sender: s,
+            release: release_r,
+        })),
+        batch_uploads: Some(5),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+
+    let parse = |body: &[u8]| serde_json::from_slice::<net::PingBatch>(body).unwrap();
+
+    // The first ping is the only one pending, so it's sent on its own.
+    custom_ping.submit(None);
+    let (url, body, _) = r.recv().unwrap();
+    assert_eq!(
+        "invalid-test-host/batch/org-mozilla-rlb-test/test-ping",
+        url
+    );
+    assert_eq!(1, parse(&body).pings.len());
+
+    // While that upload is in progress, more pings become pending.
+    for _ in 0..3 {
+        custom_ping.submit(None);
+    }
+    // Wait for the submissions to be processed.
+    let _ = crate::test_snapshot_store(PING_NAME, false);
+    release_s.send(()).unwrap();
+
+    let (url, body, headers) = r.recv().unwrap();
+    release_s.send(()).unwrap();
+    assert_eq!(
+        "invalid-test-host/batch/org-mozilla-rlb-test/test-ping",
+        url
+    );
+    assert!(headers
+        .iter()
+        .any(|(name, value)| name == "Content-Type" && value == net::BATCH_CONTENT_TYPE));
+    assert!(!headers.iter().any(|(name, _)| name == "Content-Encoding"));
+
+    let batch = parse(&body);
+    assert_eq!(3, batch.pings.len());
+    for ping in &batch.pings {
+        assert!(ping.path.ends_with(&ping.document_id));
+        assert!(ping.payload["ping_info"]["seq"].is_i64());
+    }
+    let mut doc_ids: Vec<_> = batch.pings.iter().map(|p| &p.document_id).collect();
+    doc_ids.sort();
+    doc_ids.dedup();
+    assert_eq!(3, doc_ids.len());
+
+    // Nothing else is sent.
+    assert!(r.recv_timeout(std::time::Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn last_upload_time_advances_on_successful_uploads() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+    assert!(test::last_upload_time().is_none());
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+
+    // A response is processed before the next task is fetched,
+    // so once the second ping is uploaded the first one was processed.
+    let before = chrono::Local::now();
+    custom_ping.submit(None);
+    uploader.wait_for_uploads("test-ping", 1);
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    custom_ping.submit(None);
+    uploader.wait_for_uploads("test-ping", 2);
+
+    let first = test::last_upload_time().unwrap();
+    assert!(first >= before);
+
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    custom_ping.submit(None);
+    uploader.wait_for_uploads("test-ping", 3);
+
+    let second = test::last_upload_time().unwrap();
+    assert!(second > first);
+}
+
+#[test]
+fn reset_clears_a_single_metric_in_order_with_recordings() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    let new_counter = |name: &str| {
+        CounterMetric::new(CommonMetricData {
+            name: name.into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        })
+    };
+    let counter = new_counter("reset_counter");
+    let other = new_counter("other_counter");
+
+    counter.add(3);
+    other.add(1);
+    counter.reset();
+    assert_eq!(None, counter.test_get_value(None));
+    assert_eq!(Some(1), other.test_get_value(None));
+
+    // Recordings made after the reset are kept.
+    counter.reset();
+    counter.add(2);
+    assert_eq!(Some(2), counter.test_get_value(None));
+}
+
+#[test]
+fn ping_transform_replaces_the_uploaded_body() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    fn base64(input: &[u8]) -> Vec<u8> {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut output = Vec::with_capacity(input.len() * 4 / 3 + 4);
+        for chunk in input.chunks(3) {
+            let b = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    output.push(ALPHABET[(n >> (18 - 6 * i)) & 0x3f]);
+                } else {
+                    output.push(b'=');
+                }
+            }
+        }
+        output
+    }
+    assert_eq!(b"Z2xlYW4=".to_vec(), base64(b"glean"));
+
+    // The transform reports back the original body.
+    let (original_s, original_r) = crossbeam_channel::bounded::<Vec<u8>>(1);
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ping_transform: Some(Box::new(move |body| {
+            let transformed = base64(&body);
+            original_s.send(body).unwrap();
+            transformed
+        })),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+    custom_ping.submit(None);
+
+    // The transform is passed the uncompressed payload.
+    let original = original_r.recv().unwrap();
+    let payload: serde_json::Value = serde_json::from_slice(&original).unwrap();
+    assert!(payload["ping_info"].is_object());
+
+    // Its result is compressed again.
+    let (_, body, headers) = uploader.wait_for_uploads("test-ping", 1).remove(0);
+    let mut decoder = flate2::read::GzDecoder::new(&body[..]);
+    let mut transformed = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut transformed).unwrap();
+    assert_eq!(base64(&original), transformed);
+
+    let header = |wanted: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name == wanted)
+            .map(|(_, value)| value.clone())
+    };
+    assert_eq!(Some("gzip".to_string()), header("Content-Encoding"));
+    assert_eq!(Some(body.len().to_string()), header("Content-Length"));
+}
+
+#[test]
+#[cfg(feature = "ping_encryption")]
+fn pings_are_encrypted_for_the_ping_public_key() {
+    use aes_gcm::aead::{AeadInPlace, KeyInit, OsRng};
+    use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+
+    let _lock = lock_test();
+
+    fn decode(input: &str) -> Vec<u8> {
+        base64::decode_config(input, base64::URL_SAFE_NO_PAD).unwrap()
+    }
+
+    let private_key = p256::SecretKey::random(&mut OsRng);
+    let public_key = private_key.public_key().to_encoded_point(false);
+    let jwk = net::Jwk {
+        kty: "EC".into(),
+        crv: "P-256".into(),
+        x: base64::encode_config(public_key.x().unwrap(), base64::URL_SAFE_NO_PAD),
+        y: base64::encode_config(public_key.y().unwrap(), base64::URL_SAFE_NO_PAD),
+        kid: Some("test-key".into()),
+    };
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ping_public_key: Some(jwk),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+    custom_ping.submit(None);
+    uploader.assert_sent("test-ping");
+
+    let (_, body, _) = uploader.uploads_of("test-ping").remove(0);
+    let mut decoder = flate2::read::GzDecoder::new(&body[..]);
+    let mut json = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut json).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    let jwe = body["payload"].as_str().unwrap();
+
+    // Decrypt the compact serialization with the private key.
+    let parts: Vec<&str> = jwe.split('.').collect();
+    assert_eq!(5, parts.len());
+    assert_eq!("", parts[1]);
+    let header: serde_json::Value = serde_json::from_slice(&decode(parts[0])).unwrap();
+    assert_eq!("ECDH-ES", header["alg"]);
+    assert_eq!("A256GCM", header["enc"]);
+    assert_eq!("test-key", header["kid"]);
+
+    let x = decode(header["epk"]["x"].as_str().unwrap());
+    let y = decode(header["epk"]["y"].as_str().unwrap());
+    let point = p256::EncodedPoint::from_affine_coordinates(x[..].into(), y[..].into(), false);
+    let ephemeral_key = p256::PublicKey::from_encoded_point(&point).unwrap();
+    let shared_secret =
+        p256::ecdh::diffie_hellman(private_key.to_nonzero_scalar(), ephemeral_key.as_affine());
+
+    let mut other_info = Vec::new();
+    other_info.extend_from_slice(&7u32.to_be_bytes());
+    other_info.extend_from_slice(b"A256GCM");
+    other_info.extend_from_slice(&[0; 8]);
+    other_info.extend_from_slice(&256u32.to_be_bytes());
+    let mut key = [0; 32];
+    concat_kdf::derive_key_into::<sha2::Sha256>(
+        shared_secret.raw_secret_bytes(),
+        &other_info,
+        &mut key,
+    )
+    .unwrap();
+
+    let mut payload = decode(parts[3]);
+    aes_gcm::Aes256Gcm::new(&key.into())
+        .decrypt_in_place_detached(
+            decode(parts[2])[..].into(),
+            parts[0].as_bytes(),
+            &mut payload,
+            decode(parts[4])[..].into(),
+        )
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+    assert!(payload["ping_info"].is_object());
+    assert!(payload["client_info"].is_object());
+}
+
+#[test]
+#[cfg(feature = "ping_encryption")]
+fn invalid_ping_public_keys_are_rejected() {
+    let jwk = net::Jwk {
+        kty: "EC".into(),
+        crv: "P-256".into(),
+        x: "AAAA".into(),
+        y: "AAAA".into(),
+        kid: None,
+    };
+    assert!(net::PingEncryption::new(&jwk).is_err());
+
+    let jwk = net::Jwk {
+        kty: "RSA".into(),
+        ..jwk
+    };
+    assert!(net::PingEncryption::new(&jwk).is_err());
+}
+
+#[test]
+fn reaching_max_events_submits_the_events_ping() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        max_events: Some(3),
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let event = private::EventMetric::<traits::NoExtraKeys>::new(CommonMetricData {
+        name: "click".into(),
+        category: "ui".into(),
+        send_in_pings: vec!["events".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    for _ in 0..3 {
+        event.record(None);
+    }
+    // The next event starts a new buffer.
+    event.record(None);
+
+    let (_, body, _) = uploader.wait_for_uploads("events", 1).remove(0);
+    let payload = decode_payload(&body);
+    assert_eq!("max_capacity", payload["ping_info"]["reason"]);
+    assert_eq!(3, payload["events"].as_array().unwrap().len());
+
+    assert_eq!(1, event.test_get_value(None).unwrap().len());
+}
+
+#[test]
+fn fallback_uploader_is_used_when_the_primary_keeps_failing() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that returns a fixed result and reports back
+    // the submission URL using a crossbeam channel.
+    let (primary_s, primary_r) = crossbeam_channel::unbounded::<String>();
+    let (fallback_s, fallback_r) = crossbeam_channel::unbounded::<String>();
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+        succeed: bool,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            if self.succeed {
+                net::UploadResult::http_status(200)
+            } else {
+                net::UploadResult::recoverable_failure()
+            }
+        }
+    }
+
+    // Create a custom configuration to use a fake uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader {
+            sender: primary_s,
+            succeed: false,
+        })),
+        fallback_uploader: Some(Box::new(FakeUploader {
+            sender: fallback_s,
+            succeed: true,
+        })),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    let url = fallback_r.recv().unwrap();
+    assert!(url.contains(PING_NAME));
+
+    // The primary uploader was tried first, for the same ping.
+    let attempts: Vec<String> = primary_r.try_iter().collect();
+    assert_eq!(2, attempts.len());
+    assert!(attempts.iter().all(|attempt| *attempt == url));
+
+    // The ping was uploaded, so it's not retried.
+    assert!(fallback_r
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .is_err());
+}
+
+#[test]
+fn disabled_pings_are_not_submitted() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a recording uploader.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec![PING_NAME.into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    set_ping_enabled(PING_NAME, false);
+    counter.add(1);
+    assert_eq!(None, counter.test_get_value(None));
+    custom_ping.submit(None);
+
+    // Once enabled again, the ping is sent as usual.
+    set_ping_enabled(PING_NAME, true);
+    counter.add(1);
+    assert_eq!(Some(1), counter.test_get_value(None));
+    custom_ping.submit(None);
+    uploader.wait_for_uploads(PING_NAME, 1);
+    // The first one never was, it would have been sent before.
+    assert_eq!(1, uploader.uploads_of(PING_NAME).len());
+}
+
+#[test]
+fn slow_ping_assembly_is_counted() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    // Create a custom configuration to use a fake uploader
+    // and a threshold no assembly can stay under.
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        slow_assembly_threshold: Some(std::time::Duration::from_nanos(1)),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    for i in 0..500 {
+        let metric = StringMetric::new(CommonMetricData {
+            name: format!("string_{}", i),
+            category: "test".into(),
+            send_in_pings: vec![PING_NAME.into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        });
+        metric.set("a value that takes up some space in the store".into());
+    }
+
+    custom_ping.submit(None);
+
+    // The ping is still assembled and sent.
+    uploader.assert_sent(PING_NAME);
+
+    let slow_assembly = CounterMetric::new(CommonMetricData {
+        name: "slow".into(),
+        category: "glean.assembly".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    assert!(slow_assembly.test_get_value(None).unwrap() >= 1);
+}
+
+#[test]
+fn destroy_allows_a_clean_reinitialization() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+    let cfg = || Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname.clone())
+    };
+
+    let metric = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::User,
+        disabled: false,
+        ..Default::default()
+    });
+
+    let _documented = CounterMetric::new(CommonMetricData {
+        name: "documented".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        description: Some("A counter to describe.".into()),
+        ..Default::default()
+    });
+    assert!(!crate::test::describe_metrics().is_empty());
+
+    test_reset_glean(cfg(), ClientInfoMetrics::unknown(), true);
+    metric.set("persisted".into());
+    assert_eq!("persisted", metric.test_get_value(None).unwrap());
+
+    crate::test::destroy();
+    assert!(crate::test::describe_metrics().is_empty());
+
+    // Leftovers from before initialization are dropped by `destroy` too.
+    set_debug_view_tag("stale-tag");
+    crate::test::destroy();
+
+    // Initialize again with the same data directory, without clearing anything ourselves.
+    test_reset_glean(cfg(), ClientInfoMetrics::unknown(), false);
+    assert!(metric.test_get_value(None).is_none());
+
+    submit_ping_by_name("baseline", Some("inactive"));
+    let (_, _, headers) = uploader.wait_for_uploads("baseline", 1).remove(0);
+    assert!(!headers.iter().any(|(name, _)| name == "X-Debug-ID"));
+}
+
+#[test]
+fn build_id_is_reported_in_client_info() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let client_info = ClientInfoMetrics {
+        build_id: Some("20230615093512".into()),
+        ..ClientInfoMetrics::unknown()
+    };
+    test_reset_glean(
+        Configuration {
+            uploader: Some(Box::new(uploader.clone())),
+            ..test_configuration(tmpname)
+        },
+        client_info,
+        true,
+    );
+
+    submit_ping_by_name("baseline", Some("inactive"));
+
+    let (_, body, _) = uploader.wait_for_uploads("baseline", 1).remove(0);
+    let payload = decode_payload(&body);
+    assert_eq!("20230615093512", payload["client_info"]["build_id"]);
+}
+
+#[test]
+fn recording_latency_is_measured_when_profiling() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        profile_recording: true,
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    for i in 0..50 {
+        counter.add(1);
+        string.set(format!("value {}", i));
+    }
+    // Other tasks aren't measured.
+    set_experiment_active("experiment".into(), "branch".into(), None);
+    assert_eq!(50, counter.test_get_value(None).unwrap());
+
+    let record_latency = private::TimingDistributionMetric::new(
+        CommonMetricData {
+            name: "record_latency".into(),
+            category: "glean.sdk".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        },
+        TimeUnit::Nanosecond,
+    );
+    let latency = record_latency.test_get_value(None).unwrap();
+    assert_eq!(100, latency.count);
+}
+
+#[test]
+fn pending_ping_files_are_named_after_the_configured_format() {
+    let _lock = lock_test();
+
+    // Define a fake uploader that reports back the files in the pending pings directory
+    // at the time of the upload using a crossbeam channel.
+    let (s, r) = crossbeam_channel::unbounded::<Vec<String>>();
+
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        pending_pings_dir: std::path::PathBuf,
+        sender: crossbeam_channel::Sender<Vec<String>>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            let files = std::fs::read_dir(&self.pending_pings_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            self.sender.send(files).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader {
+            pending_pings_dir: tmpname.join("pending_pings"),
+            sender: s,
+        })),
+        pending_ping_filename_format: Some("{ping_name}-{timestamp}-{doc_id}.json".into()),
+        ..test_configuration(tmpname.clone())
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+
+    let is_formatted = |file_name: &str| {
+        let rest = file_name.strip_prefix("test-ping-").unwrap();
+        let (timestamp, doc_id) = rest
+            .strip_suffix(".json")
+            .unwrap()
+            .split_at(rest.len() - 41);
+        timestamp.strip_suffix('-').unwrap().parse::<u64>().unwrap();
+        uuid::Uuid::parse_str(doc_id).is_ok()
+    };
+
+    custom_ping.submit(None);
+    let files = r.recv().unwrap();
+    assert_eq!(1, files.len());
+    assert!(is_formatted(&files[0]), "{}", files[0]);
+
+    // The first ping's file is found and deleted after its successful upload.
+    custom_ping.submit(None);
+    let files = r.recv().unwrap();
+    assert_eq!(1, files.len());
+    assert!(is_formatted(&files[0]), "{}", files[0]);
+}
+
+#[test]
+fn metrics_are_mirrored_into_the_debug_store() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        mirror_to_debug_store: true,
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into(), "store2".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store3".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+    let event = private::EventMetric::<traits::NoExtraKeys>::new(CommonMetricData {
+        name: "click".into(),
+        category: "ui".into(),
+        send_in_pings: vec!["events".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    counter.add(2);
+    counter.add(3);
+    string.set("mirrored".into());
+    event.record(None);
+
+    // Taking the data out of a ping's store doesn't affect the debug store.
+    test_snapshot_store("store1", true);
+
+    let debug = dump_store("debug");
+    assert_eq!(5, debug["counter"]["test.counter"]);
+    assert_eq!("mirrored", debug["string"]["test.string"]);
+    let events = debug["events"].as_array().unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!("click", events[0]["name"]);
+}
+
+#[test]
+fn all_errors_are_enumerated() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+    let labeled = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "labeled".into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Application,
+            disabled: false,
+            ..Default::default()
+        },
+        None,
+    );
+    let timespan = private::TimespanMetric::new(
+        CommonMetricData {
+            name: "timespan".into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Application,
+            disabled: false,
+            ..Default::default()
+        },
+        TimeUnit::Millisecond,
+    );
+
+    counter.add(-1);
+    counter.add(0);
+    string.set("x".repeat(200));
+    labeled.get("Not Valid").add(1);
+    timespan.stop();
+
+    assert_eq!(
+        vec![
+            ("test.counter".to_string(), ErrorType::InvalidValue, 2),
+            ("test.labeled".to_string(), ErrorType::InvalidLabel, 1),
+            ("test.string".to_string(), ErrorType::InvalidOverflow, 1),
+            ("test.timespan".to_string(), ErrorType::InvalidState, 1),
+        ],
+        crate::test::all_errors()
+    );
+}
+
+#[test]
+fn deleting_a_metric_removes_it_from_all_stores() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into(), "store2".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    let other = CounterMetric::new(CommonMetricData {
+        name: "other".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    counter.add(3);
+    counter.add(-1);
+    other.add(1);
+    assert_eq!(Some(3), counter.test_get_value(Some("store1".into())));
+    assert_eq!(Some(3), counter.test_get_value(Some("store2".into())));
+    assert_eq!(
+        1,
+        counter.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+
+    delete_metric("test", "counter");
+
+    assert_eq!(None, counter.test_get_value(Some("store1".into())));
+    assert_eq!(None, counter.test_get_value(Some("store2".into())));
+    assert_eq!(
+        0,
+        counter.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+    assert!(crate::test::all_errors().is_empty());
+    assert_eq!(Some(1), other.test_get_value(None));
+
+    // Deleting again, or deleting a metric that was never recorded, is a no-op.
+    delete_metric("test", "counter");
+    delete_metric("test", "unknown");
+    assert_eq!(Some(1), other.test_get_value(None));
+}
+
+#[test]
+fn distribution_buckets_are_bounded() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        max_distribution_buckets: Some(10),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let metric = private::MemoryDistributionMetric::new(
+        CommonMetricData {
+            name: "memory".into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        },
+        MemoryUnit::Byte,
+    );
+
+    // Samples spanning 1 byte to 1 gigabyte fall into far more than 10 buckets.
+    let samples: Vec<i64> = (0..30).map(|exp| 1 << exp).collect();
+    metric.accumulate_samples(samples.clone());
+    metric.accumulate(1 << 35);
+
+    let data = metric.test_get_value(None).unwrap();
+    let filled = data.values.values().filter(|&&count| count > 0).count();
+    assert_eq!(10, filled);
+    assert_eq!(31, data.count);
+    assert_eq!(samples.iter().sum::<i64>() + (1 << 35), data.sum);
+    assert_eq!(31, data.values.values().sum::<i64>());
+
+    let buckets_merged = CounterMetric::new(CommonMetricData {
+        name: "buckets_merged".into(),
+        category: "glean.distribution".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    // 20 buckets are merged on the first accumulation, 1 on the second.
+    assert_eq!(Some(21), buckets_merged.test_get_value(None));
+}
+
+#[test]
+fn metrics_are_recorded_from_json() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let meta = |name: &str| CommonMetricData {
+        name: name.into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    };
+    let boolean = private::BooleanMetric::new(meta("boolean"));
+    let counter = CounterMetric::new(meta("counter"));
+    let string = StringMetric::new(meta("string"));
+
+    record_from_json(&boolean, serde_json::json!(true));
+    record_from_json(&counter, serde_json::json!(2));
+    record_from_json(&counter, serde_json::json!(3));
+    record_from_json(&string, serde_json::json!("from the bus"));
+
+    assert_eq!(Some(true), boolean.test_get_value(None));
+    assert_eq!(Some(5), counter.test_get_value(None));
+    assert_eq!(Some("from the bus".into()), string.test_get_value(None));
+
+    // Values not matching the metric type are rejected.
+    record_from_json(&boolean, serde_json::json!("true"));
+    record_from_json(&counter, serde_json::json!(1.5));
+    record_from_json(&counter, serde_json::json!(i64::MAX));
+    record_from_json(&string, serde_json::json!({ "value": "nested" }));
+
+    assert_eq!(Some(true), boolean.test_get_value(None));
+    assert_eq!(Some(5), counter.test_get_value(None));
+    assert_eq!(Some("from the bus".into()), string.test_get_value(None));
+    assert_eq!(
+        1,
+        boolean.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+    assert_eq!(
+        2,
+        counter.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+    assert_eq!(
+        1,
+        string.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+}
+
+#[test]
+fn pings_can_be_submitted_at_a_given_time() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+
+    let submitted_at = chrono::DateTime::parse_from_rfc3339("2020-06-11T09:30:00+02:00").unwrap();
+    custom_ping.submit_at(None, submitted_at);
+    let payload = |count| {
+        let (_, body, _) = uploader
+            .wait_for_uploads("test-ping", count)
+            .remove(count - 1);
+        decode_payload(&body)
+    };
+    let first = payload(1);
+    assert_eq!(
+        "2020-06-11T09:30+02:00",
+        first["ping_info"]["end_time"].as_str().unwrap()
+    );
+
+    // The replayed ping doesn't move the start time of the next one.
+    custom_ping.submit(None);
+    let second = payload(2);
+    assert_ne!(
+        "2020-06-11T09:30+02:00",
+        second["ping_info"]["start_time"].as_str().unwrap()
+    );
+    let end_time = second["ping_info"]["end_time"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Submission times too far in the future are rejected.
+    let future: chrono::DateTime<chrono::FixedOffset> =
+        (chrono::Local::now() + chrono::Duration::hours(1)).into();
+    custom_ping.submit_at(None, future);
+    custom_ping.submit(None);
+    let third = payload(3);
+    assert_ne!(
+        future.format("%Y-%m-%dT%H:%M%:z").to_string(),
+        third["ping_info"]["end_time"].as_str().unwrap()
+    );
+    assert_eq!(end_time, third["ping_info"]["start_time"].as_str().unwrap());
+    assert_eq!(2, third["ping_info"]["seq"]);
+    assert_eq!(3, uploader.uploads_of("test-ping").len());
+}
+
+#[test]
+fn pings_failing_schema_validation_are_not_assembled_anymore() {
+    let _lock = lock_test();
+
+    // The validator rejects every ping and reports the rejected ping names back
+    // using a crossbeam channel.
+    let (s, r) = crossbeam_channel::unbounded::<String>();
+    let validator = move |ping_name: &str, _payload: &serde_json::Value| {
+        s.send(ping_name.to_string()).unwrap();
+        Err("Missing required property".to_string())
+    };
+
+    // The observer is alerted once the circuit opens.
+    let (alert_s, alert_r) = crossbeam_channel::unbounded::<String>();
+    let on_circuit_open = move |ping_name: &str| alert_s.send(ping_name.to_string()).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(net::NoopUploader)),
+        schema_validator: Some(Box::new(validator)),
+        schema_failure_threshold: Some(2),
+        on_schema_circuit_open: Some(Box::new(on_circuit_open)),
+        ..test_configuration(tmpname.clone())
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+
+    custom_ping.submit(None);
+    assert_eq!("test-ping", r.recv().unwrap());
+    custom_ping.submit(None);
+    assert_eq!("test-ping", r.recv().unwrap());
+    assert_eq!("test-ping", alert_r.recv().unwrap());
+
+    // The ping isn't assembled anymore.
+    custom_ping.submit(None);
+
+    let pings_submitted = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "pings_submitted".into(),
+            category: "glean.validation".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        },
+        None,
+    );
+    assert_eq!(
+        Some(2),
+        pings_submitted.get("test-ping").test_get_value(None)
+    );
+    assert!(r
+        .recv_timeout(std::time::Duration::from_millis(100))
+        .is_err());
+
+    let circuit_open = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "schema_circuit_open".into(),
+            category: "glean.error".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        },
+        None,
+    );
+    assert_eq!(Some(1), circuit_open.get("test-ping").test_get_value(None));
+
+    // Rejected pings are not kept around for retries.
+    let pending_pings = tmpname.join("pending_pings");
+    assert_eq!(
+        0,
+        std::fs::read_dir(pending_pings)
+            .map(|dir| dir.count())
+            .unwrap_or(0)
+    );
+}
+
+#[test]
+fn timespans_can_be_set_from_raw_nanoseconds() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    let meta = |name: &str| CommonMetricData {
+        name: name.into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    };
+    let millis = private::TimespanMetric::new(meta("millis"), TimeUnit::Millisecond);
+    let seconds = private::TimespanMetric::new(meta("seconds"), TimeUnit::Second);
+
+    millis.set_raw_nanos(1_234_567_890);
+    seconds.set_raw_nanos(1_234_567_890);
+
+    assert_eq!(Some(1_234), millis.test_get_value(None));
+    assert_eq!(Some(1), seconds.test_get_value(None));
+
+    // A running timer can't be overwritten with a raw value.
+    let running = private::TimespanMetric::new(meta("running"), TimeUnit::Nanosecond);
+    running.start();
+    running.set_raw_nanos(1_000);
+    running.stop();
+
+    assert_ne!(Some(1_000), running.test_get_value(None));
+    assert_eq!(
+        1,
+        running.test_get_num_recorded_errors(ErrorType::InvalidState)
+    );
+}
+
+#[test]
+fn identical_pings_are_deduplicated() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ping_dedup_window: Some(std::time::Duration::from_secs(60)),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = private::PingType::new("test-ping", true, true, vec![]);
+    let other_ping = private::PingType::new("other-ping", true, true, vec![]);
+
+    custom_ping.submit(None);
+    custom_ping.submit(None);
+    // Pings of another type are not compared to it.
+    other_ping.submit(None);
+
+    // Pings are uploaded in order, so the duplicate would have been uploaded by now.
+    uploader.assert_sent("other-ping");
+    assert_eq!(1, uploader.uploads_of("test-ping").len());
+
+    let deduplicated = CounterMetric::new(CommonMetricData {
+        name: "deduplicated".into(),
+        category: "glean.upload".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    assert_eq!(Some(1), deduplicated.test_get_value(None));
+}
+
+#[test]
+fn first_run_date_can_be_seeded() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let cfg = |dir: &tempfile::TempDir, first_run_date| Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        first_run_date,
+        ..test_configuration(dir.path().to_path_buf())
+    };
+
+    let first_run_date_of_baseline = |count| {
+        let (_, body, _) = uploader
+            .wait_for_uploads("baseline", count)
+            .remove(count - 1);
+        decode_payload(&body)["client_info"]["first_run_date"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let seeded = chrono::DateTime::parse_from_rfc3339("2019-03-05T14:00:00+01:00").unwrap();
+    let _t = new_glean(Some(cfg(&dir, Some(seeded))), true);
+
+    submit_ping_by_name("baseline", Some("active"));
+    assert_eq!("2019-03-05+01:00", first_run_date_of_baseline(1));
+
+    // Dates in the future are ignored.
+    let dir = tempfile::tempdir().unwrap();
+    let future: chrono::DateTime<chrono::FixedOffset> =
+        (chrono::Local::now() + chrono::Duration::days(2)).into();
+    let _t = new_glean(Some(cfg(&dir, Some(future))), true);
+
+    submit_ping_by_name("baseline", Some("active"));
+    assert_ne!(
+        future.format("%Y-%m-%d%:z").to_string(),
+        first_run_date_of_baseline(2)
+    );
+}
+
+#[test]
+fn counters_can_be_restricted_to_channels() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let client_info = ClientInfoMetrics {
+        channel: Some("testing".into()),
+        ..ClientInfoMetrics::unknown()
+    };
+    test_reset_glean(test_configuration(tmpname), client_info, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "channel_counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["validation".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    });
+
+    counter.add_if_channel(&["nightly", "beta"], 1);
+    assert_eq!(None, counter.test_get_value(None));
+
+    counter.add_if_channel(&["nightly", "testing"], 2);
+    assert_eq!(Some(2), counter.test_get_value(None));
+}
+
+#[test]
+fn toggling_delay_ping_lifetime_io_keeps_persisted_data() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = |delay_ping_lifetime_io| Configuration {
+        delay_ping_lifetime_io,
+        ..test_configuration(tmpname.clone())
+    };
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "delayed".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    let data_lost = CounterMetric::new(CommonMetricData {
+        name: "ping_lifetime_data_lost".into(),
+        category: "glean.database".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+
+    test_reset_glean(cfg(true), ClientInfoMetrics::unknown(), true);
+    counter.add(1);
+    persist_ping_lifetime_data();
+    counter.add(1);
+    assert_eq!(Some(2), counter.test_get_value(None));
+
+    // Resetting without clearing the stores doesn't persist the in-memory data,
+    // just like a crash.
+    test_reset_glean(cfg(false), ClientInfoMetrics::unknown(), false);
+    assert_eq!(Some(1), counter.test_get_value(None));
+    assert_eq!(Some(1), data_lost.test_get_value(None));
+    counter.add(2);
+
+    // Data recorded without delay is picked up with delay.
+    test_reset_glean(cfg(true), ClientInfoMetrics::unknown(), false);
+    assert_eq!(Some(3), counter.test_get_value(None));
+    counter.add(1);
+    persist_ping_lifetime_data();
+    counter.add(1);
+
+    // Shutting down persists the rest, nothing was lost this time.
+    crate::shutdown();
+    test_reset_glean(cfg(false), ClientInfoMetrics::unknown(), false);
+    assert_eq!(Some(5), counter.test_get_value(None));
+    assert_eq!(Some(1), data_lost.test_get_value(None));
+}
+
+#[test]
+fn store_stats_reflect_recorded_metrics() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+
+    for i in 0..3 {
+        let counter = CounterMetric::new(CommonMetricData {
+            name: format!("counter{}", i),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            ..Default::default()
+        });
+        counter.add(1);
+    }
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into(), "store2".into()],
+        ..Default::default()
+    });
+    string.set("a string value, taking up some space".into());
+
+    let stats = test::store_stats();
+    let stats_of = |name: &str| {
+        stats
+            .iter()
+            .find(|(store, _, _)| store == name)
+            .map(|(_, count, size)| (*count, *size))
+            .unwrap()
+    };
+
+    let (store1_count, store1_size) = stats_of("store1");
+    let (store2_count, store2_size) = stats_of("store2");
+    assert_eq!(4, store1_count);
+    assert_eq!(1, store2_count);
+    assert!(store1_size > store2_size);
+    assert!(store2_size > "a string value, taking up some space".len());
+
+    // Stores are sorted by name.
+    let names: Vec<_> = stats.iter().map(|(name, _, _)| name.clone()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(sorted, names);
+}
+
+#[test]
+fn session_id_changes_across_initializations() {
+    let _lock = lock_test();
+
+    let _t = new_glean(None, true);
+    let first = test::session_id();
+    assert!(uuid::Uuid::parse_str(&first).is_ok());
+
+    let _t = new_glean(None, true);
+    let second = test::session_id();
+
+    assert_ne!(first, second);
+}
+
+#[test]
+fn session_count_increments_per_initialization() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = || test_configuration(tmpname.clone());
+
+    test_reset_glean(cfg(), ClientInfoMetrics::unknown(), true);
+    assert_eq!(1, test::session_count());
+
+    test_reset_glean(cfg(), ClientInfoMetrics::unknown(), false);
+    assert_eq!(2, test::session_count());
+}
+
+#[test]
+fn custom_url_builder_is_used_for_uploads() {
+    let _lock = lock_test();
+
+    let (s, r) = crossbeam_channel::bounded::<String>(1);
+
+    // Define a fake uploader that reports back the submission URL
+    // using a crossbeam channel.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<String>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender.send(url).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        url_builder: Some(Box::new(|app_id, ping_name, doc_id| {
+            format!(
+                "https://ingest.example.com/{}/{}?id={}",
+                app_id, ping_name, doc_id
+            )
+        })),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    // The URL comes from the builder, not the server endpoint.
+    // It is passed the application id as sanitized for the default URL.
+    let url = r.recv().unwrap();
+    let prefix = format!(
+        "https://ingest.example.com/org-mozilla-rlb-test/{}?id=",
+        PING_NAME
+    );
+    assert!(url.starts_with(&prefix), "Unexpected URL {}", url);
+    assert!(!url.contains("invalid-test-host"));
+}
+
+#[test]
+fn metrics_ping_can_be_submitted_on_demand() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let metric = CounterMetric::new(CommonMetricData {
+        name: "counter_metric".into(),
+        category: "test".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        disabled: false,
+        ..Default::default()
+    });
+    metric.add(3);
+    let snapshot = test_snapshot_store("metrics", false);
+
+    // Reasons the metrics ping doesn't declare are rejected.
+    submit_metrics_ping(Some("invalid"));
+    assert_eq!(Some(3), metric.test_get_value(None));
+
+    // So the first metrics ping sent is the next one.
+    submit_metrics_ping(Some("today"));
+    let (_, body, _) = uploader.wait_for_uploads("metrics", 1).remove(0);
+    let payload = decode_payload(&body);
+    assert_eq!("today", payload["ping_info"]["reason"]);
+    assert_eq!(3, payload["metrics"]["counter"]["test.counter_metric"]);
+    assert_eq!(
+        snapshot["counter"]["test.counter_metric"],
+        payload["metrics"]["counter"]["test.counter_metric"]
+    );
+    assert_eq!(None, metric.test_get_value(None));
+}
+
+#[test]
+fn deletion_request_is_uploaded_after_disabling_upload() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    // Even a validator rejecting every ping can't hold back the deletion-request ping.
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        schema_validator: Some(Box::new(|_, _| Err("Rejecting everything".into()))),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let metric = private::StringMetric::new(CommonMetricData {
+        name: "string_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::User,
+        disabled: false,
+        ..Default::default()
+    });
+    metric.set("before".into());
+    let client_id = test_snapshot_store("glean_client_info", false)["uuid"]["client_id"].clone();
+    assert!(client_id.is_string());
+
+    set_upload_enabled(false);
+
+    // Recordings are suppressed while upload is disabled...
+    metric.set("after".into());
+    assert_eq!(None, metric.test_get_value(None));
+
+    // ...but the deletion-request ping is uploaded, with the client id to delete data for.
+    let (_, body, _) = uploader.wait_for_uploads("deletion-request", 1).remove(0);
+    let payload = decode_payload(&body);
+    assert_eq!(client_id, payload["client_info"]["client_id"]);
+    assert_ne!("c0ffeec0-ffee-c0ff-eec0-ffeec0ffeec0", client_id);
+    assert_eq!("set_upload_enabled", payload["ping_info"]["reason"]);
+
+    // No other ping is sent.
+    thread::sleep(std::time::Duration::from_millis(100));
+    assert_eq!(1, uploader.uploads().len());
+}
+
+#[test]
+fn recordings_within_the_min_record_interval_are_dropped() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "throttled".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        min_record_interval_ms: Some(200),
+        ..Default::default()
+    });
+
+    // Only the first of a burst of recordings lands.
+    for _ in 0..100 {
+        counter.add(1);
+    }
+    assert_eq!(Some(1), counter.test_get_value(None));
+
+    // Once the interval passed, the next recording lands again.
+    thread::sleep(std::time::Duration::from_millis(250));
+    for _ in 0..100 {
+        counter.add(1);
+    }
+    assert_eq!(Some(2), counter.test_get_value(None));
+}
+
+#[test]
+fn submit_ping_and_await_returns_the_upload_result() {
+    let _lock = lock_test();
+
+    #[derive(Debug)]
+    pub struct FakeUploader;
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            net::UploadResult::http_status(202)
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader)),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let custom_ping = PingType::new("await-ping", true, true, vec![]);
+    assert!(matches!(
+        test::submit_ping_and_await(&custom_ping, None),
+        net::UploadResult::HttpStatus { code: 202 }
+    ));
+
+    // Empty pings are not submitted, so there's nothing to wait for.
+    let empty_ping = PingType::new("empty-await-ping", true, false, vec![]);
+    assert!(matches!(
+        test::submit_ping_and_await(&empty_ping, None),
+        net::UploadResult::Done { .. }
+    ));
+}
+
+#[test]
+fn documented_metrics_can_be_described() {
+    let _lock = lock_test();
+
+    let _counter = CounterMetric::new(CommonMetricData {
+        name: "described_counter".into(),
+        category: "describe".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        description: Some("Counts what is described.".into()),
+        data_sensitivity: Some(vec![DataSensitivity::Technical]),
+        ..Default::default()
+    });
+    let _labeled = private::LabeledMetric::<StringMetric>::new(
+        CommonMetricData {
+            name: "described_labeled".into(),
+            category: "describe".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Application,
+            data_sensitivity: Some(vec![DataSensitivity::Interaction]),
+            ..Default::default()
+        },
+        None,
+    );
+    let _undocumented = BooleanMetric::new(CommonMetricData {
+        name: "undocumented".into(),
+        category: "describe".into(),
+        send_in_pings: vec!["store1".into()],
+        ..Default::default()
+    });
+
+    let described: Vec<MetricDescriptor> = test::describe_metrics()
+        .into_iter()
+        .filter(|descriptor| descriptor.category == "describe")
+        .collect();
+    assert_eq!(
+        vec![
+            MetricDescriptor {
+                category: "describe".into(),
+                name: "described_counter".into(),
+                metric_type: "counter".into(),
+                lifetime: Lifetime::Ping,
+                send_in_pings: vec!["store1".into()],
+                description: Some("Counts what is described.".into()),
+                data_sensitivity: vec![DataSensitivity::Technical],
+            },
+            MetricDescriptor {
+                category: "describe".into(),
+                name: "described_labeled".into(),
+                metric_type: "string".into(),
+                lifetime: Lifetime::Application,
+                send_in_pings: vec!["store1".into()],
+                description: None,
+                data_sensitivity: vec![DataSensitivity::Interaction],
+            },
+        ],
+        described
+    );
+}
+
+#[test]
+fn disabled_internal_metrics_are_not_recorded_into_the_metrics_ping() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        enable_internal_metrics: false,
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "app_counter".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    counter.add(1);
+    // Would record an error into `glean.error.invalid_value`.
+    counter.add(-1);
+    assert_eq!(Some(1), counter.test_get_value(None));
+
+    let snapshot = test_snapshot_store("metrics", false);
+    assert_eq!(1, snapshot["counter"]["telemetry.app_counter"]);
+    // Errors of the application's metrics are kept.
+    assert_eq!(
+        serde_json::json!({ "telemetry.app_counter": 1 }),
+        snapshot["labeled_counter"]["glean.error.invalid_value"]
+    );
+    let sections = snapshot.as_object().unwrap();
+    for (section, metrics) in sections {
+        for id in metrics.as_object().unwrap().keys() {
+            assert!(
+                !id.starts_with("glean.") || id == "glean.error.invalid_value",
+                "{} {} was recorded",
+                section,
+                id
+            );
+        }
+    }
+}
+
+#[test]
+fn upload_interceptor_sees_what_the_uploader_receives() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    type Observed = Arc<Mutex<Vec<(String, Vec<(String, String)>, usize)>>>;
+
+    #[derive(Debug)]
+    struct Interceptor(Observed);
+    impl net::UploadInterceptor for Interceptor {
+        fn before_upload(&self, url: &str, headers: &[(String, String)], body_len: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((url.to_string(), headers.to_vec(), body_len));
+        }
+    }
+
+    let observed = Observed::default();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        upload_interceptor: Some(Box::new(Interceptor(Arc::clone(&observed)))),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    const PING_NAME: &str = "test-ping";
+    let custom_ping = private::PingType::new(PING_NAME, true, true, vec![]);
+    custom_ping.submit(None);
+
+    let (url, _, _) = uploader.wait_for_uploads(PING_NAME, 1).remove(0);
+
+    // The interceptor saw the request before the uploader did.
+    let observed = observed.lock().unwrap();
+    assert_eq!(1, observed.len());
+    let (observed_url, headers, body_len) = &observed[0];
+    assert_eq!(&url, observed_url);
+    assert!(url.contains(PING_NAME));
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "Content-Length")
+        .map(|(_, value)| value.clone());
+    assert_eq!(Some(body_len.to_string()), content_length);
+}
+
+#[test]
+fn persisted_experiments_survive_a_restart() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let uploader = test::RecordingUploader::new();
+    let cfg = |persist_experiments| Configuration {
+        persist_experiments,
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(tmpname.clone())
+    };
+
+    test_reset_glean(cfg(true), ClientInfoMetrics::unknown(), true);
+    set_experiment_active("persisted".to_string(), "branch_a".to_string(), None);
+    assert!(test_is_experiment_active("persisted".to_string()));
+
+    // The experiment is still active on the next run, without setting it again.
+    test_reset_glean(cfg(true), ClientInfoMetrics::unknown(), false);
+    let stored_data = test_get_experiment_data("persisted".to_string()).unwrap();
+    assert_eq!("branch_a", stored_data.branch);
+
+    // And annotates the pings.
+    PingType::new("with-experiments", true, true, vec![]).submit(None);
+    let (_, body, _) = uploader.wait_for_uploads("with-experiments", 1).remove(0);
+    let payload = decode_payload(&body);
+    assert_eq!(
+        "branch_a",
+        payload["ping_info"]["experiments"]["persisted"]["branch"]
+    );
+
+    // Until it is set inactive.
+    set_experiment_inactive("persisted".to_string());
+    test_reset_glean(cfg(true), ClientInfoMetrics::unknown(), false);
+    assert!(!test_is_experiment_active("persisted".to_string()));
+
+    // Experiments are set active again on every run by default.
+    test_reset_glean(cfg(false), ClientInfoMetrics::unknown(), false);
+    set_experiment_active("session".to_string(), "branch_b".to_string(), None);
+    test_reset_glean(cfg(false), ClientInfoMetrics::unknown(), false);
+    assert!(!test_is_experiment_active("session".to_string()));
+}
+
+#[test]
+fn data_path_is_the_configured_one() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = test_configuration(tmpname.clone());
+
+    test_reset_glean(cfg, ClientInfoMetrics::unknown(), true);
+
+    assert_eq!(Some(tmpname), data_path());
+}
+
+#[test]
+fn endpoint_overrides_route_pings_to_other_servers() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        endpoint_overrides: Some(HashMap::from([(
+            "routed-ping".to_string(),
+            "https://other.example.com".to_string(),
+        )])),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    private::PingType::new("routed-ping", true, true, vec![]).submit(None);
+    private::PingType::new("test-ping", true, true, vec![]).submit(None);
+
+    uploader.assert_sent("routed-ping");
+    uploader.assert_sent("test-ping");
+    let mut urls: Vec<String> = uploader
+        .uploads()
+        .into_iter()
+        .map(|(url, _, _)| url)
+        .collect();
+    urls.sort();
+
+    // Only the overridden ping goes to the other server.
+    assert!(
+        urls[0].starts_with("https://other.example.com/submit/org-mozilla-rlb-test/routed-ping/"),
+        "Unexpected URL {}",
+        urls[0]
+    );
+    assert!(
+        urls[1].starts_with("invalid-test-host/submit/org-mozilla-rlb-test/test-ping/"),
+        "Unexpected URL {}",
+        urls[1]
+    );
+}
+
+#[test]
+fn configured_content_type_reaches_the_uploader() {
+    let _lock = lock_test();
+
+    let uploader = test::RecordingUploader::new();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        content_type: Some("application/x-glean-ping".into()),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    private::PingType::new("test-ping", true, true, vec![]).submit(None);
+
+    let (_, _, headers) = uploader.wait_for_uploads("test-ping", 1).remove(0);
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(Some("application/x-glean-ping"), header("Content-Type"));
+    // The encoding is still sent separately.
+    assert_eq!(Some("gzip"), header("Content-Encoding"));
+}
+
+#[test]
+fn nothing_is_recorded_while_recording_is_paused() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "paused".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    counter.add(1);
+
+    // Recordings while paused are dropped, the last value is kept.
+    crate::set_recording_paused(true);
+    counter.add(10);
+    assert_eq!(Some(1), counter.test_get_value(None));
+
+    crate::set_recording_paused(false);
+    counter.add(1);
+    assert_eq!(Some(2), counter.test_get_value(None));
+}
+
+#[test]
+fn error_summary_covers_the_errors_destined_for_a_ping() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into(), "store2".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+
+    counter.add(-1);
+    counter.add(0);
+    string.set("x".repeat(200));
+
+    let summary = crate::test::error_summary("store1");
+    assert_eq!(2, summary.len());
+    assert_eq!(
+        HashMap::from([(ErrorType::InvalidValue, 2)]),
+        summary["test.counter"]
+    );
+    assert_eq!(
+        HashMap::from([(ErrorType::InvalidOverflow, 1)]),
+        summary["test.string"]
+    );
+    let summary = crate::test::error_summary("store2");
+    assert_eq!(vec!["test.string"], summary.keys().collect::<Vec<_>>());
+
+    // Clearing only affects the given ping.
+    crate::test::clear_errors("store1");
+    assert!(crate::test::error_summary("store1").is_empty());
+    assert_eq!(1, crate::test::error_summary("store2").len());
+    assert_eq!(2, crate::test::error_summary("metrics").len());
+}
+
+#[test]
+fn recordings_of_a_child_process_are_ingested() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "child".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    let labeled = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "labeled".into(),
+            category: "child".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+    let flag = BooleanMetric::new(CommonMetricData {
+        name: "flag".into(),
+        category: "child".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+
+    // The child records into a batch instead of its own database.
+    let blob = {
+        let mut batch = RecordingBatch::new();
+        batch.add_counter(&counter, 2);
+        batch.add_counter(&labeled.get("label"), 1);
+        batch.set_boolean(&flag, true);
+        // Invalid recordings are reported by the main process.
+        batch.add_counter(&counter, -1);
+        batch.serialize()
+    };
+
+    crate::ingest_recordings(&blob);
+    assert_eq!(Some(2), counter.test_get_value(None));
+    assert_eq!(Some(1), labeled.get("label").test_get_value(None));
+    assert_eq!(Some(true), flag.test_get_value(None));
+    assert_eq!(
+        1,
+        counter.test_get_num_recorded_errors(ErrorType::InvalidValue)
+    );
+
+    // Garbage is ignored.
+    crate::ingest_recordings(b"garbage");
+    assert_eq!(Some(2), counter.test_get_value(None));
+}
+
+#[test]
+fn upload_enabled_tracks_the_flag() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+    assert!(crate::upload_enabled());
+
+    // Reading a value waits for the queued tasks, including the flip.
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+
+    crate::set_upload_enabled(false);
+    assert_eq!(None, counter.test_get_value(None));
+    assert!(!crate::upload_enabled());
+
+    crate::set_upload_enabled(true);
+    assert_eq!(None, counter.test_get_value(None));
+    assert!(crate::upload_enabled());
+}
+
+#[test]
+fn distinct_labels_are_capped() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        max_distinct_labels: Some(5),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let labeled = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "labeled".into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+    for i in 0..10 {
+        labeled.get(format!("label_{}", i)).add(1);
+    }
+    // Labels requested before are still available.
+    labeled.get("label_0").add(1);
+
+    assert_eq!(Some(2), labeled.get("label_0").test_get_value(None));
+    assert_eq!(Some(1), labeled.get("label_4").test_get_value(None));
+    assert_eq!(Some(5), labeled.get("__other__").test_get_value(None));
+
+    let label_overflow = CounterMetric::new(CommonMetricData {
+        name: "label_overflow".into(),
+        category: "glean.error".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    assert_eq!(Some(5), label_overflow.test_get_value(None));
+}
+
+#[test]
+fn exported_pings_are_uploaded_after_importing() {
+    let _lock = lock_test();
+
+    // Fails all uploads, so pings stay pending.
+    #[derive(Debug)]
+    pub struct OfflineUploader;
+    impl net::PingUploader for OfflineUploader {
+        fn upload(
+            &self,
+            _url: String,
+            _body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            net::UploadResult::recoverable_failure()
+        }
+    }
+
+    let ping = PingType::new("exported-ping", true, true, vec![]);
+
+    // The first device can't upload, so the ping stays pending.
+    let first_dir = tempfile::tempdir().unwrap();
+    let cfg = Configuration {
+        uploader: Some(Box::new(OfflineUploader)),
+        ..test_configuration(first_dir.path().to_path_buf())
+    };
+    let _t = new_glean(Some(cfg), true);
+    ping.submit(None);
+
+    let archive = tempfile::tempdir().unwrap();
+    let archive_path = archive.path().join("pings.archive");
+    assert_eq!(1, export_pending_pings(&archive_path).unwrap());
+    let pending_dir = first_dir.path().join("pending_pings");
+    let document_id = std::fs::read_dir(pending_dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .file_name()
+        .into_string()
+        .unwrap();
+
+    // The second device starts from scratch.
+    let second_dir = tempfile::tempdir().unwrap();
+    let uploader = test::RecordingUploader::new();
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        ..test_configuration(second_dir.path().to_path_buf())
+    };
+    test_reset_glean(cfg, ClientInfoMetrics::unknown(), true);
+    assert_eq!(1, import_pending_pings(&archive_path).unwrap());
+
+    // Same document id, same URL.
+    let (url, _, _) = uploader.wait_for_uploads("exported-ping", 1).remove(0);
+    assert!(url.contains(&document_id));
+}
+
+#[test]
+fn only_allowed_metric_categories_record() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        allowed_metric_categories: Some(vec!["allowed".into()]),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let counter_in = |category: &str| {
+        CounterMetric::new(CommonMetricData {
+            name: "counter".into(),
+            category: category.into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        })
+    };
+    let allowed = counter_in("allowed");
+    let disallowed = counter_in("unsanctioned");
+    allowed.add(1);
+    disallowed.add(1);
+    disallowed.add(1);
+
+    assert_eq!(Some(1), allowed.test_get_value(None));
+    assert_eq!(None, disallowed.test_get_value(None));
+
+    let disallowed_category = CounterMetric::new(CommonMetricData {
+        name: "disallowed_category".into(),
+        category: "glean.error".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    assert_eq!(Some(2), disallowed_category.test_get_value(None));
+}
+
+#[test]
+fn wall_clock_timespans_include_clock_jumps() {
+    let _lock = lock_test();
+    let _t = new_glean(None, true);
+
+    // A clock whose wall clock jumps an hour ahead after a second,
+    // e.g. because the device slept.
+    #[derive(Debug, Default)]
+    struct JumpingClock {
+        jumped: AtomicBool,
+    }
+    impl Clock for JumpingClock {
+        fn monotonic_nanos(&self) -> u64 {
+            if self.jumped.load(Ordering::SeqCst) {
+                1_000_000_000
+            } else {
+                0
+            }
+        }
+        fn wall_clock_nanos(&self) -> u64 {
+            if self.jumped.load(Ordering::SeqCst) {
+                3_601_000_000_000
+            } else {
+                0
+            }
+        }
+    }
+    let clock = Arc::new(JumpingClock::default());
+
+    let timespan = |name: &str, use_wall_clock: bool| {
+        private::TimespanMetric::new_with_wall_clock(
+            CommonMetricData {
+                name: name.into(),
+                category: "test".into(),
+                send_in_pings: vec!["store1".into()],
+                lifetime: Lifetime::Ping,
+                ..Default::default()
+            },
+            TimeUnit::Second,
+            use_wall_clock,
+        )
+        .with_clock(clock.clone())
+    };
+    let monotonic = timespan("monotonic", false);
+    let wall_clock = timespan("wall_clock", true);
+
+    monotonic.start();
+    wall_clock.start();
+    clock.jumped.store(true, Ordering::SeqCst);
+    monotonic.stop();
+    wall_clock.stop();
+
+    assert_eq!(Some(1), monotonic.test_get_value(None));
+    assert_eq!(Some(3601), wall_clock.test_get_value(None));
+}
+
+#[test]
+fn init_callbacks_run_once_initialized() {
+    let _lock = lock_test();
+
+    destroy_glean(true);
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let (s, r) = crossbeam_channel::unbounded();
+    let (calls_, s_) = (calls.clone(), s.clone());
+    on_initialized(Box::new(move || {
+        calls_.fetch_add(1, Ordering::SeqCst);
+        s_.send("before init").unwrap();
+    }));
+    assert_eq!(0, calls.load(Ordering::SeqCst));
+
+    let _t = new_glean(None, true);
+    assert_eq!(
+        "before init",
+        r.recv_timeout(Duration::from_secs(5)).unwrap()
+    );
+
+    // Once initialized, callbacks run right away.
+    on_initialized(Box::new(move || s.send("after init").unwrap()));
+    assert_eq!(
+        "after init",
+        r.recv_timeout(Duration::from_secs(5)).unwrap()
+    );
+
+    // Initializing again doesn't run the callback again.
+    let _t = new_glean(None, true);
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "test".into(),
+        send_in_pings: vec!["store1".into()],
+        ..Default::default()
+    });
+    counter.add(1);
+    assert_eq!(Some(1), counter.test_get_value(None));
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+}
+
+#[test]
+fn labels_longer_than_the_configured_maximum_are_folded() {
+    let _lock = lock_test();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        max_label_length: Some(10),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    let labeled = private::LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "labeled".into(),
+            category: "test".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+    labeled.get("ten_bytes_").add(1);
+    labeled.get("eleven_byte").add(1);
+    labeled.get("within_the_default_limit").add(1);
+
+    assert_eq!(Some(1), labeled.get("ten_bytes_").test_get_value(None));
+    assert_eq!(Some(2), labeled.get("__other__").test_get_value(None));
+    assert_eq!(
+        2,
+        labeled.test_get_num_recorded_errors(ErrorType::InvalidLabel)
+    );
+}
+
+#[test]
+fn large_pings_are_uploaded_streaming() {
+    let _lock = lock_test();
+
+    // Reports how each ping was uploaded.
+    #[derive(Debug)]
+    pub struct FakeUploader {
+        sender: crossbeam_channel::Sender<(String, &'static str, u64)>,
+    }
+    impl net::PingUploader for FakeUploader {
+        fn upload(
+            &self,
+            url: String,
+            body: Vec<u8>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            self.sender
+                .send((url, "buffered", body.len() as u64))
+                .unwrap();
+            net::UploadResult::http_status(200)
+        }
+
+        fn upload_streaming(
+            &self,
+            url: String,
+            mut body: std::io::Cursor<Vec<u8>>,
+            _headers: Vec<(String, String)>,
+        ) -> net::UploadResult {
+            let len = std::io::copy(&mut body, &mut std::io::sink()).unwrap();
+            self.sender.send((url, "streaming", len)).unwrap();
+            net::UploadResult::http_status(200)
+        }
+    }
+
+    let (s, r) = crossbeam_channel::unbounded();
+    // Pads the bodies of the pings submitted while set.
+    let pad = Arc::new(AtomicBool::new(false));
+    let pad_ = pad.clone();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(FakeUploader { sender: s })),
+        ping_transform: Some(Box::new(move |mut body| {
+            if pad_.load(Ordering::SeqCst) {
+                // Pseudo-random padding, so the body stays large once compressed.
+                let mut state: u32 = 0x2545_f491;
+                body.extend((0..net::STREAMING_UPLOAD_THRESHOLD).map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                }));
+            }
+            body
+        })),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+    let recv_ping = |name: &str| loop {
+        let upload = r.recv_timeout(Duration::from_secs(5)).unwrap();
+        if upload.0.contains(name) {
+            return upload;
+        }
+    };
+
+    PingType::new("small-ping", true, true, vec![]).submit(None);
+    let (_, path, _) = recv_ping("/small-ping/");
+    assert_eq!("buffered", path);
+
+    pad.store(true, Ordering::SeqCst);
+    PingType::new("large-ping", true, true, vec![]).submit(None);
+    let (_, path, len) = recv_ping("/large-ping/");
+    assert_eq!("streaming", path);
+    assert!(len >= net::STREAMING_UPLOAD_THRESHOLD as u64);
+}
+
+#[test]
+fn drained_pings_are_returned_instead_of_uploaded() {
+    let _lock = lock_test();
+
+    // Reports each upload, then holds it back until released.
+    #[derive(Debug)]
+    pub struct BlockingInterceptor {
+        sender: crossbeam_channel::Sender<String>,
+        release: crossbeam_channel::Receiver<()>,
+    }
+    impl net::UploadInterceptor for BlockingInterceptor {
+        fn before_upload(&self, url: &str, _headers: &[(String, String)], _body_len: usize) {
+            self.sender.send(url.to_string()).unwrap();
+            let _ = self.release.recv_timeout(Duration::from_secs(5));
+        }
+    }
+
+    let (s, r) = crossbeam_channel::unbounded();
+    let (release_s, release_r) = crossbeam_channel::unbounded();
+
+    let uploader = test::RecordingUploader::new();
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        uploader: Some(Box::new(uploader.clone())),
+        upload_interceptor: Some(Box::new(BlockingInterceptor {
+            sender: s,
+            release: release_r,
+        })),
+        pipeline_header: Some(("X-Pipeline".into(), "drain-test".into())),
+        ..test_configuration(tmpname)
+    };
+
+    let _t = new_glean(Some(cfg), true);
+
+    // Keep the uploader busy, so the next ping stays pending.
+    PingType::new("uploaded", true, true, vec![]).submit(None);
+    let url = r.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(url.contains("/uploaded/"));
+
+    PingType::new("drained", true, true, vec![]).submit(None);
+    let drained: Vec<net::AssembledPing> = drain_pings()
+        .into_iter()
+        .filter(|ping| ping.ping_name == "drained")
+        .collect();
+    assert_eq!(1, drained.len());
+    let ping = &drained[0];
+    assert_eq!(
+        format!(
+            "invalid-test-host/submit/org-mozilla-rlb-test/drained/1/{}",
+            ping.document_id
+        ),
+        ping.url
+    );
+    assert!(ping
+        .headers
+        .contains(&("X-Pipeline".to_string(), "drain-test".to_string())));
+
+    assert_eq!(0, decode_payload(&ping.body)["ping_info"]["seq"]);
+
+    // Drained pings are gone, so they are never uploaded.
+    assert!(drain_pings().is_empty());
+    release_s.send(()).unwrap();
+    PingType::new("after", true, true, vec![]).submit(None);
+    release_s.send(()).unwrap();
+    uploader.assert_sent("after");
+    uploader.assert_not_sent("drained");
+}
+
+#[test]
+fn pings_are_serialized_in_the_configured_format() {
+    let _lock = lock_test();
+
+    for (format, expected_content_type) in [
+        (PingFormat::Json, "application/json; charset=utf-8"),
+        (PingFormat::MessagePack, "application/msgpack"),
+    ] {
+        let uploader = test::RecordingUploader::new();
+        let dir = tempfile::tempdir().unwrap();
+        let tmpname = dir.path().to_path_buf();
+
+        let cfg = Configuration {
+            uploader: Some(Box::new(uploader.clone())),
+            ping_format: Some(format),
+            ..test_configuration(tmpname)
+        };
+
+        let _t = new_glean(Some(cfg), true);
+
+        let counter = CounterMetric::new(CommonMetricData {
+            name: "counter".into(),
+            category: "test".into(),
+            send_in_pings: vec!["formatted".into()],
+            ..Default::default()
+        });
+        counter.add(300);
+        PingType::new("formatted", true, true, vec![]).submit(None);
+
+        let (_, body, headers) = uploader.wait_for_uploads("formatted", 1).remove(0);
+        assert!(headers.contains(&(
+            "Content-Type".to_string(),
+            expected_content_type.to_string()
+        )));
+        let payload = match format {
+            PingFormat::Json => decode_payload(&body),
+            PingFormat::MessagePack => {
+                let mut decoder = flate2::read::GzDecoder::new(&body[..]);
+                let mut payload = Vec::new();
+                std::io::Read::read_to_end(&mut decoder, &mut payload).unwrap();
+                rmp_serde::from_slice(&payload).unwrap()
+            }
+        };
+        assert_eq!(0, payload["ping_info"]["seq"]);
+        assert_eq!(300, payload["metrics"]["counter"]["test.counter"]);
+        assert!(payload["client_info"]["telemetry_sdk_build"].is_string());
+    }
+}
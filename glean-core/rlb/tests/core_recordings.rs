@@ -0,0 +1,60 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! This integration test should model how the RLB is used when embedded in another Rust application
+//! (e.g. FOG/Firefox Desktop).
+//!
+//! We write a single test scenario per file to avoid any state keeping across runs
+//! (different files run as different processes).
+
+mod common;
+
+use glean::{private::BooleanMetric, Configuration};
+use glean_core::{CommonMetricData, Lifetime};
+
+fn enabled_meta() -> CommonMetricData {
+    CommonMetricData {
+        name: "enabled".into(),
+        category: "component".into(),
+        send_in_pings: vec!["store1".into()],
+        lifetime: Lifetime::Application,
+        disabled: false,
+        ..Default::default()
+    }
+}
+
+/// Test scenario: A component records through glean-core directly.
+///
+/// The app initializes Glean through the RLB,
+/// while one of its components records data using glean-core's API.
+/// Both share the same global Glean, so the RLB sees those recordings.
+#[test]
+fn rlb_reads_recordings_made_through_glean_core() {
+    common::enable_test_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: "firefox-desktop".into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        ..Default::default()
+    };
+    common::initialize(cfg);
+
+    // The component uses its own instance of the metric, created from glean-core.
+    let core_metric = glean_core::metrics::BooleanMetric::new(enabled_meta());
+    core_metric.set(true);
+
+    let rlb_metric = BooleanMetric::new(enabled_meta());
+    assert_eq!(Some(true), rlb_metric.test_get_value(None));
+
+    glean::shutdown();
+}
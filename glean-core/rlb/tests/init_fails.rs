@@ -68,6 +68,7 @@ fn init_fails() {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: None,
         use_core_mps: false,
+        ..Default::default()
     };
     common::initialize(cfg);
 
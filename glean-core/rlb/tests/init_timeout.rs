@@ -0,0 +1,191 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! This integration test should model how the RLB is used when embedded in another Rust application
+//! (e.g. FOG/Firefox Desktop).
+//!
+//! We write a single test scenario per file to avoid any state keeping across runs
+//! (different files run as different processes).
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use glean::{Configuration, Lifetime, StorageBackend};
+
+/// Some user metrics.
+mod metrics {
+    use glean::private::*;
+    use glean::Lifetime;
+    use glean_core::CommonMetricData;
+    use once_cell::sync::Lazy;
+
+    #[allow(non_upper_case_globals)]
+    pub static early_counting: Lazy<CounterMetric> = Lazy::new(|| {
+        CounterMetric::new(CommonMetricData {
+            name: "early_counting".into(),
+            category: "sample".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        })
+    });
+
+    // To avoid reaching into RLB internals we re-create the metrics so we can look at them.
+    #[allow(non_upper_case_globals)]
+    pub static init_timeout: Lazy<CounterMetric> = Lazy::new(|| {
+        CounterMetric::new(CommonMetricData {
+            category: "glean.error".into(),
+            name: "init_timeout".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        })
+    });
+
+    #[allow(non_upper_case_globals)]
+    pub static preinit_tasks_overflow: Lazy<CounterMetric> = Lazy::new(|| {
+        CounterMetric::new(CommonMetricData {
+            category: "glean.error".into(),
+            name: "preinit_tasks_overflow".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            ..Default::default()
+        })
+    });
+}
+
+/// Keeps everything in memory, but gets stuck on its first access until released.
+#[derive(Debug)]
+struct StuckBackend {
+    data: Mutex<BTreeMap<(&'static str, String), Vec<u8>>>,
+    stuck: AtomicBool,
+    started: Sender<()>,
+    release: Receiver<()>,
+}
+
+impl StuckBackend {
+    fn new(started: Sender<()>, release: Receiver<()>) -> Self {
+        Self {
+            data: Mutex::default(),
+            stuck: AtomicBool::new(true),
+            started,
+            release,
+        }
+    }
+
+    fn wait(&self) {
+        if self.stuck.swap(false, Ordering::SeqCst) {
+            self.started.send(()).unwrap();
+            self.release.recv().unwrap();
+        }
+    }
+}
+
+impl StorageBackend for StuckBackend {
+    fn get(&self, lifetime: Lifetime, key: &str) -> glean::Result<Option<Vec<u8>>> {
+        self.wait();
+        let data = self.data.lock().unwrap();
+        Ok(data.get(&(lifetime.as_str(), key.to_string())).cloned())
+    }
+
+    fn put(&self, lifetime: Lifetime, key: &str, value: &[u8]) -> glean::Result<()> {
+        self.wait();
+        let mut data = self.data.lock().unwrap();
+        data.insert((lifetime.as_str(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, lifetime: Lifetime, key: &str) -> glean::Result<()> {
+        self.wait();
+        let mut data = self.data.lock().unwrap();
+        data.remove(&(lifetime.as_str(), key.to_string()));
+        Ok(())
+    }
+
+    fn iter(
+        &self,
+        lifetime: Lifetime,
+        prefix: &str,
+        f: &mut dyn FnMut(&str, &[u8]),
+    ) -> glean::Result<()> {
+        self.wait();
+        let data = self.data.lock().unwrap();
+        for ((store, key), value) in data.iter() {
+            if *store == lifetime.as_str() && key.starts_with(prefix) {
+                f(key, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Test scenario: Initialization hangs while metrics are recorded.
+///
+/// The app records metrics while Glean's storage is stuck during initialization.
+/// Once the init timeout passes, the recordings queued so far are dropped,
+/// and so are all recordings until initialization finishes.
+/// This is reported once Glean is initialized.
+///
+/// This is an integration test to avoid dealing with resetting the dispatcher.
+#[test]
+fn recordings_are_dropped_after_the_init_timeout() {
+    common::enable_test_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let (started, init_started) = crossbeam_channel::bounded::<()>(1);
+    let (release, release_receiver) = crossbeam_channel::bounded::<()>(1);
+
+    let cfg = Configuration {
+        data_path: tmpname,
+        application_id: "firefox-desktop".into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: None,
+        use_core_mps: false,
+        storage_backend: Some(Box::new(StuckBackend::new(started, release_receiver))),
+        init_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+
+    common::initialize(cfg);
+    init_started.recv().unwrap();
+
+    // Initialization is stuck now, these are queued...
+    for _ in 0..3 {
+        metrics::early_counting.add(1);
+    }
+    // ...until the timeout passes, then dropped...
+    thread::sleep(Duration::from_millis(500));
+    // ...as is this one, while initialization is still stuck.
+    metrics::early_counting.add(1);
+
+    release.send(()).unwrap();
+
+    assert_eq!(None, metrics::early_counting.test_get_value(None));
+    assert_eq!(Some(1), metrics::init_timeout.test_get_value(None));
+    // This might include Glean-internal tasks.
+    let dropped = metrics::preinit_tasks_overflow
+        .test_get_value(None)
+        .unwrap();
+    assert!(dropped >= 4);
+
+    // Recording works once Glean is initialized.
+    metrics::early_counting.add(1);
+    assert_eq!(Some(1), metrics::early_counting.test_get_value(None));
+
+    glean::shutdown();
+}
@@ -0,0 +1,75 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! This integration test should model how the RLB is used when embedded in another Rust application
+//! (e.g. FOG/Firefox Desktop).
+//!
+//! We write a single test scenario per file to avoid any state keeping across runs
+//! (different files run as different processes).
+
+mod common;
+
+use std::{fs, thread, time::Duration};
+
+use glean::net::NoopUploader;
+use glean::Configuration;
+
+mod pings {
+    use glean::private::PingType;
+    use once_cell::sync::Lazy;
+
+    #[allow(non_upper_case_globals)]
+    pub static benchmark: Lazy<PingType> =
+        Lazy::new(|| glean::private::PingType::new("benchmark", true, true, vec![]));
+}
+
+/// Test scenario: Benchmarking ping assembly.
+///
+/// The app is initialized with an uploader that drops every ping.
+/// Lots of pings are submitted and assembled.
+/// The ones the rate limiter lets through are "uploaded" and removed from disk.
+#[test]
+fn submitting_many_pings_with_a_noop_uploader() {
+    common::enable_test_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let tmpname = dir.path().to_path_buf();
+
+    let cfg = Configuration {
+        data_path: tmpname.clone(),
+        application_id: "firefox-desktop".into(),
+        upload_enabled: true,
+        max_events: None,
+        delay_ping_lifetime_io: false,
+        server_endpoint: Some("invalid-test-host".into()),
+        uploader: Some(Box::new(NoopUploader)),
+        use_core_mps: false,
+        ..Default::default()
+    };
+    common::initialize(cfg);
+
+    const PING_COUNT: usize = 1000;
+    for _ in 0..PING_COUNT {
+        pings::benchmark.submit(None);
+    }
+
+    // This blocks on the dispatcher, so by now all pings have been assembled.
+    assert!(glean::get_registered_ping_names().contains(&"benchmark".to_string()));
+
+    // Uploading is rate limited, so only some pings get dropped
+    // and the others stay pending.
+    let pending_dir = tmpname.join("pending_pings");
+    let mut pending = PING_COUNT;
+    for _ in 0..100 {
+        pending = fs::read_dir(&pending_dir).unwrap().count();
+        if pending < PING_COUNT {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(pending < PING_COUNT);
+    assert!(pending > 0);
+
+    glean::shutdown();
+}
@@ -51,7 +51,8 @@ mod metrics {
 ///
 /// The app starts recording metrics before Glean is initialized.
 /// Once initialized the recordings are processed and data is persisted.
-/// The pre-init dispatcher queue records how many recordings over the limit it saw.
+/// The pre-init dispatcher queue records how many recordings over the limit it saw
+/// and reports the count to the embedder's callback.
 ///
 /// This is an integration test to avoid dealing with resetting the dispatcher.
 #[test]
@@ -62,6 +63,8 @@ fn overflowing_the_task_queue_records_telemetry() {
     let dir = tempfile::tempdir().unwrap();
     let tmpname = dir.path().to_path_buf();
 
+    let (s, r) = crossbeam_channel::bounded::<usize>(1);
+
     let cfg = Configuration {
         data_path: tmpname,
         application_id: "firefox-desktop".into(),
@@ -71,6 +74,8 @@ fn overflowing_the_task_queue_records_telemetry() {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: None,
         use_core_mps: false,
+        on_queue_overflow: Some(Box::new(move |dropped| s.send(dropped).unwrap())),
+        ..Default::default()
     };
 
     // Insert a bunch of tasks to overflow the queue.
@@ -91,5 +96,11 @@ fn overflowing_the_task_queue_records_telemetry() {
         .unwrap();
     assert!(val >= 10);
 
+    // The callback is told about the same number of dropped tasks.
+    let dropped = r
+        .recv_timeout(std::time::Duration::from_secs(5))
+        .expect("on_queue_overflow should be called");
+    assert_eq!(val, dropped as i32);
+
     glean::shutdown();
 }
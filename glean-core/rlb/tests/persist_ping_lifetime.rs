@@ -43,6 +43,7 @@ fn cfg_new(tmpname: PathBuf) -> Configuration {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: None,
         use_core_mps: false,
+        ..Default::default()
     }
 }
 
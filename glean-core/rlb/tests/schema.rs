@@ -37,6 +37,7 @@ fn new_glean(configuration: Option<Configuration>) -> tempfile::TempDir {
             server_endpoint: Some("invalid-test-host".into()),
             uploader: None,
             use_core_mps: false,
+            ..Default::default()
         },
     };
 
@@ -44,6 +45,7 @@ fn new_glean(configuration: Option<Configuration>) -> tempfile::TempDir {
         app_build: env!("CARGO_PKG_VERSION").to_string(),
         app_display_version: env!("CARGO_PKG_VERSION").to_string(),
         channel: Some("testing".to_string()),
+        build_id: None,
     };
 
     glean::initialize(cfg, client_info);
@@ -88,6 +90,7 @@ fn validate_against_schema() {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: Some(Box::new(ValidatingUploader { sender: s })),
         use_core_mps: false,
+        ..Default::default()
     };
     let _ = new_glean(Some(cfg));
 
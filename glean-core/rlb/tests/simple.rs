@@ -68,6 +68,7 @@ fn simple_lifecycle() {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: None,
         use_core_mps: false,
+        ..Default::default()
     };
     common::initialize(cfg);
 
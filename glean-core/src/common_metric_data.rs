@@ -2,13 +2,25 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::error::{Error, ErrorKind};
 use crate::metrics::labeled::validate_dynamic_label;
 use crate::Glean;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+/// When metrics with a `min_record_interval_ms` were last recorded, by identifier.
+static LAST_RECORDED: Lazy<Mutex<HashMap<String, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The descriptors of all documented metrics created so far, by identifier.
+static DESCRIPTORS: Lazy<Mutex<BTreeMap<String, MetricDescriptor>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
 /// The supported metrics' lifetimes.
 ///
 /// A metric's lifetime determines when its stored data gets reset.
@@ -54,6 +66,41 @@ impl TryFrom<i32> for Lifetime {
     }
 }
 
+/// The kinds of data a metric collects, by increasing sensitivity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSensitivity {
+    /// Data about the application itself, e.g. its configuration, performance or errors.
+    Technical,
+    /// Data about how the user interacts with the application.
+    Interaction,
+    /// Data about what users store or browse, e.g. form data or visited sites.
+    StoredContent,
+    /// Data that could identify the user or is otherwise sensitive.
+    HighlySensitive,
+}
+
+/// The runtime documentation of a metric, see [`glean_describe_metrics`](crate::glean_describe_metrics).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MetricDescriptor {
+    /// The metric's category.
+    pub category: String,
+    /// The metric's name.
+    pub name: String,
+    /// The metric's type, e.g. "counter".
+    ///
+    /// For labeled metrics this is the type of every label.
+    pub metric_type: String,
+    /// The metric's lifetime.
+    pub lifetime: Lifetime,
+    /// List of ping names the metric is sent in.
+    pub send_in_pings: Vec<String>,
+    /// What the metric measures.
+    pub description: Option<String>,
+    /// The kinds of data the metric collects.
+    pub data_sensitivity: Vec<DataSensitivity>,
+}
+
 /// The common set of data shared across all different metric types.
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct CommonMetricData {
@@ -75,7 +122,27 @@ pub struct CommonMetricData {
     /// metric to be recorded to, dynamic labels are stored in the specific
     /// label so that we can validate them when the Glean singleton is
     /// available.
+    /// [`MetricType::with_label`](crate::metrics::MetricType::with_label) sets it too.
     pub dynamic_label: Option<String>,
+    /// The minimum time between two recordings, in milliseconds.
+    ///
+    /// Recordings of booleans, counters, quantities, strings, events and memory distributions
+    /// less than this long after the last recording of the same metric are dropped
+    /// before they reach the dispatcher.
+    /// Use this for metrics recorded on very hot code paths.
+    /// `None`, the default, records everything.
+    pub min_record_interval_ms: Option<u64>,
+    /// What the metric measures, e.g. to show users what data is collected.
+    pub description: Option<String>,
+    /// The kinds of data the metric collects.
+    pub data_sensitivity: Option<Vec<DataSensitivity>>,
+    /// Whether to only keep the first value recorded while Glean is running.
+    ///
+    /// Later recordings are silently dropped until Glean is initialized again,
+    /// e.g. for booleans telling whether something happened at least once.
+    /// Every label of a labeled metric is recorded once.
+    /// Events are not affected.
+    pub only_once_per_session: bool,
 }
 
 impl CommonMetricData {
@@ -124,8 +191,94 @@ impl CommonMetricData {
         !self.disabled
     }
 
+    /// Whether this is one of Glean's own metrics, in the `glean` category or below it.
+    pub(crate) fn is_internal(&self) -> bool {
+        self.category == "glean" || self.category.starts_with("glean.")
+    }
+
+    /// Whether this metric reports on Glean itself rather than on the application.
+    ///
+    /// That's the case for Glean's own metrics, except for the errors
+    /// recorded for one of the application's metrics.
+    pub(crate) fn is_about_glean(&self) -> bool {
+        if self.category == "glean.error" {
+            // Errors are named `<error type>/<metric identifier>`.
+            if let Some((_, identifier)) = self.name.split_once('/') {
+                return is_internal_identifier(identifier);
+            }
+        }
+        self.is_internal()
+    }
+
+    /// Makes the metric enumerable through its descriptor,
+    /// if it has a description or data sensitivity.
+    ///
+    /// Labels of labeled metrics are not described on their own.
+    pub(crate) fn register_descriptor(&self, metric_type: &str) {
+        if self.dynamic_label.is_some()
+            || (self.description.is_none() && self.data_sensitivity.is_none())
+        {
+            return;
+        }
+
+        let descriptor = MetricDescriptor {
+            category: self.category.clone(),
+            name: self.name.clone(),
+            metric_type: metric_type.to_string(),
+            lifetime: self.lifetime,
+            send_in_pings: self.send_in_pings.clone(),
+            description: self.description.clone(),
+            data_sensitivity: self.data_sensitivity.clone().unwrap_or_default(),
+        };
+        DESCRIPTORS
+            .lock()
+            .unwrap()
+            .insert(self.base_identifier(), descriptor);
+    }
+
+    /// Whether a recording has to be dropped, because the last recording kept
+    /// happened less than `min_record_interval_ms` ago.
+    ///
+    /// Otherwise the recording is kept and the interval starts over.
+    pub(crate) fn is_throttled(&self) -> bool {
+        let interval = match self.min_record_interval_ms {
+            Some(interval) => Duration::from_millis(interval),
+            None => return false,
+        };
+
+        let key = match &self.dynamic_label {
+            Some(label) => format!("{}/{}", self.base_identifier(), label),
+            None => self.base_identifier(),
+        };
+        let now = Instant::now();
+        let mut last_recorded = LAST_RECORDED.lock().unwrap();
+        match last_recorded.get(&key) {
+            Some(last) if now.duration_since(*last) < interval => true,
+            _ => {
+                last_recorded.insert(key, now);
+                false
+            }
+        }
+    }
+
     /// The list of storages this metric should be recorded into.
     pub fn storage_names(&self) -> &[String] {
         &self.send_in_pings
     }
 }
+
+/// Gets the descriptors of all documented metrics created so far, sorted by identifier.
+pub(crate) fn metric_descriptors() -> Vec<MetricDescriptor> {
+    DESCRIPTORS.lock().unwrap().values().cloned().collect()
+}
+
+/// Forgets when metrics were last recorded and which metrics were documented.
+pub(crate) fn clear_metric_state() {
+    LAST_RECORDED.lock().unwrap().clear();
+    DESCRIPTORS.lock().unwrap().clear();
+}
+
+/// Whether `identifier` is the identifier of one of Glean's own metrics.
+pub(crate) fn is_internal_identifier(identifier: &str) -> bool {
+    identifier.starts_with("glean.")
+}
@@ -1,25 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::Duration;
 
 use chrono::{DateTime, FixedOffset};
 use once_cell::sync::OnceCell;
+use uuid::Uuid;
 
-use crate::database::Database;
+use crate::crash::CrashCause;
+use crate::database::{Database, StorageBackend, UnregisteredPingBehavior};
 use crate::debug::DebugOptions;
+use crate::deletion_request::UploadDisabledReason;
 use crate::event_database::EventDatabase;
-use crate::internal_metrics::{AdditionalMetrics, CoreMetrics, DatabaseMetrics};
+use crate::histogram::{Bucketing, Histogram};
+use crate::internal_metrics::{
+    AdditionalMetrics, CoreMetrics, CrashMetrics, DatabaseMetrics, DeletionRequestMetrics,
+};
 use crate::internal_pings::InternalPings;
-use crate::metrics::{self, ExperimentMetric, Metric, MetricType, PingType, RecordedExperiment};
+use crate::metrics::{
+    self, ExperimentMetric, Metric, MetricType, PingType, RecordedExperiment, TimeUnit,
+};
+use crate::observer::MetricObservers;
 use crate::ping::PingMaker;
 use crate::storage::{StorageManager, INTERNAL_STORAGE};
-use crate::upload::{PingUploadManager, PingUploadTask, UploadResult, UploadTaskAction};
-use crate::util::{local_now_with_offset, sanitize_application_id};
+use crate::upload::{
+    PingRequest, PingUploadManager, PingUploadTask, UploadResult, UploadTaskAction,
+};
+use crate::util::{local_now_with_offset, sanitize_application_id, system_uptime};
 use crate::{
     scheduler, system, CommonMetricData, ErrorKind, InternalConfiguration, Lifetime, Result,
-    DEFAULT_MAX_EVENTS, GLEAN_SCHEMA_VERSION, GLEAN_VERSION, KNOWN_CLIENT_ID,
+    DEFAULT_MAX_EVENTS, DEFAULT_SLOW_ASSEMBLY_THRESHOLD_MS, DEFAULT_UPLOAD_COOLDOWN_SECS,
+    GLEAN_SCHEMA_VERSION, GLEAN_VERSION, KNOWN_CLIENT_ID,
 };
 
+/// The maximum number of entries set through [`Glean::set_ping_metadata`].
+const MAX_PING_METADATA_ENTRIES: usize = 20;
+
 static GLEAN: OnceCell<Mutex<Glean>> = OnceCell::new();
 
 pub fn global_glean() -> Option<&'static Mutex<Glean>> {
@@ -108,6 +124,7 @@ where
 ///     delay_ping_lifetime_io: false,
 ///     app_build: "".into(),
 ///     use_core_mps: false,
+///     ..Default::default()
 /// };
 /// let mut glean = Glean::new(cfg).unwrap();
 /// let ping = PingType::new("sample", true, false, vec![]);
@@ -136,6 +153,8 @@ pub struct Glean {
     event_data_store: EventDatabase,
     pub(crate) core_metrics: CoreMetrics,
     pub(crate) additional_metrics: AdditionalMetrics,
+    pub(crate) crash_metrics: CrashMetrics,
+    pub(crate) deletion_request_metrics: DeletionRequestMetrics,
     pub(crate) database_metrics: DatabaseMetrics,
     pub(crate) internal_pings: InternalPings,
     data_path: PathBuf,
@@ -143,11 +162,45 @@ pub struct Glean {
     ping_registry: HashMap<String, PingType>,
     start_time: DateTime<FixedOffset>,
     max_events: u32,
+    baseline_duration_unit: TimeUnit,
+    dry_run: bool,
+    profile_recording: bool,
+    pending_ping_filename_format: Option<String>,
+    mirror_to_debug_store: bool,
+    enable_internal_metrics: bool,
+    persist_experiments: bool,
+    metrics_ping_schedule_hour: u32,
+    locale_override: Option<String>,
+    slow_assembly_threshold: Duration,
+    max_distribution_buckets: Option<usize>,
     is_first_run: bool,
     pub(crate) upload_manager: PingUploadManager,
     debug: DebugOptions,
     pub(crate) app_build: String,
+    /// The `client_info.app_display_version` of this run, once known.
+    pub(crate) app_display_version: String,
     pub(crate) schedule_metrics_pings: bool,
+    pub(crate) observers: MetricObservers,
+    /// The names of the pings disabled through [`Glean::set_ping_enabled`].
+    disabled_pings: HashSet<String>,
+    /// The names of the pings not assembled anymore after repeatedly failing schema validation.
+    schema_circuits_open: HashSet<String>,
+    /// The date to record as the `first_run_date` on the first run, instead of now.
+    seeded_first_run_date: Option<DateTime<FixedOffset>>,
+    /// The identifier of this session, regenerated every time Glean is initialized.
+    session_id: Uuid,
+    /// Whether recording is paused through [`Glean::set_recording_paused`].
+    recording_paused: bool,
+    /// The only categories metrics may record in, if restricted.
+    allowed_metric_categories: Option<HashSet<String>>,
+    /// The metadata attached to every ping through [`Glean::set_ping_metadata`].
+    ping_metadata: BTreeMap<String, String>,
+    clamp_skewed_datetimes: bool,
+    unregistered_ping_behavior: UnregisteredPingBehavior,
+    /// The maximum length of a dynamic label, in bytes.
+    max_label_length: usize,
+    /// The maximum number of distinct dynamic labels every labeled metric stores.
+    max_distinct_labels: usize,
 }
 
 impl Glean {
@@ -166,11 +219,69 @@ impl Glean {
         let data_path = Path::new(&cfg.data_path);
         let event_data_store = EventDatabase::new(data_path)?;
 
+        let baseline_duration_unit = match cfg.baseline_duration_unit {
+            None => TimeUnit::Second,
+            Some(unit @ (TimeUnit::Second | TimeUnit::Millisecond)) => unit,
+            Some(unit) => {
+                log::warn!(
+                    "Unsupported time unit {:?} for the baseline duration. Using seconds.",
+                    unit
+                );
+                TimeUnit::Second
+            }
+        };
+
+        let pending_ping_filename_format = cfg
+            .pending_ping_filename_format
+            .clone()
+            .filter(|format| {
+                let valid = crate::upload::is_valid_file_name_format(format);
+                if !valid {
+                    log::error!(
+                        "Invalid pending ping file name format '{}'. Naming files after the document id.",
+                        format
+                    );
+                }
+                valid
+            });
+
+        let metrics_ping_schedule_hour = match cfg.metrics_ping_schedule_hour {
+            Some(hour) if hour > 23 => {
+                log::error!(
+                    "Invalid metrics ping schedule hour {}. Scheduling at {}:00.",
+                    hour,
+                    scheduler::DEFAULT_SCHEDULED_HOUR
+                );
+                scheduler::DEFAULT_SCHEDULED_HOUR
+            }
+            Some(hour) => hour,
+            None => scheduler::DEFAULT_SCHEDULED_HOUR,
+        };
+
         // Create an upload manager with rate limiting of 15 pings every 60 seconds.
         let mut upload_manager = PingUploadManager::new(&cfg.data_path, &cfg.language_binding_name);
         upload_manager.set_rate_limiter(
             /* seconds per interval */ 60, /* max pings per interval */ 15,
         );
+        if let Some(threshold) = cfg.upload_failure_threshold {
+            upload_manager.set_circuit_breaker(
+                threshold,
+                cfg.upload_cooldown_secs
+                    .unwrap_or(DEFAULT_UPLOAD_COOLDOWN_SECS),
+            );
+        }
+        if let Some(max) = cfg.max_pending_pings {
+            upload_manager.set_max_pending_pings(max as usize);
+        }
+        if let Some(window) = cfg.ping_dedup_window_ms {
+            upload_manager.set_dedup_window(Duration::from_millis(window));
+        }
+        if let Some(content_type) = &cfg.content_type {
+            upload_manager.set_content_type(content_type.clone());
+        }
+        if let Some(format) = cfg.ping_format {
+            upload_manager.set_ping_format(format);
+        }
 
         // We only scan the pending ping directories when calling this from a subprocess,
         // when calling this from ::new we need to scan the directories after dealing with the upload state.
@@ -179,6 +290,26 @@ impl Glean {
         }
 
         let start_time = local_now_with_offset();
+        let seeded_first_run_date =
+            cfg.first_run_date
+                .as_ref()
+                .and_then(|date| match date.to_chrono() {
+                    Some(date) if date <= start_time => Some(date),
+                    Some(date) => {
+                        log::error!(
+                        "The first run date {} is in the future. Using the current date instead.",
+                        date
+                    );
+                        None
+                    }
+                    None => {
+                        log::error!(
+                            "Invalid first run date {:?}. Using the current date instead.",
+                            date
+                        );
+                        None
+                    }
+                });
         let mut this = Self {
             upload_enabled: cfg.upload_enabled,
             // In the subprocess, we want to avoid accessing the database entirely.
@@ -187,6 +318,8 @@ impl Glean {
             event_data_store,
             core_metrics: CoreMetrics::new(),
             additional_metrics: AdditionalMetrics::new(),
+            crash_metrics: CrashMetrics::new(),
+            deletion_request_metrics: DeletionRequestMetrics::new(),
             database_metrics: DatabaseMetrics::new(),
             internal_pings: InternalPings::new(),
             upload_manager,
@@ -195,11 +328,47 @@ impl Glean {
             ping_registry: HashMap::new(),
             start_time,
             max_events: cfg.max_events.unwrap_or(DEFAULT_MAX_EVENTS),
+            baseline_duration_unit,
+            dry_run: cfg.dry_run,
+            profile_recording: cfg.profile_recording,
+            pending_ping_filename_format,
+            mirror_to_debug_store: cfg.mirror_to_debug_store,
+            enable_internal_metrics: cfg.enable_internal_metrics,
+            persist_experiments: cfg.persist_experiments,
+            metrics_ping_schedule_hour,
+            locale_override: cfg.locale_override.clone(),
+            slow_assembly_threshold: Duration::from_millis(
+                cfg.slow_assembly_threshold_ms
+                    .unwrap_or(DEFAULT_SLOW_ASSEMBLY_THRESHOLD_MS),
+            ),
+            max_distribution_buckets: cfg.max_distribution_buckets.map(|max| max as usize),
             is_first_run: false,
             debug: DebugOptions::new(),
             app_build: cfg.app_build.to_string(),
+            app_display_version: "Unknown".into(),
             // Subprocess doesn't use "metrics" pings so has no need for a scheduler.
             schedule_metrics_pings: false,
+            observers: MetricObservers::default(),
+            disabled_pings: HashSet::new(),
+            schema_circuits_open: HashSet::new(),
+            seeded_first_run_date,
+            session_id: Uuid::new_v4(),
+            recording_paused: false,
+            allowed_metric_categories: cfg
+                .allowed_metric_categories
+                .as_ref()
+                .map(|categories| categories.iter().cloned().collect()),
+            ping_metadata: BTreeMap::new(),
+            clamp_skewed_datetimes: cfg.clamp_skewed_datetimes,
+            unregistered_ping_behavior: cfg.unregistered_ping_behavior.unwrap_or_default(),
+            max_label_length: cfg
+                .max_label_length
+                .map_or(metrics::labeled::DEFAULT_MAX_LABEL_LENGTH, |max| {
+                    max as usize
+                }),
+            max_distinct_labels: cfg
+                .max_distinct_labels
+                .map_or(usize::MAX, |max| max as usize),
         };
 
         // Ensuring these pings are registered.
@@ -208,6 +377,7 @@ impl Glean {
         this.register_ping_type(&pings.metrics);
         this.register_ping_type(&pings.events);
         this.register_ping_type(&pings.deletion_request);
+        this.register_ping_type(&pings.crash);
 
         Ok(this)
     }
@@ -218,12 +388,36 @@ impl Glean {
     /// [`cfg.data_path`](InternalConfiguration::data_path). This will also initialize
     /// the core metrics.
     pub fn new(cfg: InternalConfiguration) -> Result<Self> {
+        Self::new_with_storage_backend(cfg, None)
+    }
+
+    /// Creates and initializes a new Glean object, keeping metrics in the given storage backend
+    /// instead of the default rkv database.
+    ///
+    /// Events and pending pings are still stored in files in
+    /// [`cfg.data_path`](InternalConfiguration::data_path).
+    pub fn with_storage_backend(
+        cfg: InternalConfiguration,
+        backend: Box<dyn StorageBackend>,
+    ) -> Result<Self> {
+        Self::new_with_storage_backend(cfg, Some(backend))
+    }
+
+    pub(crate) fn new_with_storage_backend(
+        cfg: InternalConfiguration,
+        backend: Option<Box<dyn StorageBackend>>,
+    ) -> Result<Self> {
         let mut glean = Self::new_for_subprocess(&cfg, false)?;
 
         // Creating the data store creates the necessary path as well.
         // If that fails we bail out and don't initialize further.
         let data_path = Path::new(&cfg.data_path);
-        glean.data_store = Some(Database::new(data_path, cfg.delay_ping_lifetime_io)?);
+        let data_store = match backend {
+            Some(backend) => Database::with_backend(backend, cfg.delay_ping_lifetime_io),
+            None => Database::new(data_path, cfg.delay_ping_lifetime_io)?,
+        };
+        glean.data_store = Some(data_store);
+        glean.load_disabled_pings();
 
         // The upload enabled flag may have changed since the last run, for
         // example by the changing of a config file.
@@ -231,6 +425,7 @@ impl Glean {
             // If upload is enabled, just follow the normal code path to
             // instantiate the core metrics.
             glean.on_upload_enabled();
+            glean.core_metrics.session_count.add_sync(&glean, 1);
         } else {
             // If upload is disabled, and we've never run before, only set the
             // client_id to KNOWN_CLIENT_ID, but do not send a deletion request
@@ -250,7 +445,7 @@ impl Glean {
                         // Temporarily enable uploading so we can submit a
                         // deletion request ping.
                         glean.upload_enabled = true;
-                        glean.on_upload_disabled(true);
+                        glean.on_upload_disabled(true, None);
                     }
                 }
             }
@@ -284,6 +479,7 @@ impl Glean {
             delay_ping_lifetime_io: false,
             app_build: "Unknown".into(),
             use_core_mps: false,
+            ..Default::default()
         };
 
         let mut glean = Self::new(cfg).unwrap();
@@ -321,8 +517,16 @@ impl Glean {
             .get_value(self, "glean_client_info")
             .is_none()
         {
-            self.core_metrics.first_run_date.set_sync(self, None);
-            self.core_metrics.first_run_hour.set_sync(self, None);
+            match self.seeded_first_run_date {
+                Some(date) => {
+                    self.core_metrics.first_run_date.set_sync_chrono(self, date);
+                    self.core_metrics.first_run_hour.set_sync_chrono(self, date);
+                }
+                None => {
+                    self.core_metrics.first_run_date.set_sync(self, None);
+                    self.core_metrics.first_run_hour.set_sync(self, None);
+                }
+            }
             // The `first_run_date` field is generated on the very first run
             // and persisted across upload toggling. We can assume that, the only
             // time it is set, that's indeed our "first run".
@@ -346,6 +550,17 @@ impl Glean {
                 .size
                 .accumulate_sync(self, size.get() as i64)
         }
+
+        if let Some(true) = self
+            .data_store
+            .as_ref()
+            .map(|database| database.lost_unpersisted_data())
+        {
+            log::warn!("The previous run might not have persisted all of its ping lifetime data. Using the data persisted last.");
+            self.database_metrics
+                .ping_lifetime_data_lost
+                .add_sync(self, 1);
+        }
     }
 
     /// Signals that the environment is ready to submit pings.
@@ -381,13 +596,34 @@ impl Glean {
     /// Whether the flag was different from the current value,
     /// and actual work was done to clear or reinstate metrics.
     pub fn set_upload_enabled(&mut self, flag: bool) -> bool {
+        self.set_upload_enabled_with_reason(flag, None)
+    }
+
+    /// Same as [`Glean::set_upload_enabled`],
+    /// reporting why upload is disabled in the `deletion-request` ping.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag` - When true, enable metric collection.
+    /// * `reason` - Why upload is disabled, recorded in
+    ///   `glean.deletion_request.opt_out_reason`. Ignored when enabling.
+    ///
+    /// # Returns
+    ///
+    /// Whether the flag was different from the current value,
+    /// and actual work was done to clear or reinstate metrics.
+    pub fn set_upload_enabled_with_reason(
+        &mut self,
+        flag: bool,
+        reason: Option<UploadDisabledReason>,
+    ) -> bool {
         log::info!("Upload enabled: {:?}", flag);
 
         if self.upload_enabled != flag {
             if flag {
                 self.on_upload_enabled();
             } else {
-                self.on_upload_disabled(false);
+                self.on_upload_disabled(false, reason);
             }
             true
         } else {
@@ -402,6 +638,131 @@ impl Glean {
         self.upload_enabled
     }
 
+    /// Pauses or resumes recording metrics.
+    ///
+    /// Unlike disabling upload, pausing keeps the data recorded so far
+    /// and pings are still submitted and uploaded.
+    /// While paused, recording a metric doesn't change it.
+    /// Glean's own metrics, e.g. the ones in `ping_info`, are still recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `paused` - Whether to pause recording.
+    pub fn set_recording_paused(&mut self, paused: bool) {
+        log::info!("Recording paused: {:?}", paused);
+        self.recording_paused = paused;
+    }
+
+    /// Determines whether recording is paused.
+    pub fn is_recording_paused(&self) -> bool {
+        self.recording_paused
+    }
+
+    /// Determines whether metrics in `category` may record,
+    /// according to the configured `allowed_metric_categories`.
+    pub fn is_category_allowed(&self, category: &str) -> bool {
+        match &self.allowed_metric_categories {
+            Some(allowed) => allowed.contains(category),
+            None => true,
+        }
+    }
+
+    /// Attaches metadata to every ping submitted from now on, for the rest of this session.
+    ///
+    /// The entries are merged into the `metadata` object of each ping payload,
+    /// alongside the metadata set for that ping through [`PingType::set_metadata`](crate::metrics::PingType::set_metadata),
+    /// which takes precedence over entries with the same key.
+    /// Setting a key again replaces its value.
+    ///
+    /// Keys must be non-empty and at most 40 bytes long, values at most 100 bytes long.
+    /// At most 20 keys can be set.
+    ///
+    /// # Returns
+    ///
+    /// Whether the entry was set. Invalid entries are logged and ignored.
+    pub fn set_ping_metadata(&mut self, key: String, value: String) -> bool {
+        if key.is_empty() || key.len() > metrics::MAX_METADATA_KEY_LENGTH {
+            log::warn!("Invalid ping metadata key '{}'. Ignoring it.", key);
+            return false;
+        }
+        if value.len() > metrics::MAX_METADATA_VALUE_LENGTH {
+            log::warn!(
+                "Ping metadata value for key '{}' is too long. Ignoring it.",
+                key
+            );
+            return false;
+        }
+        if !self.ping_metadata.contains_key(&key)
+            && self.ping_metadata.len() >= MAX_PING_METADATA_ENTRIES
+        {
+            log::warn!(
+                "Can't set ping metadata key '{}', already {} keys are set. Ignoring it.",
+                key,
+                MAX_PING_METADATA_ENTRIES
+            );
+            return false;
+        }
+
+        self.ping_metadata.insert(key, value);
+        true
+    }
+
+    /// The metadata attached to every ping through [`Glean::set_ping_metadata`].
+    pub(crate) fn ping_metadata(&self) -> &BTreeMap<String, String> {
+        &self.ping_metadata
+    }
+
+    /// Collects and submits the `crash` ping.
+    ///
+    /// Records the time and `cause` of the crash, as well as the `annotations`,
+    /// and stores the ping in the pending pings directory right away.
+    /// It is uploaded on the next opportunity, usually on the next start.
+    ///
+    /// # Arguments
+    ///
+    /// * `cause` - What caused the crash.
+    /// * `annotations` - Additional information, keyed by label.
+    ///   Labels follow the same rules as for any labeled metric.
+    ///
+    /// # Returns
+    ///
+    /// Whether the ping was successfully assembled and queued.
+    pub fn submit_crash_ping(
+        &self,
+        cause: CrashCause,
+        annotations: HashMap<String, String>,
+    ) -> bool {
+        self.crash_metrics.time.set_sync(self, None);
+        self.crash_metrics.cause.set_sync(self, cause.as_str());
+        for (label, value) in annotations {
+            self.crash_metrics
+                .annotations
+                .get(label)
+                .set_sync(self, value);
+        }
+
+        self.internal_pings.crash.submit_sync(self, None)
+    }
+
+    /// Replaces the client id with a newly generated one.
+    ///
+    /// The new id is persisted and used for all pings from now on.
+    /// Other client info, such as the `first_run_date`, is left untouched,
+    /// so any cohorting based on it continues to apply to the new id.
+    ///
+    /// # Returns
+    ///
+    /// The new client id, or `None` if upload is disabled.
+    /// In that case the client id remains the known "disabled" id.
+    pub fn rotate_client_id(&self) -> Option<Uuid> {
+        if !self.is_upload_enabled() {
+            log::info!("Upload disabled: not rotating the client id.");
+            return None;
+        }
+
+        Some(self.core_metrics.client_id.generate_and_set_sync(self))
+    }
+
     /// Handles the changing of state from upload disabled to enabled.
     ///
     /// Should only be called when the state actually changes.
@@ -421,9 +782,18 @@ impl Glean {
     /// A deletion_request ping is sent, all pending metrics, events and queued
     /// pings are cleared, and the client_id is set to KNOWN_CLIENT_ID.
     /// Afterward, the upload_enabled flag is set to false.
-    fn on_upload_disabled(&mut self, during_init: bool) {
+    fn on_upload_disabled(
+        &mut self,
+        during_init: bool,
+        opt_out_reason: Option<UploadDisabledReason>,
+    ) {
         // The upload_enabled flag should be true here, or the deletion ping
         // won't be submitted.
+        if let Some(opt_out_reason) = opt_out_reason {
+            self.deletion_request_metrics
+                .opt_out_reason
+                .set_sync(self, opt_out_reason.as_str());
+        }
         let reason = if during_init {
             Some("at_init")
         } else {
@@ -541,6 +911,98 @@ impl Glean {
         self.max_events as usize
     }
 
+    /// Whether assembled pings are only logged, instead of being stored and uploaded.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Whether every recorded metric is also stored in the [`DEBUG_STORE`](crate::storage::DEBUG_STORE).
+    pub(crate) fn mirror_to_debug_store(&self) -> bool {
+        self.mirror_to_debug_store
+    }
+
+    /// Whether implausible datetimes are clamped, see [`InternalConfiguration::clamp_skewed_datetimes`].
+    pub(crate) fn clamp_skewed_datetimes(&self) -> bool {
+        self.clamp_skewed_datetimes
+    }
+
+    /// The maximum length of a dynamic label, in bytes, see [`InternalConfiguration::max_label_length`].
+    pub(crate) fn max_label_length(&self) -> usize {
+        self.max_label_length
+    }
+
+    /// The maximum number of distinct dynamic labels every labeled metric stores,
+    /// see [`InternalConfiguration::max_distinct_labels`].
+    pub(crate) fn max_distinct_labels(&self) -> usize {
+        self.max_distinct_labels
+    }
+
+    /// What happens to recordings into pings that aren't registered.
+    pub(crate) fn unregistered_ping_behavior(&self) -> UnregisteredPingBehavior {
+        self.unregistered_ping_behavior
+    }
+
+    /// Whether Glean's own `glean.*` metrics are recorded into the "metrics" ping.
+    pub(crate) fn enable_internal_metrics(&self) -> bool {
+        self.enable_internal_metrics
+    }
+
+    /// Whether active experiments are kept across runs.
+    pub(crate) fn persist_experiments(&self) -> bool {
+        self.persist_experiments
+    }
+
+    /// The local hour of the day the "metrics" ping is scheduled for.
+    pub(crate) fn metrics_ping_schedule_hour(&self) -> u32 {
+        self.metrics_ping_schedule_hour
+    }
+
+    /// The format pending ping files are named after, if not the document id.
+    pub(crate) fn pending_ping_filename_format(&self) -> Option<&str> {
+        self.pending_ping_filename_format.as_deref()
+    }
+
+    /// Whether the time spent recording metrics is measured in `glean.sdk.record_latency`.
+    pub(crate) fn profile_recording(&self) -> bool {
+        self.profile_recording
+    }
+
+    /// Gets how long assembling a ping may take before it's counted as slow.
+    pub(crate) fn slow_assembly_threshold(&self) -> Duration {
+        self.slow_assembly_threshold
+    }
+
+    /// Merges the highest buckets of a distribution's histogram
+    /// if it holds more buckets than configured in `max_distribution_buckets`.
+    ///
+    /// Returns the number of buckets merged away.
+    /// Pass it to [`record_merged_buckets`](Self::record_merged_buckets) once the histogram is stored.
+    pub(crate) fn bound_distribution<B: Bucketing>(&self, hist: &mut Histogram<B>) -> usize {
+        match self.max_distribution_buckets {
+            Some(max) => hist.merge_tail(max),
+            None => 0,
+        }
+    }
+
+    /// Counts merged distribution buckets in `glean.distribution.buckets_merged`.
+    pub(crate) fn record_merged_buckets(&self, merged: usize) {
+        if merged > 0 {
+            self.additional_metrics
+                .buckets_merged
+                .add_sync(self, merged as i32);
+        }
+    }
+
+    /// Gets the locale to report instead of the one provided by the platform, if any.
+    pub(crate) fn locale_override(&self) -> Option<&str> {
+        self.locale_override.as_deref()
+    }
+
+    /// Gets the time unit the baseline `duration` is reported in.
+    pub(crate) fn baseline_duration_unit(&self) -> TimeUnit {
+        self.baseline_duration_unit
+    }
+
     /// Gets the next task for an uploader.
     ///
     /// This can be one of:
@@ -555,10 +1017,60 @@ impl Glean {
     /// # Returns
     ///
     /// A [`PingUploadTask`] representing the next task.
+    /// In dry-run mode this is always [`Done`](PingUploadTask::Done),
+    /// so pings left over from previous runs are not uploaded either.
     pub fn get_upload_task(&self) -> PingUploadTask {
+        if self.dry_run {
+            return PingUploadTask::done();
+        }
+
         self.upload_manager.get_upload_task(self, self.log_pings())
     }
 
+    /// Removes all pending pings and returns their requests,
+    /// for embedders sending pings through a transport of their own.
+    ///
+    /// In dry-run mode nothing is pending, so this is always empty.
+    ///
+    /// See [`PingUploadManager::drain_pings`].
+    pub fn drain_pings(&self) -> Vec<PingRequest> {
+        if self.dry_run {
+            return Vec::new();
+        }
+
+        self.upload_manager.drain_pings(self)
+    }
+
+    /// Writes all pending pings into a single archive file at `path`,
+    /// e.g. to move them to a device that can upload them.
+    ///
+    /// The pings stay pending.
+    /// Document ids, URL paths and the persisted metadata of the pings are preserved.
+    ///
+    /// # Returns
+    ///
+    /// The number of pings written.
+    pub fn export_pending_pings(&self, path: &Path) -> Result<usize> {
+        Ok(self.upload_manager.export_pending_pings(path)?)
+    }
+
+    /// Stores the pings of an archive written by [`Glean::export_pending_pings`]
+    /// and enqueues them for upload.
+    ///
+    /// Pings already pending are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of pings imported.
+    pub fn import_pending_pings(&self, path: &Path) -> Result<usize> {
+        Ok(self.upload_manager.import_pending_pings(self, path)?)
+    }
+
+    /// Gets the time of the last successful ping upload, if any.
+    pub fn last_upload_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.upload_manager.last_upload_time(self)
+    }
+
     /// Processes the response from an attempt to upload a ping.
     ///
     /// # Arguments
@@ -640,7 +1152,118 @@ impl Glean {
         self.ping_registry.get(ping_name)
     }
 
+    /// Gets the names of all registered pings, sorted alphabetically.
+    ///
+    /// This includes the built-in pings, which are registered on initialization.
+    pub fn get_registered_ping_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.ping_registry.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Enables or disables a single ping.
+    ///
+    /// A disabled ping is neither assembled nor uploaded when submitted,
+    /// and metrics that are only sent in disabled pings are not recorded.
+    /// This is persisted across restarts.
+    ///
+    /// The `deletion-request` ping can't be disabled.
+    pub fn set_ping_enabled(&mut self, ping_name: &str, enabled: bool) {
+        if ping_name == "deletion-request" {
+            log::warn!("The deletion-request ping can't be disabled.");
+            return;
+        }
+
+        let identifier = format!("{}#enabled", ping_name);
+        if enabled {
+            self.disabled_pings.remove(ping_name);
+            if let Err(e) =
+                self.storage()
+                    .remove_single_metric(Lifetime::User, INTERNAL_STORAGE, &identifier)
+            {
+                log::warn!("Failed to enable ping {}: {}", ping_name, e);
+            }
+        } else {
+            self.disabled_pings.insert(ping_name.to_string());
+            if let Err(e) = self.storage().record_per_lifetime(
+                Lifetime::User,
+                INTERNAL_STORAGE,
+                &identifier,
+                &Metric::Boolean(false),
+            ) {
+                log::warn!("Failed to disable ping {}: {}", ping_name, e);
+            }
+        }
+    }
+
+    /// Whether the ping was not disabled through [`Glean::set_ping_enabled`].
+    pub fn is_ping_enabled(&self, ping_name: &str) -> bool {
+        !self.disabled_pings.contains(ping_name)
+    }
+
+    /// Stops assembling a ping after it repeatedly failed schema validation.
+    ///
+    /// Unlike disabling a ping, this only lasts until Glean is initialized again,
+    /// e.g. with an application update fixing the ping.
+    /// It is counted in `glean.error.schema_circuit_open`, labeled by the ping name,
+    /// which notifies observers of that metric.
+    pub fn open_schema_circuit(&mut self, ping_name: &str) {
+        if ping_name == "deletion-request" {
+            log::warn!("Can't stop assembling the deletion-request ping.");
+            return;
+        }
+
+        if self.schema_circuits_open.insert(ping_name.to_string()) {
+            log::error!(
+                "The {} ping failed schema validation repeatedly. Not assembling it anymore.",
+                ping_name
+            );
+            self.additional_metrics
+                .schema_circuit_open
+                .get(ping_name)
+                .add_sync(self, 1);
+        }
+    }
+
+    /// Whether the application reported one of the given release channels in its client info.
+    pub(crate) fn is_on_channel<S: AsRef<str>>(&self, channels: &[S]) -> bool {
+        match crate::core_metrics::internal_metrics::app_channel
+            .get_value(self, "glean_client_info")
+        {
+            Some(channel) => channels.iter().any(|c| c.as_ref() == channel),
+            None => false,
+        }
+    }
+
+    /// Whether the ping is not assembled anymore because it repeatedly failed schema validation.
+    pub(crate) fn is_schema_circuit_open(&self, ping_name: &str) -> bool {
+        self.schema_circuits_open.contains(ping_name)
+    }
+
+    /// Whether any ping was disabled through [`Glean::set_ping_enabled`].
+    pub(crate) fn has_disabled_pings(&self) -> bool {
+        !self.disabled_pings.is_empty()
+    }
+
+    /// Loads the pings disabled in a previous run.
+    fn load_disabled_pings(&mut self) {
+        let mut disabled_pings = HashSet::new();
+        self.storage()
+            .iter_store_from(Lifetime::User, INTERNAL_STORAGE, None, |id, metric| {
+                let id = String::from_utf8_lossy(id);
+                if let (Some(ping_name), Metric::Boolean(false)) =
+                    (id.strip_suffix("#enabled"), metric)
+                {
+                    disabled_pings.insert(ping_name.to_string());
+                }
+            });
+        self.disabled_pings = disabled_pings;
+    }
+
     /// Register a new [`PingType`](metrics/struct.PingType.html).
+    ///
+    /// Recordings held for the ping until it was registered are stored now,
+    /// see [`UnregisteredPingBehavior::Buffer`].
     pub fn register_ping_type(&mut self, ping: &PingType) {
         if self.ping_registry.contains_key(ping.name()) {
             log::debug!("Duplicate ping named '{}'", ping.name())
@@ -648,6 +1271,9 @@ impl Glean {
 
         self.ping_registry
             .insert(ping.name().to_string(), ping.clone());
+        if let Some(data_store) = &self.data_store {
+            data_store.flush_buffered(self, ping.name());
+        }
     }
 
     /// Get create time of the Glean object.
@@ -697,6 +1323,31 @@ impl Glean {
         metric.test_get_value(self)
     }
 
+    /// Deletes all data stored for a metric, in all stores.
+    ///
+    /// Unlike resetting a metric, this also removes the errors recorded for it.
+    /// Nothing happens if no data is stored for the metric.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category of the metric.
+    /// * `name` - The name of the metric.
+    pub fn delete_metric(&self, category: &str, name: &str) {
+        let identifier = if category.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", category, name)
+        };
+
+        if let Some(data) = self.data_store.as_ref() {
+            if let Err(e) = data.remove_metric_everywhere(&identifier) {
+                log::warn!("Failed to delete metric {}: {:?}", identifier, e);
+            }
+        }
+        self.event_data_store
+            .remove_metric_everywhere(category, name);
+    }
+
     /// Persists [`Lifetime::Ping`] data that might be in memory in case
     /// [`delay_ping_lifetime_io`](InternalConfiguration::delay_ping_lifetime_io) is set
     /// or was set at a previous time.
@@ -710,9 +1361,26 @@ impl Glean {
         Ok(())
     }
 
+    /// Persists [`Lifetime::Ping`] data that might be in memory for the last time before shutting down.
+    ///
+    /// Unlike [`persist_ping_lifetime_data`](Self::persist_ping_lifetime_data),
+    /// this also tells the next run that nothing was lost.
+    pub fn persist_ping_lifetime_data_on_shutdown(&self) -> Result<()> {
+        if let Some(data) = self.data_store.as_ref() {
+            return data.persist_ping_lifetime_data_on_shutdown();
+        }
+
+        Ok(())
+    }
+
     /// Sets internally-handled application lifetime metrics.
     fn set_application_lifetime_core_metrics(&self) {
         self.core_metrics.os.set_sync(self, system::OS);
+        if let Some(uptime) = system_uptime() {
+            self.core_metrics
+                .os_uptime
+                .set_sync(self, uptime.as_secs() as i64);
+        }
     }
 
     /// **This is not meant to be used directly.**
@@ -733,6 +1401,14 @@ impl Glean {
         self.is_first_run
     }
 
+    /// The identifier of the current session.
+    ///
+    /// It's randomly generated every time Glean is initialized.
+    /// It is not sent in pings yet.
+    pub fn session_id(&self) -> Uuid {
+        self.session_id
+    }
+
     /// Sets a debug view tag.
     ///
     /// This will return `false` in case `value` is not a valid tag.
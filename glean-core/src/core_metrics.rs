@@ -36,6 +36,9 @@ pub struct ClientInfoMetrics {
     /// The locale of the application during initialization (e.g. "es-ES").
     /// If the locale can't be determined on the system, the value is "und", to indicate "undetermined".
     pub locale: Option<String>,
+    /// The identifier of the exact binary the application is running (e.g. "20230615093512").
+    /// Must not be empty.
+    pub build_id: Option<String>,
 }
 
 /// Metrics included in every ping as `client_info`.
@@ -53,6 +56,7 @@ impl ClientInfoMetrics {
             device_manufacturer: None,
             device_model: None,
             locale: None,
+            build_id: None,
         }
     }
 }
@@ -174,6 +178,17 @@ pub mod internal_metrics {
         })
     });
 
+    pub static build_id: Lazy<StringMetric> = Lazy::new(|| {
+        StringMetric::new(CommonMetricData {
+            name: "build_id".into(),
+            category: "".into(),
+            send_in_pings: vec!["glean_client_info".into()],
+            lifetime: Lifetime::Application,
+            disabled: false,
+            ..Default::default()
+        })
+    });
+
     pub static baseline_duration: Lazy<TimespanMetric> = Lazy::new(|| {
         TimespanMetric::new(
             CommonMetricData {
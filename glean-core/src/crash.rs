@@ -0,0 +1,30 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reporting crashes through the built-in `crash` ping.
+
+/// What caused a crash reported in the `crash` ping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashCause {
+    /// The application panicked or threw an unhandled exception.
+    Panic,
+    /// The process received a fatal signal (e.g. `SIGSEGV`).
+    Signal,
+    /// The process ran out of memory.
+    OutOfMemory,
+    /// Any other cause.
+    Other,
+}
+
+impl CrashCause {
+    /// The value recorded in `glean.crash.cause`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CrashCause::Panic => "panic",
+            CrashCause::Signal => "signal",
+            CrashCause::OutOfMemory => "oom",
+            CrashCause::Other => "other",
+        }
+    }
+}
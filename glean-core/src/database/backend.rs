@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The storage the metrics database keeps its data in.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str;
+
+use rkv::{StoreError, StoreOptions};
+
+use super::{migrate, rkv_new, Rkv, SingleStore};
+use crate::Lifetime;
+use crate::Result;
+
+/// A change to the stored data, applied as part of a [`StorageBackend::write_batch`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageChange {
+    /// Stores `value` under `key` in the store of the lifetime, replacing any previous value.
+    Put(Lifetime, String, Vec<u8>),
+    /// Deletes `key` from the store of the lifetime, if it exists.
+    Delete(Lifetime, String),
+}
+
+/// A key-value storage for the metrics database, with a separate store per [`Lifetime`].
+///
+/// Glean uses an rkv database on disk by default.
+/// Implement this to keep metrics elsewhere, e.g. in memory or in storage
+/// provided by a sandboxed environment.
+///
+/// Keys are strings, values are opaque bytes encoded by Glean.
+/// Failures are reported as errors, e.g. converted from an [`std::io::Error`],
+/// and logged by Glean, which continues without the data.
+pub trait StorageBackend: fmt::Debug + Send + Sync {
+    /// Gets the value stored under `key`, if any.
+    fn get(&self, lifetime: Lifetime, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn put(&self, lifetime: Lifetime, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Deletes the value stored under `key`.
+    ///
+    /// Deleting a key that isn't stored is not an error.
+    fn delete(&self, lifetime: Lifetime, key: &str) -> Result<()>;
+
+    /// Calls `f` with every key starting with `prefix` and its value, in key order.
+    ///
+    /// An empty `prefix` iterates over the entire store.
+    fn iter(&self, lifetime: Lifetime, prefix: &str, f: &mut dyn FnMut(&str, &[u8])) -> Result<()>;
+
+    /// Deletes everything stored for `lifetime`.
+    fn clear(&self, lifetime: Lifetime) -> Result<()> {
+        let mut keys = Vec::new();
+        self.iter(lifetime, "", &mut |key, _| keys.push(key.to_string()))?;
+        self.write_batch(
+            keys.into_iter()
+                .map(|key| StorageChange::Delete(lifetime, key))
+                .collect(),
+        )
+    }
+
+    /// Applies several changes at once.
+    ///
+    /// Backends supporting transactions should apply all or none of them.
+    /// By default the changes are applied one after the other.
+    fn write_batch(&self, changes: Vec<StorageChange>) -> Result<()> {
+        for change in changes {
+            match change {
+                StorageChange::Put(lifetime, key, value) => self.put(lifetime, &key, &value)?,
+                StorageChange::Delete(lifetime, key) => self.delete(lifetime, &key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The default storage backend, a safe-mode rkv database on disk.
+pub(crate) struct RkvBackend {
+    /// Handle to the database environment.
+    rkv: Rkv,
+
+    /// Handles to the "lifetime" stores.
+    ///
+    /// A "store" is a handle to the underlying database.
+    /// We keep them open for fast and frequent access.
+    user_store: SingleStore,
+    ping_store: SingleStore,
+    application_store: SingleStore,
+}
+
+impl fmt::Debug for RkvBackend {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RkvBackend")
+            .field("rkv", &self.rkv)
+            .field("user_store", &"SingleStore")
+            .field("ping_store", &"SingleStore")
+            .field("application_store", &"SingleStore")
+            .finish()
+    }
+}
+
+impl RkvBackend {
+    /// Creates the storage directory and opens the rkv database in it,
+    /// migrating an LMDB database left behind by older versions.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        fs::create_dir_all(path)?;
+
+        let rkv = rkv_new(path)?;
+        migrate(path, &rkv);
+
+        let user_store = rkv.open_single(Lifetime::User.as_str(), StoreOptions::create())?;
+        let ping_store = rkv.open_single(Lifetime::Ping.as_str(), StoreOptions::create())?;
+        let application_store =
+            rkv.open_single(Lifetime::Application.as_str(), StoreOptions::create())?;
+
+        log::info!("Database initialized");
+        Ok(Self {
+            rkv,
+            user_store,
+            ping_store,
+            application_store,
+        })
+    }
+
+    fn store(&self, lifetime: Lifetime) -> &SingleStore {
+        match lifetime {
+            Lifetime::User => &self.user_store,
+            Lifetime::Ping => &self.ping_store,
+            Lifetime::Application => &self.application_store,
+        }
+    }
+}
+
+/// Ignores errors deleting keys that don't exist.
+fn ignore_not_found(result: std::result::Result<(), StoreError>) -> Result<()> {
+    match result {
+        Ok(()) | Err(StoreError::KeyValuePairNotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl StorageBackend for RkvBackend {
+    fn get(&self, lifetime: Lifetime, key: &str) -> Result<Option<Vec<u8>>> {
+        let reader = self.rkv.read()?;
+        match self.store(lifetime).get(&reader, key)? {
+            Some(rkv::Value::Blob(blob)) => Ok(Some(blob.to_vec())),
+            _ => Ok(None),
+        }
+    }
+
+    fn put(&self, lifetime: Lifetime, key: &str, value: &[u8]) -> Result<()> {
+        let mut writer = self.rkv.write()?;
+        self.store(lifetime)
+            .put(&mut writer, key, &rkv::Value::Blob(value))?;
+        writer.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, lifetime: Lifetime, key: &str) -> Result<()> {
+        let mut writer = self.rkv.write()?;
+        ignore_not_found(self.store(lifetime).delete(&mut writer, key))?;
+        writer.commit()?;
+        Ok(())
+    }
+
+    fn iter(&self, lifetime: Lifetime, prefix: &str, f: &mut dyn FnMut(&str, &[u8])) -> Result<()> {
+        let reader = self.rkv.read()?;
+        let store = self.store(lifetime);
+        let mut iter = if prefix.is_empty() {
+            store.iter_start(&reader)?
+        } else {
+            store.iter_from(&reader, prefix)?
+        };
+
+        while let Some(Ok((key, value))) = iter.next() {
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            let key = match str::from_utf8(key) {
+                Ok(key) => key,
+                _ => continue,
+            };
+            if let rkv::Value::Blob(blob) = value {
+                f(key, blob);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&self, lifetime: Lifetime) -> Result<()> {
+        let mut writer = self.rkv.write()?;
+        self.store(lifetime).clear(&mut writer)?;
+        writer.commit()?;
+        Ok(())
+    }
+
+    fn write_batch(&self, changes: Vec<StorageChange>) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        // The safe-mode database is rewritten on every commit,
+        // so all changes go into a single transaction.
+        let mut writer = self.rkv.write()?;
+        for change in changes {
+            match change {
+                StorageChange::Put(lifetime, key, value) => {
+                    self.store(lifetime)
+                        .put(&mut writer, key, &rkv::Value::Blob(&value))?;
+                }
+                StorageChange::Delete(lifetime, key) => {
+                    ignore_not_found(self.store(lifetime).delete(&mut writer, key))?;
+                }
+            }
+        }
+        writer.commit()?;
+        Ok(())
+    }
+}
@@ -3,15 +3,20 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::collections::btree_map::Entry;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::num::NonZeroU64;
 use std::path::Path;
 use std::str;
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
 
 use rkv::migrator::Migrator;
-use rkv::StoreOptions;
+
+mod backend;
+
+pub(crate) use backend::RkvBackend;
+pub use backend::{StorageBackend, StorageChange};
 
 /// Unwrap a `Result`s `Ok` value or do the specified action.
 ///
@@ -32,8 +37,6 @@ macro_rules! unwrap_or {
 pub type Rkv = rkv::Rkv<rkv::backend::SafeModeEnvironment>;
 /// cbindgen:ignore
 pub type SingleStore = rkv::SingleStore<rkv::backend::SafeModeDatabase>;
-/// cbindgen:ignore
-pub type Writer<'t> = rkv::Writer<rkv::backend::SafeModeRwTransaction<'t>>;
 
 pub fn rkv_new(path: &Path) -> std::result::Result<Rkv, rkv::StoreError> {
     match Rkv::new::<rkv::backend::SafeMode>(path) {
@@ -144,28 +147,70 @@ pub fn migrate(path: &Path, dst_env: &Rkv) {
 }
 
 use crate::metrics::Metric;
+use crate::storage::{DEBUG_STORE, INTERNAL_STORAGE};
 use crate::CommonMetricData;
 use crate::Glean;
 use crate::Lifetime;
 use crate::Result;
 
+/// The identifier of the marker stored while ping-lifetime data
+/// is held in memory without being persisted.
+const UNPERSISTED_MARKER: &str = "ping_lifetime_unpersisted";
+
+/// What happens to recordings into a ping that isn't registered (yet).
+///
+/// Glean's own stores and the debug store are always recorded into.
+/// Events are not affected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnregisteredPingBehavior {
+    /// Recordings are stored right away, as if the ping was registered.
+    Record,
+    /// Recordings are held in memory until the ping is registered,
+    /// and are lost if it isn't registered before Glean shuts down.
+    /// Until then, their values can't be read back.
+    Buffer,
+    /// Recordings are dropped and counted in `glean.error.unknown_ping`.
+    Strict,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] on variants needs Rust 1.62, above our MSRV
+impl Default for UnregisteredPingBehavior {
+    fn default() -> Self {
+        UnregisteredPingBehavior::Record
+    }
+}
+
+/// The recordings held for a ping, with their lifetime, by metric identifier.
+type BufferedRecordings = BTreeMap<String, (Lifetime, Metric)>;
+
 pub struct Database {
-    /// Handle to the database environment.
-    rkv: Rkv,
+    /// The storage all data is kept in, an rkv database unless another backend was provided.
+    backend: Box<dyn StorageBackend>,
 
-    /// Handles to the "lifetime" stores.
-    ///
-    /// A "store" is a handle to the underlying database.
-    /// We keep them open for fast and frequent access.
-    user_store: SingleStore,
-    ping_store: SingleStore,
-    application_store: SingleStore,
+    /// Held while reading and then writing data, so no other change happens in between.
+    write_lock: Mutex<()>,
 
     /// If the `delay_ping_lifetime_io` Glean config option is `true`,
     /// we will save metrics with 'ping' lifetime data in a map temporarily
-    /// so as to persist them to disk using rkv in bulk on demand.
+    /// so as to persist them to the backend in bulk on demand.
     ping_lifetime_data: Option<RwLock<BTreeMap<String, Metric>>>,
 
+    /// Whether `ping_lifetime_data` has changes that were not persisted yet.
+    ping_lifetime_dirty: AtomicBool,
+
+    /// Whether the previous run ended without persisting its in-memory ping-lifetime data.
+    lost_unpersisted_data: bool,
+
+    /// The identifiers of the metrics recorded only once per session that were recorded already.
+    recorded_once: Mutex<HashSet<String>>,
+
+    /// Recordings into pings that aren't registered yet, by ping name,
+    /// held with [`UnregisteredPingBehavior::Buffer`].
+    buffered: Mutex<HashMap<String, BufferedRecordings>>,
+
+    /// Whether a failed write is being counted, so failing to count it isn't counted again.
+    counting_io_failure: AtomicBool,
+
     // Initial file size when opening the database.
     file_size: Option<NonZeroU64>,
 }
@@ -173,10 +218,7 @@ pub struct Database {
 impl std::fmt::Debug for Database {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         fmt.debug_struct("Database")
-            .field("rkv", &self.rkv)
-            .field("user_store", &"SingleStore")
-            .field("ping_store", &"SingleStore")
-            .field("application_store", &"SingleStore")
+            .field("backend", &self.backend)
             .field("ping_lifetime_data", &self.ping_lifetime_data)
             .finish()
     }
@@ -225,53 +267,88 @@ impl Database {
         log::debug!("Database path: {:?}", path.display());
         let file_size = database_size(&path);
 
-        let rkv = Self::open_rkv(&path)?;
-        let user_store = rkv.open_single(Lifetime::User.as_str(), StoreOptions::create())?;
-        let ping_store = rkv.open_single(Lifetime::Ping.as_str(), StoreOptions::create())?;
-        let application_store =
-            rkv.open_single(Lifetime::Application.as_str(), StoreOptions::create())?;
+        let backend = RkvBackend::open(&path)?;
+        let mut db = Self::with_backend(Box::new(backend), delay_ping_lifetime_io);
+        db.file_size = file_size;
+        Ok(db)
+    }
+
+    /// Initializes the data store on top of the given storage backend.
+    ///
+    /// Like [`Database::new`], this loads any Lifetime::Ping data that might be
+    /// persisted, in case `delay_ping_lifetime_io` is set.
+    pub fn with_backend(backend: Box<dyn StorageBackend>, delay_ping_lifetime_io: bool) -> Self {
         let ping_lifetime_data = if delay_ping_lifetime_io {
             Some(RwLock::new(BTreeMap::new()))
         } else {
             None
         };
 
-        let db = Self {
-            rkv,
-            user_store,
-            ping_store,
-            application_store,
+        let mut db = Self {
+            backend,
+            write_lock: Mutex::new(()),
             ping_lifetime_data,
-            file_size,
+            ping_lifetime_dirty: AtomicBool::new(false),
+            lost_unpersisted_data: false,
+            recorded_once: Mutex::new(HashSet::new()),
+            buffered: Mutex::new(HashMap::new()),
+            counting_io_failure: AtomicBool::new(false),
+            file_size: None,
         };
 
+        // Check for the marker regardless of `delay_ping_lifetime_io`,
+        // the previous run might have used a different setting.
+        db.lost_unpersisted_data = db.take_unpersisted_marker();
         db.load_ping_lifetime_data();
 
-        Ok(db)
+        db
     }
 
     /// Get the initial database file size.
+    ///
+    /// This is only known for the default rkv backend.
     pub fn file_size(&self) -> Option<NonZeroU64> {
         self.file_size
     }
 
-    fn get_store(&self, lifetime: Lifetime) -> &SingleStore {
-        match lifetime {
-            Lifetime::User => &self.user_store,
-            Lifetime::Ping => &self.ping_store,
-            Lifetime::Application => &self.application_store,
-        }
+    /// Whether the previous run might have ended without persisting its in-memory ping-lifetime data.
+    ///
+    /// This can only happen if the previous run had `delay_ping_lifetime_io` set,
+    /// persisted changed data at least once
+    /// and wasn't shut down in an orderly fashion after, e.g. because it crashed.
+    /// Whatever it recorded after persisting last is lost, the data persisted last is used instead.
+    pub fn lost_unpersisted_data(&self) -> bool {
+        self.lost_unpersisted_data
     }
 
-    /// Creates the storage directories and inits rkv.
-    fn open_rkv(path: &Path) -> Result<Rkv> {
-        fs::create_dir_all(path)?;
+    fn unpersisted_marker_key() -> String {
+        Self::get_storage_key(INTERNAL_STORAGE, Some(UNPERSISTED_MARKER))
+    }
 
-        let rkv = rkv_new(path)?;
-        migrate(path, &rkv);
+    /// Removes the marker for unpersisted ping-lifetime data.
+    ///
+    /// # Returns
+    ///
+    /// Whether the marker was stored.
+    fn take_unpersisted_marker(&self) -> bool {
+        let key = Self::unpersisted_marker_key();
+        let res = match self.backend.get(Lifetime::User, &key) {
+            Ok(Some(_)) => self.backend.delete(Lifetime::User, &key).map(|()| true),
+            Ok(None) => Ok(false),
+            Err(e) => Err(e),
+        };
+        res.unwrap_or_else(|e| {
+            log::warn!("Can't check for unpersisted ping lifetime data: {:?}", e);
+            false
+        })
+    }
 
-        log::info!("Database initialized");
-        Ok(rkv)
+    /// Marks the in-memory ping-lifetime data as changed.
+    ///
+    /// This only flips a flag in memory,
+    /// the marker for unpersisted data is stored along with the data once it's persisted.
+    fn mark_ping_lifetime_dirty(&self) {
+        self.ping_lifetime_dirty.store(true, Ordering::SeqCst);
     }
 
     /// Build the key of the final location of the data in the database.
@@ -293,7 +370,7 @@ impl Database {
         }
     }
 
-    /// Loads Lifetime::Ping data from rkv to memory,
+    /// Loads Lifetime::Ping data from the backend to memory,
     /// if `delay_ping_lifetime_io` is set to true.
     ///
     /// Does nothing if it isn't or if there is not data to load.
@@ -303,22 +380,12 @@ impl Database {
                 .write()
                 .expect("Can't read ping lifetime data");
 
-            let reader = unwrap_or!(self.rkv.read(), return);
-            let store = self.get_store(Lifetime::Ping);
-            let mut iter = unwrap_or!(store.iter_start(&reader), return);
-
-            while let Some(Ok((metric_id, value))) = iter.next() {
-                let metric_id = match str::from_utf8(metric_id) {
-                    Ok(metric_id) => metric_id.to_string(),
-                    _ => continue,
-                };
-                let metric: Metric = match value {
-                    rkv::Value::Blob(blob) => unwrap_or!(bincode::deserialize(blob), continue),
-                    _ => continue,
-                };
-
-                data.insert(metric_id, metric);
-            }
+            let _ = self
+                .backend
+                .iter(Lifetime::Ping, "", &mut |metric_id, value| {
+                    let metric: Metric = unwrap_or!(bincode::deserialize(value), return);
+                    data.insert(metric_id.to_string(), metric);
+                });
         }
     }
 
@@ -373,24 +440,12 @@ impl Database {
             }
         }
 
-        let reader = unwrap_or!(self.rkv.read(), return);
-        let mut iter = unwrap_or!(
-            self.get_store(lifetime).iter_from(&reader, &iter_start),
-            return
-        );
-
-        while let Some(Ok((metric_id, value))) = iter.next() {
-            if !metric_id.starts_with(iter_start.as_bytes()) {
-                break;
-            }
-
-            let metric_id = &metric_id[len..];
-            let metric: Metric = match value {
-                rkv::Value::Blob(blob) => unwrap_or!(bincode::deserialize(blob), continue),
-                _ => continue,
-            };
-            transaction_fn(metric_id, &metric);
-        }
+        let _ = self
+            .backend
+            .iter(lifetime, &iter_start, &mut |metric_id, value| {
+                let metric: Metric = unwrap_or!(bincode::deserialize(value), return);
+                transaction_fn(&metric_id.as_bytes()[len..], &metric);
+            });
     }
 
     /// Determines if the storage has the given metric.
@@ -425,27 +480,38 @@ impl Database {
             }
         }
 
-        let reader = unwrap_or!(self.rkv.read(), return false);
-        self.get_store(lifetime)
-            .get(&reader, &key)
-            .unwrap_or(None)
-            .is_some()
+        matches!(self.backend.get(lifetime, &key), Ok(Some(_)))
     }
 
-    /// Writes to the specified storage with the provided transaction function.
-    ///
-    /// If the storage is unavailable, it will return an error.
-    ///
-    /// # Panics
-    ///
-    /// * This function will **not** panic on database errors.
-    fn write_with_store<F>(&self, store_name: Lifetime, mut transaction_fn: F) -> Result<()>
+    /// Gets the keys in the store of `lifetime`, starting with `prefix`, that match `filter`.
+    fn matching_keys<F>(
+        &self,
+        lifetime: Lifetime,
+        prefix: &str,
+        mut filter: F,
+    ) -> Result<Vec<String>>
     where
-        F: FnMut(Writer, &SingleStore) -> Result<()>,
+        F: FnMut(&str, &[u8]) -> bool,
     {
-        let writer = self.rkv.write().unwrap();
-        let store = self.get_store(store_name);
-        transaction_fn(writer, store)
+        let mut keys = Vec::new();
+        self.backend.iter(lifetime, prefix, &mut |key, value| {
+            if filter(key, value) {
+                keys.push(key.to_string());
+            }
+        })?;
+        Ok(keys)
+    }
+
+    /// Counts a failed write in `glean.error.io_failure`.
+    ///
+    /// The count itself is written to the same storage and might fail as well,
+    /// which isn't counted again.
+    fn count_io_failure(&self, glean: &Glean) {
+        if self.counting_io_failure.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        glean.additional_metrics.io_failure.add_sync(glean, 1);
+        self.counting_io_failure.store(false, Ordering::SeqCst);
     }
 
     /// Records a metric in the underlying storage system.
@@ -456,10 +522,164 @@ impl Database {
         }
 
         let name = data.identifier(glean);
+        if self.was_recorded_once(data, &name) {
+            return;
+        }
+
+        let mut recorded = false;
+        for ping_name in Self::mirrored_storage_names(glean, data) {
+            let result = match Self::unregistered_ping_behavior(glean, ping_name) {
+                UnregisteredPingBehavior::Record => {
+                    self.record_per_lifetime(data.lifetime, ping_name, &name, value)
+                }
+                UnregisteredPingBehavior::Buffer => {
+                    self.buffer(data.lifetime, ping_name, &name, |_| value.clone());
+                    Ok(())
+                }
+                UnregisteredPingBehavior::Strict => {
+                    Self::reject_unknown_ping(glean, ping_name, &name);
+                    continue;
+                }
+            };
+            match result {
+                Ok(()) => recorded = true,
+                Err(e) => {
+                    log::error!("Failed to record metric into {}: {:?}", ping_name, e);
+                    self.count_io_failure(glean);
+                }
+            }
+        }
+
+        if recorded {
+            self.mark_recorded_once(data, name.clone());
+            glean.observers.notify(&name, value);
+        }
+    }
+
+    /// Whether the metric is only recorded once per session and was recorded already.
+    fn was_recorded_once(&self, data: &CommonMetricData, identifier: &str) -> bool {
+        data.only_once_per_session && self.recorded_once.lock().unwrap().contains(identifier)
+    }
+
+    /// Remembers that the metric was recorded, if it's only recorded once per session.
+    fn mark_recorded_once(&self, data: &CommonMetricData, identifier: String) {
+        if data.only_once_per_session {
+            self.recorded_once.lock().unwrap().insert(identifier);
+        }
+    }
+
+    /// Gets the names of the stores to record into, including the debug store if mirroring is enabled.
+    ///
+    /// Glean's own metrics skip the "metrics" ping if internal metrics are disabled,
+    /// except for the errors of the application's metrics.
+    fn mirrored_storage_names<'a>(
+        glean: &Glean,
+        data: &'a CommonMetricData,
+    ) -> impl Iterator<Item = &'a str> {
+        let names = data.storage_names();
+        let mirror =
+            if glean.mirror_to_debug_store() && !names.iter().any(|name| name == DEBUG_STORE) {
+                Some(DEBUG_STORE)
+            } else {
+                None
+            };
+        let skip_metrics_ping = !glean.enable_internal_metrics() && data.is_about_glean();
+        names
+            .iter()
+            .map(String::as_str)
+            .filter(move |name| !(skip_metrics_ping && *name == "metrics"))
+            .chain(mirror)
+    }
+
+    /// How to record into `storage_name`, which is [`UnregisteredPingBehavior::Record`]
+    /// unless it's a ping that isn't registered.
+    fn unregistered_ping_behavior(glean: &Glean, storage_name: &str) -> UnregisteredPingBehavior {
+        let behavior = glean.unregistered_ping_behavior();
+        if behavior == UnregisteredPingBehavior::Record
+            || storage_name == DEBUG_STORE
+            || storage_name.starts_with("glean_")
+            || glean.get_ping_by_name(storage_name).is_some()
+        {
+            UnregisteredPingBehavior::Record
+        } else {
+            behavior
+        }
+    }
+
+    /// Drops a recording into the unregistered ping `storage_name`, counting it.
+    fn reject_unknown_ping(glean: &Glean, storage_name: &str, key: &str) {
+        log::warn!(
+            "Not recording '{}' into unknown ping '{}'.",
+            key,
+            storage_name
+        );
+        crate::glean_metrics::error::unknown_ping.add_sync(glean, 1);
+    }
+
+    /// Holds a recording into the unregistered ping `storage_name`
+    /// until [`flush_buffered`](Self::flush_buffered).
+    ///
+    /// `transform` gets the value held so far, or the stored one, like when recording.
+    ///
+    /// # Returns
+    ///
+    /// The new value held.
+    fn buffer<F>(
+        &self,
+        lifetime: Lifetime,
+        storage_name: &str,
+        key: &str,
+        mut transform: F,
+    ) -> Metric
+    where
+        F: FnMut(Option<Metric>) -> Metric,
+    {
+        let mut buffered = self.buffered.lock().unwrap();
+        let recordings = buffered.entry(storage_name.to_string()).or_default();
+        let old_value = match recordings.get(key) {
+            Some((_, value)) => Some(value.clone()),
+            None => self.stored_value(lifetime, storage_name, key),
+        };
+        let new_value = transform(old_value);
+        recordings.insert(key.to_string(), (lifetime, new_value.clone()));
+        new_value
+    }
+
+    /// Gets the value stored for `key` in `storage_name`, if any.
+    fn stored_value(&self, lifetime: Lifetime, storage_name: &str, key: &str) -> Option<Metric> {
+        let final_key = Self::get_storage_key(storage_name, Some(key));
+        if lifetime == Lifetime::Ping {
+            if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
+                return ping_lifetime_data
+                    .read()
+                    .expect("Can't read ping lifetime data")
+                    .get(&final_key)
+                    .cloned();
+            }
+        }
+
+        match self.backend.get(lifetime, &final_key) {
+            Ok(Some(blob)) => bincode::deserialize(&blob).ok(),
+            _ => None,
+        }
+    }
 
-        for ping_name in data.storage_names() {
-            if let Err(e) = self.record_per_lifetime(data.lifetime, ping_name, &name, value) {
-                log::error!("Failed to record metric into {}: {:?}", ping_name, e);
+    /// Stores the recordings held for `storage_name` since before it was registered.
+    pub(crate) fn flush_buffered(&self, glean: &Glean, storage_name: &str) {
+        let recordings = match self.buffered.lock().unwrap().remove(storage_name) {
+            Some(recordings) => recordings,
+            None => return,
+        };
+
+        log::info!(
+            "Storing {} recordings held for ping '{}'",
+            recordings.len(),
+            storage_name
+        );
+        for (key, (lifetime, value)) in recordings {
+            if let Err(e) = self.record_per_lifetime(lifetime, storage_name, &key, &value) {
+                log::error!("Failed to record metric into {}: {:?}", storage_name, e);
+                self.count_io_failure(glean);
             }
         }
     }
@@ -475,7 +695,7 @@ impl Database {
     /// # Panics
     ///
     /// This function will **not** panic on database errors.
-    fn record_per_lifetime(
+    pub(crate) fn record_per_lifetime(
         &self,
         lifetime: Lifetime,
         storage_name: &str,
@@ -488,6 +708,7 @@ impl Database {
         // Glean has `delay_ping_lifetime_io` set to true
         if lifetime == Lifetime::Ping {
             if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
+                self.mark_ping_lifetime_dirty();
                 let mut data = ping_lifetime_data
                     .write()
                     .expect("Can't read ping lifetime data");
@@ -497,13 +718,7 @@ impl Database {
         }
 
         let encoded = bincode::serialize(&metric).expect("IMPOSSIBLE: Serializing metric failed");
-        let value = rkv::Value::Blob(&encoded);
-
-        let mut writer = self.rkv.write()?;
-        self.get_store(lifetime)
-            .put(&mut writer, final_key, &value)?;
-        writer.commit()?;
-        Ok(())
+        self.backend.put(lifetime, &final_key, &encoded)
     }
 
     /// Records the provided value, with the given lifetime,
@@ -518,13 +733,40 @@ impl Database {
         }
 
         let name = data.identifier(glean);
-        for ping_name in data.storage_names() {
-            if let Err(e) =
-                self.record_per_lifetime_with(data.lifetime, ping_name, &name, &mut transform)
-            {
-                log::error!("Failed to record metric into {}: {:?}", ping_name, e);
+        if self.was_recorded_once(data, &name) {
+            return;
+        }
+
+        // Each store keeps its own value, observers get the one from the first store.
+        let mut new_value = None;
+        for ping_name in Self::mirrored_storage_names(glean, data) {
+            let result = match Self::unregistered_ping_behavior(glean, ping_name) {
+                UnregisteredPingBehavior::Record => {
+                    self.record_per_lifetime_with(data.lifetime, ping_name, &name, &mut transform)
+                }
+                UnregisteredPingBehavior::Buffer => {
+                    Ok(self.buffer(data.lifetime, ping_name, &name, &mut transform))
+                }
+                UnregisteredPingBehavior::Strict => {
+                    Self::reject_unknown_ping(glean, ping_name, &name);
+                    continue;
+                }
+            };
+            match result {
+                Ok(value) => {
+                    new_value.get_or_insert(value);
+                }
+                Err(e) => {
+                    log::error!("Failed to record metric into {}: {:?}", ping_name, e);
+                    self.count_io_failure(glean);
+                }
             }
         }
+
+        if let Some(value) = new_value {
+            self.mark_recorded_once(data, name.clone());
+            glean.observers.notify(&name, &value);
+        }
     }
 
     /// Records a metric in the underlying storage system,
@@ -534,7 +776,7 @@ impl Database {
     ///
     /// If the storage is unavailable or the write fails, no data will be stored and an error will be returned.
     ///
-    /// Otherwise `Ok(())` is returned.
+    /// Otherwise the newly stored value is returned.
     ///
     /// # Panics
     ///
@@ -545,7 +787,7 @@ impl Database {
         storage_name: &str,
         key: &str,
         mut transform: F,
-    ) -> Result<()>
+    ) -> Result<Metric>
     where
         F: FnMut(Option<Metric>) -> Metric,
     {
@@ -555,43 +797,34 @@ impl Database {
         // Glean has `delay_ping_lifetime_io` set to true
         if lifetime == Lifetime::Ping {
             if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
+                self.mark_ping_lifetime_dirty();
                 let mut data = ping_lifetime_data
                     .write()
                     .expect("Can't access ping lifetime data as writable");
                 let entry = data.entry(final_key);
-                match entry {
-                    Entry::Vacant(entry) => {
-                        entry.insert(transform(None));
-                    }
+                let new_value = match entry {
+                    Entry::Vacant(entry) => entry.insert(transform(None)).clone(),
                     Entry::Occupied(mut entry) => {
                         let old_value = entry.get().clone();
                         entry.insert(transform(Some(old_value)));
+                        entry.get().clone()
                     }
-                }
-                return Ok(());
+                };
+                return Ok(new_value);
             }
         }
 
-        let mut writer = self.rkv.write()?;
-        let store = self.get_store(lifetime);
-        let new_value: Metric = {
-            let old_value = store.get(&writer, &final_key)?;
-
-            match old_value {
-                Some(rkv::Value::Blob(blob)) => {
-                    let old_value = bincode::deserialize(blob).ok();
-                    transform(old_value)
-                }
-                _ => transform(None),
-            }
-        };
+        let _lock = self.write_lock.lock().unwrap();
+        let old_value = self
+            .backend
+            .get(lifetime, &final_key)?
+            .and_then(|blob| bincode::deserialize(&blob).ok());
+        let new_value = transform(old_value);
 
         let encoded =
             bincode::serialize(&new_value).expect("IMPOSSIBLE: Serializing metric failed");
-        let value = rkv::Value::Blob(&encoded);
-        store.put(&mut writer, final_key, &value)?;
-        writer.commit()?;
-        Ok(())
+        self.backend.put(lifetime, &final_key, &encoded)?;
+        Ok(new_value)
     }
 
     /// Clears a storage (only Ping Lifetime).
@@ -617,30 +850,15 @@ impl Database {
                 .retain(|metric_id, _| !metric_id.starts_with(storage_name));
         }
 
-        self.write_with_store(Lifetime::Ping, |mut writer, store| {
-            let mut metrics = Vec::new();
-            {
-                let mut iter = store.iter_from(&writer, storage_name)?;
-                while let Some(Ok((metric_id, _))) = iter.next() {
-                    if let Ok(metric_id) = std::str::from_utf8(metric_id) {
-                        if !metric_id.starts_with(storage_name) {
-                            break;
-                        }
-                        metrics.push(metric_id.to_owned());
-                    }
-                }
-            }
-
-            let mut res = Ok(());
-            for to_delete in metrics {
-                if let Err(e) = store.delete(&mut writer, to_delete) {
-                    log::warn!("Can't delete from store: {:?}", e);
-                    res = Err(e);
-                }
-            }
-
-            writer.commit()?;
-            Ok(res?)
+        let _lock = self.write_lock.lock().unwrap();
+        let metrics = self.matching_keys(Lifetime::Ping, storage_name, |_, _| true)?;
+        let changes = metrics
+            .into_iter()
+            .map(|metric_id| StorageChange::Delete(Lifetime::Ping, metric_id))
+            .collect();
+        self.backend.write_batch(changes).map_err(|e| {
+            log::warn!("Can't delete from store: {:?}", e);
+            e
         })
     }
 
@@ -681,18 +899,62 @@ impl Database {
             }
         }
 
-        self.write_with_store(lifetime, |mut writer, store| {
-            if let Err(e) = store.delete(&mut writer, final_key.clone()) {
-                if self.ping_lifetime_data.is_some() {
-                    // If ping_lifetime_data exists, it might be
-                    // that data is in memory, but not yet in rkv.
-                    return Ok(());
-                }
-                return Err(e.into());
-            }
-            writer.commit()?;
-            Ok(())
-        })
+        // If ping_lifetime_data exists, the data might be in memory only,
+        // the backend doesn't fail deleting a key it doesn't have.
+        self.backend.delete(lifetime, &final_key)
+    }
+
+    /// Removes a metric from all storages and lifetimes,
+    /// including its labels and the errors recorded for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric_id` - the metric category + name.
+    ///
+    /// # Returns
+    ///
+    /// * If the metric could not be deleted, an error is returned.
+    ///
+    /// Otherwise `Ok(())` is returned, also if nothing was stored for the metric.
+    ///
+    /// # Panics
+    ///
+    /// This function will **not** panic on database errors.
+    pub fn remove_metric_everywhere(&self, metric_id: &str) -> Result<()> {
+        let is_metric_key = |key: &str| {
+            let id = match key.split_once('#') {
+                Some((_, id)) => id,
+                None => return false,
+            };
+            // Errors are stored as `glean.error.<error type>/<metric id>`.
+            let id = match id.strip_prefix("glean.error.") {
+                Some(error) => match error.split_once('/') {
+                    Some((_, id)) => id,
+                    None => return false,
+                },
+                None => id,
+            };
+            id == metric_id
+                || matches!(id.strip_prefix(metric_id), Some(label) if label.starts_with('/'))
+        };
+
+        if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
+            ping_lifetime_data
+                .write()
+                .expect("Can't access ping lifetime data as writable")
+                .retain(|key, _| !is_metric_key(key));
+        }
+
+        let _lock = self.write_lock.lock().unwrap();
+        let mut changes = Vec::new();
+        for lifetime in [Lifetime::User, Lifetime::Ping, Lifetime::Application].iter() {
+            let keys = self.matching_keys(*lifetime, "", |key, _| is_metric_key(key))?;
+            changes.extend(
+                keys.into_iter()
+                    .map(|key| StorageChange::Delete(*lifetime, key)),
+            );
+        }
+        self.backend.write_batch(changes)
     }
 
     /// Clears all the metrics in the database, for the provided lifetime.
@@ -703,12 +965,7 @@ impl Database {
     ///
     /// * This function will **not** panic on database errors.
     pub fn clear_lifetime(&self, lifetime: Lifetime) {
-        let res = self.write_with_store(lifetime, |mut writer, store| {
-            store.clear(&mut writer)?;
-            writer.commit()?;
-            Ok(())
-        });
-        if let Err(e) = res {
+        if let Err(e) = self.backend.clear(lifetime) {
             log::warn!("Could not clear store for lifetime {:?}: {:?}", lifetime, e);
         }
     }
@@ -721,6 +978,7 @@ impl Database {
     ///
     /// * This function will **not** panic on database errors.
     pub fn clear_all(&self) {
+        self.buffered.lock().unwrap().clear();
         if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
             ping_lifetime_data
                 .write()
@@ -731,33 +989,116 @@ impl Database {
         for lifetime in [Lifetime::User, Lifetime::Ping, Lifetime::Application].iter() {
             self.clear_lifetime(*lifetime);
         }
+        // Nothing is left to persist, and the marker is gone with the user lifetime data.
+        self.ping_lifetime_dirty.store(false, Ordering::SeqCst);
+    }
+
+    /// Gets the number of entries and their approximate size in bytes, by storage name.
+    ///
+    /// The size includes the keys and the encoded values.
+    /// Labeled metrics take up an entry per label, events are not part of this database.
+    ///
+    /// # Returns
+    ///
+    /// A `(storage name, number of entries, size)` tuple for every storage with data,
+    /// sorted by storage name.
+    pub fn store_stats(&self) -> Vec<(String, usize, usize)> {
+        let mut stats: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        let mut add = |key: &str, size: usize| {
+            if let Some((storage_name, _)) = key.split_once('#') {
+                let entry = stats.entry(storage_name.to_string()).or_default();
+                entry.0 += 1;
+                entry.1 += key.len() + size;
+            }
+        };
+
+        for lifetime in [Lifetime::User, Lifetime::Ping, Lifetime::Application].iter() {
+            // The in-memory ping lifetime data contains everything persisted before.
+            if let (Lifetime::Ping, Some(ping_lifetime_data)) = (lifetime, &self.ping_lifetime_data)
+            {
+                let data = ping_lifetime_data
+                    .read()
+                    .expect("Can't read ping lifetime data");
+                for (key, metric) in data.iter() {
+                    add(key, bincode::serialized_size(metric).unwrap_or(0) as usize);
+                }
+                continue;
+            }
+
+            let _ = self
+                .backend
+                .iter(*lifetime, "", &mut |key, value| add(key, value.len()));
+        }
+
+        stats
+            .into_iter()
+            .map(|(name, (count, size))| (name, count, size))
+            .collect()
     }
 
-    /// Persists ping_lifetime_data to disk.
+    /// Persists ping_lifetime_data to the backend.
     ///
     /// Does nothing in case there is nothing to persist.
+    /// If the data changed since it was persisted last, a marker is stored in the same batch.
+    /// It stays around until [`persist_ping_lifetime_data_on_shutdown`](Self::persist_ping_lifetime_data_on_shutdown),
+    /// so the next run learns that data recorded after this call might have been lost.
     ///
     /// # Panics
     ///
     /// * This function will **not** panic on database errors.
     pub fn persist_ping_lifetime_data(&self) -> Result<()> {
+        let marker = if self.ping_lifetime_dirty.swap(false, Ordering::SeqCst) {
+            let encoded = bincode::serialize(&Metric::Boolean(true))
+                .expect("IMPOSSIBLE: Serializing metric failed");
+            Some(StorageChange::Put(
+                Lifetime::User,
+                Self::unpersisted_marker_key(),
+                encoded,
+            ))
+        } else {
+            None
+        };
+        self.write_ping_lifetime_data(marker)
+    }
+
+    /// Persists ping_lifetime_data to the backend for the last time in this run.
+    ///
+    /// Does nothing in case there is nothing to persist.
+    /// The marker for unpersisted data is removed in the same batch, after the data,
+    /// so the next run either finds all of the data or learns that some of it might have been lost.
+    ///
+    /// # Panics
+    ///
+    /// * This function will **not** panic on database errors.
+    pub fn persist_ping_lifetime_data_on_shutdown(&self) -> Result<()> {
+        self.ping_lifetime_dirty.store(false, Ordering::SeqCst);
+        // The marker is only stored after changes, it might not be around.
+        self.write_ping_lifetime_data(Some(StorageChange::Delete(
+            Lifetime::User,
+            Self::unpersisted_marker_key(),
+        )))
+    }
+
+    /// Writes ping_lifetime_data to the backend, followed by the `marker` change, if any.
+    fn write_ping_lifetime_data(&self, marker: Option<StorageChange>) -> Result<()> {
         if let Some(ping_lifetime_data) = &self.ping_lifetime_data {
             let data = ping_lifetime_data
                 .read()
                 .expect("Can't read ping lifetime data");
 
-            self.write_with_store(Lifetime::Ping, |mut writer, store| {
-                for (key, value) in data.iter() {
+            let changes: Vec<StorageChange> = data
+                .iter()
+                .map(|(key, value)| {
                     let encoded =
                         bincode::serialize(&value).expect("IMPOSSIBLE: Serializing metric failed");
                     // There is no need for `get_storage_key` here because
                     // the key is already formatted from when it was saved
                     // to ping_lifetime_data.
-                    store.put(&mut writer, key, &rkv::Value::Blob(&encoded))?;
-                }
-                writer.commit()?;
-                Ok(())
-            })?;
+                    StorageChange::Put(Lifetime::Ping, key.clone(), encoded)
+                })
+                .chain(marker)
+                .collect();
+            self.backend.write_batch(changes)?;
         }
         Ok(())
     }
@@ -1133,21 +1474,23 @@ mod test {
             // At this stage we expect `test_value1` to be persisted and in memory,
             // since it was recorded before calling `persist_ping_lifetime_data`,
             // and `test_value2` to be only in memory, since it was recorded after.
-            let store: SingleStore = db
-                .rkv
-                .open_single(Lifetime::Ping.as_str(), StoreOptions::create())
-                .unwrap();
-            let reader = db.rkv.read().unwrap();
-
-            // Verify that test_value1 is in rkv.
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id1))
-                .unwrap_or(None)
+            // Verify that test_value1 is in the backend.
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id1)
+                )
+                .unwrap()
                 .is_some());
-            // Verifiy that test_value2 is **not** in rkv.
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id2))
-                .unwrap_or(None)
+            // Verifiy that test_value2 is **not** in the backend.
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id2)
+                )
+                .unwrap()
                 .is_none());
 
             let data = match &db.ping_lifetime_data {
@@ -1172,21 +1515,23 @@ mod test {
             // At this stage we expect `test_value1` and `test_value2` to
             // be persisted, since both were created before a call to
             // `persist_ping_lifetime_data`.
-            let store: SingleStore = db
-                .rkv
-                .open_single(Lifetime::Ping.as_str(), StoreOptions::create())
-                .unwrap();
-            let reader = db.rkv.read().unwrap();
-
-            // Verify that test_value1 is in rkv.
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id1))
-                .unwrap_or(None)
+            // Verify that test_value1 is in the backend.
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id1)
+                )
+                .unwrap()
                 .is_some());
-            // Verifiy that test_value2 is also in rkv.
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id2))
-                .unwrap_or(None)
+            // Verifiy that test_value2 is also in the backend.
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id2)
+                )
+                .unwrap()
                 .is_some());
 
             let data = match &db.ping_lifetime_data {
@@ -1240,14 +1585,13 @@ mod test {
             db.persist_ping_lifetime_data().unwrap();
 
             // Verify that test_value is now in rkv.
-            let store: SingleStore = db
-                .rkv
-                .open_single(Lifetime::Ping.as_str(), StoreOptions::create())
-                .unwrap();
-            let reader = db.rkv.read().unwrap();
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id))
-                .unwrap_or(None)
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id)
+                )
+                .unwrap()
                 .is_some());
         }
 
@@ -1267,14 +1611,13 @@ mod test {
                 .is_some());
 
             // Verify that test_value is also in rkv.
-            let store: SingleStore = db
-                .rkv
-                .open_single(Lifetime::Ping.as_str(), StoreOptions::create())
-                .unwrap();
-            let reader = db.rkv.read().unwrap();
-            assert!(store
-                .get(&reader, format!("{}#{}", test_storage, test_metric_id))
-                .unwrap_or(None)
+            assert!(db
+                .backend
+                .get(
+                    Lifetime::Ping,
+                    &format!("{}#{}", test_storage, test_metric_id)
+                )
+                .unwrap()
                 .is_some());
         }
     }
@@ -1438,7 +1781,7 @@ mod test {
         use std::fs::File;
 
         use super::*;
-        use rkv::Value;
+        use rkv::{StoreOptions, Value};
 
         #[test]
         fn empty_data_file() {
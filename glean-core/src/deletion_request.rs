@@ -0,0 +1,24 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reporting why upload was disabled through the built-in `deletion-request` ping.
+
+/// Why upload was disabled, reported in the `deletion-request` ping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UploadDisabledReason {
+    /// The user opted out of data collection.
+    UserOptOut,
+    /// A policy, e.g. set by an administrator, disabled data collection.
+    Policy,
+}
+
+impl UploadDisabledReason {
+    /// The value recorded in `glean.deletion_request.opt_out_reason`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadDisabledReason::UserOptOut => "user_opt_out",
+            UploadDisabledReason::Policy => "policy",
+        }
+    }
+}
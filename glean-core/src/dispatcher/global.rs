@@ -70,6 +70,13 @@ pub fn block_on_queue() {
     guard().block_on_queue();
 }
 
+/// The number of tasks launched on the global dispatch queue that haven't run yet.
+///
+/// While the pre-init queue isn't flushed, this includes all tasks queued so far.
+pub fn queue_depth() -> usize {
+    guard().queue_depth()
+}
+
 /// Starts processing queued tasks in the global dispatch queue.
 ///
 /// This function blocks until queued tasks prior to this call are finished.
@@ -83,6 +90,21 @@ pub fn flush_init() -> Result<usize, DispatchError> {
     guard().flush_init()
 }
 
+/// Drops the tasks queued in the global dispatch queue before it was flushed,
+/// and any launched until it is.
+///
+/// Use this if initialization takes too long, to bound the memory used by queued tasks.
+/// Dropped tasks are counted as overflowing the queue,
+/// so the count [`flush_init`] returns includes them.
+///
+/// # Returns
+///
+/// Returns the number of tasks dropped right away,
+/// or an error if the queue was already flushed.
+pub fn discard_preinit() -> Result<usize, DispatchError> {
+    guard().discard_preinit()
+}
+
 fn join_dispatcher_thread() -> Result<(), DispatchError> {
     // After we issue the shutdown command, make sure to wait for the
     // worker thread to join.
@@ -30,12 +30,12 @@ use std::{
     mem,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{self, JoinHandle},
 };
 
-use crossbeam_channel::{bounded, unbounded, SendError, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, SendError, Sender};
 use thiserror::Error;
 
 pub use global::*;
@@ -101,6 +101,12 @@ struct DispatchGuard {
     /// The number of items that were added to the queue after it filled up.
     overflow_count: Arc<AtomicUsize>,
 
+    /// Whether the pre-init buffer was discarded and refuses further tasks.
+    preinit_discarded: Arc<AtomicBool>,
+
+    /// Held while flushing or discarding the pre-init buffer, so only one of them happens.
+    preinit_lock: Arc<Mutex<()>>,
+
     /// The maximum pre-init queue size
     max_queue_size: usize,
 
@@ -110,6 +116,9 @@ struct DispatchGuard {
     /// Sender for the preinit queue.
     preinit_sender: Sender<Command>,
 
+    /// Receiver for the preinit queue, to discard it.
+    preinit_receiver: Receiver<Command>,
+
     /// Sender for the unbounded queue.
     sender: Sender<Command>,
 }
@@ -129,7 +138,10 @@ impl DispatchGuard {
 
     fn send(&self, task: Command) -> Result<(), DispatchError> {
         if self.queue_preinit.load(Ordering::SeqCst) {
-            if self.preinit_sender.len() < self.max_queue_size {
+            if self.preinit_discarded.load(Ordering::SeqCst) {
+                self.overflow_count.fetch_add(1, Ordering::SeqCst);
+                Err(DispatchError::QueueFull)
+            } else if self.preinit_sender.len() < self.max_queue_size {
                 self.preinit_sender.send(task)?;
                 Ok(())
             } else {
@@ -145,6 +157,13 @@ impl DispatchGuard {
         }
     }
 
+    /// The number of commands waiting in either queue.
+    ///
+    /// A task being executed is not waiting anymore and isn't counted.
+    fn queue_depth(&self) -> usize {
+        self.preinit_sender.len() + self.sender.len()
+    }
+
     fn block_on_queue(&self) {
         let (tx, rx) = crossbeam_channel::bounded(0);
 
@@ -177,7 +196,27 @@ impl DispatchGuard {
         Ok(())
     }
 
+    /// Drops all tasks in the pre-init buffer, and any launched until it is flushed.
+    ///
+    /// The dropped tasks are counted as overflowing the buffer.
+    /// Returns the number of tasks dropped right away,
+    /// or an error if the buffer was already flushed.
+    fn discard_preinit(&self) -> Result<usize, DispatchError> {
+        let _lock = self.preinit_lock.lock().unwrap();
+        if !self.queue_preinit.load(Ordering::SeqCst) {
+            return Err(DispatchError::AlreadyFlushed);
+        }
+
+        // Refuse new tasks first, so none slips in after draining.
+        self.preinit_discarded.store(true, Ordering::SeqCst);
+        let dropped = self.preinit_receiver.try_iter().count();
+        self.overflow_count.fetch_add(dropped, Ordering::SeqCst);
+        Ok(dropped)
+    }
+
     fn flush_init(&mut self) -> Result<usize, DispatchError> {
+        let _lock = self.preinit_lock.lock().unwrap();
+
         // We immediately stop queueing in the pre-init buffer.
         let old_val = self.queue_preinit.swap(false, Ordering::SeqCst);
         if !old_val {
@@ -239,6 +278,7 @@ impl Dispatcher {
 
         let queue_preinit = Arc::new(AtomicBool::new(true));
         let overflow_count = Arc::new(AtomicUsize::new(0));
+        let worker_preinit_receiver = preinit_receiver.clone();
 
         let worker = thread::Builder::new()
             .name("glean.dispatcher".into())
@@ -259,7 +299,7 @@ impl Dispatcher {
                     }
                 }
 
-                let mut receiver = preinit_receiver;
+                let mut receiver = worker_preinit_receiver;
                 loop {
                     use Command::*;
 
@@ -304,9 +344,12 @@ impl Dispatcher {
         let guard = DispatchGuard {
             queue_preinit,
             overflow_count,
+            preinit_discarded: Arc::new(AtomicBool::new(false)),
+            preinit_lock: Arc::new(Mutex::new(())),
             max_queue_size,
             block_sender,
             preinit_sender,
+            preinit_receiver,
             sender,
         };
 
@@ -324,6 +367,10 @@ impl Dispatcher {
         self.guard().block_on_queue()
     }
 
+    fn queue_depth(&self) -> usize {
+        self.guard().queue_depth()
+    }
+
     /// Waits for the worker thread to finish and finishes the dispatch queue.
     ///
     /// You need to call `shutdown` to initiate a shutdown of the queue.
@@ -343,6 +390,16 @@ impl Dispatcher {
     pub fn flush_init(&mut self) -> Result<usize, DispatchError> {
         self.guard().flush_init()
     }
+
+    /// Drops the pre-init buffer, and any task launched until [`flush_init`] is called.
+    ///
+    /// Returns the number of tasks dropped right away,
+    /// or an error if the buffer was already flushed.
+    ///
+    /// [`flush_init`]: #method.flush_init
+    pub fn discard_preinit(&self) -> Result<usize, DispatchError> {
+        self.guard().discard_preinit()
+    }
 }
 
 #[cfg(test)]
@@ -530,6 +587,56 @@ mod test {
         assert_eq!(&*result.lock().unwrap(), &[1, 2, 3, 4, 5, 20]);
     }
 
+    #[test]
+    fn discarded_preinit_tasks_are_not_processed() {
+        enable_test_logging();
+
+        let mut dispatcher = Dispatcher::new(5);
+
+        let result = Arc::new(Mutex::new(vec![]));
+
+        for i in 1..=3 {
+            let result = Arc::clone(&result);
+            dispatcher
+                .guard()
+                .launch(move || {
+                    result.lock().unwrap().push(i);
+                })
+                .unwrap();
+        }
+
+        assert_eq!(Ok(3), dispatcher.discard_preinit());
+
+        // Tasks launched after discarding are dropped too.
+        {
+            let result = Arc::clone(&result);
+            let err = dispatcher.guard().launch(move || {
+                result.lock().unwrap().push(10);
+            });
+            assert_eq!(Err(DispatchError::QueueFull), err);
+        }
+
+        assert_eq!(Ok(4), dispatcher.flush_init());
+        assert_eq!(
+            Err(DispatchError::AlreadyFlushed),
+            dispatcher.discard_preinit()
+        );
+
+        {
+            let result = Arc::clone(&result);
+            dispatcher
+                .guard()
+                .launch(move || {
+                    result.lock().unwrap().push(20);
+                })
+                .unwrap();
+        }
+
+        dispatcher.block_on_queue();
+
+        assert_eq!(&*result.lock().unwrap(), &[20]);
+    }
+
     #[test]
     fn normal_queue_is_unbounded() {
         enable_test_logging();
@@ -574,4 +681,22 @@ mod test {
         let expected = (1..=20).collect::<Vec<_>>();
         assert_eq!(&*result.lock().unwrap(), &expected);
     }
+
+    #[test]
+    fn queue_depth_counts_tasks_not_yet_run() {
+        enable_test_logging();
+
+        let mut dispatcher = Dispatcher::new(10);
+        assert_eq!(0, dispatcher.queue_depth());
+
+        // The dispatcher isn't flushed yet, so none of the tasks run.
+        for _ in 0..3 {
+            dispatcher.guard().launch(|| {}).unwrap();
+        }
+        assert_eq!(3, dispatcher.queue_depth());
+
+        dispatcher.flush_init().unwrap();
+        dispatcher.block_on_queue();
+        assert_eq!(0, dispatcher.queue_depth());
+    }
 }
@@ -62,6 +62,12 @@ pub enum ErrorKind {
 
     /// Ping request body size overflowed
     PingBodyOverflow(usize),
+
+    /// A custom bucketing function didn't produce valid buckets
+    InvalidBucketing(String),
+
+    /// Serialized recordings of another process couldn't be decoded
+    InvalidRecordings(String),
 }
 
 /// A specialized [`Error`] type for this crate's operations.
@@ -117,6 +123,8 @@ impl Display for Error {
                 "Ping request body size exceeded maximum size allowed: {}kB.",
                 s / 1024
             ),
+            InvalidBucketing(e) => write!(f, "Invalid custom bucketing: {}", e),
+            InvalidRecordings(e) => write!(f, "Invalid serialized recordings: {}", e),
         }
     }
 }
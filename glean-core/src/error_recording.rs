@@ -17,7 +17,7 @@ use std::fmt::Display;
 
 use crate::error::{Error, ErrorKind};
 use crate::metrics::labeled::{combine_base_identifier_and_label, strip_label};
-use crate::metrics::CounterMetric;
+use crate::metrics::{CounterMetric, Metric};
 use crate::CommonMetricData;
 use crate::Glean;
 use crate::Lifetime;
@@ -27,7 +27,7 @@ use crate::Lifetime;
 /// in the platform-specific code (e.g. `ErrorType.kt`) and with the
 /// metrics in the registry files.
 // When adding a new error type ensure it's also added to `ErrorType::iter()` below.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ErrorType {
     /// For when the value to be recorded does not match the metric-specific restrictions
     InvalidValue,
@@ -37,6 +37,8 @@ pub enum ErrorType {
     InvalidState,
     /// For when the value to be recorded overflows the metric-specific upper range
     InvalidOverflow,
+    /// For when a datetime was implausible, most likely because the system clock is skewed
+    ClockSkew,
 }
 
 impl ErrorType {
@@ -47,6 +49,7 @@ impl ErrorType {
             ErrorType::InvalidLabel => "invalid_label",
             ErrorType::InvalidState => "invalid_state",
             ErrorType::InvalidOverflow => "invalid_overflow",
+            ErrorType::ClockSkew => "clock_skew",
         }
     }
 
@@ -56,7 +59,7 @@ impl ErrorType {
     /// # use glean_core::ErrorType;
     /// let errors = ErrorType::iter();
     /// let all_errors = errors.collect::<Vec<_>>();
-    /// assert_eq!(4, all_errors.len());
+    /// assert_eq!(5, all_errors.len());
     /// ```
     pub fn iter() -> impl Iterator<Item = Self> {
         // N.B.: This has no compile-time guarantees that it is complete.
@@ -66,6 +69,7 @@ impl ErrorType {
             ErrorType::InvalidLabel,
             ErrorType::InvalidState,
             ErrorType::InvalidOverflow,
+            ErrorType::ClockSkew,
         ]
         .iter()
         .copied()
@@ -81,6 +85,7 @@ impl TryFrom<i32> for ErrorType {
             1 => Ok(ErrorType::InvalidLabel),
             2 => Ok(ErrorType::InvalidState),
             3 => Ok(ErrorType::InvalidOverflow),
+            4 => Ok(ErrorType::ClockSkew),
             e => Err(ErrorKind::Lifetime(e).into()),
         }
     }
@@ -168,6 +173,104 @@ pub fn test_get_num_recorded_errors(
     })
 }
 
+/// Gets the number of recorded errors for every metric that recorded any.
+///
+/// Errors are taken from the "metrics" store, which every error is recorded into,
+/// so this covers all errors since the last "metrics" ping.
+///
+/// *Notes: This is a **test-only** API, but we need to expose it to be used in integration tests.
+///
+/// # Arguments
+///
+/// * `glean` - The Glean object holding the database
+///
+/// # Returns
+///
+/// A `(metric identifier, error type, number of errors)` tuple for every error,
+/// sorted by metric identifier.
+pub fn test_get_all_recorded_errors(glean: &Glean) -> Vec<(String, ErrorType, i32)> {
+    test_get_recorded_errors_for_ping(glean, "metrics")
+}
+
+/// Gets the number of recorded errors destined for a ping, for every metric that recorded any.
+///
+/// *Notes: This is a **test-only** API, but we need to expose it to be used in integration tests.
+///
+/// # Arguments
+///
+/// * `glean` - The Glean object holding the database
+/// * `ping_name` - The name of the ping, i.e. of its store
+///
+/// # Returns
+///
+/// A `(metric identifier, error type, number of errors)` tuple for every error,
+/// sorted by metric identifier.
+pub fn test_get_recorded_errors_for_ping(
+    glean: &Glean,
+    ping_name: &str,
+) -> Vec<(String, ErrorType, i32)> {
+    let mut errors = Vec::new();
+    let mut snapshotter = |error_id: &[u8], metric: &Metric| {
+        let error_id = String::from_utf8_lossy(error_id);
+        let (error, identifier) = match error_id.split_once('/') {
+            Some(parts) => parts,
+            None => return,
+        };
+        let error = match ErrorType::iter().find(|e| e.as_str() == error) {
+            Some(error) => error,
+            None => return,
+        };
+        if let Metric::Counter(count) = metric {
+            errors.push((identifier.to_string(), error, *count));
+        }
+    };
+
+    if let Some(storage) = glean.storage_opt() {
+        storage.iter_store_from(
+            Lifetime::Ping,
+            ping_name,
+            Some("glean.error."),
+            &mut snapshotter,
+        );
+    }
+
+    errors.sort_by(|(a, a_error, _), (b, b_error, _)| {
+        a.cmp(b).then((*a_error as i32).cmp(&(*b_error as i32)))
+    });
+    errors
+}
+
+/// Clears the recorded errors destined for a ping.
+///
+/// The same errors recorded into other pings, e.g. the "metrics" ping, are kept.
+///
+/// *Notes: This is a **test-only** API, but we need to expose it to be used in integration tests.
+///
+/// # Arguments
+///
+/// * `glean` - The Glean object holding the database
+/// * `ping_name` - The name of the ping, i.e. of its store
+pub fn test_clear_recorded_errors(glean: &Glean, ping_name: &str) {
+    let storage = match glean.storage_opt() {
+        Some(storage) => storage,
+        None => return,
+    };
+
+    let mut error_ids = Vec::new();
+    storage.iter_store_from(
+        Lifetime::Ping,
+        ping_name,
+        Some("glean.error."),
+        |error_id, _| error_ids.push(String::from_utf8_lossy(error_id).into_owned()),
+    );
+    for error_id in error_ids {
+        let metric_id = format!("glean.error.{}", error_id);
+        if let Err(e) = storage.remove_single_metric(Lifetime::Ping, ping_name, &metric_id) {
+            log::warn!("Failed to clear error {}: {:?}", metric_id, e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
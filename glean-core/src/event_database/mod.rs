@@ -15,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 
 use crate::coverage::record_coverage;
+use crate::storage::DEBUG_STORE;
 use crate::CommonMetricData;
 use crate::Glean;
 use crate::Result;
@@ -177,16 +178,20 @@ impl EventDatabase {
     ///   monotonically increasing timer (this value is obtained on the
     ///   platform-specific side).
     /// * `extra` - Extra data values, mapping strings to strings.
+    ///
+    /// # Returns
+    ///
+    /// Whether a ping was submitted because one of the stores reached its capacity.
     pub fn record(
         &self,
         glean: &Glean,
         meta: &CommonMetricData,
         timestamp: u64,
         extra: Option<HashMap<String, String>>,
-    ) {
+    ) -> bool {
         // If upload is disabled we don't want to record.
         if !glean.is_upload_enabled() {
-            return;
+            return false;
         }
 
         // Create RecordedEvent object, and its JSON form for serialization
@@ -204,20 +209,34 @@ impl EventDatabase {
         {
             let mut db = self.event_stores.write().unwrap(); // safe unwrap, only error case is poisoning
             for store_name in meta.send_in_pings.iter() {
-                let store = db.entry(store_name.to_string()).or_insert_with(Vec::new);
+                let store = db.entry(store_name.to_string()).or_default();
                 store.push(event.clone());
                 self.write_event_to_disk(store_name, &event_json);
-                if store.len() == glean.get_max_events() {
+                if store.len() >= glean.get_max_events() {
                     stores_to_submit.push(store_name);
                 }
             }
+
+            // The debug store is only kept in memory and never submitted,
+            // it holds the most recent events instead.
+            if glean.mirror_to_debug_store()
+                && !meta.send_in_pings.iter().any(|name| name == DEBUG_STORE)
+            {
+                let store = db.entry(DEBUG_STORE.to_string()).or_default();
+                store.push(event);
+                if store.len() > glean.get_max_events() {
+                    store.remove(0);
+                }
+            }
         }
 
         // If any of the event stores reached maximum size, submit the pings
         // containing those events immediately.
+        let mut submitted = false;
         for store_name in stores_to_submit {
-            glean.submit_ping_by_name(store_name, Some("max_capacity"));
+            submitted |= glean.submit_ping_by_name(store_name, Some("max_capacity"));
         }
+        submitted
     }
 
     /// Writes an event to a single store on disk.
@@ -249,8 +268,10 @@ impl EventDatabase {
     ///
     /// A array of events, JSON encoded, if any. Otherwise `None`.
     pub fn snapshot_as_json(&self, store_name: &str, clear_store: bool) -> Option<JsonValue> {
+        // Hold the lock until the store is cleared,
+        // so no event recorded in between is lost.
+        let mut db = self.event_stores.write().unwrap(); // safe unwrap, only error case is poisoning
         let result = {
-            let mut db = self.event_stores.write().unwrap(); // safe unwrap, only error case is poisoning
             db.get_mut(&store_name.to_string()).and_then(|store| {
                 if !store.is_empty() {
                     // Timestamps may have been recorded out-of-order, so sort the events
@@ -274,10 +295,7 @@ impl EventDatabase {
         };
 
         if clear_store {
-            self.event_stores
-                .write()
-                .unwrap() // safe unwrap, only error case is poisoning
-                .remove(&store_name.to_string());
+            db.remove(&store_name.to_string());
 
             let _lock = self.file_lock.write().unwrap(); // safe unwrap, only error case is poisoning
             if let Err(err) = fs::remove_file(self.path.join(store_name)) {
@@ -293,6 +311,65 @@ impl EventDatabase {
         result
     }
 
+    /// Removes all events recorded for the given event metric, both in memory and on-disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `meta` - The metadata about the event metric. Used to get the category,
+    ///   name and stores for the metric.
+    pub fn remove_metric(&self, meta: &CommonMetricData) {
+        self.remove_events(&meta.send_in_pings, &meta.category, &meta.name);
+    }
+
+    /// Removes all events recorded for the given event metric from every store,
+    /// both in memory and on-disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category of the event metric.
+    /// * `name` - The name of the event metric.
+    pub fn remove_metric_everywhere(&self, category: &str, name: &str) {
+        // safe unwrap, only error case is poisoning
+        let store_names: Vec<String> = self.event_stores.read().unwrap().keys().cloned().collect();
+        self.remove_events(&store_names, category, name);
+    }
+
+    fn remove_events(&self, store_names: &[String], category: &str, name: &str) {
+        let mut db = self.event_stores.write().unwrap(); // safe unwrap, only error case is poisoning
+        let _lock = self.file_lock.write().unwrap(); // safe unwrap, only error case is poisoning
+
+        for store_name in store_names {
+            let store = match db.get_mut(store_name) {
+                Some(store) => store,
+                None => continue,
+            };
+            store.retain(|event| event.category != category || event.name != name);
+
+            // The debug store is only kept in memory.
+            if store_name == DEBUG_STORE {
+                if store.is_empty() {
+                    db.remove(store_name);
+                }
+                continue;
+            }
+
+            let path = self.path.join(store_name);
+            let result = if store.is_empty() {
+                db.remove(store_name);
+                fs::remove_file(path)
+            } else {
+                let contents: String = store
+                    .iter()
+                    .map(|event| serde_json::to_string(event).unwrap() + "\n") // safe unwrap, event can always be serialized
+                    .collect();
+                fs::write(path, contents)
+            };
+            if let Err(err) = result {
+                log::warn!("IO error rewriting event store '{}': {}", store_name, err);
+            }
+        }
+    }
+
     /// Clears all stored events, both in memory and on-disk.
     pub fn clear_all(&self) -> Result<()> {
         // safe unwrap, only error case is poisoning
@@ -0,0 +1,203 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Bucketing, Histogram};
+
+/// The maximum number of buckets a bucketing function may split a range into.
+pub const MAX_BUCKETING_FN_BUCKETS: usize = 1000;
+
+/// Computes the buckets `bucketer` splits the range from `min` to `max` into.
+///
+/// `bucketer` maps a sample to the minimum of the bucket it falls into.
+/// It has to be deterministic and monotonic, and map every bucket minimum to itself.
+/// The returned bucket minimums are sorted and always include an underflow bucket (`0`).
+///
+/// Each bucket is found by a binary search, so `bucketer` is called
+/// `O(buckets * log(max - min))` times, but not for every sample in the range.
+pub(crate) fn buckets_from_fn(
+    min: u64,
+    max: u64,
+    bucketer: &dyn Fn(u64) -> u64,
+) -> Result<Vec<u64>, String> {
+    if min > max {
+        return Err(format!("Empty range from {} to {}", min, max));
+    }
+
+    let bucket_minimum = |sample: u64| {
+        let minimum = bucketer(sample);
+        if bucketer(sample) != minimum {
+            return Err(format!("Bucketing {} isn't deterministic", sample));
+        }
+        if minimum > sample {
+            return Err(format!(
+                "Sample {} is bucketed above itself, at {}",
+                sample, minimum
+            ));
+        }
+        Ok(minimum)
+    };
+
+    let mut current = bucket_minimum(min)?;
+    if bucket_minimum(current)? != current {
+        return Err(format!(
+            "Bucket minimum {} isn't bucketed into itself",
+            current
+        ));
+    }
+    let mut ranges = vec![0];
+    if current > 0 {
+        ranges.push(current);
+    }
+
+    loop {
+        let last = bucket_minimum(max)?;
+        if last < current {
+            return Err(format!("Bucketing isn't monotonic below {}", max));
+        }
+        if last == current {
+            break;
+        }
+        if ranges.len() >= MAX_BUCKETING_FN_BUCKETS {
+            return Err(format!(
+                "More than {} buckets up to {}",
+                MAX_BUCKETING_FN_BUCKETS, max
+            ));
+        }
+
+        // Find the first sample in a bucket above `current`.
+        // `hi` is always in one.
+        let (mut lo, mut hi) = (current + 1, max);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let minimum = bucket_minimum(mid)?;
+            if minimum < current {
+                return Err(format!("Bucketing isn't monotonic at {}", mid));
+            }
+            if minimum > current {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+
+        let next = bucket_minimum(hi)?;
+        if next != hi {
+            return Err(format!(
+                "Bucket minimum {} isn't bucketed into itself",
+                next
+            ));
+        }
+        ranges.push(next);
+        current = next;
+    }
+
+    Ok(ranges)
+}
+
+/// A bucketing algorithm with explicitly given buckets,
+/// e.g. computed from a custom bucketing function by [`buckets_from_fn`].
+///
+/// Unlike the other bucketing algorithms, the buckets are persisted with the histogram,
+/// as the function computing them isn't.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrecomputedBuckets {
+    bucket_ranges: Vec<u64>,
+}
+
+impl Bucketing for PrecomputedBuckets {
+    /// Get the bucket for the sample.
+    ///
+    /// This uses a binary search to locate the index `i` of the bucket such that:
+    /// bucket[i] <= sample < bucket[i+1]
+    fn sample_to_bucket_minimum(&self, sample: u64) -> u64 {
+        let limit = match self.bucket_ranges.binary_search(&sample) {
+            // Found an exact match to fit it in
+            Ok(i) => i,
+            // Sorted it fits after the bucket's limit, therefore it fits into the previous bucket
+            Err(i) => i - 1,
+        };
+
+        self.bucket_ranges[limit]
+    }
+
+    fn ranges(&self) -> &[u64] {
+        &self.bucket_ranges
+    }
+}
+
+impl Histogram<PrecomputedBuckets> {
+    /// Creates a histogram with the given bucket minimums.
+    ///
+    /// The minimums must be sorted and start with `0`.
+    pub fn bucketed(bucket_ranges: Vec<u64>) -> Histogram<PrecomputedBuckets> {
+        debug_assert_eq!(Some(&0), bucket_ranges.first());
+        Histogram {
+            values: HashMap::new(),
+            count: 0,
+            sum: 0,
+            bucketing: PrecomputedBuckets { bucket_ranges },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buckets_are_computed_from_the_function() {
+        // Buckets of 10, from 10 to 50.
+        let ranges = buckets_from_fn(10, 50, &|sample| sample / 10 * 10).unwrap();
+        assert_eq!(vec![0, 10, 20, 30, 40, 50], ranges);
+
+        // Powers of 2 up to 100.
+        let ranges = buckets_from_fn(1, 100, &|sample| 1 << (63 - sample.leading_zeros())).unwrap();
+        assert_eq!(vec![0, 1, 2, 4, 8, 16, 32, 64], ranges);
+    }
+
+    #[test]
+    fn samples_use_the_computed_buckets() {
+        let ranges = buckets_from_fn(10, 50, &|sample| sample / 10 * 10).unwrap();
+        let mut hist = Histogram::bucketed(ranges);
+
+        hist.accumulate(5);
+        hist.accumulate(15);
+        hist.accumulate(19);
+        hist.accumulate(500);
+
+        assert_eq!(1, hist.values[&0]);
+        assert_eq!(2, hist.values[&10]);
+        assert_eq!(1, hist.values[&50]);
+        assert_eq!(4, hist.count());
+    }
+
+    #[test]
+    fn invalid_functions_are_rejected() {
+        // Not monotonic.
+        let non_monotonic = |sample| match sample {
+            50..=59 => 20,
+            _ => sample / 10 * 10,
+        };
+        assert!(buckets_from_fn(0, 100, &non_monotonic).is_err());
+        // Buckets above the sample.
+        assert!(buckets_from_fn(0, 100, &|sample| sample + 1).is_err());
+        // Bucket minimums not bucketed into themselves.
+        assert!(buckets_from_fn(0, 100, &|sample| sample.saturating_sub(1) / 10 * 10).is_err());
+        // Not deterministic.
+        let calls = std::cell::Cell::new(0);
+        assert!(buckets_from_fn(0, 100, &|sample| {
+            calls.set(calls.get() + 1);
+            sample.saturating_sub(calls.get() % 2)
+        })
+        .is_err());
+        // Too many buckets.
+        assert!(buckets_from_fn(0, u64::MAX, &|sample| sample).is_err());
+        // Empty range.
+        assert!(buckets_from_fn(10, 1, &|sample| sample).is_err());
+    }
+}
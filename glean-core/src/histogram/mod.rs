@@ -11,10 +11,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, ErrorKind};
 
+pub(crate) use bucketed::buckets_from_fn;
+pub use bucketed::{PrecomputedBuckets, MAX_BUCKETING_FN_BUCKETS};
 pub use exponential::PrecomputedExponential;
 pub use functional::Functional;
 pub use linear::PrecomputedLinear;
 
+mod bucketed;
 mod exponential;
 mod functional;
 mod linear;
@@ -99,6 +102,29 @@ impl<B: Bucketing> Histogram<B> {
         self.count += 1;
     }
 
+    /// Merges the highest buckets so that at most `max_buckets` buckets are retained.
+    ///
+    /// The samples of the merged buckets are attributed to the lowest of them,
+    /// the count and sum stay unchanged.
+    ///
+    /// Returns the number of buckets merged away.
+    pub fn merge_tail(&mut self, max_buckets: usize) -> usize {
+        let max_buckets = max_buckets.max(1);
+        if self.values.len() <= max_buckets {
+            return 0;
+        }
+
+        let mut buckets: Vec<u64> = self.values.keys().copied().collect();
+        buckets.sort_unstable();
+        let tail: u64 = buckets[max_buckets..]
+            .iter()
+            .filter_map(|bucket| self.values.remove(bucket))
+            .sum();
+        *self.values.entry(buckets[max_buckets - 1]).or_insert(0) += tail;
+
+        buckets.len() - max_buckets
+    }
+
     /// Gets the total sum of values recorded in this histogram.
     pub fn sum(&self) -> u64 {
         self.sum
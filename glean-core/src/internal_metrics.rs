@@ -10,6 +10,10 @@ pub struct CoreMetrics {
     pub first_run_date: DatetimeMetric,
     pub first_run_hour: DatetimeMetric,
     pub os: StringMetric,
+    /// The system uptime in seconds when Glean was initialized.
+    pub os_uptime: QuantityMetric,
+    /// The number of sessions, i.e. how many times Glean was initialized.
+    pub session_count: CounterMetric,
 }
 
 #[derive(Debug)]
@@ -20,6 +24,32 @@ pub struct AdditionalMetrics {
 
     /// A count of the pings submitted, by ping type.
     pub pings_submitted: LabeledMetric<CounterMetric>,
+
+    /// The number of times assembling a ping took longer than the configured threshold.
+    pub slow_assembly: CounterMetric,
+
+    /// How long recording a metric took on the dispatcher thread,
+    /// if recording profiling is enabled.
+    pub record_latency: TimingDistributionMetric,
+
+    /// The number of distribution buckets merged
+    /// to stay within the configured maximum number of buckets.
+    pub buckets_merged: CounterMetric,
+
+    /// The pings not assembled anymore after repeatedly failing schema validation,
+    /// by ping type.
+    pub schema_circuit_open: LabeledMetric<CounterMetric>,
+
+    /// The size of every assembled ping body, in bytes.
+    pub assembled_ping_size: MemoryDistributionMetric,
+
+    /// The number of times writing to the metrics database or the pending pings directory
+    /// failed, e.g. because the disk is full.
+    pub io_failure: CounterMetric,
+
+    /// The number of `send_if_empty` pings submitted without any metrics or events
+    /// besides Glean's own, by ping type.
+    pub empty_submissions: LabeledMetric<CounterMetric>,
 }
 
 impl CoreMetrics {
@@ -32,6 +62,10 @@ impl CoreMetrics {
                 lifetime: Lifetime::User,
                 disabled: false,
                 dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
             }),
 
             first_run_date: DatetimeMetric::new(
@@ -42,6 +76,10 @@ impl CoreMetrics {
                     lifetime: Lifetime::User,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 TimeUnit::Day,
             ),
@@ -54,6 +92,10 @@ impl CoreMetrics {
                     lifetime: Lifetime::User,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 TimeUnit::Hour,
             ),
@@ -65,6 +107,36 @@ impl CoreMetrics {
                 lifetime: Lifetime::Application,
                 disabled: false,
                 dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            os_uptime: QuantityMetric::new(CommonMetricData {
+                name: "os_uptime".into(),
+                category: "glean.baseline".into(),
+                send_in_pings: vec!["baseline".into()],
+                lifetime: Lifetime::Application,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            session_count: CounterMetric::new(CommonMetricData {
+                name: "session_count".into(),
+                category: "".into(),
+                send_in_pings: vec!["glean_internal_info".into()],
+                lifetime: Lifetime::User,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
             }),
         }
     }
@@ -80,6 +152,10 @@ impl AdditionalMetrics {
                 lifetime: Lifetime::Ping,
                 disabled: false,
                 dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
             }),
 
             pings_submitted: LabeledMetric::<CounterMetric>::new(
@@ -90,6 +166,113 @@ impl AdditionalMetrics {
                     lifetime: Lifetime::Ping,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                None,
+            ),
+
+            slow_assembly: CounterMetric::new(CommonMetricData {
+                name: "slow".into(),
+                category: "glean.assembly".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            record_latency: TimingDistributionMetric::new(
+                CommonMetricData {
+                    name: "record_latency".into(),
+                    category: "glean.sdk".into(),
+                    send_in_pings: vec!["metrics".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                TimeUnit::Nanosecond,
+            ),
+
+            buckets_merged: CounterMetric::new(CommonMetricData {
+                name: "buckets_merged".into(),
+                category: "glean.distribution".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            schema_circuit_open: LabeledMetric::<CounterMetric>::new(
+                CommonMetricData {
+                    name: "schema_circuit_open".into(),
+                    category: "glean.error".into(),
+                    send_in_pings: vec!["metrics".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                None,
+            ),
+
+            assembled_ping_size: MemoryDistributionMetric::new(
+                CommonMetricData {
+                    name: "assembled_size".into(),
+                    category: "glean.ping".into(),
+                    send_in_pings: vec!["metrics".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                MemoryUnit::Byte,
+            ),
+
+            io_failure: CounterMetric::new(CommonMetricData {
+                name: "io_failure".into(),
+                category: "glean.error".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            empty_submissions: LabeledMetric::<CounterMetric>::new(
+                CommonMetricData {
+                    name: "empty_submissions".into(),
+                    category: "glean.ping".into(),
+                    send_in_pings: vec!["metrics".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 None,
             ),
@@ -97,6 +280,92 @@ impl AdditionalMetrics {
     }
 }
 
+#[derive(Debug)]
+pub struct CrashMetrics {
+    /// When the crash happened.
+    pub time: DatetimeMetric,
+    /// What caused the crash.
+    pub cause: StringMetric,
+    /// Additional information provided by the application.
+    pub annotations: LabeledMetric<StringMetric>,
+}
+
+impl CrashMetrics {
+    pub fn new() -> CrashMetrics {
+        CrashMetrics {
+            time: DatetimeMetric::new(
+                CommonMetricData {
+                    name: "time".into(),
+                    category: "glean.crash".into(),
+                    send_in_pings: vec!["crash".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                TimeUnit::Millisecond,
+            ),
+
+            cause: StringMetric::new(CommonMetricData {
+                name: "cause".into(),
+                category: "glean.crash".into(),
+                send_in_pings: vec!["crash".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            annotations: LabeledMetric::<StringMetric>::new(
+                CommonMetricData {
+                    name: "annotations".into(),
+                    category: "glean.crash".into(),
+                    send_in_pings: vec!["crash".into()],
+                    lifetime: Lifetime::Ping,
+                    disabled: false,
+                    dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
+                },
+                None,
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DeletionRequestMetrics {
+    /// Why upload was disabled, if the application said so.
+    pub opt_out_reason: StringMetric,
+}
+
+impl DeletionRequestMetrics {
+    pub fn new() -> DeletionRequestMetrics {
+        DeletionRequestMetrics {
+            opt_out_reason: StringMetric::new(CommonMetricData {
+                name: "opt_out_reason".into(),
+                category: "glean.deletion_request".into(),
+                send_in_pings: vec!["deletion-request".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct UploadMetrics {
     pub ping_upload_failure: LabeledMetric<CounterMetric>,
@@ -104,6 +373,10 @@ pub struct UploadMetrics {
     pub pending_pings_directory_size: MemoryDistributionMetric,
     pub deleted_pings_after_quota_hit: CounterMetric,
     pub pending_pings: CounterMetric,
+    pub circuit_open: CounterMetric,
+    pub pending_pings_evicted: CounterMetric,
+    pub deduplicated: CounterMetric,
+    pub expired: CounterMetric,
 }
 
 impl UploadMetrics {
@@ -117,6 +390,10 @@ impl UploadMetrics {
                     lifetime: Lifetime::Ping,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 Some(vec![
                     "status_code_4xx".into(),
@@ -135,6 +412,10 @@ impl UploadMetrics {
                     lifetime: Lifetime::Ping,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 MemoryUnit::Kilobyte,
             ),
@@ -147,6 +428,10 @@ impl UploadMetrics {
                     lifetime: Lifetime::Ping,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 MemoryUnit::Kilobyte,
             ),
@@ -158,6 +443,10 @@ impl UploadMetrics {
                 lifetime: Lifetime::Ping,
                 disabled: false,
                 dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
             }),
 
             pending_pings: CounterMetric::new(CommonMetricData {
@@ -167,6 +456,62 @@ impl UploadMetrics {
                 lifetime: Lifetime::Ping,
                 disabled: false,
                 dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            circuit_open: CounterMetric::new(CommonMetricData {
+                name: "circuit_open".into(),
+                category: "glean.upload".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            pending_pings_evicted: CounterMetric::new(CommonMetricData {
+                name: "pending_pings_evicted".into(),
+                category: "glean.upload".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            deduplicated: CounterMetric::new(CommonMetricData {
+                name: "deduplicated".into(),
+                category: "glean.upload".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
+
+            expired: CounterMetric::new(CommonMetricData {
+                name: "expired".into(),
+                category: "glean.upload".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
             }),
         }
     }
@@ -175,6 +520,9 @@ impl UploadMetrics {
 #[derive(Debug)]
 pub struct DatabaseMetrics {
     pub size: MemoryDistributionMetric,
+
+    /// The number of runs that ended without persisting their delayed ping-lifetime data.
+    pub ping_lifetime_data_lost: CounterMetric,
 }
 
 impl DatabaseMetrics {
@@ -188,9 +536,26 @@ impl DatabaseMetrics {
                     lifetime: Lifetime::Ping,
                     disabled: false,
                     dynamic_label: None,
+                    min_record_interval_ms: None,
+                    description: None,
+                    data_sensitivity: None,
+                    only_once_per_session: false,
                 },
                 MemoryUnit::Byte,
             ),
+
+            ping_lifetime_data_lost: CounterMetric::new(CommonMetricData {
+                name: "ping_lifetime_data_lost".into(),
+                category: "glean.database".into(),
+                send_in_pings: vec!["metrics".into()],
+                lifetime: Lifetime::Ping,
+                disabled: false,
+                dynamic_label: None,
+                min_record_interval_ms: None,
+                description: None,
+                data_sensitivity: None,
+                only_once_per_session: false,
+            }),
         }
     }
 }
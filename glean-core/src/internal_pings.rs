@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::metrics::PingType;
+use crate::metrics::{PingPriority, PingType};
 
 /// Glean-provided pings, all enabled by default.
 ///
@@ -16,6 +16,7 @@ pub struct InternalPings {
     pub metrics: PingType,
     pub events: PingType,
     pub deletion_request: PingType,
+    pub crash: PingType,
 }
 
 impl InternalPings {
@@ -53,12 +54,14 @@ impl InternalPings {
                     "max_capacity".to_string(),
                 ],
             ),
-            deletion_request: PingType::new(
+            deletion_request: PingType::new_with_priority(
                 "deletion-request",
                 true,
                 true,
                 vec!["at_init".to_string(), "set_upload_enabled".to_string()],
+                PingPriority::High,
             ),
+            crash: PingType::new("crash", true, true, vec![]),
         }
     }
 }
@@ -0,0 +1,207 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Recordings made in auxiliary processes, stored by the main process.
+//!
+//! Only one process may write to the database.
+//! Other processes of a multi-process application record into a [`RecordingBatch`] instead,
+//! serialize it and hand it to the main process,
+//! which applies it to its metrics through [`ingest_recordings`].
+//! How the serialized batch gets there is up to the application.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorKind, Result};
+use crate::metrics::{
+    BooleanMetric, CounterMetric, MetricType, QuantityMetric, StringListMetric, StringMetric,
+};
+use crate::{CommonMetricData, Glean};
+
+/// The version of the serialization format, the first byte of a serialized batch.
+///
+/// Bump this when changing [`Recording`] in an incompatible way.
+const FORMAT_VERSION: u8 = 1;
+
+/// A single recording, with the metadata of the metric recorded into.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Recording {
+    CounterAdd(CommonMetricData, i32),
+    BooleanSet(CommonMetricData, bool),
+    StringSet(CommonMetricData, String),
+    StringListAdd(CommonMetricData, String),
+    QuantitySet(CommonMetricData, i64),
+}
+
+/// Recordings made in an auxiliary process, in order.
+///
+/// Serialize it with [`RecordingBatch::serialize`] and ingest it in the main process.
+/// Recordings are validated when ingested, so errors are reported by the main process.
+///
+/// Labeled metrics are supported through the metric of a label,
+/// e.g. `batch.add_counter(&labeled.get("label"), 1)`.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingBatch {
+    recordings: Vec<Recording>,
+}
+
+impl RecordingBatch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records adding `amount` to a counter.
+    pub fn add_counter(&mut self, metric: &CounterMetric, amount: i32) {
+        self.recordings
+            .push(Recording::CounterAdd(metric.meta().clone(), amount));
+    }
+
+    /// Records setting a boolean.
+    pub fn set_boolean(&mut self, metric: &BooleanMetric, value: bool) {
+        self.recordings
+            .push(Recording::BooleanSet(metric.meta().clone(), value));
+    }
+
+    /// Records setting a string.
+    pub fn set_string(&mut self, metric: &StringMetric, value: impl Into<String>) {
+        self.recordings
+            .push(Recording::StringSet(metric.meta().clone(), value.into()));
+    }
+
+    /// Records adding a string to a string list.
+    pub fn add_to_string_list(&mut self, metric: &StringListMetric, value: impl Into<String>) {
+        self.recordings.push(Recording::StringListAdd(
+            metric.meta().clone(),
+            value.into(),
+        ));
+    }
+
+    /// Records setting a quantity.
+    pub fn set_quantity(&mut self, metric: &QuantityMetric, value: i64) {
+        self.recordings
+            .push(Recording::QuantitySet(metric.meta().clone(), value));
+    }
+
+    /// The number of recordings in this batch.
+    pub fn len(&self) -> usize {
+        self.recordings.len()
+    }
+
+    /// Whether this batch has no recordings.
+    pub fn is_empty(&self) -> bool {
+        self.recordings.is_empty()
+    }
+
+    /// Serializes the recordings, to be passed to [`ingest_recordings`] in the main process.
+    ///
+    /// The main process has to run the same version of Glean.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut blob = vec![FORMAT_VERSION];
+        bincode::serialize_into(&mut blob, &self.recordings)
+            .expect("IMPOSSIBLE: Serializing recordings failed");
+        blob
+    }
+
+    /// Decodes a batch serialized by [`RecordingBatch::serialize`].
+    fn deserialize(blob: &[u8]) -> Result<Self> {
+        match blob.split_first() {
+            Some((&FORMAT_VERSION, recordings)) => {
+                let recordings = bincode::deserialize(recordings)
+                    .map_err(|e| ErrorKind::InvalidRecordings(e.to_string()))?;
+                Ok(Self { recordings })
+            }
+            Some((version, _)) => Err(ErrorKind::InvalidRecordings(format!(
+                "unsupported format version {}",
+                version
+            ))
+            .into()),
+            None => Err(ErrorKind::InvalidRecordings("empty blob".into()).into()),
+        }
+    }
+
+    /// Applies the recordings to the metrics, in order.
+    fn apply(self, glean: &Glean) {
+        for recording in self.recordings {
+            match recording {
+                Recording::CounterAdd(meta, amount) => {
+                    CounterMetric::new(meta).add_sync(glean, amount)
+                }
+                Recording::BooleanSet(meta, value) => {
+                    BooleanMetric::new(meta).set_sync(glean, value)
+                }
+                Recording::StringSet(meta, value) => StringMetric::new(meta).set_sync(glean, value),
+                Recording::StringListAdd(meta, value) => {
+                    StringListMetric::new(meta).add_sync(glean, value)
+                }
+                Recording::QuantitySet(meta, value) => {
+                    QuantityMetric::new(meta).set_sync(glean, value)
+                }
+            }
+        }
+    }
+}
+
+/// Stores the recordings serialized by an auxiliary process.
+///
+/// # Arguments
+///
+/// * `glean` - The Glean instance of the main process.
+/// * `blob` - The recordings, as serialized by [`RecordingBatch::serialize`].
+///
+/// # Returns
+///
+/// The number of recordings applied,
+/// or an error if the blob couldn't be decoded, in which case nothing is applied.
+pub fn ingest_recordings(glean: &Glean, blob: &[u8]) -> Result<usize> {
+    let batch = RecordingBatch::deserialize(blob)?;
+    let count = batch.len();
+    batch.apply(glean);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::new_glean;
+    use crate::Lifetime;
+
+    fn meta(name: &str) -> CommonMetricData {
+        CommonMetricData {
+            name: name.into(),
+            category: "child".into(),
+            send_in_pings: vec!["store1".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn batches_are_applied_in_order() {
+        let (glean, _t) = new_glean(None);
+        let counter = CounterMetric::new(meta("counter"));
+        let string = StringMetric::new(meta("string"));
+
+        let mut batch = RecordingBatch::new();
+        batch.add_counter(&counter, 2);
+        batch.set_string(&string, "first");
+        batch.add_counter(&counter, 3);
+        batch.set_string(&string, "last");
+
+        assert_eq!(4, ingest_recordings(&glean, &batch.serialize()).unwrap());
+        assert_eq!(Some(5), counter.get_value(&glean, "store1"));
+        assert_eq!(Some("last".into()), string.get_value(&glean, "store1"));
+    }
+
+    #[test]
+    fn invalid_blobs_are_rejected() {
+        let (glean, _t) = new_glean(None);
+
+        assert!(ingest_recordings(&glean, &[]).is_err());
+        assert!(ingest_recordings(&glean, &[FORMAT_VERSION, 0xff]).is_err());
+
+        let mut blob = RecordingBatch::new().serialize();
+        blob[0] = FORMAT_VERSION + 1;
+        assert!(ingest_recordings(&glean, &blob).is_err());
+    }
+}
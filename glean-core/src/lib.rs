@@ -16,18 +16,25 @@
 
 use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossbeam_channel::RecvTimeoutError;
 
 use once_cell::sync::{Lazy, OnceCell};
+use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
 mod common_metric_data;
 mod core;
 mod core_metrics;
 mod coverage;
+mod crash;
 mod database;
 mod debug;
+mod deletion_request;
 mod dispatcher;
 mod error;
 mod error_recording;
@@ -36,7 +43,9 @@ mod glean_metrics;
 mod histogram;
 mod internal_metrics;
 mod internal_pings;
+mod ipc;
 pub mod metrics;
+mod observer;
 pub mod ping;
 mod scheduler;
 pub mod storage;
@@ -48,27 +57,43 @@ mod util;
 #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
 mod fd_logger;
 
-pub use crate::common_metric_data::{CommonMetricData, Lifetime};
+pub use crate::common_metric_data::{
+    CommonMetricData, DataSensitivity, Lifetime, MetricDescriptor,
+};
 pub use crate::core::Glean;
 pub use crate::core_metrics::ClientInfoMetrics;
+pub use crate::crash::CrashCause;
+pub use crate::database::{StorageBackend, StorageChange, UnregisteredPingBehavior};
+pub use crate::deletion_request::UploadDisabledReason;
 pub use crate::error::{Error, ErrorKind, Result};
-pub use crate::error_recording::{test_get_num_recorded_errors, ErrorType};
-pub use crate::histogram::HistogramType;
+pub use crate::error_recording::{
+    test_clear_recorded_errors, test_get_all_recorded_errors, test_get_num_recorded_errors,
+    test_get_recorded_errors_for_ping, ErrorType,
+};
+pub use crate::histogram::{HistogramType, MAX_BUCKETING_FN_BUCKETS};
+pub use crate::ipc::{ingest_recordings, RecordingBatch};
 pub use crate::metrics::labeled::{
-    AllowLabeled, LabeledBoolean, LabeledCounter, LabeledMetric, LabeledString,
+    AllowLabeled, LabeledBoolean, LabeledCounter, LabeledCustomDistribution,
+    LabeledMemoryDistribution, LabeledMetric, LabeledString,
 };
 pub use crate::metrics::{
     BooleanMetric, CounterMetric, CustomDistributionMetric, Datetime, DatetimeMetric,
     DenominatorMetric, DistributionData, EventMetric, MemoryDistributionMetric, MemoryUnit,
-    NumeratorMetric, PingType, QuantityMetric, Rate, RateMetric, RecordedEvent, RecordedExperiment,
+    MetricRef, NumeratorMetric, PingPriority, PingType, QuantityMetric, Rate, RateMetric,
+    RecordValue, RecordedEvent, RecordedExperiment, RingBufferMetric, SignedCounterMetric,
     StringListMetric, StringMetric, TextMetric, TimeUnit, TimerId, TimespanMetric,
     TimingDistributionMetric, UrlMetric, UuidMetric,
 };
-pub use crate::upload::{PingRequest, PingUploadTask, UploadResult, UploadTaskAction};
+pub use crate::observer::{GlobalMetricObserver, MetricObserver, ObserverId};
+pub use crate::upload::{PingFormat, PingRequest, PingUploadTask, UploadResult, UploadTaskAction};
 
 const GLEAN_VERSION: &str = env!("CARGO_PKG_VERSION");
 const GLEAN_SCHEMA_VERSION: u32 = 1;
 const DEFAULT_MAX_EVENTS: u32 = 500;
+
+/// The default duration after which assembling a ping is reported as slow, in milliseconds.
+const DEFAULT_SLOW_ASSEMBLY_THRESHOLD_MS: u64 = 1000;
+const DEFAULT_UPLOAD_COOLDOWN_SECS: u64 = 10 * 60;
 static KNOWN_CLIENT_ID: Lazy<Uuid> =
     Lazy::new(|| Uuid::parse_str("c0ffeec0-ffee-c0ff-eec0-ffeec0ffeec0").unwrap());
 
@@ -89,6 +114,17 @@ static PRE_INIT_SOURCE_TAGS: OnceCell<Mutex<Vec<String>>> = OnceCell::new();
 /// Keep track of pings registered before Glean is initialized.
 static PRE_INIT_PING_REGISTRATION: OnceCell<Mutex<Vec<metrics::PingType>>> = OnceCell::new();
 
+/// A callback registered through [`glean_on_initialized`].
+type InitCallback = Box<dyn FnOnce() + Send>;
+
+/// Whether the initialization finished,
+/// and the callbacks waiting for that if it didn't.
+static INIT_CALLBACKS: Lazy<Mutex<(bool, Vec<InitCallback>)>> =
+    Lazy::new(|| Mutex::new((false, Vec::new())));
+
+/// Whether the current initialization took longer than its `init_timeout_ms`.
+static INIT_TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
 /// Global singleton of the handles of the glean.init threads.
 /// For joining. For tests.
 /// (Why a Vec? There might be more than one concurrent call to initialize.)
@@ -115,6 +151,113 @@ pub struct InternalConfiguration {
     pub app_build: String,
     /// Whether Glean should schedule "metrics" pings.
     pub use_core_mps: bool,
+    /// The number of consecutive recoverable upload failures after which uploading is paused.
+    /// `None` disables pausing uploads.
+    pub upload_failure_threshold: Option<u32>,
+    /// For how many seconds uploading is paused once `upload_failure_threshold` is reached.
+    pub upload_cooldown_secs: Option<u64>,
+    /// The time unit the baseline `duration` is reported in.
+    /// Only `Second` (the default) and `Millisecond` are supported.
+    pub baseline_duration_unit: Option<TimeUnit>,
+    /// Whether assembled pings are only logged instead of being stored and uploaded.
+    pub dry_run: bool,
+    /// The locale to report in `client_info`, instead of the one provided by the platform.
+    pub locale_override: Option<String>,
+    /// How many milliseconds assembling a ping may take before it's reported as slow.
+    /// Defaults to 1000.
+    pub slow_assembly_threshold_ms: Option<u64>,
+    /// The maximum number of pending pings. Once exceeded, the oldest ones are evicted.
+    pub max_pending_pings: Option<u64>,
+    /// Whether to measure how long recording metrics takes, in `glean.sdk.record_latency`.
+    pub profile_recording: bool,
+    /// The format to name pending ping files after, e.g. `{ping_name}-{timestamp}-{doc_id}.json`.
+    /// Must contain `{doc_id}`. Defaults to the document id.
+    pub pending_ping_filename_format: Option<String>,
+    /// Whether to also record every metric into the "debug" store, which is never sent.
+    pub mirror_to_debug_store: bool,
+    /// The maximum number of buckets a distribution retains.
+    /// Once exceeded, the highest buckets are merged.
+    pub max_distribution_buckets: Option<u64>,
+    /// For how many milliseconds a submitted ping is remembered
+    /// to skip identical pings of the same type.
+    pub ping_dedup_window_ms: Option<u64>,
+    /// The date to report as `first_run_date` on the first run, instead of today,
+    /// e.g. the install date when migrating from another telemetry system.
+    /// Dates in the future are ignored.
+    pub first_run_date: Option<metrics::Datetime>,
+    /// Whether to record Glean's own `glean.*` metrics into the "metrics" ping.
+    pub enable_internal_metrics: bool,
+    /// Whether to keep active experiments across runs, until they are set inactive.
+    pub persist_experiments: bool,
+    /// How long to wait for initialization to finish, in milliseconds,
+    /// before dropping the tasks queued until then. `None` waits forever.
+    pub init_timeout_ms: Option<u64>,
+    /// The local hour of the day, from 0 to 23, to schedule the "metrics" ping for.
+    /// Defaults to 4.
+    pub metrics_ping_schedule_hour: Option<u32>,
+    /// The `Content-Type` header to send pings with.
+    /// Defaults to `application/json; charset=utf-8`.
+    pub content_type: Option<String>,
+    /// The maximum number of distinct dynamic labels every labeled metric stores per ping.
+    /// Further labels are recorded as `__other__`. `None` only applies the built-in limit of 16.
+    pub max_distinct_labels: Option<u64>,
+    /// The only categories metrics may record in, besides Glean's own.
+    /// `None` allows all categories.
+    pub allowed_metric_categories: Option<Vec<String>>,
+    /// The maximum length of a dynamic label, in bytes.
+    /// Defaults to 61.
+    pub max_label_length: Option<u64>,
+    /// Whether to clamp datetimes outside of a plausible window,
+    /// like those recorded while the system clock is way off.
+    pub clamp_skewed_datetimes: bool,
+    /// What happens to recordings into pings that aren't registered.
+    /// Defaults to recording them right away.
+    pub unregistered_ping_behavior: Option<UnregisteredPingBehavior>,
+    /// How ping bodies are serialized for upload.
+    /// Defaults to JSON.
+    pub ping_format: Option<PingFormat>,
+}
+
+/// Uploads are disabled and all optional values are unset, matching the defaults in `glean.udl`.
+///
+/// The paths and names have no meaningful default and must always be set.
+impl Default for InternalConfiguration {
+    fn default() -> Self {
+        Self {
+            upload_enabled: false,
+            data_path: String::new(),
+            application_id: String::new(),
+            language_binding_name: String::new(),
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            app_build: String::new(),
+            use_core_mps: false,
+            upload_failure_threshold: None,
+            upload_cooldown_secs: None,
+            baseline_duration_unit: None,
+            dry_run: false,
+            locale_override: None,
+            slow_assembly_threshold_ms: None,
+            max_pending_pings: None,
+            profile_recording: false,
+            pending_ping_filename_format: None,
+            mirror_to_debug_store: false,
+            max_distribution_buckets: None,
+            ping_dedup_window_ms: None,
+            first_run_date: None,
+            enable_internal_metrics: true,
+            persist_experiments: false,
+            init_timeout_ms: None,
+            metrics_ping_schedule_hour: None,
+            content_type: None,
+            max_distinct_labels: None,
+            allowed_metric_categories: None,
+            max_label_length: None,
+            clamp_skewed_datetimes: false,
+            unregistered_ping_behavior: None,
+            ping_format: None,
+        }
+    }
 }
 
 /// Launches a new task on the global dispatch queue with a reference to the Glean singleton.
@@ -122,6 +265,32 @@ fn launch_with_glean(callback: impl FnOnce(&Glean) + Send + 'static) {
     dispatcher::launch(|| core::with_glean(callback));
 }
 
+/// Launches a metric recording on the global dispatch queue with a reference to the Glean singleton.
+///
+/// See [`with_recording_profiled`].
+fn launch_recording(callback: impl FnOnce(&Glean) + Send + 'static) {
+    dispatcher::launch(|| core::with_glean(|glean| with_recording_profiled(glean, callback)));
+}
+
+/// Runs a metric recording on the dispatcher thread.
+///
+/// If recording profiling is enabled, the time the recording took
+/// is accumulated into `glean.sdk.record_latency`.
+fn with_recording_profiled<R>(glean: &Glean, task: impl FnOnce(&Glean) -> R) -> R {
+    if !glean.profile_recording() {
+        return task(glean);
+    }
+
+    let start = std::time::Instant::now();
+    let result = task(glean);
+    let elapsed = start.elapsed().as_nanos() as u64;
+    glean
+        .additional_metrics
+        .record_latency
+        .accumulate_raw_samples_nanos_sync(glean, &[elapsed]);
+    result
+}
+
 /// Launches a new task on the global dispatch queue with a mutable reference to the
 /// Glean singleton.
 fn launch_with_glean_mut(callback: impl FnOnce(&mut Glean) + Send + 'static) {
@@ -231,6 +400,13 @@ pub trait OnGleanEvents: Send {
 
     /// Called when upload is disabled and uploads should be stopped
     fn cancel_uploads(&self) -> Result<(), CallbackError>;
+
+    /// Called once initialization flushed the pre-init queue,
+    /// if tasks were dropped because the queue overflowed.
+    ///
+    /// This is not part of the foreign-language callback interface.
+    /// It is called from the init thread and should not block.
+    fn on_queue_overflow(&self, _dropped: usize) {}
 }
 
 /// Initializes Glean.
@@ -246,7 +422,24 @@ pub fn glean_initialize(
     client_info: ClientInfoMetrics,
     callbacks: Box<dyn OnGleanEvents>,
 ) {
-    initialize_inner(cfg, client_info, callbacks);
+    initialize_inner(cfg, client_info, callbacks, None);
+}
+
+/// Initializes Glean, keeping metrics in the given storage backend
+/// instead of the default rkv database.
+///
+/// See [`glean_initialize`] for the other arguments.
+///
+/// # Arguments
+///
+/// * `storage_backend` - the storage to keep metrics in.
+pub fn glean_initialize_with_storage_backend(
+    cfg: InternalConfiguration,
+    client_info: ClientInfoMetrics,
+    callbacks: Box<dyn OnGleanEvents>,
+    storage_backend: Box<dyn StorageBackend>,
+) {
+    initialize_inner(cfg, client_info, callbacks, Some(storage_backend));
 }
 
 /// Creates and initializes a new Glean object for use in a subprocess.
@@ -272,18 +465,43 @@ fn initialize_inner(
     cfg: InternalConfiguration,
     client_info: ClientInfoMetrics,
     callbacks: Box<dyn OnGleanEvents>,
+    storage_backend: Option<Box<dyn StorageBackend>>,
 ) {
     if was_initialize_called() {
         log::error!("Glean should not be initialized multiple times");
         return;
     }
 
+    INIT_TIMED_OUT.store(false, Ordering::SeqCst);
+    let init_done = cfg.init_timeout_ms.map(|timeout| {
+        let (init_done, init_waited) = crossbeam_channel::bounded::<()>(0);
+        std::thread::Builder::new()
+            .name("glean.init_timeout".into())
+            .spawn(move || {
+                // The sender is dropped once initialization finished, or failed.
+                let timeout = Duration::from_millis(timeout);
+                if let Err(RecvTimeoutError::Timeout) = init_waited.recv_timeout(timeout) {
+                    // Bound the memory used while waiting, the tasks can't run anyway.
+                    if let Ok(dropped) = dispatcher::discard_preinit() {
+                        log::error!(
+                            "Glean didn't initialize within {:?}. Dropped {} queued tasks.",
+                            timeout,
+                            dropped
+                        );
+                        INIT_TIMED_OUT.store(true, Ordering::SeqCst);
+                    }
+                }
+            })
+            .expect("Failed to spawn Glean's init timeout thread");
+        init_done
+    });
+
     let init_handle = std::thread::Builder::new()
         .name("glean.init".into())
         .spawn(move || {
             let upload_enabled = cfg.upload_enabled;
 
-            let glean = match Glean::new(cfg) {
+            let glean = match Glean::new_with_storage_backend(cfg, storage_backend) {
                 Ok(glean) => glean,
                 Err(err) => {
                     log::error!("Failed to initialize Glean: {}", err);
@@ -338,6 +556,14 @@ fn initialize_inner(
                 dirty_flag = glean.is_dirty_flag_set();
                 glean.set_dirty_flag(false);
 
+                // The metrics ping scheduler compares it to the version of the previous run.
+                glean.app_display_version = global_state()
+                    .lock()
+                    .unwrap()
+                    .client_info
+                    .app_display_version
+                    .clone();
+
                 // Perform registration of pings that were attempted to be
                 // registered before init.
                 if let Some(tags) = PRE_INIT_PING_REGISTRATION.get() {
@@ -434,13 +660,30 @@ fn initialize_inner(
                         glean_metrics::error::preinit_tasks_overflow
                             .add_sync(glean, task_count as i32);
                     });
+
+                    let state = global_state().lock().unwrap();
+                    state.callbacks.on_queue_overflow(task_count);
                 }
                 Ok(_) => {}
                 Err(err) => log::error!("Unable to flush the preinit queue: {}", err),
             }
+            drop(init_done);
+
+            if INIT_TIMED_OUT.load(Ordering::SeqCst) {
+                core::with_glean(|glean| {
+                    glean_metrics::error::init_timeout.add_sync(glean, 1);
+                });
+            }
 
             let state = global_state().lock().unwrap();
             state.callbacks.on_initialize_finished();
+            drop(state);
+
+            let mut init_callbacks = INIT_CALLBACKS.lock().unwrap();
+            init_callbacks.0 = true;
+            for callback in init_callbacks.1.drain(..) {
+                dispatcher::launch(callback);
+            }
         })
         .expect("Failed to spawn Glean's init thread");
 
@@ -496,7 +739,7 @@ pub fn shutdown() {
 
     // Be sure to call this _after_ draining the dispatcher
     core::with_glean(|glean| {
-        if let Err(e) = glean.persist_ping_lifetime_data() {
+        if let Err(e) = glean.persist_ping_lifetime_data_on_shutdown() {
             log::error!("Can't persist ping lifetime data: {:?}", e);
         }
     });
@@ -535,9 +778,51 @@ fn initialize_core_metrics(glean: &Glean, client_info: &ClientInfoMetrics) {
     if let Some(device_model) = client_info.device_model.as_ref() {
         core_metrics::internal_metrics::device_model.set_sync(glean, device_model);
     }
-    if let Some(locale) = client_info.locale.as_ref() {
+
+    let locale = match glean.locale_override() {
+        Some(locale) if util::is_valid_locale(locale) => Some(locale),
+        Some(locale) => {
+            error_recording::record_error(
+                glean,
+                metrics::MetricType::meta(&*core_metrics::internal_metrics::locale),
+                ErrorType::InvalidValue,
+                format!("Invalid locale override '{}'", locale),
+                None,
+            );
+            client_info.locale.as_deref()
+        }
+        None => client_info.locale.as_deref(),
+    };
+    if let Some(locale) = locale {
         core_metrics::internal_metrics::locale.set_sync(glean, locale);
     }
+
+    match client_info.build_id.as_deref() {
+        Some("") => error_recording::record_error(
+            glean,
+            metrics::MetricType::meta(&*core_metrics::internal_metrics::build_id),
+            ErrorType::InvalidValue,
+            "Empty build id",
+            None,
+        ),
+        Some(build_id) => core_metrics::internal_metrics::build_id.set_sync(glean, build_id),
+        None => {}
+    }
+}
+
+/// Runs `callback` on the dispatcher once the initialization finished,
+/// e.g. to read metrics only then.
+///
+/// If it finished already, `callback` is dispatched right away.
+/// If the initialization fails, `callback` is never run.
+pub fn glean_on_initialized(callback: Box<dyn FnOnce() + Send>) {
+    let mut init_callbacks = INIT_CALLBACKS.lock().unwrap();
+    if init_callbacks.0 {
+        drop(init_callbacks);
+        dispatcher::launch(callback);
+    } else {
+        init_callbacks.1.push(callback);
+    }
 }
 
 /// Checks if [`initialize`] was ever called.
@@ -611,8 +896,33 @@ pub extern "C" fn glean_enable_logging() {
     }
 }
 
+/// Stores the recordings serialized by an auxiliary process.
+///
+/// See [`ingest_recordings`].
+pub fn glean_ingest_recordings(blob: Vec<u8>) {
+    crate::launch_with_glean(move |glean| match ingest_recordings(glean, &blob) {
+        Ok(count) => log::info!("Ingested {} recordings", count),
+        Err(e) => log::error!("Failed to ingest recordings: {}", e),
+    });
+}
+
+/// Pauses or resumes recording metrics.
+///
+/// See [`Glean::set_recording_paused`].
+pub fn glean_set_recording_paused(paused: bool) {
+    crate::launch_with_glean_mut(move |glean| glean.set_recording_paused(paused));
+}
+
 /// Sets whether upload is enabled or not.
 pub fn glean_set_upload_enabled(enabled: bool) {
+    glean_set_upload_enabled_with_reason(enabled, None)
+}
+
+/// Sets whether upload is enabled or not,
+/// reporting why it's disabled in the `deletion-request` ping.
+///
+/// See [`core::Glean::set_upload_enabled_with_reason`].
+pub fn glean_set_upload_enabled_with_reason(enabled: bool, reason: Option<UploadDisabledReason>) {
     if !was_initialize_called() {
         return;
     }
@@ -630,7 +940,7 @@ pub fn glean_set_upload_enabled(enabled: bool) {
             }
         }
 
-        glean.set_upload_enabled(enabled);
+        glean.set_upload_enabled_with_reason(enabled, reason);
 
         if !original_enabled && enabled {
             initialize_core_metrics(glean, &state.client_info);
@@ -666,6 +976,20 @@ pub(crate) fn register_ping_type(ping: &PingType) {
     }
 }
 
+/// Gets the names of all registered pings, sorted alphabetically.
+///
+/// See [`core::Glean::get_registered_ping_names`].
+pub fn glean_get_registered_ping_names() -> Vec<String> {
+    block_on_dispatcher();
+    core::with_glean(|glean| {
+        glean
+            .get_registered_ping_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    })
+}
+
 /// Indicate that an experiment is running.  Glean will then add an
 /// experiment annotation to the environment which is sent with pings. This
 /// infomration is not persisted between runs.
@@ -686,6 +1010,98 @@ pub fn glean_set_experiment_inactive(experiment_id: String) {
     launch_with_glean(|glean| glean.set_experiment_inactive(experiment_id))
 }
 
+/// Records a JSON value into a metric, according to the metric's type.
+///
+/// See [`MetricRef::record_json_sync`] for how values are interpreted.
+pub fn glean_record_from_json(metric: MetricRef, value: JsonValue) {
+    launch_recording(move |glean| metric.record_json_sync(glean, value))
+}
+
+/// Deletes all data stored for a metric, including its recorded errors.
+///
+/// See [`core::Glean::delete_metric`].
+pub fn glean_delete_metric(category: String, name: String) {
+    launch_with_glean(move |glean| glean.delete_metric(&category, &name))
+}
+
+/// Registers an observer notified whenever a new value is stored for any metric.
+///
+/// The observer is called with the metric identifier and the new value,
+/// on the dispatcher thread, after the value was written.
+/// Recordings that are rejected, e.g. because of an invalid value, don't trigger it.
+/// Events are not observed.
+///
+/// Use [`glean_remove_metric_observer`] to remove it again.
+pub fn glean_observe_metrics(observer: GlobalMetricObserver) -> ObserverId {
+    let id = ObserverId::next();
+    launch_with_glean_mut(move |glean| glean.observers.add_global_observer(id, observer));
+    id
+}
+
+/// Removes an observer registered with [`glean_observe_metrics`]
+/// or [`MetricType::observe`](metrics::MetricType::observe).
+pub fn glean_remove_metric_observer(id: ObserverId) {
+    launch_with_glean_mut(move |glean| glean.observers.remove(id));
+}
+
+/// Stops assembling a ping after it repeatedly failed schema validation.
+///
+/// See [`core::Glean::open_schema_circuit`].
+pub fn glean_open_schema_circuit(ping_name: String) {
+    launch_with_glean_mut(move |glean| glean.open_schema_circuit(&ping_name));
+}
+
+/// Enables or disables a single ping.
+///
+/// See [`core::Glean::set_ping_enabled`].
+pub fn glean_set_ping_enabled(ping_name: String, enabled: bool) {
+    launch_with_glean_mut(move |glean| glean.set_ping_enabled(&ping_name, enabled));
+}
+
+/// Collects and submits the `crash` ping.
+///
+/// As the process might be about to go down, this doesn't go through the dispatcher:
+/// the ping is assembled and written to disk synchronously, on the calling thread.
+/// Tasks still queued on the dispatcher are not included.
+///
+/// See [`core::Glean::submit_crash_ping`].
+///
+/// # Returns
+///
+/// Whether the ping was successfully assembled and queued.
+/// This is `false` if Glean is not initialized.
+pub fn glean_submit_crash_ping(cause: CrashCause, annotations: HashMap<String, String>) -> bool {
+    let glean = match core::global_glean() {
+        Some(glean) => glean,
+        None => {
+            log::error!("Glean not initialized. Can't submit a crash ping.");
+            return false;
+        }
+    };
+
+    // A panic on another thread might have poisoned the lock.
+    // The data is still there, so we try our best anyway.
+    let glean = glean
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    glean.submit_crash_ping(cause, annotations)
+}
+
+/// Replaces the client id with a newly generated one.
+///
+/// This blocks on the dispatcher.
+///
+/// See [`core::Glean::rotate_client_id`].
+///
+/// # Returns
+///
+/// The new client id, or `None` if upload is disabled.
+/// Nothing is changed in that case.
+pub fn glean_rotate_client_id() -> Option<Uuid> {
+    block_on_dispatcher();
+    core::with_glean(|glean| glean.rotate_client_id())
+}
+
 /// TEST ONLY FUNCTION.
 /// Returns the [`RecordedExperiment`] for the given `experiment_id`
 /// or `None` if the id isn't found.
@@ -694,6 +1110,109 @@ pub fn glean_test_get_experiment_data(experiment_id: String) -> Option<RecordedE
     core::with_glean(|glean| glean.test_get_experiment_data(experiment_id.to_owned()))
 }
 
+/// TEST ONLY FUNCTION.
+/// Returns a JSON snapshot of the metrics stored for the ping `store_name`,
+/// the same way they are collected when assembling a ping.
+///
+/// If `clear_store` is `true`, data with `ping` lifetime is cleared afterwards.
+/// No ping is submitted or uploaded.
+/// Returns `None` if there is no data stored for this ping.
+pub fn glean_test_snapshot_store(store_name: String, clear_store: bool) -> Option<JsonValue> {
+    block_on_dispatcher();
+    core::with_glean(|glean| {
+        crate::storage::StorageManager.snapshot_as_json(glean.storage(), &store_name, clear_store)
+    })
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the identifier of the current session.
+pub fn glean_test_get_session_id() -> String {
+    block_on_dispatcher();
+    core::with_glean(|glean| glean.session_id().to_string())
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the number of sessions, i.e. how many times Glean was initialized with upload enabled.
+pub fn glean_test_get_session_count() -> i32 {
+    block_on_dispatcher();
+    core::with_glean(|glean| {
+        glean
+            .core_metrics
+            .session_count
+            .get_value(glean, "glean_internal_info")
+            .unwrap_or(0)
+    })
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the number of stored entries and their approximate size in bytes,
+/// as `(storage name, number of entries, size)` tuples sorted by storage name.
+pub fn glean_test_get_store_stats() -> Vec<(String, usize, usize)> {
+    block_on_dispatcher();
+    core::with_glean(|glean| glean.storage().store_stats())
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the number of tasks waiting on the dispatcher, not counting the one currently running.
+pub fn glean_test_get_dispatcher_queue_depth() -> usize {
+    dispatcher::global::queue_depth()
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the number of errors recorded by every metric that recorded any,
+/// as `(metric identifier, error type, number of errors)` tuples.
+pub fn glean_test_get_all_errors() -> Vec<(String, ErrorType, i32)> {
+    block_on_dispatcher();
+    core::with_glean(test_get_all_recorded_errors)
+}
+
+/// **TEST-ONLY Method**
+///
+/// Gets the number of errors destined for a ping, by every metric that recorded any,
+/// as `(metric identifier, error type, number of errors)` tuples.
+pub fn glean_test_get_errors_for_ping(ping_name: String) -> Vec<(String, ErrorType, i32)> {
+    block_on_dispatcher();
+    core::with_glean(|glean| test_get_recorded_errors_for_ping(glean, &ping_name))
+}
+
+/// **TEST-ONLY Method**
+///
+/// Clears the errors destined for a ping.
+pub fn glean_test_clear_errors(ping_name: String) {
+    block_on_dispatcher();
+    core::with_glean(|glean| test_clear_recorded_errors(glean, &ping_name))
+}
+
+/// Gets all data recorded into a store, including its events,
+/// without clearing it.
+///
+/// This is meant for inspecting the [`DEBUG_STORE`](crate::storage::DEBUG_STORE) while developing
+/// instrumentation, but works for any store.
+/// Returns `None` if there is no data stored.
+pub fn glean_dump_store(store_name: String) -> Option<JsonValue> {
+    block_on_dispatcher();
+    core::with_glean(|glean| {
+        let metrics =
+            crate::storage::StorageManager.snapshot_as_json(glean.storage(), &store_name, false);
+        let events = glean.event_storage().snapshot_as_json(&store_name, false);
+        match (metrics, events) {
+            (None, None) => None,
+            (metrics, events) => {
+                let mut dump = metrics.unwrap_or_else(|| JsonValue::Object(Default::default()));
+                if let Some(events) = events {
+                    dump["events"] = events;
+                }
+                Some(dump)
+            }
+        }
+    })
+}
+
 /// Sets a debug view tag.
 ///
 /// When the debug view tag is set, pings are sent with a `X-Debug-ID` header with the
@@ -770,6 +1289,20 @@ pub fn glean_set_log_pings(value: bool) {
     }
 }
 
+/// Attaches metadata to every ping submitted from now on, for the rest of this session.
+///
+/// See [`Glean::set_ping_metadata`] for the limits on the entries.
+///
+/// # Arguments
+///
+/// * `key` - The metadata key, e.g. `environment`.
+/// * `value` - The value of the key, e.g. `staging`.
+pub fn glean_set_ping_metadata(key: String, value: String) {
+    crate::launch_with_glean_mut(move |glean| {
+        glean.set_ping_metadata(key, value);
+    });
+}
+
 /// Performs the collection/cleanup operations required by becoming active.
 ///
 /// This functions generates a baseline ping with reason `active`
@@ -808,7 +1341,12 @@ pub fn glean_handle_client_inactive() {
     // This needs to be called before the `handle_client_inactive` api: it stops
     // measuring the duration of the previous activity time, before any ping is sent
     // by the next call.
-    core_metrics::internal_metrics::baseline_duration.stop();
+    let stop_time = time::precise_time_ns();
+    launch_with_glean(move |glean| {
+        core_metrics::internal_metrics::baseline_duration
+            .with_time_unit(glean.baseline_duration_unit())
+            .set_stop(glean, stop_time)
+    });
 
     dispatcher::launch(|| {
         core::with_glean_mut(|glean| {
@@ -851,6 +1389,68 @@ pub fn glean_submit_ping_by_name_sync(ping_name: String, reason: Option<String>)
     core::with_glean(|glean| glean.submit_ping_by_name(&ping_name, reason.as_deref()))
 }
 
+/// Describes every metric created so far with a description or data sensitivity,
+/// sorted by identifier.
+///
+/// Use this to show users what data is collected, e.g. on a privacy page.
+/// Metrics are only known once they have been created,
+/// so metrics defined lazily show up after their first use.
+pub fn glean_describe_metrics() -> Vec<MetricDescriptor> {
+    common_metric_data::metric_descriptors()
+}
+
+/// **TEST-ONLY Method**
+///
+/// Submits a ping by name on the calling thread and triggers its upload.
+///
+/// This blocks on the dispatcher first, so the ping includes everything recorded before.
+///
+/// # Returns
+///
+/// Whether the ping was submitted.
+pub fn glean_test_submit_ping_by_name(ping_name: String, reason: Option<String>) -> bool {
+    block_on_dispatcher();
+
+    let sent = core::with_glean(|glean| glean.submit_ping_by_name(&ping_name, reason.as_deref()));
+    if sent {
+        let state = global_state().lock().unwrap();
+        if let Err(e) = state.callbacks.trigger_upload() {
+            log::error!("Triggering upload failed. Error: {}", e);
+        }
+    }
+    sent
+}
+
+/// Collect and submit the "metrics" ping for eventual upload right away.
+///
+/// This doesn't change when the metrics ping scheduler submits it next.
+/// If `reason` is not one of the metrics ping's reasons, an error is logged
+/// and no ping is submitted.
+pub fn glean_submit_metrics_ping(reason: Option<String>) {
+    dispatcher::launch(|| {
+        let sent = core::with_glean(move |glean| {
+            let metrics_ping = &glean.internal_pings.metrics;
+            if let Some(reason) = &reason {
+                if !metrics_ping.reason_codes().contains(reason) {
+                    log::error!(
+                        "Invalid reason code {} for the metrics ping. Not submitting it.",
+                        reason
+                    );
+                    return false;
+                }
+            }
+            metrics_ping.submit_sync(glean, reason.as_deref())
+        });
+
+        if sent {
+            let state = global_state().lock().unwrap();
+            if let Err(e) = state.callbacks.trigger_upload() {
+                log::error!("Triggering upload failed. Error: {}", e);
+            }
+        }
+    })
+}
+
 /// **TEST-ONLY Method**
 ///
 /// Set test mode
@@ -879,9 +1479,38 @@ pub fn glean_test_destroy_glean(clear_stores: bool) {
 
         // Allow us to go through initialization again.
         INITIALIZE_CALLED.store(false, Ordering::SeqCst);
+        INIT_CALLBACKS.lock().unwrap().0 = false;
     }
 }
 
+/// **TEST-ONLY Method**
+///
+/// Fully tears down Glean.
+///
+/// Shuts Glean down, destroys the database after clearing all stores
+/// and forgets everything that was set before initialization,
+/// when throttled metrics were last recorded and the metric descriptors,
+/// so that the next initialization behaves like the first one in this process.
+pub fn glean_test_destroy() {
+    if was_initialize_called() {
+        shutdown();
+    }
+    glean_test_destroy_glean(true);
+
+    if let Some(tag) = PRE_INIT_DEBUG_VIEW_TAG.get() {
+        tag.lock().unwrap().clear();
+    }
+    PRE_INIT_LOG_PINGS.store(false, Ordering::SeqCst);
+    if let Some(tags) = PRE_INIT_SOURCE_TAGS.get() {
+        tags.lock().unwrap().clear();
+    }
+    if let Some(pings) = PRE_INIT_PING_REGISTRATION.get() {
+        pings.lock().unwrap().clear();
+    }
+    INIT_CALLBACKS.lock().unwrap().1.clear();
+    common_metric_data::clear_metric_state();
+}
+
 /// Get the next upload task
 pub fn glean_get_upload_task() -> PingUploadTask {
     core::with_opt_glean(|glean| glean.get_upload_task()).unwrap_or_else(PingUploadTask::done)
@@ -892,6 +1521,65 @@ pub fn glean_process_ping_upload_response(uuid: String, result: UploadResult) ->
     core::with_glean(|glean| glean.process_ping_upload_response(&uuid, result))
 }
 
+/// Removes all pending pings and returns their requests, without uploading them.
+///
+/// Returns nothing if Glean isn't initialized.
+///
+/// See [`core::Glean::drain_pings`].
+pub fn glean_drain_pings() -> Vec<PingRequest> {
+    block_on_dispatcher();
+    core::with_opt_glean(|glean| glean.drain_pings()).unwrap_or_default()
+}
+
+/// Writes all pending pings into a single archive file at `path`.
+///
+/// See [`core::Glean::export_pending_pings`].
+pub fn glean_export_pending_pings(path: PathBuf) -> Result<usize> {
+    block_on_dispatcher();
+    core::with_opt_glean(|glean| glean.export_pending_pings(&path))
+        .unwrap_or_else(|| Err(Error::not_initialized()))
+}
+
+/// Imports the pings of an archive written by [`glean_export_pending_pings`]
+/// and triggers their upload.
+///
+/// See [`core::Glean::import_pending_pings`].
+pub fn glean_import_pending_pings(path: PathBuf) -> Result<usize> {
+    block_on_dispatcher();
+    let imported = core::with_opt_glean(|glean| glean.import_pending_pings(&path))
+        .unwrap_or_else(|| Err(Error::not_initialized()))?;
+    if imported > 0 {
+        let state = global_state().lock().unwrap();
+        if let Err(e) = state.callbacks.trigger_upload() {
+            log::error!("Triggering upload failed. Error: {}", e);
+        }
+    }
+    Ok(imported)
+}
+
+/// Gets the time of the last successful ping upload, if any.
+///
+/// This is persisted in the data directory, so it survives restarts.
+pub fn glean_last_upload_time() -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    core::with_glean(|glean| glean.last_upload_time())
+}
+
+/// Gets whether upload is currently enabled.
+///
+/// This reflects the calls to [`glean_set_upload_enabled`] processed so far,
+/// calls still queued on the dispatcher aren't taken into account.
+/// Returns `false` until Glean is initialized.
+pub fn glean_is_upload_enabled() -> bool {
+    core::with_opt_glean(|glean| glean.is_upload_enabled()).unwrap_or(false)
+}
+
+/// Gets the data path of the Glean instance, if Glean is initialized.
+///
+/// See [`core::Glean::get_data_path`].
+pub fn glean_get_data_path() -> Option<PathBuf> {
+    core::with_opt_glean(|glean| glean.get_data_path().to_path_buf())
+}
+
 /// **TEST-ONLY Method**
 ///
 /// Set the dirty flag
@@ -332,6 +332,55 @@ fn client_id_is_managed_correctly_when_toggling_uploading() {
     assert_ne!(original_client_id, current_client_id);
 }
 
+#[test]
+fn rotating_the_client_id_persists_a_new_id() {
+    let (mut glean, _) = new_glean(None);
+
+    let first_run_date = glean
+        .core_metrics
+        .first_run_date
+        .get_value(&glean, "glean_client_info");
+    let mut seen_ids = vec![glean
+        .core_metrics
+        .client_id
+        .get_value(&glean, "glean_client_info")
+        .unwrap()];
+
+    for _ in 0..3 {
+        let new_id = glean.rotate_client_id().unwrap();
+        assert!(!seen_ids.contains(&new_id));
+        assert_eq!(
+            Some(new_id),
+            glean
+                .core_metrics
+                .client_id
+                .get_value(&glean, "glean_client_info")
+        );
+        seen_ids.push(new_id);
+    }
+
+    // The first run date is left alone.
+    assert_eq!(
+        first_run_date,
+        glean
+            .core_metrics
+            .first_run_date
+            .get_value(&glean, "glean_client_info")
+    );
+
+    // The known client id is kept while upload is disabled.
+    glean.set_upload_enabled(false);
+    assert_eq!(None, glean.rotate_client_id());
+    assert_eq!(
+        *KNOWN_CLIENT_ID,
+        glean
+            .core_metrics
+            .client_id
+            .get_value(&glean, "glean_client_info")
+            .unwrap()
+    );
+}
+
 #[test]
 fn client_id_is_set_to_known_value_when_uploading_disabled_at_start() {
     let dir = tempfile::tempdir().unwrap();
@@ -420,6 +469,8 @@ fn correct_order() {
         Jwe("eyJhbGciOiJSU0EtT0FFUCIsImVuYyI6IkEyNTZHQ00ifQ.OKOawDo13gRp2ojaHV7LFpZcgV7T6DVZKTyKOMTYUmKoTCVJRgckCL9kiMT03JGeipsEdY3mx_etLbbWSrFr05kLzcSr4qKAq7YN7e9jwQRb23nfa6c9d-StnImGyFDbSv04uVuxIp5Zms1gNxKKK2Da14B8S4rzVRltdYwam_lDp5XnZAYpQdb76FdIKLaVmqgfwX7XWRxv2322i-vDxRfqNzo_tETKzpVLzfiwQyeyPGLBIO56YJ7eObdv0je81860ppamavo35UgoRdbYaBcoh9QcfylQr66oc6vFWXRcZ_ZT2LawVCWTIy3brGPi6UklfCpIMfIjf7iGdXKHzg.48V1_ALb6US04U3b.5eym8TW_c8SuK0ltJ3rpYIzOeDQz7TALvtu6UG9oMo4vpzs9tX_EFShS8iB7j6jiSdiwkIr3ajwQzaBtQD_A.XFBoMYUZodetZdvTiFvSkQ".into()),
         Rate(0, 0),
         Text(long_string),
+        SignedCounter(0),
+        CustomDistributionBucketed(Histogram::bucketed(vec![0, 10])),
     ];
 
     for metric in all_metrics {
@@ -448,6 +499,8 @@ fn correct_order() {
             Rate(..)                          => assert_eq!(14, disc),
             Url(..)                           => assert_eq!(15, disc),
             Text(..)                          => assert_eq!(16, disc),
+            SignedCounter(..)                 => assert_eq!(17, disc),
+            CustomDistributionBucketed(..)    => assert_eq!(18, disc),
         }
     }
 }
@@ -916,6 +969,8 @@ fn records_io_errors() {
         metric.get_value(&glean, Some("metrics")).unwrap(),
         "Should have recorded an IO error"
     );
+    let metric = &glean.additional_metrics.io_failure;
+    assert_eq!(Some(1), metric.get_value(&glean, Some("metrics")));
 
     // Restore write permissions.
     fs::set_permissions(&pending_pings_dir, original_permissions).unwrap();
@@ -944,3 +999,36 @@ fn test_activity_api() {
     // Check that we set everything we needed for the 'inactuve' status.
     assert!(!glean.is_dirty_flag_set());
 }
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn os_uptime_is_recorded_for_the_baseline_ping() {
+    let (glean, _t) = new_glean(None);
+
+    let uptime = glean
+        .core_metrics
+        .os_uptime
+        .get_value(&glean, "baseline")
+        .unwrap();
+    assert!(uptime > 0);
+    // Less than 10 years.
+    assert!(uptime < 10 * 365 * 24 * 60 * 60);
+}
+
+#[test]
+fn disabled_pings_are_persisted() {
+    let (mut glean, dir) = new_glean(None);
+
+    glean.set_ping_enabled("events", false);
+    glean.set_ping_enabled("deletion-request", false);
+    assert!(!glean.is_ping_enabled("events"));
+    assert!(glean.is_ping_enabled("deletion-request"));
+
+    drop(glean);
+    let (mut glean, _t) = new_glean(Some(dir));
+    assert!(!glean.is_ping_enabled("events"));
+    assert!(glean.is_ping_enabled("baseline"));
+
+    glean.set_ping_enabled("events", true);
+    assert!(glean.is_ping_enabled("events"));
+}
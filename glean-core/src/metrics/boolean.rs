@@ -17,6 +17,7 @@ use crate::Glean;
 #[derive(Clone, Debug)]
 pub struct BooleanMetric {
     meta: Arc<CommonMetricData>,
+    default_value: Option<bool>,
 }
 
 impl MetricType for BooleanMetric {
@@ -29,6 +30,7 @@ impl MetricType for BooleanMetric {
         meta.name = name;
         Self {
             meta: Arc::new(meta),
+            default_value: self.default_value,
         }
     }
 
@@ -37,6 +39,7 @@ impl MetricType for BooleanMetric {
         meta.dynamic_label = Some(label);
         Self {
             meta: Arc::new(meta),
+            default_value: self.default_value,
         }
     }
 }
@@ -48,8 +51,18 @@ impl MetricType for BooleanMetric {
 impl BooleanMetric {
     /// Creates a new boolean metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        Self::new_with_default(meta, None)
+    }
+
+    /// Creates a new boolean metric with a default value.
+    ///
+    /// The default is reported when no value was recorded for the queried store.
+    /// It is never persisted.
+    pub fn new_with_default(meta: CommonMetricData, default_value: Option<bool>) -> Self {
+        meta.register_descriptor("boolean");
         Self {
             meta: Arc::new(meta),
+            default_value,
         }
     }
 
@@ -76,13 +89,18 @@ impl BooleanMetric {
     /// * `glean` - the Glean instance this metric belongs to.
     /// * `value` - the value to set.
     pub fn set(&self, value: bool) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, value))
     }
 
     /// **Test-only API (exported for FFI purposes).**
     ///
-    /// Gets the currently stored value as a boolean.
+    /// Gets the currently stored value as a boolean,
+    /// falling back to the default value if nothing was recorded.
     ///
     /// This doesn't clear the stored value.
     #[doc(hidden)]
@@ -96,7 +114,7 @@ impl BooleanMetric {
             self.meta.lifetime,
         ) {
             Some(Metric::Boolean(b)) => Some(b),
-            _ => None,
+            _ => self.default_value,
         }
     }
 
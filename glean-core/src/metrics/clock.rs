@@ -0,0 +1,38 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Debug;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of the current time for metrics measuring elapsed time.
+///
+/// The system clock is used by default, other implementations are mostly useful in tests.
+pub trait Clock: Debug + Send + Sync {
+    /// The current monotonic time in nanoseconds, from an arbitrary starting point.
+    ///
+    /// It never goes backwards, but might not advance while the device sleeps.
+    fn monotonic_nanos(&self) -> u64;
+
+    /// The current wall clock time in nanoseconds since the Unix epoch.
+    ///
+    /// It advances while the device sleeps, but jumps when the system time is changed.
+    fn wall_clock_nanos(&self) -> u64;
+}
+
+/// The [`Clock`] of the system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn monotonic_nanos(&self) -> u64 {
+        time::precise_time_ns()
+    }
+
+    fn wall_clock_nanos(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+}
@@ -49,6 +49,7 @@ impl MetricType for CounterMetric {
 impl CounterMetric {
     /// Creates a new counter metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("counter");
         Self {
             meta: Arc::new(meta),
         }
@@ -72,6 +73,11 @@ impl CounterMetric {
             return;
         }
 
+        self.add_valid_sync(glean, amount);
+    }
+
+    /// Increases the counter by the already validated `amount` synchronously.
+    fn add_valid_sync(&self, glean: &Glean, amount: i32) {
         // Let's be defensive here:
         // The uploader tries to store a counter metric,
         // but in tests that storage might be gone already.
@@ -103,8 +109,140 @@ impl CounterMetric {
     ///
     /// Logs an error if the `amount` is 0 or negative.
     pub fn add(&self, amount: i32) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
+        let metric = self.clone();
+        crate::launch_recording(move |glean| metric.add_sync(glean, amount))
+    }
+
+    /// Increases the counter by each of `amounts` at once.
+    ///
+    /// This is equivalent to calling [`add`](Self::add) for each amount,
+    /// but hands all of them to the dispatcher in a single task,
+    /// for hot paths recording many values.
+    ///
+    /// # Arguments
+    ///
+    /// * `amounts` - The amounts to increase by. Should be positive.
+    ///
+    /// ## Notes
+    ///
+    /// Logs an error for every amount that is 0 or negative, the others are still added.
+    /// If the metric has a minimum record interval, the batch counts as a single recording.
+    pub fn add_many(&self, amounts: &[i32]) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_sync(glean, amount))
+        let amounts = amounts.to_vec();
+        crate::launch_recording(move |glean| metric.add_many_sync(glean, &amounts))
+    }
+
+    /// Increases the counter by each of `amounts` synchronously.
+    #[doc(hidden)]
+    pub fn add_many_sync(&self, glean: &Glean, amounts: &[i32]) {
+        if !self.should_record(glean) {
+            return;
+        }
+
+        let invalid = amounts.iter().filter(|&&amount| amount <= 0).count();
+        if invalid > 0 {
+            record_error(
+                glean,
+                &self.meta,
+                ErrorType::InvalidValue,
+                format!("Added {} negative or zero values", invalid),
+                invalid as i32,
+            );
+        }
+
+        let total = amounts
+            .iter()
+            .filter(|&&amount| amount > 0)
+            .fold(0i32, |total, &amount| total.saturating_add(amount));
+        if total > 0 {
+            self.add_valid_sync(glean, total);
+        }
+    }
+
+    /// Increases the counter by `amount`, but only on some release channels.
+    ///
+    /// Use this for instrumentation that should not be recorded in release builds,
+    /// e.g. `counter.add_if_channel(&["nightly", "beta"], 1)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - The channels, as reported in the client info, to record on.
+    /// * `amount` - The amount to increase by. Should be positive.
+    ///
+    /// ## Notes
+    ///
+    /// Does nothing if the application reported any other channel or none at all.
+    pub fn add_if_channel<S: AsRef<str>>(&self, channels: &[S], amount: i32) {
+        let metric = self.clone();
+        let channels: Vec<String> = channels.iter().map(|c| c.as_ref().to_string()).collect();
+        crate::launch_recording(move |glean| metric.add_if_channel_sync(glean, &channels, amount))
+    }
+
+    /// Increases the counter by `amount` synchronously, but only on some release channels.
+    ///
+    /// Use [`add_if_channel`](Self::add_if_channel) instead.
+    #[doc(hidden)]
+    pub fn add_if_channel_sync<S: AsRef<str>>(&self, glean: &Glean, channels: &[S], amount: i32) {
+        if glean.is_on_channel(channels) {
+            self.add_sync(glean, amount);
+        }
+    }
+
+    /// Increases the counter by `amount`, but only in one of its stores.
+    ///
+    /// Use this if the metric counts something different for each ping it is sent in.
+    /// The value in each store is independent of the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store to record into, one of the metric's `send_in_pings`.
+    /// * `amount` - The amount to increase by. Should be positive.
+    ///
+    /// ## Notes
+    ///
+    /// Records an [`ErrorType::InvalidState`] error if the metric isn't sent in `store`,
+    /// and an [`ErrorType::InvalidValue`] error if the `amount` is 0 or negative.
+    pub fn add_to_store(&self, store: &str, amount: i32) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
+        let metric = self.clone();
+        let store = store.to_string();
+        crate::launch_recording(move |glean| metric.add_to_store_sync(glean, &store, amount))
+    }
+
+    /// Increases the counter by `amount` synchronously, but only in one of its stores.
+    ///
+    /// Use [`add_to_store`](Self::add_to_store) instead.
+    #[doc(hidden)]
+    pub fn add_to_store_sync(&self, glean: &Glean, store: &str, amount: i32) {
+        if !self.meta.send_in_pings.iter().any(|ping| ping == store) {
+            record_error(
+                glean,
+                &self.meta,
+                ErrorType::InvalidState,
+                format!("Metric isn't sent in store {}", store),
+                None,
+            );
+            return;
+        }
+
+        let mut meta = (*self.meta).clone();
+        meta.send_in_pings = vec![store.to_string()];
+        let in_store = Self {
+            meta: Arc::new(meta),
+        };
+        in_store.add_sync(glean, amount);
     }
 
     /// Get current value
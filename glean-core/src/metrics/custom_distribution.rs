@@ -4,8 +4,9 @@
 
 use std::sync::Arc;
 
+use crate::error::ErrorKind;
 use crate::error_recording::{record_error, test_get_num_recorded_errors, ErrorType};
-use crate::histogram::{Bucketing, Histogram, HistogramType};
+use crate::histogram::{buckets_from_fn, Bucketing, Histogram, HistogramType};
 use crate::metrics::{DistributionData, Metric, MetricType};
 use crate::storage::StorageManager;
 use crate::CommonMetricData;
@@ -21,6 +22,10 @@ pub struct CustomDistributionMetric {
     range_max: u64,
     bucket_count: u64,
     histogram_type: HistogramType,
+    /// The bucket minimums computed from a custom bucketing function, if any.
+    ///
+    /// These take precedence over the range, bucket count and histogram type.
+    bucket_ranges: Option<Arc<Vec<u64>>>,
 }
 
 /// Create a snapshot of the histogram.
@@ -42,6 +47,24 @@ impl MetricType for CustomDistributionMetric {
     fn meta(&self) -> &CommonMetricData {
         &self.meta
     }
+
+    fn with_name(&self, name: String) -> Self {
+        let mut meta = (*self.meta).clone();
+        meta.name = name;
+        Self {
+            meta: Arc::new(meta),
+            ..self.clone()
+        }
+    }
+
+    fn with_dynamic_label(&self, label: String) -> Self {
+        let mut meta = (*self.meta).clone();
+        meta.dynamic_label = Some(label);
+        Self {
+            meta: Arc::new(meta),
+            ..self.clone()
+        }
+    }
 }
 
 // IMPORTANT:
@@ -57,15 +80,52 @@ impl CustomDistributionMetric {
         bucket_count: i64,
         histogram_type: HistogramType,
     ) -> Self {
+        meta.register_descriptor("custom_distribution");
         Self {
             meta: Arc::new(meta),
             range_min: range_min as u64,
             range_max: range_max as u64,
             bucket_count: bucket_count as u64,
             histogram_type,
+            bucket_ranges: None,
         }
     }
 
+    /// Creates a new custom distribution metric with buckets defined by a function,
+    /// for bucketing neither linear nor exponential.
+    ///
+    /// `bucketer` maps a sample to the minimum of the bucket it falls into,
+    /// e.g. `|sample| sample / 10 * 10` for buckets of 10.
+    /// It is called a few times per bucket between `range_min` and `range_max` right away,
+    /// to compute the buckets, and never again afterwards.
+    /// Smaller samples go into an underflow bucket, bigger ones into the last bucket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ErrorKind::InvalidBucketing`] error if `bucketer` isn't deterministic
+    /// or monotonic, maps a sample above itself or a bucket minimum into another bucket,
+    /// or splits the range into more than
+    /// [`MAX_BUCKETING_FN_BUCKETS`](crate::MAX_BUCKETING_FN_BUCKETS) buckets.
+    pub fn new_with_bucketer(
+        meta: CommonMetricData,
+        range_min: u64,
+        range_max: u64,
+        bucketer: impl Fn(u64) -> u64,
+    ) -> crate::Result<Self> {
+        let ranges = buckets_from_fn(range_min, range_max, &bucketer)
+            .map_err(ErrorKind::InvalidBucketing)?;
+
+        let mut metric = Self::new(
+            meta,
+            range_min as i64,
+            range_max as i64,
+            ranges.len() as i64,
+            HistogramType::Linear,
+        );
+        metric.bucket_ranges = Some(Arc::new(ranges));
+        Ok(metric)
+    }
+
     /// Accumulates the provided signed samples in the metric.
     ///
     /// This is required so that the platform-specific code can provide us with
@@ -83,7 +143,7 @@ impl CustomDistributionMetric {
     /// for each of them.
     pub fn accumulate_samples(&self, samples: Vec<i64>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.accumulate_samples_sync(glean, samples))
+        crate::launch_recording(move |glean| metric.accumulate_samples_sync(glean, samples))
     }
 
     /// Accumulates the provided sample in the metric synchronously.
@@ -96,14 +156,16 @@ impl CustomDistributionMetric {
         }
 
         let mut num_negative_samples = 0;
+        let mut merged = 0;
 
         // Generic accumulation function to handle the different histogram types and count negative
         // samples.
         fn accumulate<B: Bucketing, F>(
+            glean: &Glean,
             samples: &[i64],
             mut hist: Histogram<B>,
             metric: F,
-        ) -> (i32, Metric)
+        ) -> (i32, usize, Metric)
         where
             F: Fn(Histogram<B>) -> Metric,
         {
@@ -116,12 +178,22 @@ impl CustomDistributionMetric {
                     hist.accumulate(sample);
                 }
             }
-            (num_negative_samples, metric(hist))
+            let merged = glean.bound_distribution(&mut hist);
+            (num_negative_samples, merged, metric(hist))
         }
 
         glean.storage().record_with(glean, &self.meta, |old_value| {
-            let (num_negative, hist) = match self.histogram_type {
-                HistogramType::Linear => {
+            let (num_negative, num_merged, hist) = match (&self.bucket_ranges, self.histogram_type)
+            {
+                (Some(ranges), _) => {
+                    let hist = if let Some(Metric::CustomDistributionBucketed(hist)) = old_value {
+                        hist
+                    } else {
+                        Histogram::bucketed(ranges.to_vec())
+                    };
+                    accumulate(glean, &samples, hist, Metric::CustomDistributionBucketed)
+                }
+                (None, HistogramType::Linear) => {
                     let hist = if let Some(Metric::CustomDistributionLinear(hist)) = old_value {
                         hist
                     } else {
@@ -131,9 +203,9 @@ impl CustomDistributionMetric {
                             self.bucket_count as usize,
                         )
                     };
-                    accumulate(&samples, hist, Metric::CustomDistributionLinear)
+                    accumulate(glean, &samples, hist, Metric::CustomDistributionLinear)
                 }
-                HistogramType::Exponential => {
+                (None, HistogramType::Exponential) => {
                     let hist = if let Some(Metric::CustomDistributionExponential(hist)) = old_value
                     {
                         hist
@@ -144,13 +216,15 @@ impl CustomDistributionMetric {
                             self.bucket_count as usize,
                         )
                     };
-                    accumulate(&samples, hist, Metric::CustomDistributionExponential)
+                    accumulate(glean, &samples, hist, Metric::CustomDistributionExponential)
                 }
             };
 
             num_negative_samples = num_negative;
+            merged += num_merged;
             hist
         });
+        glean.record_merged_buckets(merged);
 
         if num_negative_samples > 0 {
             let msg = format!("Accumulated {} negative samples", num_negative_samples);
@@ -184,6 +258,7 @@ impl CustomDistributionMetric {
             // Boxing the value, in order to return either of the possible buckets
             Some(Metric::CustomDistributionExponential(hist)) => Some(snapshot(&hist)),
             Some(Metric::CustomDistributionLinear(hist)) => Some(snapshot(&hist)),
+            Some(Metric::CustomDistributionBucketed(hist)) => Some(snapshot(&hist)),
             _ => None,
         }
     }
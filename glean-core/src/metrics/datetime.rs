@@ -6,6 +6,7 @@ use std::fmt;
 use std::sync::Arc;
 
 use crate::error_recording::{record_error, test_get_num_recorded_errors, ErrorType};
+use crate::metrics::clock::{Clock, SystemClock};
 use crate::metrics::time_unit::TimeUnit;
 use crate::metrics::Metric;
 use crate::metrics::MetricType;
@@ -14,7 +15,17 @@ use crate::util::{get_iso_time_string, local_now_with_offset};
 use crate::CommonMetricData;
 use crate::Glean;
 
-use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Timelike};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Timelike, Utc};
+
+/// The earliest plausible datetime, as a Unix timestamp: 2010-01-01T00:00:00Z.
+///
+/// With [`clamp_skewed_datetimes`](crate::InternalConfiguration::clamp_skewed_datetimes),
+/// earlier datetimes are recorded as this one.
+const EARLIEST_PLAUSIBLE_TIMESTAMP: i64 = 1_262_304_000;
+
+/// How far in the future datetimes are plausible, in seconds,
+/// to allow for clock differences between the source of a datetime and this device.
+const FUTURE_TOLERANCE_SECS: i64 = 24 * 60 * 60;
 
 /// A datetime type.
 ///
@@ -85,6 +96,9 @@ impl Default for Datetime {
 pub struct DatetimeMetric {
     meta: Arc<CommonMetricData>,
     time_unit: TimeUnit,
+    /// Whether values are converted to UTC before they are stored.
+    force_utc: bool,
+    clock: Arc<dyn Clock>,
 }
 
 impl MetricType for DatetimeMetric {
@@ -111,19 +125,88 @@ impl From<ChronoDatetime> for Datetime {
     }
 }
 
+impl Datetime {
+    /// Converts this into a [`ChronoDatetime`].
+    ///
+    /// Returns `None` if it is not a valid date and time.
+    pub(crate) fn to_chrono(&self) -> Option<ChronoDatetime> {
+        FixedOffset::east_opt(self.offset_seconds)?
+            .ymd_opt(self.year, self.month, self.day)
+            .and_hms_nano_opt(self.hour, self.minute, self.second, self.nanosecond)
+            .single()
+    }
+}
+
 // IMPORTANT:
 //
 // When changing this implementation, make sure all the operations are
 // also declared in the related trait in `../traits/`.
 impl DatetimeMetric {
     /// Creates a new datetime metric.
+    ///
+    /// Values are recorded with the timezone offset they were set with.
     pub fn new(meta: CommonMetricData, time_unit: TimeUnit) -> Self {
+        Self::new_with_utc(meta, time_unit, false)
+    }
+
+    /// Creates a new datetime metric, optionally normalizing values to UTC.
+    ///
+    /// # Arguments
+    ///
+    /// * `meta` - The metric's metadata.
+    /// * `time_unit` - The precision to record values with.
+    /// * `force_utc` - Whether to convert values to UTC before they are recorded,
+    ///   so they are serialized with a `+00:00` offset.
+    ///   Otherwise the offset values are set with is kept, which is the local offset by default.
+    pub fn new_with_utc(meta: CommonMetricData, time_unit: TimeUnit, force_utc: bool) -> Self {
+        meta.register_descriptor("datetime");
         Self {
             meta: Arc::new(meta),
             time_unit,
+            force_utc,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Replaces the clock providing the current time,
+    /// which is recorded when no value is given and bounds plausible values,
+    /// e.g. to simulate a skewed system clock in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time with the local timezone offset, according to the clock.
+    fn now(&self) -> ChronoDatetime {
+        let offset = *local_now_with_offset().offset();
+        Utc.timestamp_nanos(self.clock.wall_clock_nanos() as i64)
+            .with_timezone(&offset)
+    }
+
+    /// Clamps `value` to the plausible window, from 2010 to a day from now,
+    /// reporting a `clock_skew` error if it's outside of it.
+    fn clamp_to_plausible(&self, glean: &Glean, value: ChronoDatetime) -> ChronoDatetime {
+        let earliest = Utc.timestamp(EARLIEST_PLAUSIBLE_TIMESTAMP, 0);
+        // With the clock itself set before 2010, nothing but the earliest datetime is plausible.
+        let latest = (self.now() + Duration::seconds(FUTURE_TOLERANCE_SECS)).max(earliest.into());
+
+        let clamped = if value < earliest {
+            earliest.with_timezone(value.offset())
+        } else if value > latest {
+            latest.with_timezone(value.offset())
+        } else {
+            return value;
+        };
+
+        let msg = format!(
+            "Datetime {} is implausible, recording {} instead.",
+            value.to_rfc3339(),
+            clamped.to_rfc3339()
+        );
+        record_error(glean, &self.meta, ErrorType::ClockSkew, msg, None);
+        clamped
+    }
+
     /// Sets the metric to a date/time including the timezone offset.
     ///
     /// # Arguments
@@ -131,7 +214,7 @@ impl DatetimeMetric {
     /// * `dt` - the optinal datetime to set this to. If missing the current date is used.
     pub fn set(&self, dt: Option<Datetime>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| {
+        crate::launch_recording(move |glean| {
             metric.set_sync(glean, dt);
         })
     }
@@ -146,7 +229,7 @@ impl DatetimeMetric {
         }
 
         let value = match value {
-            None => local_now_with_offset(),
+            None => self.now(),
             Some(dt) => {
                 let timezone_offset = FixedOffset::east_opt(dt.offset_seconds);
                 if timezone_offset.is_none() {
@@ -181,6 +264,16 @@ impl DatetimeMetric {
     }
 
     pub(crate) fn set_sync_chrono(&self, glean: &Glean, value: ChronoDatetime) {
+        let value = if glean.clamp_skewed_datetimes() {
+            self.clamp_to_plausible(glean, value)
+        } else {
+            value
+        };
+        let value = if self.force_utc {
+            value.with_timezone(&FixedOffset::east(0))
+        } else {
+            value
+        };
         let value = Metric::Datetime(value, self.time_unit);
         glean.storage().record(glean, &self.meta, &value)
     }
@@ -33,8 +33,11 @@ impl MetricType for DenominatorMetric {
 impl DenominatorMetric {
     /// Creates a new denominator metric.
     pub fn new(meta: CommonMetricData, numerators: Vec<CommonMetricData>) -> Self {
+        let counter = CounterMetric::new(meta);
+        // Described as a denominator rather than the counter it's built on.
+        counter.meta().register_descriptor("denominator");
         Self {
-            counter: CounterMetric::new(meta),
+            counter,
             numerators: numerators.into_iter().map(RateMetric::new).collect(),
         }
     }
@@ -51,7 +54,7 @@ impl DenominatorMetric {
     /// Logs an error if the `amount` is 0 or negative.
     pub fn add(&self, amount: i32) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_sync(glean, amount))
+        crate::launch_recording(move |glean| metric.add_sync(glean, amount))
     }
 
     #[doc(hidden)]
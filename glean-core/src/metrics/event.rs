@@ -28,6 +28,10 @@ impl MetricType for EventMetric {
     fn meta(&self) -> &CommonMetricData {
         &self.meta
     }
+
+    fn reset_sync(&self, glean: &Glean) {
+        glean.event_storage().remove_metric(&self.meta);
+    }
 }
 
 // IMPORTANT:
@@ -37,6 +41,7 @@ impl MetricType for EventMetric {
 impl EventMetric {
     /// Creates a new event metric.
     pub fn new(meta: CommonMetricData, allowed_extra_keys: Vec<String>) -> Self {
+        meta.register_descriptor("event");
         Self {
             meta,
             allowed_extra_keys,
@@ -66,8 +71,27 @@ impl EventMetric {
     ///             Keys must be one of the allowed extra keys.
     ///             If any key is not allowed, an error is reported and no event is recorded.
     pub fn record_with_time(&self, timestamp: u64, extra: HashMap<String, String>) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.record_sync(glean, timestamp, extra));
+
+        // Need to separate access to the Glean object from access to global state.
+        // `trigger_upload` itself might lock the Glean object and we need to avoid that deadlock.
+        crate::dispatcher::launch(move || {
+            let submitted = crate::core::with_glean(|glean| {
+                crate::with_recording_profiled(glean, |glean| {
+                    metric.record_sync(glean, timestamp, extra)
+                })
+            });
+            if submitted {
+                let state = crate::global_state().lock().unwrap();
+                if let Err(e) = state.callbacks.trigger_upload() {
+                    log::error!("Triggering upload failed. Error: {}", e);
+                }
+            }
+        });
     }
 
     /// Validate that extras are empty or all extra keys are allowed.
@@ -103,20 +127,27 @@ impl EventMetric {
     }
 
     /// Records an event.
+    ///
+    /// Returns whether a ping was submitted because the event buffer reached its capacity.
     #[doc(hidden)]
-    pub fn record_sync(&self, glean: &Glean, timestamp: u64, extra: HashMap<String, String>) {
+    pub fn record_sync(
+        &self,
+        glean: &Glean,
+        timestamp: u64,
+        extra: HashMap<String, String>,
+    ) -> bool {
         if !self.should_record(glean) {
-            return;
+            return false;
         }
 
         let extra_strings = match self.validate_extra(glean, extra) {
             Ok(extra) => extra,
-            Err(()) => return,
+            Err(()) => return false,
         };
 
         glean
             .event_storage()
-            .record(glean, &self.meta, timestamp, extra_strings);
+            .record(glean, &self.meta, timestamp, extra_strings)
     }
 
     /// **Test-only API (exported for FFI purposes).**
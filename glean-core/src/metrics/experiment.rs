@@ -47,6 +47,9 @@ impl ExperimentMetric {
     /// * `id` - the id of the experiment. Please note that this will be
     ///          truncated to `MAX_EXPERIMENTS_IDS_LEN`, if needed.
     ///
+    /// Experiments have a user lifetime if Glean persists experiments,
+    /// and an application lifetime otherwise.
+    ///
     /// # Implementation note
     ///
     /// This runs synchronously and queries the database to record potential errors.
@@ -72,7 +75,11 @@ impl ExperimentMetric {
                 // We don't need a category, the name is already unique
                 category: "".into(),
                 send_in_pings: vec![INTERNAL_STORAGE.into()],
-                lifetime: Lifetime::Application,
+                lifetime: if glean.persist_experiments() {
+                    Lifetime::User
+                } else {
+                    Lifetime::Application
+                },
                 ..Default::default()
             },
         };
@@ -176,12 +183,15 @@ impl ExperimentMetric {
             return;
         }
 
-        if let Err(e) = glean.storage().remove_single_metric(
-            Lifetime::Application,
-            INTERNAL_STORAGE,
-            &self.meta.name,
-        ) {
-            log::error!("Failed to set experiment as inactive: {:?}", e);
+        // The experiment might have been set active while experiments were (not) persisted.
+        for lifetime in [Lifetime::Application, Lifetime::User] {
+            if let Err(e) =
+                glean
+                    .storage()
+                    .remove_single_metric(lifetime, INTERNAL_STORAGE, &self.meta.name)
+            {
+                log::error!("Failed to set experiment as inactive: {:?}", e);
+            }
         }
     }
 
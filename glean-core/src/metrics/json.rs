@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::convert::TryFrom;
+
+use serde_json::Value as JsonValue;
+
+use crate::error_recording::{record_error, ErrorType};
+use crate::metrics::{
+    BooleanMetric, CounterMetric, MetricType, QuantityMetric, StringListMetric, StringMetric,
+};
+use crate::CommonMetricData;
+use crate::Glean;
+
+/// A metric that can be recorded from a JSON value.
+///
+/// See [`MetricRef::record_json_sync`] for how values are interpreted.
+#[derive(Clone, Debug)]
+pub enum MetricRef {
+    /// A boolean metric, set from a JSON boolean.
+    Boolean(BooleanMetric),
+    /// A counter metric, incremented by a JSON integer.
+    Counter(CounterMetric),
+    /// A quantity metric, set from a JSON integer.
+    Quantity(QuantityMetric),
+    /// A string metric, set from a JSON string.
+    String(StringMetric),
+    /// A string list metric, set from a JSON array of strings.
+    StringList(StringListMetric),
+}
+
+macro_rules! impl_from_metric {
+    ($($variant:ident($metric:ty)),* $(,)?) => {
+        $(
+            impl From<&$metric> for MetricRef {
+                fn from(metric: &$metric) -> Self {
+                    MetricRef::$variant(metric.clone())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_metric!(
+    Boolean(BooleanMetric),
+    Counter(CounterMetric),
+    Quantity(QuantityMetric),
+    String(StringMetric),
+    StringList(StringListMetric),
+);
+
+impl MetricType for MetricRef {
+    fn meta(&self) -> &CommonMetricData {
+        match self {
+            MetricRef::Boolean(metric) => metric.meta(),
+            MetricRef::Counter(metric) => metric.meta(),
+            MetricRef::Quantity(metric) => metric.meta(),
+            MetricRef::String(metric) => metric.meta(),
+            MetricRef::StringList(metric) => metric.meta(),
+        }
+    }
+}
+
+impl MetricRef {
+    /// Records a JSON value into the metric.
+    ///
+    /// Use [`glean_record_from_json`](crate::glean_record_from_json) instead.
+    ///
+    /// ## Notes
+    ///
+    /// Records an [`ErrorType::InvalidValue`] error if the value doesn't match the metric type,
+    /// e.g. a string for a counter or a counter increment that doesn't fit into 32 bits.
+    /// Valid values are checked by the metric type as usual.
+    #[doc(hidden)]
+    pub fn record_json_sync(&self, glean: &Glean, value: JsonValue) {
+        let recorded = match (self, &value) {
+            (MetricRef::Boolean(metric), JsonValue::Bool(value)) => {
+                metric.set_sync(glean, *value);
+                true
+            }
+            (MetricRef::Counter(metric), JsonValue::Number(value)) => {
+                match value.as_i64().and_then(|value| i32::try_from(value).ok()) {
+                    Some(amount) => {
+                        metric.add_sync(glean, amount);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            (MetricRef::Quantity(metric), JsonValue::Number(value)) => match value.as_i64() {
+                Some(value) => {
+                    metric.set_sync(glean, value);
+                    true
+                }
+                None => false,
+            },
+            (MetricRef::String(metric), JsonValue::String(value)) => {
+                metric.set_sync(glean, value.as_str());
+                true
+            }
+            (MetricRef::StringList(metric), JsonValue::Array(values)) => {
+                let strings: Option<Vec<String>> = values
+                    .iter()
+                    .map(|value| value.as_str().map(String::from))
+                    .collect();
+                match strings {
+                    Some(strings) => {
+                        metric.set_sync(glean, strings);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+
+        if !recorded && self.should_record(glean) {
+            let msg = format!("Can't record JSON value {} into this metric", value);
+            record_error(glean, self.meta(), ErrorType::InvalidValue, msg, None);
+        }
+    }
+}
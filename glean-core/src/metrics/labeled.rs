@@ -7,12 +7,17 @@ use std::sync::{Arc, Mutex};
 
 use crate::common_metric_data::CommonMetricData;
 use crate::error_recording::{record_error, test_get_num_recorded_errors, ErrorType};
-use crate::metrics::{BooleanMetric, CounterMetric, Metric, MetricType, StringMetric};
+use crate::histogram::HistogramType;
+use crate::metrics::{
+    BooleanMetric, CounterMetric, CustomDistributionMetric, MemoryDistributionMetric, MemoryUnit,
+    Metric, MetricType, StringMetric,
+};
 use crate::Glean;
 
 const MAX_LABELS: usize = 16;
 const OTHER_LABEL: &str = "__other__";
-const MAX_LABEL_LENGTH: usize = 61;
+/// The maximum length of a dynamic label, in bytes, unless configured through `max_label_length`.
+pub(crate) const DEFAULT_MAX_LABEL_LENGTH: usize = 61;
 
 /// A labeled counter.
 pub type LabeledCounter = LabeledMetric<CounterMetric>;
@@ -23,6 +28,16 @@ pub type LabeledBoolean = LabeledMetric<BooleanMetric>;
 /// A labeled string.
 pub type LabeledString = LabeledMetric<StringMetric>;
 
+/// A labeled custom distribution.
+///
+/// All labels share the same bucketing, but each accumulates into its own histogram.
+pub type LabeledCustomDistribution = LabeledMetric<CustomDistributionMetric>;
+
+/// A labeled memory distribution.
+///
+/// All labels share the same memory unit, but each accumulates into its own histogram.
+pub type LabeledMemoryDistribution = LabeledMetric<MemoryDistributionMetric>;
+
 /// Checks whether the given label is sane.
 ///
 /// The check corresponds to the following regular expression:
@@ -163,7 +178,46 @@ where
         let submetric = T::new_labeled(meta);
         LabeledMetric::new_inner(submetric, labels)
     }
+}
+
+impl LabeledCustomDistribution {
+    /// Creates a new labeled custom distribution.
+    ///
+    /// The histogram configuration applies to the distribution of every label.
+    /// See [`get`](LabeledMetric::get) for information on how static or dynamic labels are handled.
+    pub fn new_custom_distribution(
+        meta: CommonMetricData,
+        range_min: i64,
+        range_max: i64,
+        bucket_count: i64,
+        histogram_type: HistogramType,
+        labels: Option<Vec<String>>,
+    ) -> LabeledCustomDistribution {
+        let submetric =
+            CustomDistributionMetric::new(meta, range_min, range_max, bucket_count, histogram_type);
+        LabeledMetric::new_inner(submetric, labels)
+    }
+}
 
+impl LabeledMemoryDistribution {
+    /// Creates a new labeled memory distribution.
+    ///
+    /// Samples for every label are interpreted in the given `memory_unit`.
+    /// See [`get`](LabeledMetric::get) for information on how static or dynamic labels are handled.
+    pub fn new_memory_distribution(
+        meta: CommonMetricData,
+        memory_unit: MemoryUnit,
+        labels: Option<Vec<String>>,
+    ) -> LabeledMemoryDistribution {
+        let submetric = MemoryDistributionMetric::new(meta, memory_unit);
+        LabeledMetric::new_inner(submetric, labels)
+    }
+}
+
+impl<T> LabeledMetric<T>
+where
+    T: MetricType + Clone,
+{
     fn new_inner(submetric: T, labels: Option<Vec<String>>) -> LabeledMetric<T> {
         let label_map = Default::default();
         LabeledMetric {
@@ -221,8 +275,12 @@ where
     /// only the first 16 unique labels will be used.
     /// After that, any additional labels will be recorded under the special `OTHER_LABEL` label.
     ///
-    /// Labels must be `snake_case` and less than 30 characters.
+    /// Labels must be `snake_case` and at most 61 bytes long, unless configured otherwise.
     /// If an invalid label is used, the metric will be recorded in the special `OTHER_LABEL` label.
+    ///
+    /// Once the configured maximum number of distinct dynamic labels were stored,
+    /// any other label is recorded under `OTHER_LABEL` too,
+    /// which is counted in `glean.error.label_overflow`.
     pub fn get<S: AsRef<str>>(&self, label: S) -> Arc<T> {
         let label = label.as_ref();
 
@@ -276,6 +334,42 @@ where
             test_get_num_recorded_errors(glean, self.submetric.meta(), error).unwrap_or(0)
         })
     }
+
+    /// Clears the stored values for all labels of this metric.
+    ///
+    /// This is dispatched, so values recorded before this call are cleared,
+    /// values recorded after it are kept.
+    pub fn reset(&self) {
+        let meta = self.submetric.meta().clone();
+        crate::launch_with_glean(move |glean| reset_labels(glean, &meta));
+    }
+
+    /// Clears the stored values for all labels of this metric, synchronously.
+    pub fn reset_sync(&self, glean: &Glean) {
+        reset_labels(glean, self.submetric.meta());
+    }
+}
+
+/// Removes the values stored for any label of the labeled metric described by `meta`.
+fn reset_labels(glean: &Glean, meta: &CommonMetricData) {
+    let prefix = format!("{}/", meta.base_identifier());
+    for ping_name in &meta.send_in_pings {
+        let mut identifiers = vec![];
+        glean
+            .storage()
+            .iter_store_from(meta.lifetime, ping_name, Some(&prefix), |id, _| {
+                identifiers.push(String::from_utf8_lossy(id).into_owned());
+            });
+        for identifier in identifiers {
+            if let Err(e) =
+                glean
+                    .storage()
+                    .remove_single_metric(meta.lifetime, ping_name, &identifier)
+            {
+                log::warn!("Failed to reset metric {}: {}", identifier, e);
+            }
+        }
+    }
 }
 
 /// Combines a metric's base identifier and label
@@ -327,13 +421,23 @@ pub fn validate_dynamic_label(
             .iter_store_from(lifetime, store, Some(prefix), &mut snapshotter);
     }
 
+    let max_label_length = glean.max_label_length();
     let error = if label_count >= MAX_LABELS {
         true
-    } else if label.len() > MAX_LABEL_LENGTH {
+    } else if label_count >= glean.max_distinct_labels() {
+        log::warn!(
+            "{}: too many distinct labels, recording '{}' as '{}'",
+            base_identifier,
+            label,
+            OTHER_LABEL
+        );
+        crate::glean_metrics::error::label_overflow.add_sync(glean, 1);
+        true
+    } else if label.len() > max_label_length {
         let msg = format!(
             "label length {} exceeds maximum of {}",
             label.len(),
-            MAX_LABEL_LENGTH
+            max_label_length
         );
         record_error(glean, meta, ErrorType::InvalidLabel, msg, None);
         true
@@ -52,6 +52,24 @@ impl MetricType for MemoryDistributionMetric {
     fn meta(&self) -> &CommonMetricData {
         &self.meta
     }
+
+    fn with_name(&self, name: String) -> Self {
+        let mut meta = (*self.meta).clone();
+        meta.name = name;
+        Self {
+            meta: Arc::new(meta),
+            memory_unit: self.memory_unit,
+        }
+    }
+
+    fn with_dynamic_label(&self, label: String) -> Self {
+        let mut meta = (*self.meta).clone();
+        meta.dynamic_label = Some(label);
+        Self {
+            meta: Arc::new(meta),
+            memory_unit: self.memory_unit,
+        }
+    }
 }
 
 // IMPORTANT:
@@ -61,6 +79,7 @@ impl MetricType for MemoryDistributionMetric {
 impl MemoryDistributionMetric {
     /// Creates a new memory distribution metric.
     pub fn new(meta: CommonMetricData, memory_unit: MemoryUnit) -> Self {
+        meta.register_descriptor("memory_distribution");
         Self {
             meta: Arc::new(meta),
             memory_unit,
@@ -79,8 +98,12 @@ impl MemoryDistributionMetric {
     /// Values bigger than 1 Terabyte (2<sup>40</sup> bytes) are truncated
     /// and an [`ErrorType::InvalidValue`] error is recorded.
     pub fn accumulate(&self, sample: i64) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.accumulate_sync(glean, sample))
+        crate::launch_recording(move |glean| metric.accumulate_sync(glean, sample))
     }
 
     /// Accumulates the provided sample in the metric synchronously.
@@ -111,19 +134,17 @@ impl MemoryDistributionMetric {
             sample = MAX_BYTES;
         }
 
-        glean
-            .storage()
-            .record_with(glean, &self.meta, |old_value| match old_value {
-                Some(Metric::MemoryDistribution(mut hist)) => {
-                    hist.accumulate(sample);
-                    Metric::MemoryDistribution(hist)
-                }
-                _ => {
-                    let mut hist = Histogram::functional(LOG_BASE, BUCKETS_PER_MAGNITUDE);
-                    hist.accumulate(sample);
-                    Metric::MemoryDistribution(hist)
-                }
-            });
+        let mut merged = 0;
+        glean.storage().record_with(glean, &self.meta, |old_value| {
+            let mut hist = match old_value {
+                Some(Metric::MemoryDistribution(hist)) => hist,
+                _ => Histogram::functional(LOG_BASE, BUCKETS_PER_MAGNITUDE),
+            };
+            hist.accumulate(sample);
+            merged += glean.bound_distribution(&mut hist);
+            Metric::MemoryDistribution(hist)
+        });
+        glean.record_merged_buckets(merged);
     }
 
     /// Accumulates the provided signed samples in the metric.
@@ -151,7 +172,7 @@ impl MemoryDistributionMetric {
     /// and an [`ErrorType::InvalidValue`] error is recorded.
     pub fn accumulate_samples(&self, samples: Vec<i64>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.accumulate_samples_sync(glean, samples))
+        crate::launch_recording(move |glean| metric.accumulate_samples_sync(glean, samples))
     }
 
     /// Accumulates the provided signed samples in the metric synchronously.
@@ -165,6 +186,7 @@ impl MemoryDistributionMetric {
 
         let mut num_negative_samples = 0;
         let mut num_too_log_samples = 0;
+        let mut merged = 0;
 
         glean.storage().record_with(glean, &self.meta, |old_value| {
             let mut hist = match old_value {
@@ -186,8 +208,10 @@ impl MemoryDistributionMetric {
                     hist.accumulate(sample);
                 }
             }
+            merged += glean.bound_distribution(&mut hist);
             Metric::MemoryDistribution(hist)
         });
+        glean.record_merged_buckets(merged);
 
         if num_negative_samples > 0 {
             let msg = format!("Accumulated {} negative samples", num_negative_samples);
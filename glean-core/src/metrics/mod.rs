@@ -11,12 +11,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 
 mod boolean;
+mod clock;
 mod counter;
 mod custom_distribution;
 mod datetime;
 mod denominator;
 mod event;
 mod experiment;
+mod json;
 pub(crate) mod labeled;
 mod memory_distribution;
 mod memory_unit;
@@ -25,6 +27,8 @@ mod ping;
 mod quantity;
 mod rate;
 mod recorded_experiment;
+mod ring_buffer;
+mod signed_counter;
 mod string;
 mod string_list;
 mod text;
@@ -35,26 +39,37 @@ mod url;
 mod uuid;
 
 pub use crate::event_database::RecordedEvent;
-use crate::histogram::{Functional, Histogram, PrecomputedExponential, PrecomputedLinear};
+use crate::histogram::{
+    Functional, Histogram, PrecomputedBuckets, PrecomputedExponential, PrecomputedLinear,
+};
 pub use crate::metrics::datetime::Datetime;
 use crate::util::get_iso_time_string;
 use crate::CommonMetricData;
 use crate::Glean;
+use crate::{MetricObserver, ObserverId};
 
 pub use self::boolean::BooleanMetric;
+pub use self::clock::{Clock, SystemClock};
 pub use self::counter::CounterMetric;
 pub use self::custom_distribution::CustomDistributionMetric;
 pub use self::datetime::DatetimeMetric;
 pub use self::denominator::DenominatorMetric;
 pub use self::event::EventMetric;
 pub(crate) use self::experiment::ExperimentMetric;
-pub use self::labeled::{LabeledBoolean, LabeledCounter, LabeledMetric, LabeledString};
+pub use self::json::MetricRef;
+pub use self::labeled::{
+    LabeledBoolean, LabeledCounter, LabeledCustomDistribution, LabeledMemoryDistribution,
+    LabeledMetric, LabeledString,
+};
 pub use self::memory_distribution::MemoryDistributionMetric;
 pub use self::memory_unit::MemoryUnit;
 pub use self::numerator::NumeratorMetric;
-pub use self::ping::PingType;
+pub use self::ping::{PingPriority, PingType};
+pub(crate) use self::ping::{MAX_METADATA_KEY_LENGTH, MAX_METADATA_VALUE_LENGTH};
 pub use self::quantity::QuantityMetric;
 pub use self::rate::{Rate, RateMetric};
+pub use self::ring_buffer::{RecordValue, RingBufferMetric};
+pub use self::signed_counter::SignedCounterMetric;
 pub use self::string::StringMetric;
 pub use self::string_list::StringListMetric;
 pub use self::text::TextMetric;
@@ -137,6 +152,11 @@ pub enum Metric {
     Url(String),
     /// A Text metric. See [`TextMetric`] for more information.
     Text(String),
+    /// A signed counter metric. See [`SignedCounterMetric`] for more information.
+    SignedCounter(i64),
+    /// A custom distribution with buckets computed by a custom bucketing function.
+    /// See [`CustomDistributionMetric::new_with_bucketer`] for more information.
+    CustomDistributionBucketed(Histogram<PrecomputedBuckets>),
 }
 
 /// A [`MetricType`] describes common behavior across all metrics.
@@ -160,12 +180,115 @@ pub trait MetricType {
         unimplemented!()
     }
 
+    /// Creates a sub-metric of this metric recording under `label`,
+    /// chosen at runtime.
+    ///
+    /// The sub-metric is stored and reported like the label of a labeled metric,
+    /// e.g. in `labeled_counter` for a counter.
+    /// The label is validated when recording, like a dynamic label of a [`LabeledMetric`]:
+    /// labels that aren't `snake_case`, are too long, or come after 16 others were stored,
+    /// are recorded under `__other__` with an `InvalidLabel` error.
+    ///
+    /// Unlike [`LabeledMetric::get`], this doesn't support a static list of labels,
+    /// nor does it keep the sub-metrics around, so labels it creates
+    /// don't count towards the maximum number of distinct labels of a [`LabeledMetric`].
+    /// Prefer a [`LabeledMetric`] for metrics declared as labeled.
+    ///
+    /// # Panics
+    ///
+    /// Panics for metric types that can't be labeled, e.g. events.
+    fn with_label<S: AsRef<str>>(&self, label: S) -> Self
+    where
+        Self: Sized,
+    {
+        self.with_dynamic_label(label.as_ref().to_string())
+    }
+
     /// Whether this metric should currently be recorded
     ///
     /// This depends on the metrics own state, as determined by its metadata,
-    /// and whether upload is enabled on the Glean object.
+    /// whether upload is enabled on the Glean object
+    /// and whether any of the pings it is sent in is enabled.
+    ///
+    /// Recording into a metric of a category not allowed by the configuration
+    /// is counted in `glean.error.disallowed_category`.
     fn should_record(&self, glean: &Glean) -> bool {
-        glean.is_upload_enabled() && self.meta().should_record()
+        let meta = self.meta();
+        // Glean's own metrics are never restricted.
+        if !meta.category.is_empty()
+            && !meta.is_internal()
+            && !glean.is_category_allowed(&meta.category)
+        {
+            if glean.is_upload_enabled() {
+                crate::glean_metrics::error::disallowed_category.add_sync(glean, 1);
+            }
+            return false;
+        }
+
+        glean.is_upload_enabled()
+            && self.meta().should_record()
+            // Pausing doesn't stop the bookkeeping of Glean's own metrics.
+            && (!glean.is_recording_paused()
+                || self.meta().category.is_empty()
+                || self.meta().is_internal())
+            && (!glean.has_disabled_pings()
+                || self
+                    .meta()
+                    .send_in_pings
+                    .iter()
+                    .any(|ping| glean.is_ping_enabled(ping)))
+    }
+
+    /// Clears the stored value of this metric from all the pings it is sent in.
+    ///
+    /// This applies immediately, regardless of the metric's lifetime.
+    fn reset_sync(&self, glean: &Glean) {
+        let meta = self.meta();
+        let identifier = meta.identifier(glean);
+        for ping_name in &meta.send_in_pings {
+            if let Err(e) =
+                glean
+                    .storage()
+                    .remove_single_metric(meta.lifetime, ping_name, &identifier)
+            {
+                log::warn!("Failed to reset metric {}: {}", identifier, e);
+            }
+        }
+    }
+
+    /// Clears the stored value of this metric from all the pings it is sent in.
+    ///
+    /// This is dispatched, so values recorded before this call are cleared,
+    /// values recorded after it are kept.
+    fn reset(&self)
+    where
+        Self: Clone + Send + 'static,
+    {
+        let metric = self.clone();
+        crate::launch_with_glean(move |glean| metric.reset_sync(glean));
+    }
+
+    /// Registers an observer notified whenever a new value is stored for this metric.
+    ///
+    /// The observer is called with the new value on the dispatcher thread,
+    /// after the value was written.
+    /// Recordings that are rejected, e.g. because of an invalid value, don't trigger it.
+    /// It must not block on Glean, e.g. by calling `test_get_value`.
+    ///
+    /// # Returns
+    ///
+    /// The id to pass to [`glean_remove_metric_observer`](crate::glean_remove_metric_observer)
+    /// to remove the observer again.
+    fn observe(&self, observer: MetricObserver) -> ObserverId {
+        let id = ObserverId::next();
+        let meta = self.meta().clone();
+        crate::launch_with_glean_mut(move |glean| {
+            let identifier = meta.identifier(glean);
+            glean
+                .observers
+                .add_metric_observer(identifier, id, observer);
+        });
+        id
     }
 }
 
@@ -194,6 +317,8 @@ impl Metric {
             Metric::MemoryDistribution(_) => "memory_distribution",
             Metric::Jwe(_) => "jwe",
             Metric::Text(_) => "text",
+            Metric::SignedCounter(_) => "signed_counter",
+            Metric::CustomDistributionBucketed(_) => "custom_distribution",
         }
     }
 
@@ -223,6 +348,8 @@ impl Metric {
             Metric::MemoryDistribution(hist) => json!(memory_distribution::snapshot(hist)),
             Metric::Jwe(s) => json!(s),
             Metric::Text(s) => json!(s),
+            Metric::SignedCounter(c) => json!(c),
+            Metric::CustomDistributionBucketed(hist) => json!(custom_distribution::snapshot(hist)),
         }
     }
 }
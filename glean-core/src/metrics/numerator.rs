@@ -28,7 +28,10 @@ impl MetricType for NumeratorMetric {
 impl NumeratorMetric {
     /// The public constructor used by automatically generated metrics.
     pub fn new(meta: CommonMetricData) -> Self {
-        Self(Arc::new(RateMetric::new(meta)))
+        let rate = RateMetric::new(meta);
+        // Described as a numerator rather than the rate it's built on.
+        rate.meta().register_descriptor("numerator");
+        Self(Arc::new(rate))
     }
 
     /// Increases the numerator by `amount`.
@@ -42,7 +45,7 @@ impl NumeratorMetric {
     /// Logs an error if the `amount` is negative.
     pub fn add_to_numerator(&self, amount: i32) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_to_numerator_sync(glean, amount));
+        crate::launch_recording(move |glean| metric.add_to_numerator_sync(glean, amount));
     }
 
     #[doc(hidden)]
@@ -2,14 +2,53 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::collections::BTreeMap;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::{Deserialize, Serialize};
 
 use crate::ping::PingMaker;
+use crate::upload::PingMetadata;
+use crate::util::local_now_with_offset;
 use crate::Glean;
 
 use uuid::Uuid;
 
+/// The maximum length of a ping metadata key, in bytes.
+pub(crate) const MAX_METADATA_KEY_LENGTH: usize = 40;
+
+/// The maximum length of a ping metadata value, in bytes.
+pub(crate) const MAX_METADATA_VALUE_LENGTH: usize = 100;
+
+/// How far in the future an explicit submission time may be, in seconds,
+/// to allow for clock differences between the source of the timestamp and this device.
+const SUBMITTED_AT_TOLERANCE_SECS: i64 = 60;
+
+/// The upload priority of a ping.
+///
+/// Pending pings of a higher priority are uploaded before those of a lower priority.
+/// Pings of the same priority are uploaded in the order they were submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PingPriority {
+    /// Bulk data that can wait for other pings to be uploaded first.
+    Low,
+    /// The default priority.
+    Normal,
+    /// Pings that should be uploaded as soon as possible, e.g. privacy-critical ones.
+    High,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] on variants needs Rust 1.62, above our MSRV
+impl Default for PingPriority {
+    fn default() -> Self {
+        PingPriority::Normal
+    }
+}
+
 /// Stores information about a ping.
 ///
 /// This is required so that given metric data queued on disk we can send
@@ -26,6 +65,12 @@ struct InnerPing {
     pub send_if_empty: bool,
     /// The "reason" codes that this ping can send
     pub reason_codes: Vec<String>,
+    /// The upload priority of this ping
+    pub priority: PingPriority,
+    /// Static metadata included in every instance of this ping
+    pub metadata: RwLock<BTreeMap<String, String>>,
+    /// For how long after submission an instance of this ping may still be uploaded
+    pub upload_deadline: RwLock<Option<std::time::Duration>>,
 }
 
 impl fmt::Debug for PingType {
@@ -35,6 +80,9 @@ impl fmt::Debug for PingType {
             .field("include_client_id", &self.0.include_client_id)
             .field("send_if_empty", &self.0.send_if_empty)
             .field("reason_codes", &self.0.reason_codes)
+            .field("priority", &self.0.priority)
+            .field("metadata", &self.0.metadata)
+            .field("upload_deadline", &self.0.upload_deadline)
             .finish()
     }
 }
@@ -58,12 +106,38 @@ impl PingType {
         include_client_id: bool,
         send_if_empty: bool,
         reason_codes: Vec<String>,
+    ) -> Self {
+        Self::new_with_priority(
+            name,
+            include_client_id,
+            send_if_empty,
+            reason_codes,
+            PingPriority::default(),
+        )
+    }
+
+    /// Creates a new ping type with the given upload priority.
+    ///
+    /// See [`PingType::new`] for the other arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The upload priority of this ping.
+    pub fn new_with_priority<A: Into<String>>(
+        name: A,
+        include_client_id: bool,
+        send_if_empty: bool,
+        reason_codes: Vec<String>,
+        priority: PingPriority,
     ) -> Self {
         let this = Self(Arc::new(InnerPing {
             name: name.into(),
             include_client_id,
             send_if_empty,
             reason_codes,
+            priority,
+            metadata: RwLock::new(BTreeMap::new()),
+            upload_deadline: RwLock::new(None),
         }));
 
         // Register this ping.
@@ -73,7 +147,8 @@ impl PingType {
         this
     }
 
-    pub(crate) fn name(&self) -> &str {
+    /// The name of the ping.
+    pub fn name(&self) -> &str {
         &self.0.name
     }
 
@@ -85,6 +160,90 @@ impl PingType {
         self.0.send_if_empty
     }
 
+    pub(crate) fn priority(&self) -> PingPriority {
+        self.0.priority
+    }
+
+    /// The reason codes this ping declared as valid.
+    ///
+    /// Submitting the ping with any other reason drops the reason.
+    /// Submitting it without a reason is always allowed.
+    pub fn reason_codes(&self) -> &[String] {
+        &self.0.reason_codes
+    }
+
+    /// Attaches static metadata to every instance of this ping submitted from now on.
+    ///
+    /// The metadata is included in the `metadata` object of the ping payload.
+    /// Setting a key again replaces its value.
+    ///
+    /// Keys must be non-empty and at most 40 bytes long, values at most 100 bytes long.
+    /// Invalid entries are logged and ignored.
+    pub fn set_metadata<K: Into<String>, V: Into<String>>(&self, key: K, value: V) {
+        let key = key.into();
+        let value = value.into();
+
+        if key.is_empty() || key.len() > MAX_METADATA_KEY_LENGTH {
+            log::warn!(
+                "Invalid metadata key '{}' for ping '{}'. Ignoring it.",
+                key,
+                self.0.name
+            );
+            return;
+        }
+        if value.len() > MAX_METADATA_VALUE_LENGTH {
+            log::warn!(
+                "Metadata value for key '{}' of ping '{}' is too long. Ignoring it.",
+                key,
+                self.0.name
+            );
+            return;
+        }
+
+        self.0
+            .metadata
+            .write()
+            .expect("Can't write to the ping metadata.")
+            .insert(key, value);
+    }
+
+    /// The static metadata of this ping, if any was set.
+    pub(crate) fn metadata(&self) -> Option<BTreeMap<String, String>> {
+        let metadata = self
+            .0
+            .metadata
+            .read()
+            .expect("Can't read the ping metadata.");
+        if metadata.is_empty() {
+            None
+        } else {
+            Some(metadata.clone())
+        }
+    }
+
+    /// Drops instances of this ping submitted from now on
+    /// if they weren't uploaded within `deadline` of their submission.
+    ///
+    /// Use this for time-sensitive pings that are useless when stale.
+    /// Dropped pings are counted in `glean.upload.expired`.
+    /// Deletion-request pings never expire.
+    pub fn set_upload_deadline(&self, deadline: std::time::Duration) {
+        *self
+            .0
+            .upload_deadline
+            .write()
+            .expect("Can't write to the ping upload deadline.") = Some(deadline);
+    }
+
+    /// The upload deadline of this ping, if one was set.
+    pub(crate) fn upload_deadline(&self) -> Option<std::time::Duration> {
+        *self
+            .0
+            .upload_deadline
+            .read()
+            .expect("Can't read the ping upload deadline.")
+    }
+
     /// Submits the ping for eventual uploading.
     ///
     /// The ping content is assembled as soon as possible, but upload is not
@@ -98,13 +257,37 @@ impl PingType {
     /// * `reason` - the reason the ping was triggered. Included in the
     ///   `ping_info.reason` part of the payload.
     pub fn submit(&self, reason: Option<String>) {
+        self.launch_submit(reason, None)
+    }
+
+    /// **Debug-only API**
+    ///
+    /// Submits the ping for eventual uploading,
+    /// reporting `submitted_at` as its `ping_info.end_time` instead of the current time.
+    ///
+    /// This is meant for replaying historical data and for deterministic tests of ping contents.
+    /// The ping is not submitted if `submitted_at` lies in the future
+    /// by more than a minute.
+    /// It doesn't change the `ping_info.start_time` of the pings submitted after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - the reason the ping was triggered. Included in the
+    ///   `ping_info.reason` part of the payload.
+    /// * `submitted_at` - the time the ping is reported to have been submitted at.
+    pub fn submit_at(&self, reason: Option<String>, submitted_at: DateTime<FixedOffset>) {
+        self.launch_submit(reason, Some(submitted_at))
+    }
+
+    fn launch_submit(&self, reason: Option<String>, submitted_at: Option<DateTime<FixedOffset>>) {
         let ping = PingType(Arc::clone(&self.0));
 
         // Need to separate access to the Glean object from access to global state.
         // `trigger_upload` itself might lock the Glean object and we need to avoid that deadlock.
-        crate::dispatcher::launch(|| {
-            let sent =
-                crate::core::with_glean(move |glean| ping.submit_sync(glean, reason.as_deref()));
+        crate::dispatcher::launch(move || {
+            let sent = crate::core::with_glean(move |glean| {
+                ping.submit_at_sync(glean, reason.as_deref(), submitted_at)
+            });
             if sent {
                 let state = crate::global_state().lock().unwrap();
                 if let Err(e) = state.callbacks.trigger_upload() {
@@ -121,6 +304,24 @@ impl PingType {
     /// Whether the ping was succesfully assembled and queued.
     #[doc(hidden)]
     pub fn submit_sync(&self, glean: &Glean, reason: Option<&str>) -> bool {
+        self.submit_at_sync(glean, reason, None)
+    }
+
+    /// Collects and submits a ping for eventual uploading,
+    /// optionally with the given submission time instead of now.
+    ///
+    /// See [`submit_at`](Self::submit_at) for details.
+    ///
+    /// # Returns
+    ///
+    /// Whether the ping was succesfully assembled and queued.
+    #[doc(hidden)]
+    pub fn submit_at_sync(
+        &self,
+        glean: &Glean,
+        reason: Option<&str>,
+        submitted_at: Option<DateTime<FixedOffset>>,
+    ) -> bool {
         if !glean.is_upload_enabled() {
             log::info!("Glean disabled: not submitting any pings.");
             return false;
@@ -128,6 +329,31 @@ impl PingType {
 
         let ping = &self.0;
 
+        if !glean.is_ping_enabled(&ping.name) {
+            log::info!("The {} ping is disabled: not submitting it.", ping.name);
+            return false;
+        }
+
+        if glean.is_schema_circuit_open(&ping.name) {
+            log::info!(
+                "The {} ping repeatedly failed schema validation: not submitting it.",
+                ping.name
+            );
+            return false;
+        }
+
+        if let Some(submitted_at) = submitted_at {
+            let latest = local_now_with_offset() + Duration::seconds(SUBMITTED_AT_TOLERANCE_SECS);
+            if submitted_at > latest {
+                log::error!(
+                    "Submission time {} of the {} ping is in the future: not submitting it.",
+                    submitted_at,
+                    ping.name
+                );
+                return false;
+            }
+        }
+
         // Allowing `clippy::manual_filter`.
         // This causes a false positive.
         // We have a side-effect in the `else` branch,
@@ -149,7 +375,26 @@ impl PingType {
         let ping_maker = PingMaker::new();
         let doc_id = Uuid::new_v4().to_string();
         let url_path = glean.make_path(&ping.name, &doc_id);
-        match ping_maker.collect(glean, self, corrected_reason, &doc_id, &url_path) {
+        let collection_start = Instant::now();
+        let collected = ping_maker.collect_at(
+            glean,
+            self,
+            corrected_reason,
+            &doc_id,
+            &url_path,
+            submitted_at,
+        );
+        let collection_time = collection_start.elapsed();
+        if collection_time > glean.slow_assembly_threshold() {
+            log::warn!(
+                "Assembling the ping '{}' took {:?}.",
+                ping.name,
+                collection_time
+            );
+            glean.additional_metrics.slow_assembly.add_sync(glean, 1);
+        }
+
+        match collected {
             None => {
                 log::info!(
                     "No content for ping '{}', therefore no ping queued.",
@@ -157,6 +402,13 @@ impl PingType {
                 );
                 false
             }
+            Some(ping)
+                if glean
+                    .upload_manager
+                    .is_duplicate(glean, ping.name, &ping.content) =>
+            {
+                false
+            }
             Some(ping) => {
                 // This metric is recorded *after* the ping is collected (since
                 // that is the only way to know *if* it will be submitted). The
@@ -168,9 +420,36 @@ impl PingType {
                     .get(ping.name)
                     .add_sync(glean, 1);
 
-                if let Err(e) = ping_maker.store_ping(glean.get_data_path(), &ping) {
+                // `ping.content` is already a `JsonValue`, so this can't fail.
+                let size = ::serde_json::to_string(&ping.content)
+                    .expect("ping serialization failed")
+                    .len();
+                glean
+                    .additional_metrics
+                    .assembled_ping_size
+                    .accumulate_sync(glean, size as i64);
+
+                if glean.is_dry_run() {
+                    // `ping.content` is already a `JsonValue`, so this can't fail.
+                    let content = ::serde_json::to_string_pretty(&ping.content)
+                        .expect("ping serialization failed");
+                    log::info!(
+                        "Dry run: not storing or uploading the ping '{}' to {}\n{}",
+                        ping.name,
+                        ping.url_path,
+                        content
+                    );
+                    return true;
+                }
+
+                if let Err(e) = ping_maker.store_ping(
+                    glean.get_data_path(),
+                    &ping,
+                    glean.pending_ping_filename_format(),
+                ) {
                     log::warn!("IO error while writing ping to file: {}. Enqueuing upload of what we have in memory.", e);
                     glean.additional_metrics.io_errors.add_sync(glean, 1);
+                    glean.additional_metrics.io_failure.add_sync(glean, 1);
                     // `serde_json::to_string` only fails if serialization of the content
                     // fails or it contains maps with non-string keys.
                     // However `ping.content` is already a `JsonValue`,
@@ -182,7 +461,11 @@ impl PingType {
                         ping.doc_id,
                         ping.url_path,
                         &content,
-                        Some(ping.headers),
+                        Some(PingMetadata {
+                            headers: ping.headers,
+                            priority: ping.priority,
+                            expires_at: ping.expires_at,
+                        }),
                     );
                     return true;
                 }
@@ -30,6 +30,7 @@ impl MetricType for QuantityMetric {
 impl QuantityMetric {
     /// Creates a new quantity metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("quantity");
         Self { meta }
     }
 
@@ -43,8 +44,12 @@ impl QuantityMetric {
     ///
     /// Logs an error if the `value` is negative.
     pub fn set(&self, value: i64) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, value))
     }
 
     /// Sets the value synchronously. Must be non-negative.
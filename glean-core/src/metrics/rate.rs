@@ -52,6 +52,7 @@ impl MetricType for RateMetric {
 impl RateMetric {
     /// Creates a new rate metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("rate");
         Self { meta }
     }
 
@@ -67,7 +68,7 @@ impl RateMetric {
     /// Logs an error if the `amount` is negative.
     pub fn add_to_numerator(&self, amount: i32) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_to_numerator_sync(glean, amount))
+        crate::launch_recording(move |glean| metric.add_to_numerator_sync(glean, amount))
     }
 
     #[doc(hidden)]
@@ -107,7 +108,7 @@ impl RateMetric {
     /// Logs an error if the `amount` is negative.
     pub fn add_to_denominator(&self, amount: i32) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_to_denominator_sync(glean, amount))
+        crate::launch_recording(move |glean| metric.add_to_denominator_sync(glean, amount))
     }
 
     #[doc(hidden)]
@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::{BooleanMetric, CounterMetric, MetricType, QuantityMetric, StringMetric};
+use crate::Glean;
+
+/// A metric whose recordings can be kept in a [`RingBufferMetric`].
+pub trait RecordValue: MetricType + Clone + Send + 'static {
+    /// The type of a single recording.
+    type Value: Clone + fmt::Debug + Send + 'static;
+
+    /// Records a value into the metric synchronously.
+    fn record_value_sync(&self, glean: &Glean, value: Self::Value);
+}
+
+macro_rules! impl_record_value {
+    ($($metric:ty => $value:ty, $record:ident);* $(;)?) => {
+        $(
+            impl RecordValue for $metric {
+                type Value = $value;
+
+                fn record_value_sync(&self, glean: &Glean, value: $value) {
+                    self.$record(glean, value)
+                }
+            }
+        )*
+    };
+}
+
+impl_record_value!(
+    BooleanMetric => bool, set_sync;
+    CounterMetric => i32, add_sync;
+    QuantityMetric => i64, set_sync;
+    StringMetric => String, set_sync;
+);
+
+/// A metric that also keeps its last recorded values in memory, for debugging.
+///
+/// Values are recorded into the wrapped metric as usual,
+/// so pings carry its latest or aggregated value.
+/// The history is never persisted nor sent in a ping.
+#[derive(Clone)]
+pub struct RingBufferMetric<M: RecordValue> {
+    metric: M,
+    size: usize,
+    history: Arc<Mutex<VecDeque<M::Value>>>,
+}
+
+impl<M: RecordValue + fmt::Debug> fmt::Debug for RingBufferMetric<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingBufferMetric")
+            .field("metric", &self.metric)
+            .field("size", &self.size)
+            .field("history", &self.history)
+            .finish()
+    }
+}
+
+impl<M: RecordValue> RingBufferMetric<M> {
+    /// Creates a new ring buffer metric, keeping the last `size` values recorded into `metric`.
+    ///
+    /// A `size` of 0 is treated as 1.
+    pub fn new(metric: M, size: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            metric,
+            size,
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(size))),
+        }
+    }
+
+    /// The wrapped metric.
+    pub fn metric(&self) -> &M {
+        &self.metric
+    }
+
+    /// Records a value into the wrapped metric and the history.
+    pub fn record(&self, value: M::Value) {
+        let metric = self.clone();
+        crate::launch_recording(move |glean| metric.record_sync(glean, value))
+    }
+
+    /// Records a value into the wrapped metric and the history synchronously.
+    ///
+    /// Values are kept in the history even if the wrapped metric rejects them as invalid,
+    /// but not if the metric is disabled.
+    #[doc(hidden)]
+    pub fn record_sync(&self, glean: &Glean, value: M::Value) {
+        if !self.metric.should_record(glean) {
+            return;
+        }
+
+        self.metric.record_value_sync(glean, value.clone());
+
+        let mut history = self.history.lock().unwrap();
+        if history.len() == self.size {
+            history.pop_front();
+        }
+        history.push_back(value);
+    }
+
+    /// Gets the recorded values, oldest first.
+    #[doc(hidden)]
+    pub fn get_history(&self) -> Vec<M::Value> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// **Test-only API (exported for FFI purposes).**
+    ///
+    /// Gets the last recorded values, oldest first.
+    ///
+    /// This doesn't clear the history.
+    pub fn test_get_history(&self) -> Vec<M::Value> {
+        crate::block_on_dispatcher();
+        self.get_history()
+    }
+}
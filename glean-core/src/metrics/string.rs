@@ -52,6 +52,7 @@ impl MetricType for StringMetric {
 impl StringMetric {
     /// Creates a new string metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("string");
         Self {
             meta: Arc::new(meta),
         }
@@ -67,8 +68,12 @@ impl StringMetric {
     ///
     /// Truncates the value if it is longer than `MAX_LENGTH_VALUE` bytes and logs an error.
     pub fn set(&self, value: String) {
+        if self.meta.is_throttled() {
+            return;
+        }
+
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, &value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, &value))
     }
 
     /// Sets to the specified value synchronously.
@@ -158,6 +163,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         let sample_string = "0123456789".repeat(11);
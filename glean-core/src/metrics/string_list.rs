@@ -38,6 +38,7 @@ impl MetricType for StringListMetric {
 impl StringListMetric {
     /// Creates a new string list metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("string_list");
         Self {
             meta: Arc::new(meta),
         }
@@ -54,7 +55,7 @@ impl StringListMetric {
     /// Truncates the value if it is longer than `MAX_STRING_LENGTH` bytes and logs an error.
     pub fn add(&self, value: String) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.add_sync(glean, value))
+        crate::launch_recording(move |glean| metric.add_sync(glean, value))
     }
 
     /// Adds a new string to the list synchronously
@@ -106,7 +107,7 @@ impl StringListMetric {
     /// Truncates any value in the list if it is longer than `MAX_STRING_LENGTH` and logs an error.
     pub fn set(&self, values: Vec<String>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, values))
+        crate::launch_recording(move |glean| metric.set_sync(glean, values))
     }
 
     /// Sets to a specific list of strings synchronously.
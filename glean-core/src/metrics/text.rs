@@ -54,6 +54,7 @@ impl MetricType for TextMetric {
 impl TextMetric {
     /// Creates a new text metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("text");
         Self {
             meta: Arc::new(meta),
         }
@@ -71,7 +72,7 @@ impl TextMetric {
     /// and logs an error.
     pub fn set(&self, value: String) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, &value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, &value))
     }
 
     /// Sets to the specified value synchronously,
@@ -162,6 +163,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         let sample_string = "0123456789".repeat(200 * 1024);
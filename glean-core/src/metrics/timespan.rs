@@ -7,6 +7,7 @@ use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use crate::error_recording::{record_error, test_get_num_recorded_errors, ErrorType};
+use crate::metrics::clock::{Clock, SystemClock};
 use crate::metrics::time_unit::TimeUnit;
 use crate::metrics::Metric;
 use crate::metrics::MetricType;
@@ -27,6 +28,8 @@ pub struct TimespanMetric {
     meta: Arc<CommonMetricData>,
     time_unit: TimeUnit,
     start_time: Arc<RwLock<Option<u64>>>,
+    use_wall_clock: bool,
+    clock: Arc<dyn Clock>,
 }
 
 impl MetricType for TimespanMetric {
@@ -41,11 +44,61 @@ impl MetricType for TimespanMetric {
 // also declared in the related trait in `../traits/`.
 impl TimespanMetric {
     /// Creates a new timespan metric.
+    ///
+    /// It measures time with the monotonic clock.
     pub fn new(meta: CommonMetricData, time_unit: TimeUnit) -> Self {
+        Self::new_with_wall_clock(meta, time_unit, false)
+    }
+
+    /// Creates a new timespan metric, optionally measuring time with the wall clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `meta` - The metric's metadata.
+    /// * `time_unit` - The precision to record values with.
+    /// * `use_wall_clock` - Whether to measure with the wall clock instead of the monotonic clock.
+    ///   The monotonic clock might not advance while the device sleeps,
+    ///   so the wall clock measures the real elapsed time of timespans spanning sleep.
+    ///   But it jumps when the system time changes, e.g. through a network time sync,
+    ///   making timespans too long, or negative, which records an [`ErrorType::InvalidValue`] error.
+    pub fn new_with_wall_clock(
+        meta: CommonMetricData,
+        time_unit: TimeUnit,
+        use_wall_clock: bool,
+    ) -> Self {
+        meta.register_descriptor("timespan");
         Self {
             meta: Arc::new(meta),
             time_unit,
             start_time: Arc::new(RwLock::new(None)),
+            use_wall_clock,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Replaces the clock used by [`start`](TimespanMetric::start) and [`stop`](TimespanMetric::stop),
+    /// e.g. to simulate the passing of time in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time in nanoseconds, according to the clock this measures with.
+    fn now(&self) -> u64 {
+        if self.use_wall_clock {
+            self.clock.wall_clock_nanos()
+        } else {
+            self.clock.monotonic_nanos()
+        }
+    }
+
+    /// Creates a copy of this metric recording in a different time unit.
+    ///
+    /// The copy shares the running timer with the original metric.
+    pub(crate) fn with_time_unit(&self, time_unit: TimeUnit) -> Self {
+        Self {
+            time_unit,
+            ..self.clone()
         }
     }
 
@@ -56,10 +109,10 @@ impl TimespanMetric {
     /// [`set_stop`](TimespanMetric::set_stop)): in that case the original start
     /// time will be preserved.
     pub fn start(&self) {
-        let start_time = time::precise_time_ns();
+        let start_time = self.now();
 
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_start(glean, start_time));
+        crate::launch_recording(move |glean| metric.set_start(glean, start_time));
     }
 
     /// Set start time synchronously.
@@ -92,10 +145,10 @@ impl TimespanMetric {
     ///
     /// This will record an error if no [`set_start`](TimespanMetric::set_start) was called.
     pub fn stop(&self) {
-        let stop_time = time::precise_time_ns();
+        let stop_time = self.now();
 
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_stop(glean, stop_time));
+        crate::launch_recording(move |glean| metric.set_stop(glean, stop_time));
     }
 
     /// Set stop time synchronously.
@@ -173,7 +226,7 @@ impl TimespanMetric {
     /// * `elapsed` - The elapsed time to record.
     pub fn set_raw(&self, elapsed: Duration) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_raw_sync(glean, elapsed));
+        crate::launch_recording(move |glean| metric.set_raw_sync(glean, elapsed));
     }
 
     /// Explicitly sets the timespan value in nanoseconds.
@@ -187,9 +240,19 @@ impl TimespanMetric {
     /// [`set_raw`](TimespanMetric::set_raw) should generally be followed by
     /// sending a custom ping containing the timespan.
     ///
+    /// This is the timespan equivalent of
+    /// [`TimingDistributionMetric::accumulate_raw_samples_nanos`](crate::TimingDistributionMetric::accumulate_raw_samples_nanos):
+    /// the value is reported in the metric's time unit, truncated.
+    ///
     /// # Arguments
     ///
     /// * `elapsed_nanos` - The elapsed time to record, in nanoseconds.
+    ///   Negative values are recorded as 0.
+    ///
+    /// ## Notes
+    ///
+    /// Records an [`ErrorType::InvalidState`] error if the timespan is running
+    /// or already has a value, like [`set_raw`](TimespanMetric::set_raw).
     pub fn set_raw_nanos(&self, elapsed_nanos: i64) {
         let elapsed = Duration::from_nanos(elapsed_nanos.try_into().unwrap_or(0));
         self.set_raw(elapsed)
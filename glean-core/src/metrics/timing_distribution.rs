@@ -27,8 +27,19 @@ const BUCKETS_PER_MAGNITUDE: f64 = 8.0;
 // - `nanosecond` - 10 minutes
 // - `microsecond` - ~6.94 days
 // - `millisecond` - ~19 years
+//
+// Samples are stored in nanoseconds, so for coarser units
+// it is capped at `u64::MAX` nanoseconds (~584 years), see `max_sample_time`.
 const MAX_SAMPLE_TIME: u64 = 1000 * 1000 * 1000 * 60 * 10;
 
+/// The longest sample recorded for `time_unit`, in nanoseconds.
+///
+/// This is `MAX_SAMPLE_TIME` in `time_unit`,
+/// unless that isn't representable in nanoseconds.
+fn max_sample_time(time_unit: TimeUnit) -> u64 {
+    time_unit.as_nanos(1).saturating_mul(MAX_SAMPLE_TIME)
+}
+
 /// Identifier for a running timer.
 ///
 /// Its internals are considered private,
@@ -91,7 +102,11 @@ impl MetricType for TimingDistributionMetric {
 // also declared in the related trait in `../traits/`.
 impl TimingDistributionMetric {
     /// Creates a new timing distribution metric.
+    ///
+    /// Samples are stored in nanoseconds and recorded up to `MAX_SAMPLE_TIME` (600 billion)
+    /// of `time_unit`, or the longest time representable in nanoseconds (~584 years) if less.
     pub fn new(meta: CommonMetricData, time_unit: TimeUnit) -> Self {
+        meta.register_descriptor("timing_distribution");
         Self {
             meta: Arc::new(meta),
             time_unit,
@@ -148,7 +163,7 @@ impl TimingDistributionMetric {
     pub fn stop_and_accumulate(&self, id: TimerId) {
         let stop_time = time::precise_time_ns();
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_stop_and_accumulate(glean, id, stop_time));
+        crate::launch_recording(move |glean| metric.set_stop_and_accumulate(glean, id, stop_time));
     }
 
     fn set_stop(&self, id: TimerId, stop_time: u64) -> Result<u64, (ErrorType, &str)> {
@@ -194,7 +209,7 @@ impl TimingDistributionMetric {
         };
 
         let min_sample_time = self.time_unit.as_nanos(1);
-        let max_sample_time = self.time_unit.as_nanos(MAX_SAMPLE_TIME);
+        let max_sample_time = max_sample_time(self.time_unit);
 
         duration = if duration < min_sample_time {
             // If measurement is less than the minimum, just truncate. This is
@@ -215,19 +230,17 @@ impl TimingDistributionMetric {
             return;
         }
 
-        glean
-            .storage()
-            .record_with(glean, &self.meta, |old_value| match old_value {
-                Some(Metric::TimingDistribution(mut hist)) => {
-                    hist.accumulate(duration);
-                    Metric::TimingDistribution(hist)
-                }
-                _ => {
-                    let mut hist = Histogram::functional(LOG_BASE, BUCKETS_PER_MAGNITUDE);
-                    hist.accumulate(duration);
-                    Metric::TimingDistribution(hist)
-                }
-            });
+        let mut merged = 0;
+        glean.storage().record_with(glean, &self.meta, |old_value| {
+            let mut hist = match old_value {
+                Some(Metric::TimingDistribution(hist)) => hist,
+                _ => Histogram::functional(LOG_BASE, BUCKETS_PER_MAGNITUDE),
+            };
+            hist.accumulate(duration);
+            merged += glean.bound_distribution(&mut hist);
+            Metric::TimingDistribution(hist)
+        });
+        glean.record_merged_buckets(merged);
     }
 
     /// Aborts a previous [`start`](Self::start) call.
@@ -273,7 +286,7 @@ impl TimingDistributionMetric {
     /// are longer than `MAX_SAMPLE_TIME`.
     pub fn accumulate_samples(&self, samples: Vec<i64>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.accumulate_samples_sync(glean, samples))
+        crate::launch_recording(move |glean| metric.accumulate_samples_sync(glean, samples))
     }
 
     /// **Test-only API (exported for testing purposes).**
@@ -288,7 +301,9 @@ impl TimingDistributionMetric {
 
         let mut num_negative_samples = 0;
         let mut num_too_long_samples = 0;
-        let max_sample_time = self.time_unit.as_nanos(MAX_SAMPLE_TIME);
+        let nanos_per_unit = self.time_unit.as_nanos(1);
+        let max_sample_time = max_sample_time(self.time_unit);
+        let mut merged = 0;
 
         glean.storage().record_with(glean, &self.meta, |old_value| {
             let mut hist = match old_value {
@@ -300,26 +315,23 @@ impl TimingDistributionMetric {
                 if sample < 0 {
                     num_negative_samples += 1;
                 } else {
-                    let mut sample = sample as u64;
-
-                    // Check the range prior to converting the incoming unit to
-                    // nanoseconds, so we can compare against the constant
-                    // MAX_SAMPLE_TIME.
-                    if sample == 0 {
-                        sample = 1;
-                    } else if sample > MAX_SAMPLE_TIME {
-                        num_too_long_samples += 1;
-                        sample = MAX_SAMPLE_TIME;
-                    }
-
-                    sample = self.time_unit.as_nanos(sample);
+                    // Samples are in `time_unit`, converted to nanoseconds here.
+                    let sample = match nanos_per_unit.checked_mul((sample as u64).max(1)) {
+                        Some(sample) if sample <= max_sample_time => sample,
+                        _ => {
+                            num_too_long_samples += 1;
+                            max_sample_time
+                        }
+                    };
 
                     hist.accumulate(sample);
                 }
             }
 
+            merged += glean.bound_distribution(&mut hist);
             Metric::TimingDistribution(hist)
         });
+        glean.record_merged_buckets(merged);
 
         if num_negative_samples > 0 {
             let msg = format!("Accumulated {} negative samples", num_negative_samples);
@@ -359,7 +371,7 @@ impl TimingDistributionMetric {
     /// are longer than `MAX_SAMPLE_TIME`.
     pub fn accumulate_raw_samples_nanos(&self, samples: Vec<u64>) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| {
+        crate::launch_recording(move |glean| {
             metric.accumulate_raw_samples_nanos_sync(glean, &samples)
         })
     }
@@ -377,7 +389,8 @@ impl TimingDistributionMetric {
 
         let mut num_too_long_samples = 0;
         let min_sample_time = self.time_unit.as_nanos(1);
-        let max_sample_time = self.time_unit.as_nanos(MAX_SAMPLE_TIME);
+        let max_sample_time = max_sample_time(self.time_unit);
+        let mut merged = 0;
 
         glean.storage().record_with(glean, &self.meta, |old_value| {
             let mut hist = match old_value {
@@ -399,8 +412,10 @@ impl TimingDistributionMetric {
                 hist.accumulate(sample);
             }
 
+            merged += glean.bound_distribution(&mut hist);
             Metric::TimingDistribution(hist)
         });
+        glean.record_merged_buckets(merged);
 
         if num_too_long_samples > 0 {
             let msg = format!(
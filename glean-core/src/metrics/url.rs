@@ -37,6 +37,7 @@ impl MetricType for UrlMetric {
 impl UrlMetric {
     /// Creates a new string metric.
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("url");
         Self {
             meta: Arc::new(meta),
         }
@@ -71,7 +72,7 @@ impl UrlMetric {
     pub fn set<S: Into<String>>(&self, value: S) {
         let value = value.into();
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, value))
     }
 
     /// Sets to the specified stringified URL synchronously.
@@ -176,6 +177,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         let sample_url = "glean://test".to_string();
@@ -194,6 +199,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         // Whenever the URL is longer than our MAX_URL_LENGTH, we truncate the URL to the
@@ -232,6 +241,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         let test_url = "data:application/json";
@@ -256,6 +269,10 @@ mod test {
             lifetime: Lifetime::Application,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         });
 
         let incorrects = vec![
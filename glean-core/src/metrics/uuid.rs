@@ -34,6 +34,7 @@ impl MetricType for UuidMetric {
 impl UuidMetric {
     /// Creates a new UUID metric
     pub fn new(meta: CommonMetricData) -> Self {
+        meta.register_descriptor("uuid");
         Self {
             meta: Arc::new(meta),
         }
@@ -46,7 +47,7 @@ impl UuidMetric {
     /// * `value` - The [`Uuid`] to set the metric to.
     pub fn set(&self, value: String) {
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, &value))
+        crate::launch_recording(move |glean| metric.set_sync(glean, &value))
     }
 
     /// Sets to the specified value synchronously.
@@ -87,7 +88,7 @@ impl UuidMetric {
 
         let value = uuid.to_string();
         let metric = self.clone();
-        crate::launch_with_glean(move |glean| metric.set_sync(glean, value));
+        crate::launch_recording(move |glean| metric.set_sync(glean, value));
 
         uuid.to_string()
     }
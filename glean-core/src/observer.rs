@@ -0,0 +1,102 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Notifications about changes to stored metric values.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::metrics::Metric;
+
+/// A callback notified with the new value of a single metric.
+pub type MetricObserver = Box<dyn Fn(&Metric) + Send + Sync + 'static>;
+
+/// A callback notified with the identifier and the new value of any metric.
+pub type GlobalMetricObserver = Box<dyn Fn(&str, &Metric) + Send + Sync + 'static>;
+
+/// Identifies a registered observer, so that it can be removed again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+impl ObserverId {
+    /// Allocates a new, unique observer id.
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        ObserverId(NEXT_ID.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+/// The observers registered on a Glean instance.
+///
+/// Observers are notified on the dispatcher thread, after a new value was stored.
+/// They must not block on Glean, e.g. by calling `test_get_value`,
+/// as this would deadlock.
+#[derive(Default)]
+pub(crate) struct MetricObservers {
+    /// Observers for a single metric, keyed by the metric identifier.
+    metric: HashMap<String, Vec<(ObserverId, MetricObserver)>>,
+    /// Observers for all metrics.
+    global: Vec<(ObserverId, GlobalMetricObserver)>,
+}
+
+impl fmt::Debug for MetricObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricObservers")
+            .field(
+                "metric",
+                &self
+                    .metric
+                    .iter()
+                    .map(|(id, observers)| (id, observers.len()))
+                    .collect::<HashMap<_, _>>(),
+            )
+            .field("global", &self.global.len())
+            .finish()
+    }
+}
+
+impl MetricObservers {
+    /// Registers an observer for the metric with the given identifier.
+    pub fn add_metric_observer(
+        &mut self,
+        identifier: String,
+        id: ObserverId,
+        observer: MetricObserver,
+    ) {
+        self.metric
+            .entry(identifier)
+            .or_default()
+            .push((id, observer));
+    }
+
+    /// Registers an observer for all metrics.
+    pub fn add_global_observer(&mut self, id: ObserverId, observer: GlobalMetricObserver) {
+        self.global.push((id, observer));
+    }
+
+    /// Removes a previously registered observer.
+    ///
+    /// This is a no-op if no observer with that id is registered.
+    pub fn remove(&mut self, id: ObserverId) {
+        self.metric.retain(|_, observers| {
+            observers.retain(|(observer_id, _)| *observer_id != id);
+            !observers.is_empty()
+        });
+        self.global.retain(|(observer_id, _)| *observer_id != id);
+    }
+
+    /// Notifies all interested observers about a newly stored value.
+    pub fn notify(&self, identifier: &str, value: &Metric) {
+        if let Some(observers) = self.metric.get(identifier) {
+            for (_, observer) in observers {
+                observer(value);
+            }
+        }
+
+        for (_, observer) in &self.global {
+            observer(identifier, value);
+        }
+    }
+}
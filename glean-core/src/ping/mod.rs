@@ -8,14 +8,17 @@ use std::fs::{create_dir_all, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, FixedOffset};
 use log::info;
 use serde_json::{json, Value as JsonValue};
 
-use crate::common_metric_data::{CommonMetricData, Lifetime};
-use crate::metrics::{CounterMetric, DatetimeMetric, Metric, MetricType, PingType, TimeUnit};
+use crate::common_metric_data::{is_internal_identifier, CommonMetricData, Lifetime};
+use crate::metrics::{
+    CounterMetric, DatetimeMetric, Metric, MetricType, PingPriority, PingType, TimeUnit,
+};
 use crate::storage::{StorageManager, INTERNAL_STORAGE};
-use crate::upload::HeaderMap;
-use crate::util::{get_iso_time_string, local_now_with_offset};
+use crate::upload::{format_file_name, HeaderMap, PingMetadata};
+use crate::util::{get_iso_time_string, local_now_with_offset, unix_millis_now};
 use crate::{Glean, Result, DELETION_REQUEST_PINGS_DIRECTORY, PENDING_PINGS_DIRECTORY};
 
 /// Holds everything you need to store or send a ping.
@@ -30,6 +33,10 @@ pub struct Ping<'a> {
     pub content: JsonValue,
     /// The headers to upload with the payload.
     pub headers: HeaderMap,
+    /// The upload priority of the ping.
+    pub priority: PingPriority,
+    /// When the ping expires if it wasn't uploaded yet, in milliseconds since the Unix epoch.
+    pub expires_at: Option<u64>,
 }
 
 /// Collect a ping's data, assemble it into its full payload and store it on disk.
@@ -48,6 +55,53 @@ fn merge(a: &mut JsonValue, b: &JsonValue) {
     }
 }
 
+/// Whether a metrics snapshot has any metrics besides Glean's own `glean.*` metrics.
+fn has_user_metrics(snapshot: &JsonValue) -> bool {
+    let sections = match snapshot.as_object() {
+        Some(sections) => sections,
+        None => return false,
+    };
+    sections.values().any(|metrics| match metrics.as_object() {
+        Some(metrics) => metrics.keys().any(|id| !id.starts_with("glean.")),
+        None => true,
+    })
+}
+
+/// Removes Glean's own `glean.*` metrics from a metrics snapshot,
+/// except for the errors of the application's metrics.
+///
+/// Returns `None` if nothing else is left.
+fn without_internal_metrics(mut snapshot: JsonValue) -> Option<JsonValue> {
+    let sections = snapshot.as_object_mut()?;
+    for metrics in sections.values_mut() {
+        if let Some(metrics) = metrics.as_object_mut() {
+            metrics.retain(|id, value| {
+                if !is_internal_identifier(id) {
+                    return true;
+                }
+                // Errors are kept for the application's metrics, which label them.
+                match value.as_object_mut() {
+                    Some(labels) if id.starts_with("glean.error.") => {
+                        labels.retain(|label, _| !is_internal_identifier(label));
+                        !labels.is_empty()
+                    }
+                    _ => false,
+                }
+            });
+        }
+    }
+    sections.retain(|_, metrics| match metrics.as_object() {
+        Some(metrics) => !metrics.is_empty(),
+        None => true,
+    });
+
+    if sections.is_empty() {
+        None
+    } else {
+        Some(snapshot)
+    }
+}
+
 impl Default for PingMaker {
     fn default() -> Self {
         Self::new()
@@ -91,7 +145,14 @@ impl PingMaker {
     }
 
     /// Gets the formatted start and end times for this ping and update for the next ping.
-    fn get_start_end_times(&self, glean: &Glean, storage_name: &str) -> (String, String) {
+    ///
+    /// The ping ends now, unless `submitted_at` is given.
+    fn get_start_end_times(
+        &self,
+        glean: &Glean,
+        storage_name: &str,
+        submitted_at: Option<DateTime<FixedOffset>>,
+    ) -> (String, String) {
         let time_unit = TimeUnit::Minute;
 
         let start_time = DatetimeMetric::new(
@@ -110,10 +171,14 @@ impl PingMaker {
         let start_time_data = start_time
             .get_value(glean, INTERNAL_STORAGE)
             .unwrap_or_else(|| glean.start_time());
-        let end_time_data = local_now_with_offset();
+        let end_time_data = submitted_at.unwrap_or_else(local_now_with_offset);
 
         // Update the start time with the current time.
-        start_time.set_sync_chrono(glean, end_time_data);
+        // Pings replayed with an explicit submission time don't move it,
+        // so the next ping still covers the time since the last regular one.
+        if submitted_at.is_none() {
+            start_time.set_sync_chrono(glean, end_time_data);
+        }
 
         // Format the times.
         let start_time_data = get_iso_time_string(start_time_data, time_unit);
@@ -121,8 +186,14 @@ impl PingMaker {
         (start_time_data, end_time_data)
     }
 
-    fn get_ping_info(&self, glean: &Glean, storage_name: &str, reason: Option<&str>) -> JsonValue {
-        let (start_time, end_time) = self.get_start_end_times(glean, storage_name);
+    fn get_ping_info(
+        &self,
+        glean: &Glean,
+        storage_name: &str,
+        reason: Option<&str>,
+        submitted_at: Option<DateTime<FixedOffset>>,
+    ) -> JsonValue {
+        let (start_time, end_time) = self.get_start_end_times(glean, storage_name, submitted_at);
         let mut map = json!({
             "seq": self.get_ping_seq(glean, storage_name),
             "start_time": start_time,
@@ -221,10 +292,42 @@ impl PingMaker {
         reason: Option<&str>,
         doc_id: &'a str,
         url_path: &'a str,
+    ) -> Option<Ping<'a>> {
+        self.collect_at(glean, ping, reason, doc_id, url_path, None)
+    }
+
+    /// Collects a snapshot for the given ping, like [`collect`](Self::collect),
+    /// but optionally with a given submission time instead of now.
+    ///
+    /// # Arguments
+    ///
+    /// * `glean` - the [`Glean`] instance to collect data from.
+    /// * `ping` - the ping to collect for.
+    /// * `reason` - an optional reason code to include in the ping.
+    /// * `doc_id` - the ping's unique document identifier.
+    /// * `url_path` - the path on the server to upload this ping to.
+    /// * `submitted_at` - the time to report as the ping's `end_time`, if not now.
+    ///
+    /// # Returns
+    ///
+    /// A fully assembled representation of the ping payload and associated metadata.
+    /// If there is no data stored for the ping, `None` is returned.
+    pub fn collect_at<'a>(
+        &self,
+        glean: &Glean,
+        ping: &'a PingType,
+        reason: Option<&str>,
+        doc_id: &'a str,
+        url_path: &'a str,
+        submitted_at: Option<DateTime<FixedOffset>>,
     ) -> Option<Ping<'a>> {
         info!("Collecting {}", ping.name());
 
-        let metrics_data = StorageManager.snapshot_as_json(glean.storage(), ping.name(), true);
+        let mut metrics_data = StorageManager.snapshot_as_json(glean.storage(), ping.name(), true);
+        if ping.name() == "metrics" && !glean.enable_internal_metrics() {
+            // Data recorded before internal metrics were disabled is dropped too.
+            metrics_data = metrics_data.and_then(without_internal_metrics);
+        }
         let events_data = glean.event_storage().snapshot_as_json(ping.name(), true);
 
         let is_empty = metrics_data.is_none() && events_data.is_none();
@@ -237,8 +340,18 @@ impl PingMaker {
                 ping.name()
             );
         }
+        if ping.send_if_empty()
+            && events_data.is_none()
+            && !matches!(&metrics_data, Some(metrics) if has_user_metrics(metrics))
+        {
+            glean
+                .additional_metrics
+                .empty_submissions
+                .get(ping.name())
+                .add_sync(glean, 1);
+        }
 
-        let ping_info = self.get_ping_info(glean, ping.name(), reason);
+        let ping_info = self.get_ping_info(glean, ping.name(), reason, submitted_at);
         let client_info = self.get_client_info(glean, ping.include_client_id());
 
         let mut json = json!({
@@ -252,6 +365,13 @@ impl PingMaker {
         if let Some(events_data) = events_data {
             json_obj.insert("events".to_string(), events_data);
         }
+        let mut metadata = glean.ping_metadata().clone();
+        if let Some(ping_metadata) = ping.metadata() {
+            metadata.extend(ping_metadata);
+        }
+        if !metadata.is_empty() {
+            json_obj.insert("metadata".to_string(), json!(metadata));
+        }
 
         Some(Ping {
             content: json,
@@ -259,6 +379,10 @@ impl PingMaker {
             doc_id,
             url_path,
             headers: self.get_headers(glean),
+            priority: ping.priority(),
+            expires_at: ping
+                .upload_deadline()
+                .map(|deadline| unix_millis_now().saturating_add(deadline.as_millis() as u64)),
         })
     }
 
@@ -312,14 +436,27 @@ impl PingMaker {
     }
 
     /// Stores a ping to disk in the pings directory.
-    pub fn store_ping(&self, data_path: &Path, ping: &Ping) -> std::io::Result<()> {
+    ///
+    /// The file is named after the document id,
+    /// or according to `file_name_format` if one is given.
+    pub fn store_ping(
+        &self,
+        data_path: &Path,
+        ping: &Ping,
+        file_name_format: Option<&str>,
+    ) -> std::io::Result<()> {
         let pings_dir = self.get_pings_dir(data_path, Some(ping.name))?;
         let temp_dir = self.get_tmp_dir(data_path)?;
 
+        let file_name = match file_name_format {
+            Some(format) => format_file_name(format, ping.doc_id, ping.name, unix_millis_now()),
+            None => ping.doc_id.to_string(),
+        };
+
         // Write to a temporary location and then move when done,
         // for transactional writes.
-        let temp_ping_path = temp_dir.join(ping.doc_id);
-        let ping_path = pings_dir.join(ping.doc_id);
+        let temp_ping_path = temp_dir.join(&file_name);
+        let ping_path = pings_dir.join(&file_name);
 
         log::debug!(
             "Storing ping '{}' at '{}'",
@@ -332,10 +469,17 @@ impl PingMaker {
             file.write_all(ping.url_path.as_bytes())?;
             file.write_all(b"\n")?;
             file.write_all(::serde_json::to_string(&ping.content)?.as_bytes())?;
-            if !ping.headers.is_empty() {
-                file.write_all(b"\n{\"headers\":")?;
-                file.write_all(::serde_json::to_string(&ping.headers)?.as_bytes())?;
-                file.write_all(b"}")?;
+            if !ping.headers.is_empty()
+                || ping.priority != PingPriority::default()
+                || ping.expires_at.is_some()
+            {
+                let metadata = PingMetadata {
+                    headers: ping.headers.clone(),
+                    priority: ping.priority,
+                    expires_at: ping.expires_at,
+                };
+                file.write_all(b"\n")?;
+                file.write_all(::serde_json::to_string(&metadata)?.as_bytes())?;
             }
         }
 
@@ -385,4 +529,38 @@ mod test {
         assert_eq!(0, ping_maker.get_ping_seq(&glean, "custom"));
         assert_eq!(1, ping_maker.get_ping_seq(&glean, "custom"));
     }
+
+    #[test]
+    fn internal_metrics_are_removed_except_for_errors_of_other_metrics() {
+        let snapshot = json!({
+            "counter": {
+                "glean.upload.expired": 1,
+                "telemetry.counter": 2,
+            },
+            "labeled_counter": {
+                "glean.error.invalid_value": {
+                    "glean.upload.expired": 1,
+                    "telemetry.counter": 3,
+                },
+                "glean.error.invalid_label": {
+                    "glean.upload.expired": 1,
+                },
+            },
+            "string": {
+                "glean.internal.metrics.os": "Linux",
+            },
+        });
+
+        let expected = json!({
+            "counter": {
+                "telemetry.counter": 2,
+            },
+            "labeled_counter": {
+                "glean.error.invalid_value": {
+                    "telemetry.counter": 3,
+                },
+            },
+        });
+        assert_eq!(Some(expected), without_internal_metrics(snapshot));
+    }
 }
@@ -18,7 +18,8 @@ use once_cell::sync::Lazy;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread::JoinHandle;
 
-const SCHEDULED_HOUR: u32 = 4;
+/// The local hour of the day the "metrics" ping is scheduled for, unless configured otherwise.
+pub(crate) const DEFAULT_SCHEDULED_HOUR: u32 = 4;
 
 // Clippy thinks an AtomicBool would be preferred, but Condvar requires a full Mutex.
 // See https://github.com/rust-lang/rust-clippy/issues/1516
@@ -40,11 +41,13 @@ trait MetricsPingScheduler {
     /// Begins a recurring schedule of "metrics" ping submissions, on another thread.
     /// `now` is used with `when` to determine the first schedule interval and
     /// may not be _right now_ due to processing delays (or in tests).
+    /// Pings are scheduled for the local hour of the day `hour`.
     fn start_scheduler(
         &self,
         submitter: impl MetricsPingSubmitter + Send + 'static,
         now: DateTime<FixedOffset>,
         when: When,
+        hour: u32,
     );
 }
 
@@ -66,8 +69,9 @@ impl MetricsPingScheduler for GleanMetricsPingScheduler {
         submitter: impl MetricsPingSubmitter + Send + 'static,
         now: DateTime<FixedOffset>,
         when: When,
+        hour: u32,
     ) {
-        start_scheduler(submitter, now, when);
+        start_scheduler(submitter, now, when, hour);
     }
 }
 
@@ -102,22 +106,22 @@ fn schedule_internal(
     scheduler: impl MetricsPingScheduler,
     now: DateTime<FixedOffset>,
 ) {
-    let last_sent_build_metric = get_last_sent_build_metric();
-    if let Some(last_sent_build) = last_sent_build_metric.get_value(glean, Some(INTERNAL_STORAGE)) {
-        // If `app_build` is longer than StringMetric's max length, we will always
-        // treat it as a changed build when really it isn't.
-        // This will be externally-observable as InvalidOverflow errors on both the core
-        // `client_info.app_build` metric and the scheduler's internal metric.
-        if last_sent_build != glean.app_build {
-            last_sent_build_metric.set_sync(glean, &glean.app_build);
-            log::info!("App build changed. Sending 'metrics' ping");
-            submitter.submit_metrics_ping(glean, Some("upgrade"), now);
-            scheduler.start_scheduler(submitter, now, When::Reschedule);
-            return;
-        }
-    } else {
-        // No value in last_sent_build. Better set one.
-        last_sent_build_metric.set_sync(glean, &glean.app_build);
+    let hour = glean.metrics_ping_schedule_hour();
+    // If `app_build` is longer than StringMetric's max length, we will always
+    // treat it as a changed build when really it isn't.
+    // This will be externally-observable as InvalidOverflow errors on both the core
+    // `client_info.app_build` metric and the scheduler's internal metric.
+    let build_changed = update_last_sent(glean, get_last_sent_build_metric(), &glean.app_build);
+    let version_changed = update_last_sent(
+        glean,
+        get_last_sent_version_metric(),
+        &glean.app_display_version,
+    );
+    if build_changed || version_changed {
+        log::info!("App build or version changed. Sending 'metrics' ping");
+        submitter.submit_metrics_ping(glean, Some("upgrade"), now);
+        scheduler.start_scheduler(submitter, now, When::Reschedule, hour);
+        return;
     }
 
     let last_sent_time = get_last_sent_time_metric().get_value(glean, INTERNAL_STORAGE);
@@ -138,16 +142,16 @@ fn schedule_internal(
     if already_sent_today {
         // Case #1
         log::info!("The 'metrics' ping was already sent today, {}", now);
-        scheduler.start_scheduler(submitter, now, When::Tomorrow);
-    } else if now > now.date().and_hms(SCHEDULED_HOUR, 0, 0) {
+        scheduler.start_scheduler(submitter, now, When::Tomorrow, hour);
+    } else if now > now.date().and_hms(hour, 0, 0) {
         // Case #2
         log::info!("Sending the 'metrics' ping immediately, {}", now);
         submitter.submit_metrics_ping(glean, Some("overdue"), now);
-        scheduler.start_scheduler(submitter, now, When::Reschedule);
+        scheduler.start_scheduler(submitter, now, When::Reschedule, hour);
     } else {
         // Case #3
         log::info!("The 'metrics' collection is scheduled for today, {}", now);
-        scheduler.start_scheduler(submitter, now, When::Today);
+        scheduler.start_scheduler(submitter, now, When::Today, hour);
     }
 }
 
@@ -160,16 +164,16 @@ enum When {
 }
 
 impl When {
-    /// Returns the duration from now until our deadline.
+    /// Returns the duration from now until our deadline at the local hour of the day `hour`.
     /// Note that std::time::Duration doesn't do negative time spans, so if
     /// our deadline has passed, this will return zero.
-    fn until(&self, now: DateTime<FixedOffset>) -> std::time::Duration {
+    fn until(&self, now: DateTime<FixedOffset>, hour: u32) -> std::time::Duration {
         let fire_date = match self {
-            Self::Today => now.date().and_hms(SCHEDULED_HOUR, 0, 0),
+            Self::Today => now.date().and_hms(hour, 0, 0),
             // Doesn't actually save us from being an hour off on DST because
             // chrono doesn't know when DST changes. : (
             Self::Tomorrow | Self::Reschedule => {
-                (now.date() + Duration::days(1)).and_hms(SCHEDULED_HOUR, 0, 0)
+                (now.date() + Duration::days(1)).and_hms(hour, 0, 0)
             }
         };
         // After rust-lang/rust#73544 can use std::time::Duration::ZERO
@@ -192,6 +196,7 @@ fn start_scheduler(
     submitter: impl MetricsPingSubmitter + Send + 'static,
     now: DateTime<FixedOffset>,
     when: When,
+    hour: u32,
 ) -> JoinHandle<()> {
     let pair = Arc::clone(&TASK_CONDVAR);
     std::thread::Builder::new()
@@ -201,7 +206,7 @@ fn start_scheduler(
             let mut when = when;
             let mut now = now;
             loop {
-                let dur = when.until(now);
+                let dur = when.until(now, hour);
                 log::info!("Scheduling for {:?} after {}, reason {:?}", dur, now, when);
                 let mut timed_out = false;
                 {
@@ -256,6 +261,29 @@ fn get_last_sent_time_metric() -> DatetimeMetric {
     )
 }
 
+/// Stores `current` in `metric` and returns whether it changed since the previous run.
+///
+/// Without a previous value, e.g. on the first run, it didn't change.
+fn update_last_sent(glean: &Glean, metric: StringMetric, current: &str) -> bool {
+    match metric.get_value(glean, Some(INTERNAL_STORAGE)) {
+        Some(last_sent) if last_sent == current => false,
+        last_sent => {
+            metric.set_sync(glean, current);
+            last_sent.is_some()
+        }
+    }
+}
+
+fn get_last_sent_version_metric() -> StringMetric {
+    StringMetric::new(CommonMetricData {
+        name: "last_sent_version".into(),
+        category: "mps".into(),
+        send_in_pings: vec![INTERNAL_STORAGE.into()],
+        lifetime: Lifetime::User,
+        ..Default::default()
+    })
+}
+
 fn get_last_sent_build_metric() -> StringMetric {
     StringMetric::new(CommonMetricData {
         name: "last_sent_build".into(),
@@ -270,6 +298,7 @@ fn get_last_sent_build_metric() -> StringMetric {
 mod test {
     use super::*;
     use crate::tests::new_glean;
+    use crate::InternalConfiguration;
     use std::sync::atomic::{AtomicU32, Ordering};
 
     struct ValidatingSubmitter<F: Fn(DateTime<FixedOffset>, Option<&str>)> {
@@ -297,6 +326,7 @@ mod test {
             _submitter: impl MetricsPingSubmitter + Send + 'static,
             now: DateTime<FixedOffset>,
             when: When,
+            _hour: u32,
         ) {
             (self.schedule_validator)(now, when);
             self.validator_run_count.fetch_add(1, Ordering::Relaxed);
@@ -340,7 +370,7 @@ mod test {
 
         let fake_now = FixedOffset::east(0)
             .ymd(2022, 11, 15)
-            .and_hms(SCHEDULED_HOUR, 0, 1);
+            .and_hms(DEFAULT_SCHEDULED_HOUR, 0, 1);
 
         let (submitter, submitter_count, scheduler, scheduler_count) = new_proxies(
             |_, reason| assert_eq!(reason, Some("overdue")),
@@ -376,6 +406,46 @@ mod test {
         assert_eq!(1, scheduler_count.swap(0, Ordering::Relaxed));
     }
 
+    // Ensure that if the app version changed since the previous run,
+    // we immediately submit an "upgrade" ping, even if the build didn't change.
+    #[test]
+    fn different_app_versions_submit_and_reschedule() {
+        let fake_now =
+            FixedOffset::east(0)
+                .ymd(2022, 11, 15)
+                .and_hms(DEFAULT_SCHEDULED_HOUR - 1, 0, 0);
+
+        let (mut glean, t) = new_glean(None);
+        glean.app_display_version = "1.0".into();
+        let (submitter, submitter_count, scheduler, _) = new_proxies(
+            |_, reason| {
+                panic!(
+                    "The first run shouldn't submit a ping! reason: {:?}",
+                    reason
+                )
+            },
+            |_, when| assert_eq!(when, When::Today),
+        );
+        schedule_internal(&glean, submitter, scheduler, fake_now);
+        assert_eq!(0, submitter_count.swap(0, Ordering::Relaxed));
+        drop(glean);
+
+        // The next run has a new version.
+        let (mut glean, _t) = new_glean(Some(t));
+        glean.app_display_version = "2.0".into();
+        let (submitter, submitter_count, scheduler, scheduler_count) = new_proxies(
+            |_, reason| assert_eq!(reason, Some("upgrade")),
+            |_, when| assert_eq!(when, When::Reschedule),
+        );
+        schedule_internal(&glean, submitter, scheduler, fake_now);
+        assert_eq!(1, submitter_count.swap(0, Ordering::Relaxed));
+        assert_eq!(1, scheduler_count.swap(0, Ordering::Relaxed));
+        assert_eq!(
+            Some("2.0".to_string()),
+            get_last_sent_version_metric().get_value(&glean, Some(INTERNAL_STORAGE))
+        );
+    }
+
     // If we've already sent a ping today, ensure we don't send a ping but we
     // do schedule a ping for tomorrow. ("Case #1" in schedule_internal)
     #[test]
@@ -401,9 +471,10 @@ mod test {
     fn case_2_submit_ping_and_reschedule() {
         let (glean, _t) = new_glean(None);
 
-        let fake_yesterday = FixedOffset::east(0)
-            .ymd(2021, 4, 29)
-            .and_hms(SCHEDULED_HOUR, 0, 1);
+        let fake_yesterday =
+            FixedOffset::east(0)
+                .ymd(2021, 4, 29)
+                .and_hms(DEFAULT_SCHEDULED_HOUR, 0, 1);
         get_last_sent_time_metric().set_sync_chrono(&glean, fake_yesterday);
         let fake_now = fake_yesterday + Duration::days(1);
 
@@ -426,7 +497,7 @@ mod test {
         let fake_yesterday =
             FixedOffset::east(0)
                 .ymd(2021, 4, 29)
-                .and_hms(SCHEDULED_HOUR - 1, 0, 1);
+                .and_hms(DEFAULT_SCHEDULED_HOUR - 1, 0, 1);
         get_last_sent_time_metric().set_sync_chrono(&glean, fake_yesterday);
         let fake_now = fake_yesterday + Duration::days(1);
 
@@ -443,13 +514,16 @@ mod test {
     #[test]
     fn when_gets_at_least_some_date_math_correct() {
         let now = FixedOffset::east(0).ymd(2021, 4, 30).and_hms(15, 2, 10);
-        // `now` is after `SCHEDULED_HOUR` so should be zero:
-        assert_eq!(std::time::Duration::from_secs(0), When::Today.until(now));
-        // If we bring it back before `SCHEDULED_HOUR` it should give us the duration:
-        let earlier = now.date().and_hms(SCHEDULED_HOUR - 1, 0, 0);
+        // `now` is after `DEFAULT_SCHEDULED_HOUR` so should be zero:
+        assert_eq!(
+            std::time::Duration::from_secs(0),
+            When::Today.until(now, DEFAULT_SCHEDULED_HOUR)
+        );
+        // If we bring it back before `DEFAULT_SCHEDULED_HOUR` it should give us the duration:
+        let earlier = now.date().and_hms(DEFAULT_SCHEDULED_HOUR - 1, 0, 0);
         assert_eq!(
             std::time::Duration::from_secs(3600),
-            When::Today.until(earlier)
+            When::Today.until(earlier, DEFAULT_SCHEDULED_HOUR)
         );
 
         // `Tomorrow` and `Reschedule` should differ only in their `reason()`
@@ -457,16 +531,81 @@ mod test {
         // (when the timezone doesn't change between them)).
         assert_eq!(
             std::time::Duration::from_secs(46670),
-            When::Tomorrow.until(now)
+            When::Tomorrow.until(now, DEFAULT_SCHEDULED_HOUR)
         );
         assert_eq!(
             std::time::Duration::from_secs(46670),
-            When::Reschedule.until(now)
+            When::Reschedule.until(now, DEFAULT_SCHEDULED_HOUR)
+        );
+        assert_eq!(
+            When::Tomorrow.until(now, DEFAULT_SCHEDULED_HOUR),
+            When::Reschedule.until(now, DEFAULT_SCHEDULED_HOUR)
         );
-        assert_eq!(When::Tomorrow.until(now), When::Reschedule.until(now));
         assert_ne!(When::Tomorrow.reason(), When::Reschedule.reason());
     }
 
+    fn new_glean_scheduled_at(hour: Option<u32>) -> (Glean, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = InternalConfiguration {
+            data_path: dir.path().display().to_string(),
+            application_id: "org.mozilla.glean.test.app".into(),
+            language_binding_name: "Rust".into(),
+            upload_enabled: true,
+            max_events: None,
+            delay_ping_lifetime_io: false,
+            app_build: "Unknown".into(),
+            use_core_mps: false,
+            metrics_ping_schedule_hour: hour,
+            ..Default::default()
+        };
+        (Glean::new(cfg).unwrap(), dir)
+    }
+
+    #[test]
+    fn custom_hours_are_used_for_the_schedule() {
+        let (glean, _t) = new_glean_scheduled_at(Some(18));
+        assert_eq!(18, glean.metrics_ping_schedule_hour());
+
+        // 17:02:10 is before the custom hour, so the ping is due today, in 57m50s.
+        let now = FixedOffset::east(0).ymd(2021, 4, 30).and_hms(17, 2, 10);
+        assert_eq!(
+            std::time::Duration::from_secs(3470),
+            When::Today.until(now, 18)
+        );
+        // From 18:00:01 the next ping is due tomorrow, almost a day later.
+        let later = now.date().and_hms(18, 0, 1);
+        assert_eq!(
+            std::time::Duration::from_secs(0),
+            When::Today.until(later, 18)
+        );
+        assert_eq!(
+            std::time::Duration::from_secs(86399),
+            When::Tomorrow.until(later, 18)
+        );
+
+        // Past the default hour, but before the custom one: schedule for today.
+        let fake_now =
+            FixedOffset::east(0)
+                .ymd(2022, 11, 15)
+                .and_hms(DEFAULT_SCHEDULED_HOUR + 1, 0, 0);
+        let (submitter, submitter_count, scheduler, scheduler_count) = new_proxies(
+            |_, reason| panic!("No ping is due before 18:00! reason: {:?}", reason),
+            |_, when| assert_eq!(when, When::Today),
+        );
+        schedule_internal(&glean, submitter, scheduler, fake_now);
+        assert_eq!(0, submitter_count.swap(0, Ordering::Relaxed));
+        assert_eq!(1, scheduler_count.swap(0, Ordering::Relaxed));
+    }
+
+    #[test]
+    fn invalid_hours_fall_back_to_the_default() {
+        let (glean, _t) = new_glean_scheduled_at(Some(24));
+        assert_eq!(DEFAULT_SCHEDULED_HOUR, glean.metrics_ping_schedule_hour());
+
+        let (glean, _t) = new_glean_scheduled_at(None);
+        assert_eq!(DEFAULT_SCHEDULED_HOUR, glean.metrics_ping_schedule_hour());
+    }
+
     // Scheduler tests mutate global state and thus must not be run in parallel.
     // Otherwise one test could cancel the other.
     // This Mutex aims to solve that.
@@ -485,7 +624,7 @@ mod test {
         // (So that this test will time out if cancellation fails).
         let now = FixedOffset::east(0)
             .ymd(2021, 4, 30)
-            .and_hms(SCHEDULED_HOUR - 2, 0, 0);
+            .and_hms(DEFAULT_SCHEDULED_HOUR - 2, 0, 0);
 
         let proxy_factory = || {
             new_proxies(
@@ -501,7 +640,7 @@ mod test {
 
         // Test Today.
         let (submitter, submitter_count, _, _) = proxy_factory();
-        let handle = start_scheduler(submitter, now, When::Today);
+        let handle = start_scheduler(submitter, now, When::Today, DEFAULT_SCHEDULED_HOUR);
         super::cancel();
         handle.join().unwrap(); // Should complete immediately.
         assert_eq!(0, submitter_count.swap(0, Ordering::Relaxed));
@@ -509,7 +648,7 @@ mod test {
         // Test Tomorrow.
         let (submitter, submitter_count, _, _) = proxy_factory();
         *cancelled_lock.lock().unwrap() = false; // Uncancel.
-        let handle = start_scheduler(submitter, now, When::Tomorrow);
+        let handle = start_scheduler(submitter, now, When::Tomorrow, DEFAULT_SCHEDULED_HOUR);
         super::cancel();
         handle.join().unwrap(); // Should complete immediately.
         assert_eq!(0, submitter_count.swap(0, Ordering::Relaxed));
@@ -517,7 +656,7 @@ mod test {
         // Test Reschedule.
         let (submitter, submitter_count, _, _) = proxy_factory();
         *cancelled_lock.lock().unwrap() = false; // Uncancel.
-        let handle = start_scheduler(submitter, now, When::Reschedule);
+        let handle = start_scheduler(submitter, now, When::Reschedule, DEFAULT_SCHEDULED_HOUR);
         super::cancel();
         handle.join().unwrap(); // Should complete immediately.
         assert_eq!(0, submitter_count.swap(0, Ordering::Relaxed));
@@ -540,7 +679,7 @@ mod test {
         );
         assert!(crate::core::setup_glean(glean).is_ok());
 
-        // We're choosing a time after SCHEDULED_HOUR so `When::Today` will give us a duration of 0.
+        // We're choosing a time after DEFAULT_SCHEDULED_HOUR so `When::Today` will give us a duration of 0.
         let now = FixedOffset::east(0).ymd(2021, 4, 20).and_hms(15, 42, 0);
 
         let (submitter, submitter_count, _, _) = new_proxies(
@@ -553,7 +692,7 @@ mod test {
             |_, _| panic!("Not using the scheduler this time."),
         );
 
-        let handle = start_scheduler(submitter, now, When::Today);
+        let handle = start_scheduler(submitter, now, When::Today, DEFAULT_SCHEDULED_HOUR);
         handle.join().unwrap();
         assert_eq!(1, submitter_count.swap(0, Ordering::Relaxed));
     }
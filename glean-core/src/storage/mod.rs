@@ -18,6 +18,11 @@ use crate::Lifetime;
 // An internal ping name, not to be touched by anything else
 pub(crate) const INTERNAL_STORAGE: &str = "glean_internal_info";
 
+/// The store every recorded metric is mirrored into, if enabled.
+///
+/// It is never sent in a ping.
+pub const DEBUG_STORE: &str = "debug";
+
 /// Snapshot metrics from the underlying database.
 pub struct StorageManager;
 
@@ -210,6 +215,8 @@ impl StorageManager {
             }
         };
 
+        // Persisted experiments have a user lifetime.
+        storage.iter_store_from(Lifetime::User, store_name, None, &mut snapshotter);
         storage.iter_store_from(Lifetime::Application, store_name, None, &mut snapshotter);
 
         if snapshot.is_empty() {
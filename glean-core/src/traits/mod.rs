@@ -18,6 +18,7 @@ mod numerator;
 mod ping;
 mod quantity;
 mod rate;
+mod signed_counter;
 mod string;
 mod string_list;
 mod text;
@@ -40,6 +41,7 @@ pub use self::numerator::Numerator;
 pub use self::ping::Ping;
 pub use self::quantity::Quantity;
 pub use self::rate::Rate;
+pub use self::signed_counter::SignedCounter;
 pub use self::string::String;
 pub use self::string_list::StringList;
 pub use self::text::Text;
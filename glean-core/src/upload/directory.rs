@@ -6,18 +6,33 @@
 
 use std::cmp::Ordering;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use super::request::HeaderMap;
+use crate::metrics::PingPriority;
 use crate::{DELETION_REQUEST_PINGS_DIRECTORY, PENDING_PINGS_DIRECTORY};
 
+/// The metadata persisted with a ping, as the optional third line of a ping file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PingMetadata {
+    /// Additional headers to be added to the ping request.
+    #[serde(default)]
+    pub headers: HeaderMap,
+    /// The upload priority of the ping.
+    #[serde(default)]
+    pub priority: PingPriority,
+    /// When the ping expires if it wasn't uploaded yet, in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
 /// A representation of the data extracted from a ping file,
-/// this will contain the document_id, path, JSON encoded body of a ping and the persisted headers.
-pub type PingPayload = (String, String, String, Option<HeaderMap>);
+/// this will contain the document_id, path, JSON encoded body of a ping and the persisted metadata.
+pub type PingPayload = (String, String, String, Option<PingMetadata>);
 
 /// A struct to hold the result of scanning all pings directories.
 #[derive(Clone, Debug, Default)]
@@ -41,6 +56,63 @@ impl PingPayloadsByDirectory {
     }
 }
 
+/// A pending ping in an archive of pending pings, one per line.
+#[derive(Serialize, Deserialize)]
+struct ArchivedPing {
+    document_id: String,
+    path: String,
+    body: String,
+    #[serde(default)]
+    metadata: Option<PingMetadata>,
+}
+
+/// The placeholder for the document id in a ping file name format.
+const DOC_ID_PLACEHOLDER: &str = "{doc_id}";
+
+/// Checks whether `format` can be used to name ping files.
+///
+/// It must contain the `{doc_id}` placeholder exactly once,
+/// so that the document id can be recovered from the file name,
+/// and must not contain path separators.
+pub(crate) fn is_valid_file_name_format(format: &str) -> bool {
+    format.matches(DOC_ID_PLACEHOLDER).count() == 1 && !format.contains(['/', '\\'])
+}
+
+/// Builds the name of a ping file from a format.
+///
+/// The placeholders `{doc_id}`, `{ping_name}` and `{timestamp}`, the milliseconds since the
+/// Unix epoch, are replaced by their values.
+pub(crate) fn format_file_name(
+    format: &str,
+    doc_id: &str,
+    ping_name: &str,
+    timestamp: u64,
+) -> String {
+    format
+        .replace("{ping_name}", ping_name)
+        .replace("{timestamp}", &timestamp.to_string())
+        .replace(DOC_ID_PLACEHOLDER, doc_id)
+}
+
+/// Gets the document id of the ping stored in a file.
+///
+/// That is the file name itself for the default naming,
+/// or the first UUID in it for file names built from a custom format.
+fn document_id_from_file_name(file_name: &str) -> Option<&str> {
+    const UUID_LENGTH: usize = 36;
+
+    if Uuid::parse_str(file_name).is_ok() {
+        return Some(file_name);
+    }
+
+    (0..file_name.len().saturating_sub(UUID_LENGTH) + 1)
+        .filter(|&start| {
+            file_name.is_char_boundary(start) && file_name.is_char_boundary(start + UUID_LENGTH)
+        })
+        .map(|start| &file_name[start..start + UUID_LENGTH])
+        .find(|candidate| Uuid::parse_str(candidate).is_ok())
+}
+
 /// Gets the file name from a path as a &str.
 ///
 /// # Panics
@@ -65,17 +137,11 @@ fn get_file_name_as_str(path: &Path) -> Option<&str> {
 /// Processes a ping's metadata.
 ///
 /// The metadata is an optional third line in the ping file,
-/// currently it contains only additonal headers to be added to each ping request.
-/// Therefore, we will process the contents of this line
-/// and return a HeaderMap of the persisted headers.
-fn process_metadata(path: &str, metadata: &str) -> Option<HeaderMap> {
-    #[derive(Deserialize)]
-    struct PingMetadata {
-        pub headers: HeaderMap,
-    }
-
+/// it contains additonal headers to be added to each ping request
+/// and the ping's upload priority.
+fn process_metadata(path: &str, metadata: &str) -> Option<PingMetadata> {
     if let Ok(metadata) = serde_json::from_str::<PingMetadata>(metadata) {
-        return Some(metadata.headers);
+        return Some(metadata);
     } else {
         log::warn!("Error while parsing ping metadata: {}", path);
     }
@@ -153,7 +219,14 @@ impl PingDirectoryManager {
                 return None;
             }
         };
-        let file = match File::open(&path) {
+        self.process_path(document_id, &path)
+    }
+
+    /// Reads the ping file at `path` and returns the data from it.
+    ///
+    /// If the file is not properly formatted, it will be deleted and `None` will be returned.
+    fn process_path(&self, document_id: &str, path: &Path) -> Option<PingPayload> {
+        let file = match File::open(path) {
             Ok(file) => file,
             Err(e) => {
                 log::warn!("Error reading ping file {}. {}", path.display(), e);
@@ -171,8 +244,8 @@ impl PingDirectoryManager {
         if let (Some(Ok(path)), Some(Ok(body)), Ok(metadata)) =
             (lines.next(), lines.next(), lines.next().transpose())
         {
-            let headers = metadata.and_then(|m| process_metadata(&path, &m));
-            return Some((document_id.into(), path, body, headers));
+            let metadata = metadata.and_then(|m| process_metadata(&path, &m));
+            return Some((document_id.into(), path, body, metadata));
         } else {
             log::warn!(
                 "Error processing ping file: {}. Ping file is not formatted as expected.",
@@ -195,7 +268,7 @@ impl PingDirectoryManager {
     /// corresponding to each valid ping file in the directory.
     /// This vector will be ordered by file `modified_date`.
     ///
-    /// Any files that don't match the UUID regex will be deleted
+    /// Any files that don't contain a UUID will be deleted
     /// to prevent files from polluting the pings directory.
     ///
     /// # Returns
@@ -219,12 +292,15 @@ impl PingDirectoryManager {
                 let path = entry.path();
                 if let Some(file_name) = get_file_name_as_str(&path) {
                     // Delete file if it doesn't match the pattern.
-                    if Uuid::parse_str(file_name).is_err() {
-                        log::warn!("Pattern mismatch. Deleting {}", path.display());
-                        self.delete_file(file_name);
-                        return None;
-                    }
-                    if let Some(data) = self.process_file(file_name) {
+                    let document_id = match document_id_from_file_name(file_name) {
+                        Some(document_id) => document_id,
+                        None => {
+                            log::warn!("Pattern mismatch. Deleting {}", path.display());
+                            self.delete_file(file_name);
+                            return None;
+                        }
+                    };
+                    if let Some(data) = self.process_path(document_id, &path) {
                         let metadata = match fs::metadata(&path) {
                             Ok(metadata) => metadata,
                             Err(e) => {
@@ -264,17 +340,117 @@ impl PingDirectoryManager {
             .collect()
     }
 
+    /// Writes all pending pings, including deletion-request pings,
+    /// into a single archive file at `path`, replacing any existing file.
+    ///
+    /// The pings are kept in the pings directories.
+    ///
+    /// # Returns
+    ///
+    /// The number of pings written.
+    pub fn export_to(&self, path: &Path) -> io::Result<usize> {
+        let payloads = self.process_dirs();
+        let mut archive = BufWriter::new(File::create(path)?);
+        let pings = payloads
+            .deletion_request_pings
+            .into_iter()
+            .chain(payloads.pending_pings);
+        let mut count = 0;
+        for (_, (document_id, path, body, metadata)) in pings {
+            let ping = ArchivedPing {
+                document_id,
+                path,
+                body,
+                metadata,
+            };
+            serde_json::to_writer(&mut archive, &ping)?;
+            archive.write_all(b"\n")?;
+            count += 1;
+        }
+        archive.flush()?;
+        Ok(count)
+    }
+
+    /// Stores the pings of an archive written by [`PingDirectoryManager::export_to`]
+    /// in the pings directories.
+    ///
+    /// Pings already pending and malformed lines are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The pings stored.
+    pub fn import_from(&self, path: &Path) -> io::Result<Vec<PingPayload>> {
+        let archive = BufReader::new(File::open(path)?);
+        let mut imported = Vec::new();
+        for line in archive.lines() {
+            let ping: ArchivedPing = match serde_json::from_str(&line?) {
+                Ok(ping) => ping,
+                Err(e) => {
+                    log::warn!(
+                        "Skipping malformed ping in archive {}: {}",
+                        path.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if Uuid::parse_str(&ping.document_id).is_err() {
+                log::warn!("Skipping ping with invalid id {}", ping.document_id);
+                continue;
+            }
+            if self.get_file_path(&ping.document_id).is_some() {
+                log::info!("Ping {} is pending already", ping.document_id);
+                continue;
+            }
+
+            let dir = if ping.path.split('/').nth(3) == Some("deletion-request") {
+                &self.deletion_request_pings_dir
+            } else {
+                &self.pending_pings_dir
+            };
+            fs::create_dir_all(dir)?;
+            let mut file = File::create(dir.join(&ping.document_id))?;
+            file.write_all(ping.path.as_bytes())?;
+            file.write_all(b"\n")?;
+            file.write_all(ping.body.as_bytes())?;
+            if let Some(metadata) = &ping.metadata {
+                file.write_all(b"\n")?;
+                serde_json::to_writer(&mut file, metadata)?;
+            }
+
+            imported.push((ping.document_id, ping.path, ping.body, ping.metadata));
+        }
+        Ok(imported)
+    }
+
     /// Gets the path for a ping file based on its document_id.
     ///
-    /// Will look for files in each ping directory until something is found.
+    /// Will look for files in each ping directory until something is found,
+    /// first by the default file name, then by any file name containing the document id.
     /// If nothing is found, returns `None`.
     fn get_file_path(&self, document_id: &str) -> Option<PathBuf> {
-        for dir in [&self.pending_pings_dir, &self.deletion_request_pings_dir].iter() {
+        let dirs = [&self.pending_pings_dir, &self.deletion_request_pings_dir];
+        for dir in dirs.iter() {
             let path = dir.join(document_id);
             if path.exists() {
                 return Some(path);
             }
         }
+
+        for dir in dirs.iter() {
+            let entries = match dir.read_dir() {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let found = entries.filter_map(|entry| entry.ok()).find(|entry| {
+                let path = entry.path();
+                get_file_name_as_str(&path).and_then(document_id_from_file_name)
+                    == Some(document_id)
+            });
+            if let Some(entry) = found {
+                return Some(entry.path());
+            }
+        }
         None
     }
 }
@@ -417,4 +593,36 @@ mod test {
         let request_ping_type = ping.1.split('/').nth(3).unwrap();
         assert_eq!(request_ping_type, "deletion-request");
     }
+
+    #[test]
+    fn exported_pings_are_imported_into_their_directories() {
+        let (mut glean, dir) = new_glean(None);
+
+        let ping_type = PingType::new("test", true, true, vec![]);
+        glean.register_ping_type(&ping_type);
+        ping_type.submit_sync(&glean, None);
+        glean
+            .internal_pings
+            .deletion_request
+            .submit_sync(&glean, None);
+
+        let archive = dir.path().join("pings.archive");
+        let exporting_manager = PingDirectoryManager::new(dir.path());
+        assert_eq!(2, exporting_manager.export_to(&archive).unwrap());
+        let exported = exporting_manager.process_dirs();
+
+        let other_dir = tempfile::tempdir().unwrap();
+        let directory_manager = PingDirectoryManager::new(other_dir.path());
+        assert_eq!(2, directory_manager.import_from(&archive).unwrap().len());
+
+        let imported = directory_manager.process_dirs();
+        assert_eq!(exported.pending_pings[0].1, imported.pending_pings[0].1);
+        assert_eq!(
+            exported.deletion_request_pings[0].1,
+            imported.deletion_request_pings[0].1
+        );
+
+        // Pings already pending are skipped.
+        assert!(directory_manager.import_from(&archive).unwrap().is_empty());
+    }
 }
@@ -12,19 +12,29 @@
 //!   API to check the HTTP response from the ping upload and either delete the
 //!   corresponding ping from disk or re-enqueue it for sending.
 
-use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryInto;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, RwLock, RwLockWriteGuard};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value as JsonValue;
+
 use crate::error::ErrorKind;
-use crate::{internal_metrics::UploadMetrics, Glean};
+use crate::metrics::{DatetimeMetric, PingPriority};
+use crate::storage::INTERNAL_STORAGE;
+use crate::util::{local_now_with_offset, unix_millis_now};
+use crate::{internal_metrics::UploadMetrics, CommonMetricData, Glean, Lifetime, TimeUnit};
+pub use directory::PingMetadata;
+pub(crate) use directory::{format_file_name, is_valid_file_name_format};
 use directory::{PingDirectoryManager, PingPayloadsByDirectory};
 use policy::Policy;
-pub use request::{HeaderMap, PingRequest};
+pub use request::{HeaderMap, PingFormat, PingRequest};
 pub use result::{UploadResult, UploadTaskAction};
 
 mod directory;
@@ -121,6 +131,121 @@ impl RateLimiter {
     }
 }
 
+/// Pauses uploading after too many consecutive recoverable upload failures.
+///
+/// Once open, the breaker stays open for the cooldown period.
+/// After that a single upload attempt is allowed:
+/// if it fails again the breaker opens again, if it succeeds the failure count is reset.
+#[derive(Debug)]
+struct CircuitBreaker {
+    /// The number of consecutive recoverable failures that opens the breaker.
+    threshold: u32,
+    /// For how long the breaker stays open.
+    cooldown: Duration,
+    /// The current number of consecutive recoverable failures.
+    failures: u32,
+    /// The instant the breaker was opened, if it is open.
+    opened: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            failures: 0,
+            opened: None,
+        }
+    }
+
+    /// Whether uploading is currently paused.
+    ///
+    /// Closes the breaker once the cooldown is over.
+    pub fn is_open(&mut self) -> bool {
+        match self.opened {
+            Some(opened) if opened.elapsed() < self.cooldown => true,
+            Some(_) => {
+                self.opened = None;
+                // Allow a single attempt before opening again.
+                self.failures = self.threshold.saturating_sub(1);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records a recoverable failure.
+    ///
+    /// # Returns
+    ///
+    /// Whether this failure opened the breaker.
+    pub fn record_failure(&mut self) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        if self.opened.is_none() && self.failures >= self.threshold {
+            self.opened = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+
+    /// Records that the server was reachable.
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Remembers the content of the last submitted ping of each type, to detect duplicates.
+#[derive(Debug)]
+struct PingDeduplicator {
+    /// For how long a ping's content is remembered.
+    window: Duration,
+    /// The fingerprint of the last ping of each type and when it was submitted.
+    last_pings: HashMap<String, (u64, Instant)>,
+}
+
+impl PingDeduplicator {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_pings: HashMap::new(),
+        }
+    }
+
+    /// Computes a fingerprint of a ping's content,
+    /// leaving out the fields that differ for every ping.
+    fn fingerprint(content: &JsonValue) -> u64 {
+        let mut content = content.clone();
+        if let Some(ping_info) = content
+            .get_mut("ping_info")
+            .and_then(JsonValue::as_object_mut)
+        {
+            for field in ["seq", "start_time", "end_time"] {
+                ping_info.remove(field);
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        content.to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Checks whether the ping is identical to the previous one submitted within the window.
+    ///
+    /// Otherwise the ping is remembered, starting a new window.
+    pub fn is_duplicate(&mut self, ping_name: &str, content: &JsonValue) -> bool {
+        let fingerprint = Self::fingerprint(content);
+        if let Some((last, submitted)) = self.last_pings.get(ping_name) {
+            if *last == fingerprint && submitted.elapsed() < self.window {
+                return true;
+            }
+        }
+
+        self.last_pings
+            .insert(ping_name.to_string(), (fingerprint, Instant::now()));
+        false
+    }
+}
+
 /// An enum representing the possible upload tasks to be performed by an uploader.
 ///
 /// When asking for the next ping request to upload,
@@ -180,8 +305,15 @@ impl PingUploadTask {
 /// Manages the pending pings queue and directory.
 #[derive(Debug)]
 pub struct PingUploadManager {
-    /// A FIFO queue storing a `PingRequest` for each pending ping.
-    queue: RwLock<VecDeque<PingRequest>>,
+    /// A priority queue storing a `PingRequest` for each pending ping.
+    ///
+    /// Requests are kept ordered by descending priority,
+    /// requests of the same priority are kept in FIFO order.
+    queue: RwLock<VecDeque<(PingPriority, PingRequest)>>,
+    /// When queued pings expire, in milliseconds since the Unix epoch, by document id.
+    ///
+    /// Pings without an upload deadline are not tracked.
+    deadlines: RwLock<HashMap<String, u64>>,
     /// A manager for the pending pings directories.
     directory_manager: PingDirectoryManager,
     /// A flag signaling if we are done processing the pending pings directories.
@@ -197,12 +329,26 @@ pub struct PingUploadManager {
     /// To keep resource usage in check,
     /// we may want to limit the amount of pings sent in a given interval.
     rate_limiter: Option<RwLock<RateLimiter>>,
+    /// A circuit breaker pausing uploads when the server is persistently failing.
+    circuit_breaker: Option<RwLock<CircuitBreaker>>,
+    /// The maximum number of pending pings, not counting deletion-request pings.
+    ///
+    /// Once exceeded, the oldest pending pings are evicted.
+    max_pending_pings: Option<usize>,
+    /// Skips pings identical to the previous one of the same type submitted shortly before.
+    deduplicator: Option<RwLock<PingDeduplicator>>,
+    /// The `Content-Type` header to send instead of the default one.
+    content_type: Option<String>,
+    /// How ping bodies are serialized for upload.
+    ping_format: PingFormat,
     /// The name of the programming language used by the binding creating this instance of PingUploadManager.
     ///
     /// This will be used to build the value User-Agent header for each ping request.
     language_binding_name: String,
     /// Metrics related to ping uploading.
     upload_metrics: UploadMetrics,
+    /// The time of the last successful upload.
+    last_upload: DatetimeMetric,
     /// Policies for ping storage, uploading and requests.
     policy: Policy,
 }
@@ -221,14 +367,31 @@ impl PingUploadManager {
     pub fn new<P: Into<PathBuf>>(data_path: P, language_binding_name: &str) -> Self {
         Self {
             queue: RwLock::new(VecDeque::new()),
+            deadlines: RwLock::new(HashMap::new()),
             directory_manager: PingDirectoryManager::new(data_path),
             processed_pending_pings: Arc::new(AtomicBool::new(false)),
             cached_pings: Arc::new(RwLock::new(PingPayloadsByDirectory::default())),
             recoverable_failure_count: AtomicU32::new(0),
             wait_attempt_count: AtomicU32::new(0),
             rate_limiter: None,
+            circuit_breaker: None,
+            max_pending_pings: None,
+            deduplicator: None,
+            content_type: None,
+            ping_format: PingFormat::default(),
             language_binding_name: language_binding_name.into(),
             upload_metrics: UploadMetrics::new(),
+            last_upload: DatetimeMetric::new(
+                CommonMetricData {
+                    name: "last_upload".into(),
+                    // We don't need a category, the name is already unique
+                    category: "".into(),
+                    send_in_pings: vec![INTERNAL_STORAGE.into()],
+                    lifetime: Lifetime::User,
+                    ..Default::default()
+                },
+                TimeUnit::Nanosecond,
+            ),
             policy: Policy::default(),
         }
     }
@@ -300,19 +463,40 @@ impl PingUploadManager {
         document_id: &str,
         path: &str,
         body: &str,
-        headers: Option<HeaderMap>,
+        headers: HeaderMap,
     ) -> Option<PingRequest> {
-        let mut request = PingRequest::builder(
+        let request = PingRequest::builder(
             &self.language_binding_name,
             self.policy.max_ping_body_size(),
-        )
-        .document_id(document_id)
-        .path(path)
-        .body(body);
-
-        if let Some(headers) = headers {
-            request = request.headers(headers);
-        }
+        );
+        let (body, format) = match self.ping_format {
+            PingFormat::Json => (body.as_bytes().to_vec(), PingFormat::Json),
+            PingFormat::MessagePack => match serde_json::from_str::<JsonValue>(body) {
+                Ok(payload) => (
+                    rmp_serde::to_vec(&payload)
+                        .expect("IMPOSSIBLE: Encoding a JSON value as MessagePack failed"),
+                    PingFormat::MessagePack,
+                ),
+                Err(e) => {
+                    log::warn!(
+                        "Ping {} isn't valid JSON, sending it as is: {}",
+                        document_id,
+                        e
+                    );
+                    (body.as_bytes().to_vec(), PingFormat::Json)
+                }
+            },
+        };
+        let content_type = match &self.content_type {
+            Some(content_type) => content_type.as_str(),
+            None => format.content_type(),
+        };
+        let request = request
+            .header("Content-Type", content_type)
+            .document_id(document_id)
+            .path(path)
+            .body_bytes(body)
+            .headers(headers);
 
         match request.build() {
             Ok(request) => Some(request),
@@ -334,13 +518,15 @@ impl PingUploadManager {
     }
 
     /// Enqueue a ping for upload.
+    ///
+    /// The ping is queued after all pings of the same or a higher priority.
     pub fn enqueue_ping(
         &self,
         glean: &Glean,
         document_id: &str,
         path: &str,
         body: &str,
-        headers: Option<HeaderMap>,
+        metadata: Option<PingMetadata>,
     ) {
         let mut queue = self
             .queue
@@ -350,7 +536,7 @@ impl PingUploadManager {
         // Checks if a ping with this `document_id` is already enqueued.
         if queue
             .iter()
-            .any(|request| request.document_id == document_id)
+            .any(|(_, request)| request.document_id == document_id)
         {
             log::warn!(
                 "Attempted to enqueue a duplicate ping {} at {}.",
@@ -360,9 +546,77 @@ impl PingUploadManager {
             return;
         }
 
+        let PingMetadata {
+            headers,
+            priority,
+            expires_at,
+        } = metadata.unwrap_or_default();
+
         log::trace!("Enqueuing ping {} at {}", document_id, path);
         if let Some(request) = self.build_ping_request(glean, document_id, path, body, headers) {
-            queue.push_back(request)
+            if let Some(expires_at) = expires_at {
+                if !request.is_deletion_request() {
+                    self.deadlines
+                        .write()
+                        .expect("Can't write to the pending pings deadlines.")
+                        .insert(document_id.to_string(), expires_at);
+                }
+            }
+            let position = queue
+                .iter()
+                .position(|(queued_priority, _)| *queued_priority < priority)
+                .unwrap_or_else(|| queue.len());
+            queue.insert(position, (priority, request));
+            self.evict_pending_pings(glean, &mut queue);
+        }
+    }
+
+    /// Evicts the oldest pending pings until at most `max_pending_pings` are queued.
+    ///
+    /// Pings of the lowest priority are evicted first, deletion-request pings never are.
+    fn evict_pending_pings(
+        &self,
+        glean: &Glean,
+        queue: &mut VecDeque<(PingPriority, PingRequest)>,
+    ) {
+        let max = match self.max_pending_pings {
+            Some(max) => max,
+            None => return,
+        };
+
+        loop {
+            let pending = queue
+                .iter()
+                .filter(|(_, request)| !request.is_deletion_request());
+            if pending.clone().count() <= max {
+                return;
+            }
+
+            // Requests are ordered by descending priority and in FIFO order within a priority,
+            // so the oldest ping of the lowest priority is the first one of that priority.
+            let lowest_priority = match pending.map(|(priority, _)| *priority).min() {
+                Some(priority) => priority,
+                None => return,
+            };
+            let position = queue
+                .iter()
+                .position(|(priority, request)| {
+                    *priority == lowest_priority && !request.is_deletion_request()
+                })
+                .expect("A ping of the lowest priority must be queued");
+            let (_, evicted) = queue.remove(position).unwrap();
+
+            log::warn!(
+                "More than {} pending pings. Evicting the oldest ping {}.",
+                max,
+                evicted.document_id
+            );
+            self.directory_manager.delete_file(&evicted.document_id);
+            self.deadlines
+                .write()
+                .expect("Can't write to the pending pings deadlines.")
+                .remove(&evicted.document_id);
+            self.upload_metrics.pending_pings_evicted.add_sync(glean, 1);
         }
     }
 
@@ -451,12 +705,12 @@ impl PingUploadManager {
             // Enqueue the remaining pending pings and
             // enqueue all deletion-request pings.
             let deletion_request_pings = cached_pings.deletion_request_pings.drain(..);
-            for (_, (document_id, path, body, headers)) in deletion_request_pings {
-                self.enqueue_ping(glean, &document_id, &path, &body, headers);
+            for (_, (document_id, path, body, metadata)) in deletion_request_pings {
+                self.enqueue_ping(glean, &document_id, &path, &body, metadata);
             }
             let pending_pings = cached_pings.pending_pings.drain(..);
-            for (_, (document_id, path, body, headers)) in pending_pings {
-                self.enqueue_ping(glean, &document_id, &path, &body, headers);
+            for (_, (document_id, path, body, metadata)) in pending_pings {
+                self.enqueue_ping(glean, &document_id, &path, &body, metadata);
             }
         }
     }
@@ -480,6 +734,94 @@ impl PingUploadManager {
         )));
     }
 
+    /// Adds a circuit breaker to this upload manager.
+    ///
+    /// After `threshold` consecutive recoverable upload failures,
+    /// no upload tasks are handed out for `cooldown` seconds.
+    /// Pings stay pending in the meantime.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - the number of consecutive recoverable failures that pauses uploading.
+    /// * `cooldown` - the amount of seconds uploading is paused for.
+    pub fn set_circuit_breaker(&mut self, threshold: u32, cooldown: u64) {
+        self.circuit_breaker = Some(RwLock::new(CircuitBreaker::new(
+            threshold,
+            Duration::from_secs(cooldown),
+        )));
+    }
+
+    /// Caps the number of pending pings.
+    ///
+    /// When more than `max` pings are pending, the oldest ones are evicted.
+    /// Deletion-request pings are neither counted nor evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - the maximum number of pending pings.
+    pub fn set_max_pending_pings(&mut self, max: usize) {
+        self.max_pending_pings = Some(max);
+    }
+
+    /// Sends pings with the given `Content-Type` header,
+    /// instead of `application/json; charset=utf-8`.
+    ///
+    /// The `Content-Encoding` header is still set separately if the body is gzipped.
+    pub fn set_content_type(&mut self, content_type: String) {
+        self.content_type = Some(content_type);
+    }
+
+    /// Serializes ping bodies in `format` for upload.
+    ///
+    /// The `Content-Type` header is set accordingly, unless set through
+    /// [`set_content_type`](Self::set_content_type).
+    pub fn set_ping_format(&mut self, format: PingFormat) {
+        self.ping_format = format;
+    }
+
+    /// Skips pings identical to the previous ping of the same type,
+    /// if that was submitted less than `window` ago.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - for how long a ping's content is remembered.
+    pub fn set_dedup_window(&mut self, window: Duration) {
+        self.deduplicator = Some(RwLock::new(PingDeduplicator::new(window)));
+    }
+
+    /// Checks whether a ping duplicates the previous ping of the same type.
+    ///
+    /// Pings are compared ignoring the fields in `ping_info` that change with every ping:
+    /// `seq`, `start_time` and `end_time`.
+    /// Duplicates are counted in `glean.upload.deduplicated`.
+    /// Deletion-request pings are never considered duplicates.
+    ///
+    /// # Returns
+    ///
+    /// Whether the ping should be skipped.
+    pub(crate) fn is_duplicate(&self, glean: &Glean, ping_name: &str, content: &JsonValue) -> bool {
+        let deduplicator = match &self.deduplicator {
+            Some(deduplicator) => deduplicator,
+            None => return false,
+        };
+        if ping_name == "deletion-request" {
+            return false;
+        }
+
+        let duplicate = deduplicator
+            .write()
+            .unwrap()
+            .is_duplicate(ping_name, content);
+        if duplicate {
+            log::info!(
+                "The {} ping is identical to the previous one, not enqueuing it.",
+                ping_name
+            );
+            self.upload_metrics.deduplicated.add_sync(glean, 1);
+        }
+        duplicate
+    }
+
     /// Reads a ping file, creates a `PingRequest` and adds it to the queue.
     ///
     /// Duplicate requests won't be added.
@@ -489,22 +831,81 @@ impl PingUploadManager {
     /// * `glean` - The Glean object holding the database.
     /// * `document_id` - The UUID of the ping in question.
     pub fn enqueue_ping_from_file(&self, glean: &Glean, document_id: &str) {
-        if let Some((doc_id, path, body, headers)) =
+        if let Some((doc_id, path, body, metadata)) =
             self.directory_manager.process_file(document_id)
         {
-            self.enqueue_ping(glean, &doc_id, &path, &body, headers)
+            self.enqueue_ping(glean, &doc_id, &path, &body, metadata)
         }
     }
 
+    /// Writes all pending pings into a single archive file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// The number of pings written.
+    pub fn export_pending_pings(&self, path: &Path) -> std::io::Result<usize> {
+        self.directory_manager.export_to(path)
+    }
+
+    /// Stores the pings of an archive written by [`PingUploadManager::export_pending_pings`]
+    /// and enqueues them for upload.
+    ///
+    /// # Returns
+    ///
+    /// The number of pings imported.
+    pub fn import_pending_pings(&self, glean: &Glean, path: &Path) -> std::io::Result<usize> {
+        let imported = self.directory_manager.import_from(path)?;
+        for (document_id, path, body, metadata) in &imported {
+            self.enqueue_ping(glean, document_id, path, body, metadata.clone());
+        }
+        Ok(imported.len())
+    }
+
+    /// Removes all pending pings, to hand them to a transport other than the uploader.
+    ///
+    /// Unlike [`get_upload_task`](Self::get_upload_task), this isn't rate limited.
+    /// Pings past their upload deadline are deleted instead.
+    /// Pings still being scanned from disk are left until a later call.
+    ///
+    /// # Returns
+    ///
+    /// The requests of the removed pings, in upload order.
+    pub fn drain_pings(&self, glean: &Glean) -> Vec<PingRequest> {
+        if self.processed_pending_pings() {
+            self.enqueue_cached_pings(glean);
+        }
+
+        let mut queue = self
+            .queue
+            .write()
+            .expect("Can't write to pending pings queue.");
+        self.drop_expired_pings(glean, &mut queue);
+        let drained: Vec<PingRequest> = queue.drain(..).map(|(_, request)| request).collect();
+        self.deadlines
+            .write()
+            .expect("Can't write to the pending pings deadlines.")
+            .clear();
+
+        for request in &drained {
+            self.directory_manager.delete_file(&request.document_id);
+        }
+        drained
+    }
+
     /// Clears the pending pings queue, leaves the deletion-request pings.
-    pub fn clear_ping_queue(&self) -> RwLockWriteGuard<'_, VecDeque<PingRequest>> {
+    pub fn clear_ping_queue(&self) -> RwLockWriteGuard<'_, VecDeque<(PingPriority, PingRequest)>> {
         log::trace!("Clearing ping queue");
         let mut queue = self
             .queue
             .write()
             .expect("Can't write to pending pings queue.");
 
-        queue.retain(|ping| ping.is_deletion_request());
+        queue.retain(|(_, ping)| ping.is_deletion_request());
+        // Deletion-request pings never expire, so none of the remaining pings has a deadline.
+        self.deadlines
+            .write()
+            .expect("Can't write to the pending pings deadlines.")
+            .clear();
         log::trace!(
             "{} pings left in the queue (only deletion-request expected)",
             queue.len()
@@ -512,6 +913,34 @@ impl PingUploadManager {
         queue
     }
 
+    /// Deletes the pings at the front of the queue that are past their upload deadline.
+    fn drop_expired_pings(&self, glean: &Glean, queue: &mut VecDeque<(PingPriority, PingRequest)>) {
+        let mut deadlines = self
+            .deadlines
+            .write()
+            .expect("Can't write to the pending pings deadlines.");
+        if deadlines.is_empty() {
+            return;
+        }
+
+        let now = unix_millis_now();
+        while let Some((_, request)) = queue.front() {
+            match deadlines.get(&request.document_id) {
+                Some(&expires_at) if expires_at <= now => (),
+                _ => return,
+            }
+
+            let (_, expired) = queue.pop_front().unwrap();
+            log::info!(
+                "Ping {} is past its upload deadline. Deleting it.",
+                expired.document_id
+            );
+            deadlines.remove(&expired.document_id);
+            self.directory_manager.delete_file(&expired.document_id);
+            self.upload_metrics.expired.add_sync(glean, 1);
+        }
+    }
+
     fn get_upload_task_internal(&self, glean: &Glean, log_ping: bool) -> PingUploadTask {
         // Helper to decide whether to return PingUploadTask::Wait or PingUploadTask::Done.
         //
@@ -536,6 +965,16 @@ impl PingUploadManager {
         // This is a no-op in case there are no cached pings.
         self.enqueue_cached_pings(glean);
 
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            let mut circuit_breaker = circuit_breaker
+                .write()
+                .expect("Can't write to the circuit breaker.");
+            if circuit_breaker.is_open() {
+                log::info!("Uploading is paused after too many failures. You are done.");
+                return PingUploadTask::done();
+            }
+        }
+
         if self.recoverable_failure_count() >= self.policy.max_recoverable_failures() {
             log::warn!(
                 "Reached maximum recoverable failures for the current uploading window. You are done."
@@ -547,8 +986,9 @@ impl PingUploadManager {
             .queue
             .write()
             .expect("Can't write to pending pings queue.");
+        self.drop_expired_pings(glean, &mut queue);
         match queue.front() {
-            Some(request) => {
+            Some((_, request)) => {
                 if let Some(rate_limiter) = &self.rate_limiter {
                     let mut rate_limiter = rate_limiter
                         .write()
@@ -575,9 +1015,12 @@ impl PingUploadManager {
                     }
                 }
 
-                PingUploadTask::Upload {
-                    request: queue.pop_front().unwrap(),
-                }
+                let request = queue.pop_front().unwrap().1;
+                self.deadlines
+                    .write()
+                    .expect("Can't write to the pending pings deadlines.")
+                    .remove(&request.document_id);
+                PingUploadTask::Upload { request }
             }
             None => {
                 log::info!("No more pings to upload! You are done.");
@@ -610,6 +1053,13 @@ impl PingUploadManager {
         task
     }
 
+    /// Gets the time of the last successful upload, if any.
+    ///
+    /// This is persisted across restarts.
+    pub fn last_upload_time(&self, glean: &Glean) -> Option<DateTime<FixedOffset>> {
+        self.last_upload.get_value(glean, INTERNAL_STORAGE)
+    }
+
     /// Processes the response from an attempt to upload a ping.
     ///
     /// Based on the HTTP status of said response,
@@ -661,10 +1111,34 @@ impl PingUploadManager {
             metric.add_sync(glean, 1);
         }
 
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            let mut circuit_breaker = circuit_breaker
+                .write()
+                .expect("Can't write to the circuit breaker.");
+            match status {
+                HttpStatus { code: 200..=499 } => circuit_breaker.record_success(),
+                RecoverableFailure { .. } | HttpStatus { .. } => {
+                    if circuit_breaker.record_failure() {
+                        log::warn!(
+                            "Too many consecutive upload failures. Pausing uploads for {:?}.",
+                            circuit_breaker.cooldown
+                        );
+                        self.upload_metrics.circuit_open.add_sync(glean, 1);
+                    }
+                }
+                UnrecoverableFailure { .. } | Done { .. } => {}
+            }
+        }
+
         match status {
             HttpStatus { code } if (200..=299).contains(&code) => {
                 log::info!("Ping {} successfully sent {}.", document_id, code);
                 self.directory_manager.delete_file(document_id);
+                // The database is gone if Glean was torn down during the upload.
+                if glean.storage_opt().is_some() {
+                    self.last_upload
+                        .set_sync_chrono(glean, local_now_with_offset());
+                }
             }
 
             UnrecoverableFailure { .. } | HttpStatus { code: 400..=499 } => {
@@ -862,6 +1336,90 @@ mod test {
         assert!(task.is_upload());
     }
 
+    #[test]
+    fn circuit_breaker_pauses_uploads_after_repeated_failures() {
+        let (mut glean, _) = new_glean(None);
+
+        // Pause uploads for an hour after 2 consecutive failures.
+        glean.upload_manager.set_circuit_breaker(2, 3600);
+
+        // Register a ping for testing
+        let ping_type = PingType::new("test", true, /* send_if_empty */ true, vec![]);
+        glean.register_ping_type(&ping_type);
+
+        // Submit a ping
+        ping_type.submit_sync(&glean, None);
+
+        // Fail to upload it twice.
+        for _ in 0..2 {
+            match glean.get_upload_task() {
+                PingUploadTask::Upload { request } => {
+                    glean.process_ping_upload_response(
+                        &request.document_id,
+                        UploadResult::recoverable_failure(),
+                    );
+                }
+                _ => panic!("Expected upload manager to return the next request!"),
+            }
+        }
+
+        // The breaker is open: no more upload tasks are handed out...
+        assert_eq!(glean.get_upload_task(), PingUploadTask::done());
+        assert_eq!(
+            Some(1),
+            glean
+                .upload_manager
+                .upload_metrics
+                .circuit_open
+                .get_value(&glean, Some("metrics"))
+        );
+
+        // ...but the ping is still pending.
+        assert_eq!(1, glean.upload_manager.queue.read().unwrap().len());
+    }
+
+    #[test]
+    fn oldest_pending_pings_are_evicted_over_the_cap() {
+        let (glean, dir) = new_glean(None);
+
+        let mut upload_manager = PingUploadManager::no_policy(dir.path());
+        upload_manager.set_max_pending_pings(3);
+
+        // Enqueue more pings than allowed, without uploading any of them.
+        let doc_ids: Vec<String> = (0..5).map(|_| Uuid::new_v4().to_string()).collect();
+        for doc_id in &doc_ids {
+            upload_manager.enqueue_ping(&glean, doc_id, PATH, "", None);
+        }
+        // Deletion-request pings are not affected by the cap.
+        let deletion_request_id = Uuid::new_v4().to_string();
+        upload_manager.enqueue_ping(
+            &glean,
+            &deletion_request_id,
+            "/submit/app_id/deletion-request/1/doc_id",
+            "",
+            None,
+        );
+
+        let queued: Vec<String> = upload_manager
+            .queue
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, request)| request.document_id.clone())
+            .collect();
+        let mut expected = doc_ids[2..].to_vec();
+        expected.push(deletion_request_id);
+        assert_eq!(expected, queued);
+
+        assert_eq!(
+            Some(2),
+            upload_manager
+                .upload_metrics
+                .pending_pings_evicted
+                .get_value(&glean, Some("metrics"))
+        );
+    }
+
     #[test]
     fn clearing_the_queue_works_correctly() {
         let (glean, dir) = new_glean(None);
@@ -1392,11 +1950,11 @@ mod test {
         // Create a new upload manager pointing to the same data_path as the glean instance.
         let mut upload_manager = PingUploadManager::no_policy(dir.path());
 
-        // From manual testing we figured out an empty ping file is 324bytes,
+        // From manual testing we figured out an empty ping file is 393bytes,
         // so this allows 3 pings.
         upload_manager
             .policy
-            .set_max_pending_pings_directory_size(Some(1000));
+            .set_max_pending_pings_directory_size(Some(1200));
         upload_manager.policy.set_max_pending_pings_count(Some(5));
 
         // Get a task once
@@ -1580,4 +2138,106 @@ mod test {
             _ => panic!("Expected upload manager to return a wait task!"),
         };
     }
+
+    #[test]
+    fn higher_priority_pings_are_uploaded_first() {
+        let (glean, dir) = new_glean(None);
+
+        let upload_manager = PingUploadManager::no_policy(dir.path());
+
+        let metadata = |priority| {
+            Some(PingMetadata {
+                priority,
+                ..Default::default()
+            })
+        };
+        let low = Uuid::new_v4().to_string();
+        let normal = Uuid::new_v4().to_string();
+        let high = Uuid::new_v4().to_string();
+        let other_high = Uuid::new_v4().to_string();
+
+        upload_manager.enqueue_ping(&glean, &low, PATH, "", metadata(PingPriority::Low));
+        upload_manager.enqueue_ping(&glean, &normal, PATH, "", None);
+        upload_manager.enqueue_ping(&glean, &high, PATH, "", metadata(PingPriority::High));
+        upload_manager.enqueue_ping(&glean, &other_high, PATH, "", metadata(PingPriority::High));
+
+        // Pings are ordered by priority, and by submission within the same priority.
+        for expected in &[high, other_high, normal, low] {
+            match upload_manager.get_upload_task(&glean, false) {
+                PingUploadTask::Upload { request } => assert_eq!(expected, &request.document_id),
+                _ => panic!("Expected upload manager to return the next request!"),
+            }
+        }
+    }
+
+    #[test]
+    fn ping_priority_is_persisted_with_the_ping() {
+        let (mut glean, dir) = new_glean(None);
+
+        // Register pings for testing
+        let low_ping = PingType::new_with_priority("low", true, true, vec![], PingPriority::Low);
+        glean.register_ping_type(&low_ping);
+        let high_ping = PingType::new_with_priority("high", true, true, vec![], PingPriority::High);
+        glean.register_ping_type(&high_ping);
+
+        // Submit the low priority ping first.
+        low_ping.submit_sync(&glean, None);
+        high_ping.submit_sync(&glean, None);
+
+        // Create a new upload manager pointing to the same data_path as the glean instance,
+        // so that pings are loaded from disk.
+        let upload_manager = PingUploadManager::no_policy(dir.path());
+
+        for expected in &["high", "low"] {
+            match upload_manager.get_upload_task(&glean, false) {
+                PingUploadTask::Upload { request } => {
+                    assert_eq!(Some(*expected), request.path.split('/').nth(3))
+                }
+                _ => panic!("Expected upload manager to return the next request!"),
+            }
+        }
+    }
+
+    #[test]
+    fn pings_past_their_upload_deadline_expire() {
+        let (mut glean, dir) = new_glean(None);
+
+        let stale_ping = PingType::new("stale", true, true, vec![]);
+        stale_ping.set_upload_deadline(Duration::from_millis(10));
+        glean.register_ping_type(&stale_ping);
+        let other_ping = PingType::new("other", true, true, vec![]);
+        glean.register_ping_type(&other_ping);
+
+        stale_ping.submit_sync(&glean, None);
+        other_ping.submit_sync(&glean, None);
+
+        // Uploading is paused until after the deadline.
+        thread::sleep(Duration::from_millis(50));
+
+        // Create a new upload manager pointing to the same data_path as the glean instance,
+        // so that pings are loaded from disk.
+        let upload_manager = PingUploadManager::no_policy(dir.path());
+
+        // Only the ping without a deadline is uploaded.
+        match upload_manager.get_upload_task(&glean, false) {
+            PingUploadTask::Upload { request } => {
+                assert_eq!(Some("other"), request.path.split('/').nth(3))
+            }
+            _ => panic!("Expected upload manager to return the next request!"),
+        }
+        assert_eq!(
+            upload_manager.get_upload_task(&glean, false),
+            PingUploadTask::done()
+        );
+
+        assert_eq!(
+            Some(1),
+            upload_manager
+                .upload_metrics
+                .expired
+                .get_value(&glean, Some("metrics"))
+        );
+        let pending_pings_dir = dir.path().join(PENDING_PINGS_DIRECTORY);
+        assert_eq!(1, std::fs::read_dir(pending_pings_dir).unwrap().count());
+    }
 }
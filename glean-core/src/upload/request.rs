@@ -17,6 +17,40 @@ use crate::system;
 /// A representation for request headers.
 pub type HeaderMap = HashMap<String, String>;
 
+/// The `Content-Type` header pings are sent with, unless configured otherwise.
+pub(crate) const DEFAULT_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+
+/// The `Content-Type` header of pings serialized as MessagePack.
+const MESSAGEPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// How ping bodies are serialized for upload.
+///
+/// Pings are always stored as JSON, they are only converted when building a request.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PingFormat {
+    /// JSON, as stored.
+    Json,
+    /// [MessagePack](https://msgpack.org/), a more compact binary encoding of the same payload.
+    MessagePack,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] on variants needs Rust 1.62, above our MSRV
+impl Default for PingFormat {
+    fn default() -> Self {
+        PingFormat::Json
+    }
+}
+
+impl PingFormat {
+    /// The `Content-Type` header of pings serialized in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PingFormat::Json => DEFAULT_CONTENT_TYPE,
+            PingFormat::MessagePack => MESSAGEPACK_CONTENT_TYPE,
+        }
+    }
+}
+
 /// Creates a formatted date string that can be used with Date headers.
 fn create_date_header_value(current_time: DateTime<Utc>) -> String {
     // Date headers are required to be in the following format:
@@ -77,10 +111,7 @@ impl Builder {
                 system::OS,
             ),
         );
-        headers.insert(
-            "Content-Type".to_string(),
-            "application/json; charset=utf-8".to_string(),
-        );
+        headers.insert("Content-Type".to_string(), DEFAULT_CONTENT_TYPE.to_string());
 
         Self {
             document_id: None,
@@ -117,17 +148,25 @@ impl Builder {
     /// # Panics
     ///
     /// This method will panic in case we try to set the body before setting the path.
-    pub fn body<S: Into<String>>(mut self, value: S) -> Self {
+    pub fn body<S: Into<String>>(self, value: S) -> Self {
+        self.body_bytes(value.into().into_bytes())
+    }
+
+    /// Sets a body that isn't text, like [`body`](Self::body) does.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic in case we try to set the body before setting the path.
+    pub fn body_bytes(mut self, value: Vec<u8>) -> Self {
         // Attempt to gzip the body contents.
-        let original_as_string = value.into();
         let gzipped_content = gzip_content(
             self.path
                 .as_ref()
                 .expect("Path must be set before attempting to set the body"),
-            original_as_string.as_bytes(),
+            &value,
         );
         let add_gzip_header = gzipped_content.is_some();
-        let body = gzipped_content.unwrap_or_else(|| original_as_string.into_bytes());
+        let body = gzipped_content.unwrap_or(value);
 
         // Include headers related to body
         self = self.header("Content-Length", &body.len().to_string());
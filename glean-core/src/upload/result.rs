@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 /// The result of an attempted ping upload.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UploadResult {
     /// A recoverable failure.
     ///
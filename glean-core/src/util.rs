@@ -150,6 +150,54 @@ pub(crate) fn truncate_string_at_boundary_with_error<S: Into<String>>(
     }
 }
 
+/// Checks whether `locale` looks like a BCP 47 language tag (e.g. "es-ES" or "zh-Hant-TW").
+///
+/// This doesn't validate against the registry of known subtags:
+/// it requires a 2-3 or 5-8 letters primary language subtag,
+/// followed by any number of 1-8 alphanumeric characters subtags, separated by `-`.
+pub(crate) fn is_valid_locale(locale: &str) -> bool {
+    let mut subtags = locale.split('-');
+
+    let language = subtags.next().unwrap_or("");
+    let language_valid = matches!(language.len(), 2..=3 | 5..=8)
+        && language.chars().all(|c| c.is_ascii_alphabetic());
+
+    language_valid
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Gets the time since the system booted.
+///
+/// This is read from `/proc/uptime` on Linux and Android.
+/// Returns `None` on other platforms, or if it can't be determined.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn system_uptime() -> Option<std::time::Duration> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    if seconds.is_finite() && seconds >= 0.0 {
+        Some(std::time::Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+/// Gets the time since the system booted.
+///
+/// This is not supported on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn system_uptime() -> Option<std::time::Duration> {
+    None
+}
+
+/// Gets the milliseconds since the Unix epoch, or 0 if the system clock is before it.
+pub(crate) fn unix_millis_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis() as u64)
+}
+
 // On i686 on Windows, the CPython interpreter sets the FPU precision control
 // flag to 53 bits of precision, rather than the 64 bit default. On x86_64 on
 // Windows, the CPython interpreter changes the rounding control settings. This
@@ -302,6 +350,21 @@ mod test {
         assert_eq!("0123456789", truncated);
     }
 
+    #[test]
+    fn locale_validation() {
+        assert!(is_valid_locale("en"));
+        assert!(is_valid_locale("es-ES"));
+        assert!(is_valid_locale("zh-Hant-TW"));
+        assert!(is_valid_locale("und"));
+
+        assert!(!is_valid_locale(""));
+        assert!(!is_valid_locale("e"));
+        assert!(!is_valid_locale("en_US"));
+        assert!(!is_valid_locale("en-"));
+        assert!(!is_valid_locale("en-toolongsubtag"));
+        assert!(!is_valid_locale("1234"));
+    }
+
     #[test]
     #[should_panic]
     fn truncate_naive() {
@@ -89,3 +89,67 @@ fn set_properly_sets_the_value_in_all_stores() {
 
 // SKIPPED from glean-ac: booleans are serialized in the correct JSON format
 // Completely redundant with other tests.
+
+#[test]
+fn default_value_is_reported_until_a_value_is_set() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = BooleanMetric::new_with_default(
+        CommonMetricData {
+            name: "boolean_metric".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        Some(true),
+    );
+
+    // Nothing recorded yet: the default is reported, but not stored.
+    assert_eq!(Some(true), metric.get_value(&glean, Some("store1")));
+    assert!(StorageManager
+        .snapshot_as_json(glean.storage(), "store1", false)
+        .is_none());
+
+    metric.set_sync(&glean, false);
+    assert_eq!(Some(false), metric.get_value(&glean, Some("store1")));
+
+    // Once the store is cleared, the default is reported again.
+    StorageManager.snapshot(glean.storage(), "store1", true);
+    assert_eq!(Some(true), metric.get_value(&glean, Some("store1")));
+}
+
+#[test]
+fn only_the_first_value_is_kept_once_per_session() {
+    let (mut tempdir, _) = tempdir();
+
+    let metric = BooleanMetric::new(CommonMetricData {
+        name: "boolean_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::User,
+        only_once_per_session: true,
+        ..Default::default()
+    });
+
+    {
+        let (glean, dir) = new_glean(Some(tempdir));
+        tempdir = dir;
+
+        metric.set_sync(&glean, true);
+        metric.set_sync(&glean, false);
+        assert_eq!(Some(true), metric.get_value(&glean, Some("store1")));
+    }
+
+    // A new session records again.
+    {
+        let (glean, _t) = new_glean(Some(tempdir));
+        assert_eq!(Some(true), metric.get_value(&glean, Some("store1")));
+
+        metric.set_sync(&glean, false);
+        metric.set_sync(&glean, true);
+        assert_eq!(Some(false), metric.get_value(&glean, Some("store1")));
+    }
+}
@@ -49,8 +49,15 @@ pub fn new_glean(tempdir: Option<tempfile::TempDir>) -> (Glean, tempfile::TempDi
         None => tempfile::tempdir().unwrap(),
     };
 
-    let cfg = glean_core::InternalConfiguration {
-        data_path: dir.path().display().to_string(),
+    let glean = Glean::new(new_configuration(dir.path())).unwrap();
+
+    (glean, dir)
+}
+
+// Creates the configuration `new_glean` uses, storing data in `data_path`.
+pub fn new_configuration(data_path: &Path) -> glean_core::InternalConfiguration {
+    glean_core::InternalConfiguration {
+        data_path: data_path.display().to_string(),
         application_id: GLOBAL_APPLICATION_ID.into(),
         language_binding_name: "Rust".into(),
         upload_enabled: true,
@@ -58,10 +65,8 @@ pub fn new_glean(tempdir: Option<tempfile::TempDir>) -> (Glean, tempfile::TempDi
         delay_ping_lifetime_io: false,
         app_build: "Unknown".into(),
         use_core_mps: false,
-    };
-    let glean = Glean::new(cfg).unwrap();
-
-    (glean, dir)
+        ..Default::default()
+    }
 }
 
 /// Converts an iso8601::DateTime to a chrono::DateTime<FixedOffset>
@@ -175,3 +175,74 @@ fn saturates_at_boundary() {
         counter.get_value(&glean, Some("store1")).unwrap()
     );
 }
+
+#[test]
+fn adding_many_records_the_same_as_adding_each() {
+    let (glean, _t) = new_glean(None);
+
+    let counter = |name: &str| {
+        CounterMetric::new(CommonMetricData {
+            name: name.into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Application,
+            ..Default::default()
+        })
+    };
+    let batched = counter("batched");
+    let individual = counter("individual");
+
+    // 10k values, every 1000th of them invalid.
+    let amounts: Vec<i32> = (0..10_000)
+        .map(|i| if i % 1000 == 0 { 0 } else { i % 7 + 1 })
+        .collect();
+
+    batched.add_many_sync(&glean, &amounts);
+    for &amount in &amounts {
+        individual.add_sync(&glean, amount);
+    }
+
+    assert!(batched.get_value(&glean, "store1").is_some());
+    assert_eq!(
+        individual.get_value(&glean, "store1"),
+        batched.get_value(&glean, "store1")
+    );
+    assert_eq!(
+        Ok(10),
+        test_get_num_recorded_errors(&glean, batched.meta(), ErrorType::InvalidValue)
+    );
+    assert_eq!(
+        test_get_num_recorded_errors(&glean, individual.meta(), ErrorType::InvalidValue),
+        test_get_num_recorded_errors(&glean, batched.meta(), ErrorType::InvalidValue)
+    );
+}
+
+#[test]
+fn stores_can_be_recorded_into_independently() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into(), "store2".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+
+    metric.add_to_store_sync(&glean, "store1", 3);
+    metric.add_to_store_sync(&glean, "store2", 5);
+    metric.add_to_store_sync(&glean, "store2", 1);
+
+    assert_eq!(Some(3), metric.get_value(&glean, "store1"));
+    assert_eq!(Some(6), metric.get_value(&glean, "store2"));
+
+    // Stores the metric isn't sent in are rejected.
+    metric.add_to_store_sync(&glean, "store3", 1);
+    assert_eq!(None, metric.get_value(&glean, "store3"));
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::InvalidState)
+    );
+}
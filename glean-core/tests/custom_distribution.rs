@@ -417,3 +417,70 @@ mod exponential {
         assert!(snapshot.is_some());
     }
 }
+
+mod bucketer {
+    use super::*;
+
+    #[test]
+    fn samples_go_into_the_buckets_of_the_bucketer() {
+        let (glean, _t) = new_glean(None);
+
+        // Buckets of 10 up to 50, then one for everything above.
+        let metric = CustomDistributionMetric::new_with_bucketer(
+            CommonMetricData {
+                name: "distribution".into(),
+                category: "telemetry".into(),
+                send_in_pings: vec!["store1".into()],
+                disabled: false,
+                lifetime: Lifetime::Ping,
+                ..Default::default()
+            },
+            10,
+            50,
+            |sample| sample / 10 * 10,
+        )
+        .expect("The bucketer should be valid");
+
+        metric.accumulate_samples_sync(&glean, vec![5, 12, 19, 35, 500]);
+
+        let snapshot = metric
+            .get_value(&glean, "store1")
+            .expect("Value should be stored");
+
+        assert_eq!(5, snapshot.count);
+        assert_eq!(571, snapshot.sum);
+        assert_eq!(1, snapshot.values[&0]);
+        assert_eq!(2, snapshot.values[&10]);
+        assert_eq!(0, snapshot.values[&20]);
+        assert_eq!(1, snapshot.values[&30]);
+        assert_eq!(1, snapshot.values[&50]);
+
+        let snapshot = StorageManager
+            .snapshot_as_json(glean.storage(), "store1", true)
+            .unwrap();
+        assert_eq!(
+            json!(571),
+            snapshot["custom_distribution"]["telemetry.distribution"]["sum"]
+        );
+    }
+
+    #[test]
+    fn invalid_bucketers_are_rejected() {
+        let meta = CommonMetricData {
+            name: "distribution".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        };
+
+        // Samples from 50 to 59 go back into the bucket at 20.
+        let result =
+            CustomDistributionMetric::new_with_bucketer(meta, 0, 100, |sample| match sample {
+                50..=59 => 20,
+                _ => sample / 10 * 10,
+            });
+        assert!(result.is_err());
+    }
+}
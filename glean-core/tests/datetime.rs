@@ -5,12 +5,16 @@
 mod common;
 use crate::common::*;
 
+use std::path::Path;
+use std::sync::Arc;
+
 use chrono::prelude::*;
 use serde_json::json;
 
 use glean_core::metrics::*;
 use glean_core::storage::StorageManager;
-use glean_core::{CommonMetricData, Lifetime};
+use glean_core::{test_get_num_recorded_errors, ErrorType};
+use glean_core::{CommonMetricData, Glean, Lifetime};
 
 // SKIPPED from glean-ac: datetime deserializer should correctly parse integers
 // This test doesn't really apply to rkv
@@ -185,3 +189,147 @@ fn test_that_truncation_works() {
         );
     }
 }
+
+#[test]
+fn datetimes_can_be_normalized_to_utc() {
+    let (glean, _t) = new_glean(None);
+
+    let meta = |name: &str| CommonMetricData {
+        name: name.into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    };
+    let local = DatetimeMetric::new_with_utc(meta("local"), TimeUnit::Minute, false);
+    let utc = DatetimeMetric::new_with_utc(meta("utc"), TimeUnit::Minute, true);
+
+    // Shortly after midnight in UTC+05:30 is still the previous day in UTC.
+    let dt = FixedOffset::east(5 * 3600 + 30 * 60)
+        .ymd(2021, 3, 1)
+        .and_hms(0, 15, 0);
+    local.set_sync(&glean, Some(dt.into()));
+    utc.set_sync(&glean, Some(dt.into()));
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({
+            "datetime": {
+                "telemetry.local": "2021-03-01T00:15+05:30",
+                "telemetry.utc": "2021-02-28T18:45+00:00",
+            }
+        }),
+        snapshot
+    );
+}
+
+/// A clock stuck at a fixed time.
+#[derive(Debug)]
+struct FixedClock(DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn monotonic_nanos(&self) -> u64 {
+        0
+    }
+
+    fn wall_clock_nanos(&self) -> u64 {
+        self.0.timestamp_nanos() as u64
+    }
+}
+
+#[test]
+fn implausible_datetimes_are_clamped() {
+    let (_t, tmpname) = tempdir();
+    let cfg = glean_core::InternalConfiguration {
+        clamp_skewed_datetimes: true,
+        ..new_configuration(Path::new(&tmpname))
+    };
+    let glean = Glean::new(cfg).unwrap();
+
+    let meta = |name: &str| CommonMetricData {
+        name: name.into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    };
+
+    // The system clock is set to 1990.
+    let past = DatetimeMetric::new_with_utc(meta("past"), TimeUnit::Day, true)
+        .with_clock(Arc::new(FixedClock(Utc.ymd(1990, 6, 1).and_hms(0, 0, 0))));
+    past.set_sync(&glean, None);
+    assert_eq!(
+        "2010-01-01+00:00",
+        past.get_value_as_string(&glean, Some("store1".into()))
+            .unwrap()
+    );
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, past.meta(), ErrorType::ClockSkew)
+    );
+
+    // A datetime far ahead of a correct clock.
+    let future = DatetimeMetric::new_with_utc(meta("future"), TimeUnit::Day, true)
+        .with_clock(Arc::new(FixedClock(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0))));
+    future.set_sync(
+        &glean,
+        Some(FixedOffset::east(0).ymd(2200, 1, 1).and_hms(0, 0, 0).into()),
+    );
+    assert_eq!(
+        "2021-03-02+00:00",
+        future
+            .get_value_as_string(&glean, Some("store1".into()))
+            .unwrap()
+    );
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, future.meta(), ErrorType::ClockSkew)
+    );
+
+    // Plausible datetimes are recorded as they are.
+    future.set_sync(
+        &glean,
+        Some(FixedOffset::east(0).ymd(2021, 2, 1).and_hms(0, 0, 0).into()),
+    );
+    assert_eq!(
+        "2021-02-01+00:00",
+        future
+            .get_value_as_string(&glean, Some("store1".into()))
+            .unwrap()
+    );
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, future.meta(), ErrorType::ClockSkew)
+    );
+}
+
+#[test]
+fn implausible_datetimes_are_recorded_unless_clamping() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = DatetimeMetric::new_with_utc(
+        CommonMetricData {
+            name: "past".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        TimeUnit::Day,
+        true,
+    )
+    .with_clock(Arc::new(FixedClock(Utc.ymd(1990, 6, 1).and_hms(0, 0, 0))));
+    metric.set_sync(&glean, None);
+    assert_eq!(
+        "1990-06-01+00:00",
+        metric
+            .get_value_as_string(&glean, Some("store1".into()))
+            .unwrap()
+    );
+    assert!(test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::ClockSkew).is_err());
+}
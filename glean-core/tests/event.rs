@@ -303,3 +303,38 @@ fn snapshot_sorts_the_timestamps() {
             .unwrap()
     );
 }
+
+#[test]
+fn reset_only_removes_the_events_of_that_metric() {
+    let (glean, _t) = new_glean(None);
+
+    let new_event = |name: &str| {
+        EventMetric::new(
+            CommonMetricData {
+                name: name.into(),
+                category: "telemetry".into(),
+                send_in_pings: vec!["store1".into()],
+                disabled: false,
+                lifetime: Lifetime::Ping,
+                ..Default::default()
+            },
+            vec![],
+        )
+    };
+    let reset_event = new_event("reset_event");
+    let kept_event = new_event("kept_event");
+
+    reset_event.record_sync(&glean, 1000, HashMap::new());
+    kept_event.record_sync(&glean, 1001, HashMap::new());
+    reset_event.record_sync(&glean, 1002, HashMap::new());
+
+    reset_event.reset_sync(&glean);
+
+    assert!(reset_event.get_value(&glean, "store1").is_none());
+    assert_eq!(1, kept_event.get_value(&glean, "store1").unwrap().len());
+
+    // Only the kept event remains on disk.
+    let contents = fs::read_to_string(glean.get_data_path().join("events").join("store1")).unwrap();
+    assert_eq!(1, contents.lines().count());
+    assert!(contents.contains("kept_event"));
+}
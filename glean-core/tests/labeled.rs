@@ -524,3 +524,272 @@ fn caching_metrics_with_dynamic_labels_across_pings() {
     assert_eq!(json!(20), cached_labels["label20"]);
     assert_eq!(json!(null), cached_labels["__other__"]);
 }
+
+#[test]
+fn labeled_string_folds_labels_over_the_limit_into_other() {
+    let (glean, _t) = new_glean(None);
+    let labeled = LabeledString::new(
+        CommonMetricData {
+            name: "labeled_string".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+
+    labeled.get("region").set_sync(&glean, "west");
+    labeled.get("version").set_sync(&glean, "1.0.0");
+
+    assert_eq!(
+        Some("west".to_string()),
+        labeled.get("region").get_value(&glean, Some("store1"))
+    );
+    assert_eq!(
+        Some("1.0.0".to_string()),
+        labeled.get("version").get_value(&glean, Some("store1"))
+    );
+
+    // Fill up the remaining 14 label slots.
+    for i in 1..=14 {
+        labeled
+            .get(format!("component{}", i))
+            .set_sync(&glean, "seen");
+    }
+
+    // The 17th label goes to `__other__`.
+    labeled.get("over_limit").set_sync(&glean, "folded");
+    assert_eq!(
+        Some("folded".to_string()),
+        labeled.get("__other__").get_value(&glean, Some("store1"))
+    );
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    let recorded = &snapshot["labeled_string"]["telemetry.labeled_string"];
+    assert_eq!(json!("west"), recorded["region"]);
+    assert_eq!(json!("1.0.0"), recorded["version"]);
+    assert_eq!(json!("folded"), recorded["__other__"]);
+    assert_eq!(json!(null), recorded["over_limit"]);
+}
+
+#[test]
+fn labeled_custom_distributions_accumulate_independently() {
+    let (glean, _t) = new_glean(None);
+    let meta = CommonMetricData {
+        name: "latency".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    };
+    let labeled = LabeledCustomDistribution::new_custom_distribution(
+        meta.clone(),
+        1,
+        100,
+        10,
+        HistogramType::Linear,
+        None,
+    );
+
+    labeled
+        .get("read")
+        .accumulate_samples_sync(&glean, vec![1, 50]);
+    labeled
+        .get("write")
+        .accumulate_samples_sync(&glean, vec![100, 100, 100]);
+    labeled
+        .get("Not Valid")
+        .accumulate_samples_sync(&glean, vec![10]);
+
+    // Every label uses the configured bucketing.
+    let expected = |name: &str, samples: Vec<i64>| {
+        let metric = CustomDistributionMetric::new(
+            CommonMetricData {
+                name: name.into(),
+                ..meta.clone()
+            },
+            1,
+            100,
+            10,
+            HistogramType::Linear,
+        );
+        metric.accumulate_samples_sync(&glean, samples);
+        metric.get_value(&glean, "store1").unwrap().values
+    };
+
+    let read = labeled.get("read").get_value(&glean, "store1").unwrap();
+    assert_eq!(expected("expected_read", vec![1, 50]), read.values);
+    assert_eq!(51, read.sum);
+
+    let write = labeled.get("write").get_value(&glean, "store1").unwrap();
+    assert_eq!(
+        expected("expected_write", vec![100, 100, 100]),
+        write.values
+    );
+    assert_eq!(3, write.count);
+
+    let other = labeled
+        .get("__other__")
+        .get_value(&glean, "store1")
+        .unwrap();
+    assert_eq!(expected("expected_other", vec![10]), other.values);
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(
+            &glean,
+            labeled.get("Not Valid").meta(),
+            ErrorType::InvalidLabel
+        )
+    );
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", false)
+        .unwrap();
+    let labels = snapshot["labeled_custom_distribution"]["telemetry.latency"]
+        .as_object()
+        .unwrap();
+    let mut labels = labels.keys().collect::<Vec<_>>();
+    labels.sort();
+    assert_eq!(vec!["__other__", "read", "write"], labels);
+}
+
+#[test]
+fn labeled_memory_distributions_accumulate_independently() {
+    let (glean, _t) = new_glean(None);
+    let labeled = LabeledMemoryDistribution::new_memory_distribution(
+        CommonMetricData {
+            name: "allocations".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        MemoryUnit::Kilobyte,
+        Some(vec!["network".into(), "storage".into()]),
+    );
+
+    labeled.get("network").accumulate_sync(&glean, 1);
+    labeled.get("network").accumulate_sync(&glean, 2);
+    labeled.get("storage").accumulate_sync(&glean, 1024);
+    labeled.get("graphics").accumulate_sync(&glean, 4);
+
+    // Samples are converted from kilobytes to bytes for every label.
+    let network = labeled.get("network").get_value(&glean, "store1").unwrap();
+    assert_eq!(3 * 1024, network.sum);
+    assert_eq!(2, network.count);
+
+    let storage = labeled.get("storage").get_value(&glean, "store1").unwrap();
+    assert_eq!(1024 * 1024, storage.sum);
+    assert_eq!(1, storage.count);
+
+    // Labels outside of the static list are folded into `__other__`.
+    let other = labeled
+        .get("__other__")
+        .get_value(&glean, "store1")
+        .unwrap();
+    assert_eq!(4 * 1024, other.sum);
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", false)
+        .unwrap();
+    let labels = snapshot["labeled_memory_distribution"]["telemetry.allocations"]
+        .as_object()
+        .unwrap();
+    let mut labels = labels.keys().collect::<Vec<_>>();
+    labels.sort();
+    assert_eq!(vec!["__other__", "network", "storage"], labels);
+}
+
+#[test]
+fn metrics_can_be_labeled_at_runtime() {
+    let (glean, _t) = new_glean(None);
+    let base = CounterMetric::new(CommonMetricData {
+        name: "runtime_labeled".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+
+    base.with_label("label1").add_sync(&glean, 1);
+    base.with_label("label1").add_sync(&glean, 2);
+    base.with_label("label2").add_sync(&glean, 5);
+    // Invalid labels are folded into `__other__`.
+    let invalid = base.with_label("not snake case");
+    invalid.add_sync(&glean, 7);
+
+    assert_eq!(
+        Some(3),
+        base.with_label("label1").get_value(&glean, "store1")
+    );
+    assert_eq!(
+        Some(5),
+        base.with_label("label2").get_value(&glean, "store1")
+    );
+    assert_eq!(None, base.get_value(&glean, "store1"));
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, invalid.meta(), ErrorType::InvalidLabel)
+    );
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({
+            "labeled_counter": {
+                "glean.error.invalid_label": { "telemetry.runtime_labeled": 1 },
+                "telemetry.runtime_labeled": { "label1": 3, "label2": 5, "__other__": 7 }
+            }
+        }),
+        snapshot
+    );
+}
+
+#[test]
+fn max_label_length_only_applies_to_its_glean() {
+    let dir = tempfile::tempdir().unwrap();
+    let cfg = glean_core::InternalConfiguration {
+        max_label_length: Some(10),
+        ..new_configuration(dir.path())
+    };
+    let short = glean_core::Glean::new(cfg).unwrap();
+    let (glean, _t) = new_glean(None);
+
+    let labeled = LabeledCounter::new(
+        CommonMetricData {
+            name: "labeled_metric".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+    labeled.get("eleven_byte").add_sync(&short, 1);
+    labeled.get("eleven_byte").add_sync(&glean, 1);
+
+    let snapshot = StorageManager
+        .snapshot_as_json(short.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({ "__other__": 1 }),
+        snapshot["labeled_counter"]["telemetry.labeled_metric"]
+    );
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({ "eleven_byte": 1 }),
+        snapshot["labeled_counter"]["telemetry.labeled_metric"]
+    );
+}
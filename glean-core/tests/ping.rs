@@ -5,9 +5,13 @@
 mod common;
 use crate::common::*;
 
+use std::path::Path;
+
 use glean_core::metrics::*;
 use glean_core::CommonMetricData;
+use glean_core::Glean;
 use glean_core::Lifetime;
+use glean_core::{UnregisteredPingBehavior, UploadDisabledReason};
 
 #[test]
 fn write_ping_to_disk() {
@@ -99,6 +103,31 @@ fn deletion_request_only_when_toggled_from_on_to_off() {
     assert_eq!(1, get_deletion_pings(glean.get_data_path()).unwrap().len());
 }
 
+#[test]
+fn deletion_request_carries_the_opt_out_reason() {
+    let (mut glean, _) = new_glean(None);
+
+    glean.set_upload_enabled_with_reason(false, Some(UploadDisabledReason::Policy));
+    let dpings = get_deletion_pings(glean.get_data_path()).unwrap();
+    assert_eq!(1, dpings.len());
+    let payload = &dpings[0].1;
+    assert_eq!(
+        "policy",
+        payload["metrics"]["string"]["glean.deletion_request.opt_out_reason"]
+    );
+
+    // Without a reason, none is reported.
+    glean.set_upload_enabled(true);
+    glean.set_upload_enabled(false);
+    let dpings = get_deletion_pings(glean.get_data_path()).unwrap();
+    assert_eq!(2, dpings.len());
+    let unexplained = dpings
+        .iter()
+        .find(|(_, payload, _)| payload["metrics"]["string"].is_null())
+        .map(|(_, payload, _)| payload);
+    assert!(unexplained.is_some());
+}
+
 #[test]
 fn empty_pings_with_flag_are_sent() {
     let (mut glean, _) = new_glean(None);
@@ -133,6 +162,10 @@ fn test_pings_submitted_metric() {
             lifetime: Lifetime::Ping,
             disabled: false,
             dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
         },
         None,
     );
@@ -211,3 +244,289 @@ fn test_pings_submitted_metric() {
             .get_value(&glean, Some("baseline"))
     );
 }
+
+#[test]
+fn crash_ping_is_stored_in_the_pending_queue() {
+    let (glean, _t) = new_glean(None);
+
+    // Metrics sent in the `crash` ping are included.
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "open_tabs".into(),
+        category: "browser".into(),
+        send_in_pings: vec!["crash".into()],
+        ..Default::default()
+    });
+    counter.add_sync(&glean, 3);
+
+    let mut annotations = std::collections::HashMap::new();
+    annotations.insert("thread_name".to_string(), "renderer".to_string());
+    assert!(glean.submit_crash_ping(glean_core::CrashCause::Signal, annotations));
+
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    assert_eq!(1, queued_pings.len());
+    let (url, payload, _) = &queued_pings[0];
+    assert!(url.contains("/crash/"));
+
+    let metrics = &payload["metrics"];
+    assert_eq!("signal", metrics["string"]["glean.crash.cause"]);
+    assert!(metrics["datetime"]["glean.crash.time"].is_string());
+    assert_eq!(
+        "renderer",
+        metrics["labeled_string"]["glean.crash.annotations"]["thread_name"]
+    );
+    assert_eq!(3, metrics["counter"]["browser.open_tabs"]);
+}
+
+#[test]
+fn declared_reason_codes_are_exposed_and_enforced() {
+    let (mut glean, _t) = new_glean(None);
+
+    let ping = PingType::new(
+        "custom-with-reasons",
+        true,
+        true,
+        vec!["startup".to_string(), "shutdown".to_string()],
+    );
+    glean.register_ping_type(&ping);
+    assert_eq!(
+        &["startup".to_string(), "shutdown".to_string()],
+        ping.reason_codes()
+    );
+
+    assert!(ping.submit_sync(&glean, Some("startup")));
+    assert!(ping.submit_sync(&glean, Some("not-a-declared-reason")));
+    assert!(ping.submit_sync(&glean, None));
+
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    assert_eq!(3, queued_pings.len());
+
+    let mut reasons: Vec<_> = queued_pings
+        .iter()
+        .map(|(_, payload, _)| payload["ping_info"]["reason"].as_str().map(String::from))
+        .collect();
+    reasons.sort();
+    // Invalid reasons are dropped, but the ping is still sent.
+    assert_eq!(vec![None, None, Some("startup".to_string())], reasons);
+}
+
+#[test]
+fn ping_metadata_is_included_in_every_instance_of_the_ping() {
+    let (mut glean, _t) = new_glean(None);
+
+    let ping = PingType::new("custom-with-metadata", true, true, vec![]);
+    glean.register_ping_type(&ping);
+
+    ping.set_metadata("pipeline", "staging");
+    ping.set_metadata("pipeline", "nightly");
+    ping.set_metadata("", "empty key");
+    ping.set_metadata("a".repeat(41), "key too long");
+    ping.set_metadata("value-too-long", "v".repeat(101));
+
+    assert!(ping.submit_sync(&glean, None));
+    assert!(ping.submit_sync(&glean, None));
+
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    assert_eq!(2, queued_pings.len());
+    for (_, payload, _) in &queued_pings {
+        assert_eq!(
+            serde_json::json!({ "pipeline": "nightly" }),
+            payload["metadata"]
+        );
+    }
+}
+
+#[test]
+fn global_ping_metadata_is_included_in_every_ping() {
+    let (mut glean, _t) = new_glean(None);
+
+    let first = PingType::new("first", true, true, vec![]);
+    let second = PingType::new("second", true, true, vec![]);
+    glean.register_ping_type(&first);
+    glean.register_ping_type(&second);
+
+    assert!(glean.set_ping_metadata("environment".into(), "staging".into()));
+    assert!(glean.set_ping_metadata("region".into(), "eu".into()));
+    assert!(!glean.set_ping_metadata("".into(), "empty key".into()));
+    assert!(!glean.set_ping_metadata("a".repeat(41), "key too long".into()));
+    assert!(!glean.set_ping_metadata("value-too-long".into(), "v".repeat(101)));
+    // The ping's own metadata takes precedence.
+    second.set_metadata("region", "us");
+
+    assert!(first.submit_sync(&glean, None));
+    assert!(second.submit_sync(&glean, None));
+
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    assert_eq!(2, queued_pings.len());
+    for (url, payload, _) in &queued_pings {
+        let expected = if url.contains("/first/") {
+            serde_json::json!({ "environment": "staging", "region": "eu" })
+        } else {
+            serde_json::json!({ "environment": "staging", "region": "us" })
+        };
+        assert_eq!(expected, payload["metadata"]);
+    }
+}
+
+#[test]
+fn global_ping_metadata_is_capped() {
+    let (mut glean, _t) = new_glean(None);
+
+    for i in 0..20 {
+        assert!(glean.set_ping_metadata(format!("key{}", i), "value".into()));
+    }
+    assert!(!glean.set_ping_metadata("one-too-many".into(), "value".into()));
+    // Existing keys can still be replaced.
+    assert!(glean.set_ping_metadata("key0".into(), "replaced".into()));
+}
+
+#[test]
+fn recordings_are_buffered_until_their_ping_is_registered() {
+    let (_t, tmpname) = tempdir();
+    let cfg = glean_core::InternalConfiguration {
+        unregistered_ping_behavior: Some(UnregisteredPingBehavior::Buffer),
+        ..new_configuration(Path::new(&tmpname))
+    };
+    let mut glean = Glean::new(cfg).unwrap();
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "local".into(),
+        send_in_pings: vec!["late".into()],
+        ..Default::default()
+    });
+    counter.add_sync(&glean, 2);
+    counter.add_sync(&glean, 3);
+    // Nothing is stored until the ping is registered.
+    assert_eq!(None, counter.get_value(&glean, "late"));
+
+    let ping = PingType::new("late", true, false, vec![]);
+    glean.register_ping_type(&ping);
+    assert_eq!(Some(5), counter.get_value(&glean, "late"));
+
+    counter.add_sync(&glean, 1);
+    assert!(ping.submit_sync(&glean, None));
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    assert_eq!(1, queued_pings.len());
+    assert_eq!(6, queued_pings[0].1["metrics"]["counter"]["local.counter"]);
+}
+
+#[test]
+fn recordings_into_unknown_pings_are_rejected_in_strict_mode() {
+    let (_t, tmpname) = tempdir();
+    let cfg = glean_core::InternalConfiguration {
+        unregistered_ping_behavior: Some(UnregisteredPingBehavior::Strict),
+        ..new_configuration(Path::new(&tmpname))
+    };
+    let mut glean = Glean::new(cfg).unwrap();
+
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "local".into(),
+        send_in_pings: vec!["late".into()],
+        ..Default::default()
+    });
+    counter.add_sync(&glean, 2);
+
+    let unknown_ping = CounterMetric::new(CommonMetricData {
+        name: "unknown_ping".into(),
+        category: "glean.error".into(),
+        send_in_pings: vec!["metrics".into()],
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    assert_eq!(Some(1), unknown_ping.get_value(&glean, "metrics"));
+
+    // Registering the ping later doesn't bring the recording back.
+    glean.register_ping_type(&PingType::new("late", true, false, vec![]));
+    assert_eq!(None, counter.get_value(&glean, "late"));
+    counter.add_sync(&glean, 3);
+    assert_eq!(Some(3), counter.get_value(&glean, "late"));
+}
+
+#[test]
+fn assembled_ping_sizes_are_recorded() {
+    let (mut glean, _t) = new_glean(None);
+
+    // Reconstructed here so we can test it without reaching into the library
+    // internals.
+    let assembled_size = MemoryDistributionMetric::new(
+        CommonMetricData {
+            name: "assembled_size".into(),
+            category: "glean.ping".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            disabled: false,
+            dynamic_label: None,
+            min_record_interval_ms: None,
+            description: None,
+            data_sensitivity: None,
+            only_once_per_session: false,
+        },
+        MemoryUnit::Byte,
+    );
+
+    let ping = PingType::new("custom-sized", true, false, vec![]);
+    glean.register_ping_type(&ping);
+
+    let string = StringMetric::new(CommonMetricData {
+        name: "string".into(),
+        category: "local".into(),
+        send_in_pings: vec!["custom-sized".into()],
+        ..Default::default()
+    });
+
+    string.set_sync(&glean, "short");
+    assert!(ping.submit_sync(&glean, None));
+    string.set_sync(&glean, "a".repeat(90));
+    assert!(ping.submit_sync(&glean, None));
+
+    let queued_pings = get_queued_pings(glean.get_data_path()).unwrap();
+    let mut sizes: Vec<_> = queued_pings
+        .iter()
+        .map(|(_, payload, _)| serde_json::to_string(payload).unwrap().len() as i64)
+        .collect();
+    sizes.sort_unstable();
+    assert!(sizes[0] < sizes[1]);
+
+    let data = assembled_size.get_value(&glean, "metrics").unwrap();
+    assert_eq!(2, data.count);
+    assert_eq!(sizes.iter().sum::<i64>(), data.sum);
+}
+
+#[test]
+fn empty_submissions_are_counted_by_ping() {
+    let (mut glean, _t) = new_glean(None);
+
+    let empty_submissions = LabeledMetric::<CounterMetric>::new(
+        CommonMetricData {
+            name: "empty_submissions".into(),
+            category: "glean.ping".into(),
+            send_in_pings: vec!["metrics".into()],
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        None,
+    );
+
+    let ping = PingType::new("always-sent", true, true, vec![]);
+    glean.register_ping_type(&ping);
+    assert!(ping.submit_sync(&glean, None));
+    assert!(ping.submit_sync(&glean, None));
+
+    // Pings with data aren't counted.
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter".into(),
+        category: "local".into(),
+        send_in_pings: vec!["always-sent".into()],
+        ..Default::default()
+    });
+    counter.add_sync(&glean, 1);
+    assert!(ping.submit_sync(&glean, None));
+
+    assert_eq!(
+        Some(2),
+        empty_submissions
+            .get("always-sent")
+            .get_value(&glean, Some("metrics"))
+    );
+}
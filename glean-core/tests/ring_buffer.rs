@@ -0,0 +1,84 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod common;
+use crate::common::*;
+
+use glean_core::metrics::*;
+use glean_core::{test_get_num_recorded_errors, ErrorType};
+use glean_core::{CommonMetricData, Lifetime};
+
+#[test]
+fn ring_buffer_keeps_the_last_values() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = RingBufferMetric::new(
+        QuantityMetric::new(CommonMetricData {
+            name: "quantity_metric".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        }),
+        3,
+    );
+
+    for value in 1..=5 {
+        metric.record_sync(&glean, value);
+    }
+
+    // Only the last 3 values are kept, oldest first...
+    assert_eq!(vec![3, 4, 5], metric.get_history());
+    // ...while the ping carries the latest one.
+    assert_eq!(Some(5), metric.metric().get_value(&glean, "store1"));
+}
+
+#[test]
+fn ring_buffer_keeps_invalid_values() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = RingBufferMetric::new(
+        CounterMetric::new(CommonMetricData {
+            name: "counter_metric".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        }),
+        2,
+    );
+
+    metric.record_sync(&glean, 2);
+    metric.record_sync(&glean, -1);
+
+    assert_eq!(vec![2, -1], metric.get_history());
+    assert_eq!(Some(2), metric.metric().get_value(&glean, "store1"));
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, metric.metric().meta(), ErrorType::InvalidValue)
+    );
+}
+
+#[test]
+fn disabled_ring_buffer_keeps_nothing() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = RingBufferMetric::new(
+        StringMetric::new(CommonMetricData {
+            name: "string_metric".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: true,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        }),
+        2,
+    );
+
+    metric.record_sync(&glean, "value".to_string());
+
+    assert!(metric.get_history().is_empty());
+}
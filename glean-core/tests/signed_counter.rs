@@ -0,0 +1,77 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod common;
+use crate::common::*;
+
+use serde_json::json;
+
+use glean_core::metrics::*;
+use glean_core::storage::StorageManager;
+use glean_core::{test_get_num_recorded_errors, ErrorType};
+use glean_core::{CommonMetricData, Lifetime};
+
+fn signed_counter_metric() -> SignedCounterMetric {
+    SignedCounterMetric::new(CommonMetricData {
+        name: "signed_counter_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn signed_counter_serializer_should_correctly_serialize() {
+    let (glean, _t) = new_glean(None);
+    let metric = signed_counter_metric();
+
+    metric.add_sync(&glean, -3);
+
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({"signed_counter": {"telemetry.signed_counter_metric": -3}}),
+        snapshot
+    );
+}
+
+#[test]
+fn signed_counters_can_be_incremented() {
+    let (glean, _t) = new_glean(None);
+    let metric = signed_counter_metric();
+
+    metric.add_sync(&glean, 2);
+    metric.add_sync(&glean, 5);
+
+    assert_eq!(Some(7), metric.get_value(&glean, "store1"));
+    assert!(test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::InvalidValue).is_err());
+}
+
+#[test]
+fn signed_counters_can_be_decremented() {
+    let (glean, _t) = new_glean(None);
+    let metric = signed_counter_metric();
+
+    metric.add_sync(&glean, 2);
+    metric.add_sync(&glean, -5);
+    assert_eq!(Some(-3), metric.get_value(&glean, "store1"));
+
+    metric.add_sync(&glean, 0);
+    assert_eq!(Some(-3), metric.get_value(&glean, "store1"));
+
+    assert!(test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::InvalidValue).is_err());
+}
+
+#[test]
+fn saturates_at_boundary() {
+    let (glean, _t) = new_glean(None);
+    let metric = signed_counter_metric();
+
+    metric.add_sync(&glean, i64::MIN + 1);
+    metric.add_sync(&glean, -2);
+    assert_eq!(Some(i64::MIN), metric.get_value(&glean, "store1"));
+}
@@ -0,0 +1,187 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod common;
+use crate::common::*;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+
+use glean_core::metrics::*;
+use glean_core::storage::StorageManager;
+use glean_core::{CommonMetricData, Glean, Lifetime, Result, StorageBackend};
+
+/// Values keyed by the name of their lifetime and their key.
+type Entries = BTreeMap<(&'static str, String), Vec<u8>>;
+
+/// Keeps everything in memory, shared so the test can inspect it.
+#[derive(Clone, Debug, Default)]
+struct MemoryBackend {
+    data: Arc<Mutex<Entries>>,
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, lifetime: Lifetime, key: &str) -> Result<Option<Vec<u8>>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.get(&(lifetime.as_str(), key.to_string())).cloned())
+    }
+
+    fn put(&self, lifetime: Lifetime, key: &str, value: &[u8]) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.insert((lifetime.as_str(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, lifetime: Lifetime, key: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.remove(&(lifetime.as_str(), key.to_string()));
+        Ok(())
+    }
+
+    fn iter(&self, lifetime: Lifetime, prefix: &str, f: &mut dyn FnMut(&str, &[u8])) -> Result<()> {
+        let data = self.data.lock().unwrap();
+        for ((store, key), value) in data.iter() {
+            if *store == lifetime.as_str() && key.starts_with(prefix) {
+                f(key, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Simulates a small disk, refusing writes once `capacity` bytes are stored.
+#[derive(Clone, Debug)]
+struct SmallDiskBackend {
+    memory: MemoryBackend,
+    capacity: Arc<AtomicUsize>,
+}
+
+impl SmallDiskBackend {
+    fn new(capacity: usize) -> Self {
+        Self {
+            memory: MemoryBackend::default(),
+            capacity: Arc::new(AtomicUsize::new(capacity)),
+        }
+    }
+
+    fn used(&self) -> usize {
+        let data = self.memory.data.lock().unwrap();
+        data.values().map(Vec::len).sum()
+    }
+}
+
+impl StorageBackend for SmallDiskBackend {
+    fn get(&self, lifetime: Lifetime, key: &str) -> Result<Option<Vec<u8>>> {
+        self.memory.get(lifetime, key)
+    }
+
+    fn put(&self, lifetime: Lifetime, key: &str, value: &[u8]) -> Result<()> {
+        let replaced = self.memory.get(lifetime, key)?.map_or(0, |old| old.len());
+        if self.used() - replaced + value.len() > self.capacity.load(Ordering::SeqCst) {
+            // ENOSPC, "No space left on device".
+            return Err(io::Error::from_raw_os_error(28).into());
+        }
+        self.memory.put(lifetime, key, value)
+    }
+
+    fn delete(&self, lifetime: Lifetime, key: &str) -> Result<()> {
+        self.memory.delete(lifetime, key)
+    }
+
+    fn iter(&self, lifetime: Lifetime, prefix: &str, f: &mut dyn FnMut(&str, &[u8])) -> Result<()> {
+        self.memory.iter(lifetime, prefix, f)
+    }
+}
+
+#[test]
+fn metrics_are_kept_in_a_custom_storage_backend() {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = MemoryBackend::default();
+    let glean =
+        Glean::with_storage_backend(new_configuration(dir.path()), Box::new(backend.clone()))
+            .unwrap();
+
+    let metric = StringMetric::new(CommonMetricData {
+        name: "string_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::User,
+        ..Default::default()
+    });
+    metric.set_sync(&glean, "test_string_value");
+
+    assert_eq!(
+        Some("test_string_value".to_string()),
+        metric.get_value(&glean, "store1")
+    );
+    let snapshot = StorageManager
+        .snapshot_as_json(glean.storage(), "store1", true)
+        .unwrap();
+    assert_eq!(
+        json!({"string": {"telemetry.string_metric": "test_string_value"}}),
+        snapshot
+    );
+
+    // The value went into the backend, not into an rkv database.
+    let data = backend.data.lock().unwrap();
+    assert!(data.contains_key(&("user", "store1#telemetry.string_metric".to_string())));
+    assert!(!dir.path().join("db").exists());
+}
+
+#[test]
+fn writes_failing_on_a_full_disk_are_dropped_and_counted() {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = SmallDiskBackend::new(usize::MAX);
+    let glean =
+        Glean::with_storage_backend(new_configuration(dir.path()), Box::new(backend.clone()))
+            .unwrap();
+    // Leave space for small metrics only.
+    backend
+        .capacity
+        .store(backend.used() + 1024, Ordering::SeqCst);
+
+    let text = TextMetric::new(CommonMetricData {
+        name: "text_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    text.set_sync(&glean, "a".repeat(4096));
+    assert_eq!(None, text.get_value(&glean, "store1"));
+
+    let io_failure = CounterMetric::new(CommonMetricData {
+        name: "io_failure".into(),
+        category: "glean.error".into(),
+        send_in_pings: vec!["metrics".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    assert_eq!(Some(1), io_failure.get_value(&glean, "metrics"));
+
+    // Glean continues recording what still fits.
+    let counter = CounterMetric::new(CommonMetricData {
+        name: "counter_metric".into(),
+        category: "telemetry".into(),
+        send_in_pings: vec!["store1".into()],
+        disabled: false,
+        lifetime: Lifetime::Ping,
+        ..Default::default()
+    });
+    counter.add_sync(&glean, 1);
+    assert_eq!(Some(1), counter.get_value(&glean, "store1"));
+
+    // Nothing fits anymore, not even the failure count.
+    backend.capacity.store(0, Ordering::SeqCst);
+    counter.add_sync(&glean, 1);
+    assert_eq!(Some(1), counter.get_value(&glean, "store1"));
+    assert_eq!(Some(1), io_failure.get_value(&glean, "metrics"));
+}
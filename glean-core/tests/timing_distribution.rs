@@ -429,3 +429,61 @@ fn raw_samples_api_error_cases() {
         test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::InvalidOverflow)
     );
 }
+
+#[test]
+fn nanosecond_samples_are_bucketed_at_nanosecond_precision() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = TimingDistributionMetric::new(
+        CommonMetricData {
+            name: "distribution".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        TimeUnit::Nanosecond,
+    );
+
+    metric.accumulate_samples_sync(&glean, vec![1, 2, 3, 5, 100, 250]);
+
+    let snapshot = metric
+        .get_value(&glean, "store1")
+        .expect("Value should be stored");
+    assert_eq!(361, snapshot.sum);
+    assert_eq!(6, snapshot.count);
+    // The smallest samples each get their own bucket.
+    for bucket in [1, 2, 3, 5, 98, 234] {
+        assert_eq!(1, snapshot.values[&bucket], "bucket {}", bucket);
+    }
+}
+
+#[test]
+fn coarse_time_units_are_capped_at_the_longest_time_in_nanoseconds() {
+    let (glean, _t) = new_glean(None);
+
+    let metric = TimingDistributionMetric::new(
+        CommonMetricData {
+            name: "distribution".into(),
+            category: "telemetry".into(),
+            send_in_pings: vec!["store1".into()],
+            disabled: false,
+            lifetime: Lifetime::Ping,
+            ..Default::default()
+        },
+        TimeUnit::Day,
+    );
+
+    // 300,000 days don't fit into 64 bits of nanoseconds.
+    metric.accumulate_samples_sync(&glean, vec![1, 300_000]);
+
+    let snapshot = metric
+        .get_value(&glean, "store1")
+        .expect("Value should be stored");
+    assert_eq!(2, snapshot.count);
+    assert_eq!(
+        Ok(1),
+        test_get_num_recorded_errors(&glean, metric.meta(), ErrorType::InvalidOverflow)
+    );
+}
@@ -36,12 +36,14 @@ fn main() {
         server_endpoint: Some("invalid-test-host".into()),
         uploader: None,
         use_core_mps: true,
+        ..Default::default()
     };
 
     let client_info = ClientInfoMetrics {
         app_build: env!("CARGO_PKG_VERSION").to_string(),
         app_display_version: env!("CARGO_PKG_VERSION").to_string(),
         channel: None,
+        build_id: None,
     };
 
     glean::initialize(cfg, client_info);